@@ -1,4 +1,7 @@
 // path: crates/broker/src/config.rs
+use grepzilla_segment::analyzer::Analyzers;
+use grepzilla_segment::limits::QueryLimits;
+use grepzilla_segment::verify::DEFAULT_ENGINE;
 use serde::Deserialize;
 
 #[derive(Clone, Deserialize)]
@@ -16,9 +19,21 @@ pub struct BrokerConfig {
     pub manifest_path: Option<String>, // путь к manifest.json (файл, не папка)
     #[serde(default)]
     pub shard: u64,                    // текущий shard брокера
+    /// схема анализаторов индекса (JSON, см. `grepzilla_segment::analyzer`);
+    /// без неё — стандартная нормализация
+    #[serde(default)]
+    pub analyzers_path: Option<String>,
+    /// движок verify по умолчанию (`regex`, `pcre2`, ...); запрос может
+    /// выбрать другой полем `verify_engine`
+    #[serde(default = "default_verify_engine")]
+    pub verify_engine: String,
+    /// лимиты сложности запроса (длина паттерна, куски wildcard, размер regex)
+    #[serde(default)]
+    pub query_limits: QueryLimits,
 }
 
 fn default_parallelism() -> usize { 4 }
+fn default_verify_engine() -> String { DEFAULT_ENGINE.to_string() }
 fn default_hot_cap() -> usize { 10_000 }
 
 impl BrokerConfig {
@@ -32,7 +47,28 @@ impl BrokerConfig {
         // NEW:
         let manifest_path = std::env::var("GZ_MANIFEST").ok();
         let shard = std::env::var("GZ_SHARD").ok().and_then(|s| s.parse().ok()).unwrap_or(0);
+        let analyzers_path = std::env::var("GZ_ANALYZERS").ok();
+        // GZ_VERIFY_ENGINE — прежнее имя переменной
+        let verify_engine = std::env::var("GZ_VERIFY")
+            .or_else(|_| std::env::var("GZ_VERIFY_ENGINE"))
+            .unwrap_or_else(|_| default_verify_engine());
+        let env_limit = |name: &str, default: usize| std::env::var(name).ok().and_then(|s| s.parse().ok()).unwrap_or(default);
+        let d = QueryLimits::default();
+        let query_limits = QueryLimits {
+            max_pattern_len: env_limit("GZ_MAX_PATTERN_LEN", d.max_pattern_len),
+            max_wildcard_segments: env_limit("GZ_MAX_WILDCARD_SEGMENTS", d.max_wildcard_segments),
+            max_regex_size: env_limit("GZ_MAX_REGEX_SIZE", d.max_regex_size),
+            max_patterns: env_limit("GZ_MAX_PATTERNS", d.max_patterns),
+        };
+
+        Self { addr, wal_dir, segment_out_dir, parallelism, hot_cap, manifest_path, shard, analyzers_path, verify_engine, query_limits }
+    }
 
-        Self { addr, wal_dir, segment_out_dir, parallelism, hot_cap, manifest_path, shard }
+    /// Анализаторы полей для ingest и горячей памяти.
+    pub fn analyzers(&self) -> anyhow::Result<Analyzers> {
+        match &self.analyzers_path {
+            Some(p) => Analyzers::from_file(std::path::Path::new(p)),
+            None => Ok(Analyzers::default()),
+        }
     }
 }
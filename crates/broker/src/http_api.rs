@@ -6,11 +6,14 @@ use axum::extract::Path;
 use axum::routing::get;
 use axum::{extract::State, routing::post, Json, Router};
 use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
 
 use serde::Serialize;
 
-use crate::search::types::{SearchRequest, SearchResponse};
+use crate::search::types::{MultiSearchRequest, PageIn, SearchRequest, SearchResponse};
 use crate::search::SearchCoordinator;
+use grepzilla_segment::limits::LimitExceeded;
+use grepzilla_segment::query::InvalidQuery;
 use serde_json::{json, Value};
 use tokio::fs;
 
@@ -42,6 +45,7 @@ pub fn router(state: AppState) -> Router {
     Router::<AppState>::new()
         .route("/healthz", get(healthz))
         .route("/search", post(search))
+        .route("/msearch", post(msearch))
         // FIX: сигнатура get_manifest теперь принимает State(AppState),
         // axum сам инжектит State, маршрут остаётся тем же
         .route("/manifest/:shard", get(get_manifest))
@@ -52,38 +56,19 @@ pub fn router(state: AppState) -> Router {
 pub async fn search(
     State(st): State<AppState>,
     Json(mut req): Json<SearchRequest>,
-) -> Result<Json<SearchResponse>, (axum::http::StatusCode, String)> {
-    let mut resolved_pin_gen: Option<std::collections::HashMap<u64, u64>> = None;
-
-    // shards → resolve через манифест
-    if let Some(shards) = req.shards.clone() {
-        // FIX: берём путь из конфига
-        let manifest_path = st
-            .cfg
-            .manifest_path
-            .clone()
-            .unwrap_or_else(|| "manifest.json".to_string());
-        let store = FsManifestStore { path: manifest_path.into() };
-
-        let (seg_refs, pin_map) = store.resolve(&shards).await.map_err(internal)?;
-        req.segments = seg_refs.into_iter().map(|r| r.path).collect();
-        resolved_pin_gen = Some(pin_map.clone());
-
-        // совместимость: кладём pin_gen и во входной курсор
-        match &mut req.page.cursor {
-            Some(cur) => {
-                let obj = cur.as_object_mut().unwrap();
-                obj.insert("pin_gen".to_string(), serde_json::to_value(pin_map).unwrap());
-            }
-            None => {
-                req.page.cursor = Some(json!({ "per_seg": {}, "pin_gen": pin_map }));
-            }
-        }
-        req.shards = None;
-    }
+) -> Result<Json<SearchResponse>, Response> {
+    let resolved_pin_gen =
+        resolve_shards(&st, &mut req.shards, &mut req.segments, &mut req.page).await?;
+
+    // неизвестный движок verify — ошибка клиента, а не сервера
+    st.coord.verify_factory(req.verify_engine.as_deref()).map_err(|e| {
+        bad_request(json!({ "error": "unknown_verify_engine", "message": e.to_string() }))
+    })?;
+    // неверная форма запроса — тоже до поиска
+    req.bool_query().map_err(|e| bad_request(e.body()))?;
 
-    // выполняем поиск
-    let mut resp = st.coord.handle(req).await.map_err(internal)?;
+    // выполняем поиск; превышение лимитов сложности — 400 с телом-описанием
+    let mut resp = st.coord.handle(req).await.map_err(search_error)?;
 
     // проставляем pin_gen в ОТВЕТ, если резолвили shards
     if let (Some(pin), Some(cursor)) = (resolved_pin_gen, resp.cursor.as_mut()) {
@@ -93,6 +78,64 @@ pub async fn search(
     Ok(Json(resp))
 }
 
+/// POST /msearch — много wildcard за один проход по сегментам; хиты
+/// помечены id совпавших паттернов.
+pub async fn msearch(
+    State(st): State<AppState>,
+    Json(mut req): Json<MultiSearchRequest>,
+) -> Result<Json<SearchResponse>, Response> {
+    let resolved_pin_gen =
+        resolve_shards(&st, &mut req.shards, &mut req.segments, &mut req.page).await?;
+
+    // пустой набор и повтор id — ошибка клиента
+    grepzilla_segment::multi::validate(&req.patterns).map_err(|e| bad_request(e.body()))?;
+
+    let mut resp = st.coord.handle_multi(req).await.map_err(search_error)?;
+
+    if let (Some(pin), Some(cursor)) = (resolved_pin_gen, resp.cursor.as_mut()) {
+        cursor.pin_gen = Some(pin);
+    }
+
+    Ok(Json(resp))
+}
+
+/// shards → сегменты через манифест; возвращает `pin_gen`, если резолвили.
+async fn resolve_shards(
+    st: &AppState,
+    shards: &mut Option<Vec<u64>>,
+    segments: &mut Vec<String>,
+    page: &mut PageIn,
+) -> Result<Option<std::collections::HashMap<u64, u64>>, Response> {
+    let Some(ids) = shards.take() else {
+        return Ok(None);
+    };
+    // FIX: берём путь из конфига
+    let manifest_path = st
+        .cfg
+        .manifest_path
+        .clone()
+        .unwrap_or_else(|| "manifest.json".to_string());
+    let store = FsManifestStore { path: manifest_path.into() };
+
+    let (seg_refs, pin_map) = store
+        .resolve(&ids)
+        .await
+        .map_err(|e| internal(e).into_response())?;
+    *segments = seg_refs.into_iter().map(|r| r.path).collect();
+
+    // совместимость: кладём pin_gen и во входной курсор
+    match &mut page.cursor {
+        Some(cur) => {
+            let obj = cur.as_object_mut().unwrap();
+            obj.insert("pin_gen".to_string(), serde_json::to_value(&pin_map).unwrap());
+        }
+        None => {
+            page.cursor = Some(json!({ "per_seg": {}, "pin_gen": pin_map }));
+        }
+    }
+    Ok(Some(pin_map))
+}
+
 // FIX: берём State(AppState), читаем манифест из st.cfg.manifest_path
 async fn get_manifest(
     State(st): State<AppState>,
@@ -263,6 +306,23 @@ pub async fn ingest_batch(
     (axum::http::StatusCode::OK, Json(serde_json::Value::Object(out_obj)))
 }
 
+/// Ошибка `/search` и `/msearch`: нарушенный лимит или неверная форма запроса —
+/// `400` с JSON-описанием ([`LimitExceeded::body`], [`InvalidQuery::body`]),
+/// остальное — `500`.
+fn search_error(e: anyhow::Error) -> Response {
+    if let Some(limit) = e.downcast_ref::<LimitExceeded>() {
+        return bad_request(limit.body());
+    }
+    if let Some(invalid) = e.downcast_ref::<InvalidQuery>() {
+        return bad_request(invalid.body());
+    }
+    internal(e).into_response()
+}
+
+fn bad_request(body: Value) -> Response {
+    (axum::http::StatusCode::BAD_REQUEST, Json(body)).into_response()
+}
+
 fn internal<E: ToString>(e: E) -> (axum::http::StatusCode, String) {
     (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
 }
@@ -5,14 +5,22 @@ use tokio::fs;
 use tokio::io::AsyncReadExt;
 // FIX: поддерживаем оба алгоритма и оба шаблона имени сайдкара
 use anyhow::Context;
+use grepzilla_segment::analyzer::Analyzers;
 
 pub struct Compactor {
     pub out_dir: PathBuf,
+    pub analyzers: Analyzers,
 }
 
 impl Compactor {
     pub fn new(out_dir: PathBuf) -> Self {
-        Self { out_dir }
+        Self { out_dir, analyzers: Analyzers::default() }
+    }
+
+    /// Анализаторы полей собираемых сегментов.
+    pub fn with_analyzers(mut self, analyzers: Analyzers) -> Self {
+        self.analyzers = analyzers;
+        self
     }
 
     pub async fn wal_to_segment(&self, wal_path: &str) -> anyhow::Result<String> {
@@ -37,7 +45,10 @@ impl Compactor {
         use grepzilla_segment::segjson::JsonSegmentWriter;
         use grepzilla_segment::SegmentWriter;
 
-        let mut writer = JsonSegmentWriter::default();
+        // исходный текст полей — для превью
+        let mut writer = JsonSegmentWriter::default()
+            .with_raw_text(true)
+            .with_analyzers(self.analyzers.clone());
         writer.write_segment(
             &docs_path.to_string_lossy(),
             &seg_dir.to_string_lossy(),
@@ -1,16 +1,24 @@
 // crates/broker/src/ingest/flusher.rs
 use anyhow::Result;
+use grepzilla_segment::analyzer::Analyzers;
 use serde_json::Value;
 use std::path::{Path, PathBuf};
 use tokio::io::AsyncWriteExt;
 
 pub struct Flusher {
     out_dir: PathBuf,
+    analyzers: Analyzers,
 }
 
 impl Flusher {
     pub fn new(out_dir: impl AsRef<Path>) -> Self {
-        Self { out_dir: out_dir.as_ref().to_path_buf() }
+        Self { out_dir: out_dir.as_ref().to_path_buf(), analyzers: Analyzers::default() }
+    }
+
+    /// Анализаторы полей собираемых сегментов.
+    pub fn with_analyzers(mut self, analyzers: Analyzers) -> Self {
+        self.analyzers = analyzers;
+        self
     }
 
     pub fn choose_segment_path(&self) -> Result<std::path::PathBuf> {
@@ -38,7 +46,10 @@ impl Flusher {
         use grepzilla_segment::segjson::JsonSegmentWriter;
         use grepzilla_segment::SegmentWriter;
 
-        let mut writer = JsonSegmentWriter::default();
+        // исходный текст полей — для превью
+        let mut writer = JsonSegmentWriter::default()
+            .with_raw_text(true)
+            .with_analyzers(self.analyzers.clone());
         writer.write_segment(
             &tmp.to_string_lossy(),
             &seg_path.to_string_lossy(),
@@ -1,5 +1,6 @@
 // path: crates/broker/src/ingest/hot.rs
-use grepzilla_segment::normalizer::normalize;
+use grepzilla_segment::analyzer::Analyzers;
+use grepzilla_segment::doc_values::RangeFilter;
 use grepzilla_segment::StoredDoc;
 use serde_json::Value;
 use std::collections::{BTreeMap, VecDeque};
@@ -12,12 +13,35 @@ pub struct ApplyResult {
     pub backlog_ms: Option<u64>,
 }
 
+/// Документ горячей области: строки — как в сегменте, нестроковые скаляры
+/// (числа, bool) — отдельно, для фильтров по диапазону.
+#[derive(Clone)]
+struct HotDoc {
+    doc: StoredDoc,
+    scalars: BTreeMap<String, Value>,
+}
+
+impl HotDoc {
+    fn matches(&self, filters: &[RangeFilter]) -> bool {
+        filters.iter().all(|f| match self.scalars.get(&f.field) {
+            Some(v) => f.matches_json(v),
+            None => self
+                .doc
+                .raw
+                .get(&f.field)
+                .or_else(|| self.doc.fields.get(&f.field))
+                .is_some_and(|s| f.matches_json(&Value::String(s.clone()))),
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct HotMem {
-    inner: Arc<RwLock<VecDeque<StoredDoc>>>,
+    inner: Arc<RwLock<VecDeque<HotDoc>>>,
     cap: usize,               // удерживаем столько doc в window
     hard_cap: usize,          // NEW: порог отказа = cap
     idempotency_seen: Arc<RwLock<HashSet<String>>>, // NEW
+    analyzers: Arc<Analyzers>, // как у сегментов, которые пишет ingest
 }
 
 impl Default for HotMem {
@@ -28,6 +52,7 @@ impl Default for HotMem {
             cap,
             hard_cap: cap,
             idempotency_seen: Arc::new(RwLock::new(HashSet::new())),
+            analyzers: Arc::new(Analyzers::default()),
         }
     }
 }
@@ -43,6 +68,16 @@ impl HotMem {
         self
     }
 
+    /// Анализаторы полей (те же, что у ingest в сегменты).
+    pub fn with_analyzers(mut self, analyzers: Analyzers) -> Self {
+        self.analyzers = Arc::new(analyzers);
+        self
+    }
+
+    pub fn analyzers(&self) -> &Analyzers {
+        &self.analyzers
+    }
+
     pub fn len(&self) -> usize {
         self.inner.read().unwrap().len()
     }
@@ -53,7 +88,18 @@ impl HotMem {
     }
 
     pub fn snapshot(&self) -> Vec<StoredDoc> {
-        self.inner.read().unwrap().iter().cloned().collect()
+        self.inner.read().unwrap().iter().map(|h| h.doc.clone()).collect()
+    }
+
+    /// Снимок только тех документов, что проходят фильтры по диапазону.
+    pub fn snapshot_filtered(&self, filters: &[RangeFilter]) -> Vec<StoredDoc> {
+        self.inner
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|h| h.matches(filters))
+            .map(|h| h.doc.clone())
+            .collect()
     }
 
     /// Основной путь — идемпотентность + backpressure по hard_cap
@@ -77,12 +123,23 @@ impl HotMem {
             let ext_id = v.get("_id").and_then(|x| x.as_str()).unwrap_or("").to_string();
 
             let mut fields: BTreeMap<String, String> = BTreeMap::new();
+            // исходный текст — для превью (только там, где он отличается)
+            let mut raw: BTreeMap<String, String> = BTreeMap::new();
             collect_strings_local("", &v, &mut |path, s| {
-                fields.insert(path.to_string(), normalize(s));
+                let ns = self.analyzers.for_field(path).analyze(s);
+                if ns != s {
+                    raw.insert(path.to_string(), s.to_string());
+                }
+                fields.insert(path.to_string(), ns);
+            });
+
+            let mut scalars: BTreeMap<String, Value> = BTreeMap::new();
+            collect_scalars_local("", &v, &mut |path, x| {
+                scalars.entry(path.to_string()).or_insert_with(|| x.clone());
             });
 
             let doc_id = g.len() as u32;
-            g.push_back(StoredDoc { doc_id, ext_id, fields });
+            g.push_back(HotDoc { doc: StoredDoc { doc_id, ext_id, fields, raw }, scalars });
             added += 1;
 
             // удерживаем окно cap (самые старые выдавливаем)
@@ -101,6 +158,28 @@ impl HotMem {
 
 pub struct Backpressure { pub retry_after_ms: u64 }
 
+/// Числа и bool по путям (первый элемент массива — и под путём массива).
+fn collect_scalars_local(path: &str, v: &Value, f: &mut impl FnMut(&str, &Value)) {
+    match v {
+        Value::Number(_) | Value::Bool(_) => f(path, v),
+        Value::Object(map) => {
+            for (k, vv) in map {
+                let np = if path.is_empty() { k.clone() } else { format!("{path}.{k}") };
+                collect_scalars_local(&np, vv, f);
+            }
+        }
+        Value::Array(arr) => {
+            for (i, vv) in arr.iter().enumerate() {
+                if matches!(vv, Value::Number(_) | Value::Bool(_)) {
+                    f(path, vv);
+                }
+                collect_scalars_local(&format!("{path}[{i}]"), vv, f);
+            }
+        }
+        _ => {}
+    }
+}
+
 fn collect_strings_local(path: &str, v: &Value, f: &mut impl FnMut(&str, &str)) {
     match v {
         Value::String(s) => f(path, s),
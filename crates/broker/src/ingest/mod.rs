@@ -17,7 +17,7 @@ pub async fn handle_batch_json(
 ) -> anyhow::Result<serde_json::Value> {
     let wal = Wal::new(&cfg.wal_dir);
     let (wal_path, appended) = wal.append_batch(&records).await?;
-    let comp = Compactor::new(cfg.segment_out_dir.clone().into());
+    let comp = Compactor::new(cfg.segment_out_dir.clone().into()).with_analyzers(cfg.analyzers()?);
     let seg_path = comp.wal_to_segment(&wal_path).await?;
     Ok(serde_json::json!({
         "ok": true, "appended": appended, "wal": wal_path, "segment": seg_path
@@ -15,10 +15,17 @@ async fn main() -> Result<()> {
 
     let cfg = BrokerConfig::from_env();
 
-    // HotMem с ограничением из конфига
-    let hot = HotMem::new().with_cap(cfg.hot_cap);
-
-    let coord = Arc::new(SearchCoordinator::new(cfg.parallelism).with_hot(hot.clone()));
+    // HotMem с ограничением и анализаторами полей из конфига
+    let hot = HotMem::new()
+        .with_cap(cfg.hot_cap)
+        .with_analyzers(cfg.analyzers()?);
+
+    let coord = Arc::new(
+        SearchCoordinator::new(cfg.parallelism)
+            .with_verify_engine(&cfg.verify_engine)?
+            .with_query_limits(cfg.query_limits)
+            .with_hot(hot.clone()),
+    );
 
     let state = AppState {
         coord: coord.clone(),
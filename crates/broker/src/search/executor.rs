@@ -1,5 +1,9 @@
 // crates/broker/src/search/executor.rs
 use anyhow::Result;
+use croaring::Bitmap;
+use grepzilla_segment::multi::CompiledMulti;
+use grepzilla_segment::query::{CompiledClause, CompiledQuery, PrefilterOpts};
+use grepzilla_segment::StoredDoc;
 use std::future::Future;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -11,15 +15,79 @@ use tokio_util::sync::CancellationToken;
 #[derive(Clone)]
 pub struct SegmentTaskInput {
     pub seg_path: String,
-    pub wildcard: String,
-    /// Пустая строка == нет фильтра поля
-    pub field: String,
+    /// Запрос уже разобран и собран координатором (граммы и движки verify
+    /// всех пунктов)
+    pub query: SegmentQuery,
     pub cursor_docid: Option<u64>,
+    /// Фильтры по doc-values (пусто — без фильтра)
+    pub filters: Arc<[grepzilla_segment::doc_values::RangeFilter]>,
     pub max_candidates: u64,
+    /// Разрешён полный просмотр сегмента для пунктов без грамм
+    pub full_scan: bool,
     /// Для прогрева и эвристик (prefetch)
     pub page_size: usize,
-    /// Движок верификации уже собран координатором (Arc<dyn VerifyEngine>)
-    pub verify_engine: Arc<dyn grepzilla_segment::verify::VerifyEngine>,
+}
+
+/// Запрос задачи, собранный под схему анализаторов сегмента: булев
+/// (`/search`) или набор паттернов (`/msearch`).
+#[derive(Clone)]
+pub enum SegmentQuery {
+    Bool(Arc<CompiledQuery>),
+    Multi(Arc<CompiledMulti>),
+}
+
+/// Совпадение документа.
+pub struct DocMatch<'a> {
+    /// поле совпадения (для превью)
+    pub field: String,
+    /// пункт, давший совпадение: спан подсветки и игла превью
+    pub clause: &'a CompiledClause,
+    /// id совпавших паттернов (`/msearch`; у булева запроса пусто)
+    pub patterns: Vec<String>,
+}
+
+impl SegmentQuery {
+    /// Запрос для отсева по сводке; у набора паттернов — «хотя бы один».
+    pub fn compiled(&self) -> &CompiledQuery {
+        match self {
+            Self::Bool(q) => q,
+            Self::Multi(m) => m.query(),
+        }
+    }
+
+    /// Кандидаты сегмента; у набора паттернов — один проход по объединению
+    /// формул грамм.
+    pub fn prefilter(
+        &self,
+        opts: PrefilterOpts,
+        runs: impl FnMut(&[Vec<String>], Option<&str>) -> Result<Bitmap>,
+    ) -> Result<Bitmap> {
+        match self {
+            Self::Bool(q) => q.prefilter(opts, runs),
+            Self::Multi(m) => m.prefilter(opts, runs),
+        }
+    }
+
+    /// Verify документа; у набора паттернов поле и пункт — первого
+    /// совпавшего паттерна.
+    pub fn matches(&self, doc: &StoredDoc) -> Option<DocMatch<'_>> {
+        match self {
+            Self::Bool(q) => q.matches(doc).map(|(field, clause)| DocMatch {
+                field,
+                clause,
+                patterns: Vec::new(),
+            }),
+            Self::Multi(m) => {
+                let found = m.matches(doc);
+                let &(first, field) = found.first()?;
+                Some(DocMatch {
+                    field: field.to_string(),
+                    clause: m.clause(first),
+                    patterns: found.iter().map(|&(i, _)| m.id(i).to_string()).collect(),
+                })
+            }
+        }
+    }
 }
 
 /// Выход одной задачи.
@@ -35,6 +103,10 @@ pub struct SegmentTaskOutput {
     pub verify_ms: u64,
     pub prefetch_ms: u64,
     pub warmed_docs: u64,
+
+    /// Ошибка поиска по сегменту (`last_docid` — позиция из входного
+    /// курсора); координатор переносит такие части в `segments_failed`.
+    pub error: Option<anyhow::Error>,
 }
 
 impl SegmentTaskOutput {
@@ -48,6 +120,7 @@ impl SegmentTaskOutput {
             verify_ms: 0,
             prefetch_ms: 0,
             warmed_docs: 0,
+            error: None,
         }
     }
 }
@@ -163,7 +236,11 @@ impl ParallelExecutor {
                 let out_res = search_fn_c(inp.clone(), task_ct.clone()).await;
                 let mut out = match out_res {
                     Ok(v) => v,
-                    Err(_) => SegmentTaskOutput::empty(inp.seg_path.clone()),
+                    Err(e) => SegmentTaskOutput {
+                        last_docid: inp.cursor_docid,
+                        error: Some(e),
+                        ..SegmentTaskOutput::empty(inp.seg_path.clone())
+                    },
                 };
 
                 // Увеличим глобальный счётчик и, если страница набрана — отменим остальных
@@ -2,23 +2,33 @@
 
 pub mod executor;
 pub mod paginator;
+pub mod pruner;
 pub mod types;
 
+use std::path::Path;
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
 use crate::manifest::{ManifestStore, SegRef};
-use crate::search::executor::{ParallelExecutor, SegmentTaskInput, SegmentTaskOutput};
+use crate::search::executor::{ParallelExecutor, SegmentQuery, SegmentTaskInput, SegmentTaskOutput};
 use crate::search::paginator::Paginator;
+use crate::search::pruner::SegmentPruner;
 use crate::search::types::*;
-use grepzilla_segment::verify::{EnvVerifyFactory, VerifyFactory};
+use grepzilla_segment::verify::{EngineRegistry, VerifyFactory, DEFAULT_ENGINE};
 use crate::ingest::hot::HotMem;
+use grepzilla_segment::analyzer::Analyzers;
 use grepzilla_segment::common::preview::{build_preview, PreviewOpts};
+use grepzilla_segment::doc_values::RangeFilter;
+use grepzilla_segment::limits::QueryLimits;
+use grepzilla_segment::multi::CompiledMulti;
 
 pub struct SearchCoordinator {
     default_parallelism: usize,
     manifest: Option<Arc<dyn ManifestStore>>,
-    verify_factory: Arc<dyn VerifyFactory>,
+    engines: EngineRegistry,
+    /// движок verify, если запрос не выбрал свой
+    verify_engine: String,
+    query_limits: QueryLimits,
     hot: Option<HotMem>, // NEW: горячая область
 }
 
@@ -27,7 +37,9 @@ impl SearchCoordinator {
         Self {
             default_parallelism,
             manifest: None,
-            verify_factory: Arc::new(EnvVerifyFactory::from_env()),
+            engines: EngineRegistry::builtin(),
+            verify_engine: DEFAULT_ENGINE.to_string(),
+            query_limits: QueryLimits::default(),
             hot: None,
         }
     }
@@ -37,6 +49,32 @@ impl SearchCoordinator {
         self
     }
 
+    /// Движок verify по умолчанию; неизвестный — ошибка сразу, а не на
+    /// первом запросе.
+    pub fn with_verify_engine(mut self, name: &str) -> anyhow::Result<Self> {
+        self.engines.get(name)?;
+        self.verify_engine = name.to_lowercase();
+        Ok(self)
+    }
+
+    /// Движок verify запроса: `requested` или движок по умолчанию.
+    pub fn verify_factory(
+        &self,
+        requested: Option<&str>,
+    ) -> anyhow::Result<(String, &Arc<dyn VerifyFactory>)> {
+        let name = requested.unwrap_or(&self.verify_engine);
+        Ok((name.trim().to_lowercase(), self.engines.get(name)?))
+    }
+
+    /// Лимиты сложности запроса; нарушение — ошибка
+    /// [`grepzilla_segment::limits::LimitExceeded`] до открытия сегментов.
+    /// `max_regex_size` получают сами движки verify.
+    pub fn with_query_limits(mut self, limits: QueryLimits) -> Self {
+        self.engines = EngineRegistry::with_regex_size_limit(limits.max_regex_size);
+        self.query_limits = limits;
+        self
+    }
+
     /// Прокинуть hot-memory (по желанию).
     pub fn with_hot(mut self, hot: HotMem) -> Self {
         self.hot = Some(hot);
@@ -46,18 +84,82 @@ impl SearchCoordinator {
     pub async fn handle(&self, req: SearchRequest) -> anyhow::Result<SearchResponse> {
         let start = std::time::Instant::now();
 
-        // 0) Компилируем движок верификации один раз на весь запрос
-        let eng = self.verify_factory.compile(&req.wildcard)?;
+        // 0) Разбираем запрос и компилируем движки верификации один раз на
+        //    схему анализаторов (у большинства сегментов она стандартная)
+        let (verify_engine, factory) = self.verify_factory(req.verify_engine.as_deref())?;
+        let query = req.bool_query()?;
+        self.query_limits.check(&query)?;
+        let sens = req.sensitivity;
+        // пункт без литералов не сузит кандидатов ни в одном сегменте:
+        // без full_scan это 400 до открытия сегментов (схема по умолчанию)
+        // или отказ сегмента (своя схема анализаторов)
+        let full_scan = req.limits.as_ref().and_then(|l| l.full_scan).unwrap_or(false);
+        let queries = QueryBySchema::new(move |a: &Analyzers| {
+            let q = query.compile_with(factory.as_ref(), sens, a)?;
+            q.check_strength(full_scan)?;
+            Ok(SegmentQuery::Bool(Arc::new(q)))
+        })?;
+
+        let scope = Scope {
+            segments: &req.segments,
+            shards: req.shards.as_ref(),
+            filters: &req.filters,
+            page: &req.page,
+            limits: req.limits.as_ref(),
+        };
+        self.run(start, scope, queries, verify_engine).await
+    }
+
+    /// Мультипоиск (`/msearch`): все паттерны за один проход по сегменту —
+    /// общий префильтр и один verify-проход по тексту поля; хит помечается
+    /// id совпавших паттернов. Форма набора ([`grepzilla_segment::multi::validate`]),
+    /// лимиты — число паттернов и каждый паттерн — и литералы паттернов
+    /// ([`CompiledMulti::check_strength`]) проверяются до открытия сегментов.
+    pub async fn handle_multi(&self, req: MultiSearchRequest) -> anyhow::Result<SearchResponse> {
+        let start = std::time::Instant::now();
+
+        // движок по умолчанию нужен только для подсветки в превью
+        let (_, factory) = self.verify_factory(None)?;
+        grepzilla_segment::multi::validate(&req.patterns)?;
+        self.query_limits.check_multi(&req.patterns)?;
+        let patterns = &req.patterns;
+        // один слабый паттерн сорвал бы общий префильтр сегмента
+        let full_scan = req.limits.as_ref().and_then(|l| l.full_scan).unwrap_or(false);
+        let queries = QueryBySchema::new(move |a: &Analyzers| {
+            let m = CompiledMulti::compile(patterns, factory.as_ref(), a)?;
+            m.check_strength(full_scan)?;
+            Ok(SegmentQuery::Multi(Arc::new(m)))
+        })?;
 
+        let scope = Scope {
+            segments: &req.segments,
+            shards: req.shards.as_ref(),
+            filters: &req.filters,
+            page: &req.page,
+            limits: req.limits.as_ref(),
+        };
+        self.run(start, scope, queries, MULTI_VERIFY_ENGINE.to_string()).await
+    }
+
+    /// Общий ход `/search` и `/msearch`: выбор сегментов, отсев по сводке,
+    /// параллельный поиск, горячая память, склейка страницы.
+    async fn run(
+        &self,
+        start: std::time::Instant,
+        scope: Scope<'_>,
+        mut queries: QueryBySchema<'_>,
+        verify_engine: String,
+    ) -> anyhow::Result<SearchResponse> {
         // 1) Выбираем сегменты (shards → manifest; иначе — segments из запроса)
         let mut pin_gen = std::collections::HashMap::new();
         let selected: Vec<SegRef> =
-            if let (Some(store), Some(shards)) = (&self.manifest, req.shards.as_ref()) {
+            if let (Some(store), Some(shards)) = (&self.manifest, scope.shards) {
                 let (segs, pin) = store.resolve(shards).await?;
                 pin_gen = pin;
                 segs
             } else {
-                req.segments
+                scope
+                    .segments
                     .iter()
                     .map(|p| SegRef {
                         shard: 0,
@@ -75,10 +177,11 @@ impl SearchCoordinator {
         });
 
         // 3) Лимиты/параллелизм
-        let limits = req.limits.clone().unwrap_or(SearchLimits {
+        let limits = scope.limits.cloned().unwrap_or(SearchLimits {
             parallelism: None,
             deadline_ms: None,
             max_candidates: None,
+            full_scan: None,
         });
         let parallelism = limits
             .parallelism
@@ -86,63 +189,96 @@ impl SearchCoordinator {
             .max(1);
         let executor = ParallelExecutor::new(parallelism);
 
-        // 4) Формируем таски (каждому даём verify_engine: Arc<dyn VerifyEngine>)
+        // 4) Формируем таски (каждому даём собранный запрос);
+        //    сегменты, которые по сводке не могут совпасть, не открываем
+        let filters: Arc<[_]> = scope.filters.into();
+        let pruner = SegmentPruner::new(filters.clone());
+        let mut pruned: Vec<SegmentTaskOutput> = Vec::new();
+        let mut failed = FailedSegments::default();
         let tasks = selected
             .iter()
-            .map(|s| SegmentTaskInput {
-                seg_path: s.path.clone(),
-                wildcard: req.wildcard.clone(),
-                field: req.field.clone().unwrap_or_default(),
-                cursor_docid: extract_last_docid(&req.page.cursor, &s.path),
-                max_candidates: limits.max_candidates.unwrap_or(200_000),
-                page_size: req.page.size,
-                verify_engine: eng.clone(),
+            .filter_map(|s| {
+                let cursor_docid = extract_last_docid(&scope.page.cursor, &s.path);
+                let query = match queries.for_segment(&s.path) {
+                    Ok(q) => q,
+                    Err(e) => {
+                        failed.push(&s.path, cursor_docid, e);
+                        return None;
+                    }
+                };
+                if let Some(out) = pruner.prune(&s.path, query.compiled(), cursor_docid) {
+                    pruned.push(out);
+                    return None;
+                }
+                // строгий запрос к сегменту без исходного текста ничего бы
+                // не нашёл молча
+                if let Err(e) = query.compiled().check_raw_text(Path::new(&s.path)) {
+                    failed.push(&s.path, cursor_docid, e);
+                    return None;
+                }
+                Some(SegmentTaskInput {
+                    seg_path: s.path.clone(),
+                    query,
+                    cursor_docid,
+                    filters: filters.clone(),
+                    max_candidates: limits.max_candidates.unwrap_or(200_000),
+                    full_scan: limits.full_scan.unwrap_or(false),
+                    page_size: scope.page.size,
+                })
             })
             .collect::<Vec<_>>();
+        let segments_pruned = pruned.len() as u64;
 
         let ct = CancellationToken::new();
         let deadline = limits.deadline_duration();
 
-        // 5) Исполнение: search_one_segment читает движок из input.verify_engine
+        // 5) Исполнение: search_one_segment берёт запрос из input.query
         let search_fn = |input: SegmentTaskInput, ctok: CancellationToken| async move {
             let out = crate::storage_adapter::search_one_segment(input, ctok).await?;
             Ok::<_, anyhow::Error>(out)
         };
 
-        let (mut parts, deadline_hit, saturated_sem) = executor
-            .run_all(ct.clone(), tasks, search_fn, req.page.size, deadline)
+        let (parts, deadline_hit, saturated_sem) = executor
+            .run_all(ct.clone(), tasks, search_fn, scope.page.size, deadline)
             .await;
+        // ошибка поиска по сегменту (например, слабый паттерн без индекса
+        // коротких грамм) — не пустой результат, а отказ сегмента
+        let mut parts: Vec<SegmentTaskOutput> = parts
+            .into_iter()
+            .filter_map(|mut p| match p.error.take() {
+                Some(e) => {
+                    failed.push(&p.seg_path, p.last_docid, e);
+                    None
+                }
+                None => Some(p),
+            })
+            .collect();
+        // отсечённые — пустые части: только продвигают курсор
+        parts.extend(pruned);
+        parts.extend(failed.held);
 
         // 5.5) Поиск по горячей памяти (если настроен)
         if let Some(hot) = &self.hot {
+            let query = queries.for_analyzers(hot.analyzers())?;
             let mut hot_hits: Vec<Hit> = Vec::new();
             let mut candidates: u64 = 0;
             let mut verify_ms = 0u64;
 
             let preferred = ["text.title", "text.body", "title", "body"];
 
-            for doc in hot.snapshot().into_iter() {
+            for doc in hot.snapshot_filtered(scope.filters).into_iter() {
                 let tv0 = std::time::Instant::now();
-                let matched_field = match req.field.as_deref() {
-                    Some(f) if !f.is_empty() => {
-                        doc.fields.get(f).and_then(|t| if eng.is_match(t) { Some(f.to_string()) } else { None })
-                    }
-                    _ => {
-                        doc.fields
-                            .iter()
-                            .find(|(_, t)| eng.is_match(t))
-                            .map(|(k, _)| k.clone())
-                    }
-                };
+                let matched = query.matches(&doc).map(|m| (m.field, m.patterns));
                 verify_ms += tv0.elapsed().as_millis() as u64;
 
-                if let Some(mf) = matched_field {
+                if let Some((mf, patterns)) = matched {
                     let preview = build_preview(
                         &doc,
                         PreviewOpts {
                             preferred_fields: &preferred,
                             max_len: 180,
                             highlight_needle: None, // движок уже проверил матч
+                            analyzers: hot.analyzers(),
                         },
                     );
 
@@ -151,10 +287,11 @@ impl SearchCoordinator {
                         doc_id: doc.doc_id,
                         matched_field: mf,
                         preview,
+                        patterns,
                     });
                     candidates += 1;
 
-                    if hot_hits.len() >= req.page.size {
+                    if hot_hits.len() >= scope.page.size {
                         break;
                     }
                 }
@@ -169,12 +306,13 @@ impl SearchCoordinator {
                 verify_ms,
                 prefetch_ms: 0,
                 warmed_docs: 0,
+                error: None,
             });
         }
 
         // 6) Сшиваем и агрегируем метрики
         let (hits, mut cursor, candidates_total, dedup_dropped, totals) =
-            Paginator::merge(parts, req.page.size);
+            Paginator::merge(parts, scope.page.size);
 
         let ttfh = if hits.is_empty() {
             0
@@ -204,6 +342,9 @@ impl SearchCoordinator {
                 deadline_hit,
                 saturated_sem: saturated_sem as u64,
                 dedup_dropped,
+                segments_pruned,
+                segments_failed: failed.list,
+                verify_engine,
                 prefilter_ms: if has_any_metrics {
                     Some(prefilter_ms_total)
                 } else {
@@ -237,3 +378,76 @@ fn extract_last_docid(cursor: &Option<serde_json::Value>, seg: &str) -> Option<u
         .and_then(|s| s.get("last_docid"))
         .and_then(|v| v.as_u64())
 }
+
+/// Сегменты, которые этим запросом искать нельзя: не отсечены и не
+/// просмотрены, курсор по ним остаётся тем, что пришёл в запросе.
+#[derive(Default)]
+struct FailedSegments {
+    list: Vec<SegmentFailure>,
+    /// пустые части с позицией из входного курсора
+    held: Vec<SegmentTaskOutput>,
+}
+
+impl FailedSegments {
+    fn push(&mut self, seg_path: &str, cursor_docid: Option<u64>, e: anyhow::Error) {
+        tracing::warn!(seg = %seg_path, error = %e, "segment skipped");
+        self.list.push(SegmentFailure {
+            segment: seg_path.to_string(),
+            error: format!("{e:#}"),
+        });
+        if cursor_docid.is_some() {
+            self.held.push(SegmentTaskOutput {
+                last_docid: cursor_docid,
+                ..SegmentTaskOutput::empty(seg_path.to_string())
+            });
+        }
+    }
+}
+
+/// Движок verify в метриках `/msearch`: все паттерны — одним `RegexSet`.
+const MULTI_VERIFY_ENGINE: &str = "regex_set";
+
+/// Где искать: общая часть запросов `/search` и `/msearch`.
+struct Scope<'r> {
+    segments: &'r [String],
+    shards: Option<&'r Vec<u64>>,
+    filters: &'r [RangeFilter],
+    page: &'r PageIn,
+    limits: Option<&'r SearchLimits>,
+}
+
+/// Сборка запроса под схему анализаторов.
+type CompileFn<'a> = Box<dyn Fn(&Analyzers) -> anyhow::Result<SegmentQuery> + Send + 'a>;
+
+/// Запрос, собранный под схемы анализаторов сегментов: паттерны приводятся
+/// анализатором поля, поэтому на каждую схему — своя сборка (одна на запрос).
+struct QueryBySchema<'a> {
+    compile: CompileFn<'a>,
+    standard: SegmentQuery,
+    custom: Vec<(Analyzers, SegmentQuery)>,
+}
+
+impl<'a> QueryBySchema<'a> {
+    fn new(
+        compile: impl Fn(&Analyzers) -> anyhow::Result<SegmentQuery> + Send + 'a,
+    ) -> anyhow::Result<Self> {
+        let standard = compile(&Analyzers::default())?;
+        Ok(Self { compile: Box::new(compile), standard, custom: Vec::new() })
+    }
+
+    fn for_analyzers(&mut self, analyzers: &Analyzers) -> anyhow::Result<SegmentQuery> {
+        if analyzers.is_standard() {
+            return Ok(self.standard.clone());
+        }
+        if let Some((_, q)) = self.custom.iter().find(|(a, _)| a == analyzers) {
+            return Ok(q.clone());
+        }
+        let q = (self.compile)(analyzers)?;
+        self.custom.push((analyzers.clone(), q.clone()));
+        Ok(q)
+    }
+
+    fn for_segment(&mut self, seg_path: &str) -> anyhow::Result<SegmentQuery> {
+        self.for_analyzers(&Analyzers::load(std::path::Path::new(seg_path))?)
+    }
+}
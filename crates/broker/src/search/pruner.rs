@@ -0,0 +1,56 @@
+// crates/broker/src/search/pruner.rs
+//
+// Отсечение сегментов до открытия: по сводке `summary.bin` (число документов,
+// поля, Bloom-фильтр 3-грамм, диапазоны doc-values) решаем, может ли сегмент
+// дать совпадения. Отсечённый сегмент не открывается, но попадает в курсор
+// как полностью просмотренный.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use grepzilla_segment::doc_values::RangeFilter;
+use grepzilla_segment::query::CompiledQuery;
+use grepzilla_segment::summary::SegmentSummary;
+
+use crate::search::executor::SegmentTaskOutput;
+
+pub struct SegmentPruner {
+    filters: Arc<[RangeFilter]>,
+}
+
+impl SegmentPruner {
+    pub fn new(filters: Arc<[RangeFilter]>) -> Self {
+        Self { filters }
+    }
+
+    /// `Some(out)` — сегмент отсечён: пустой результат с курсором за
+    /// последним документом. `None` — сегмент нужно искать (в том числе
+    /// если сводки нет или она не читается). `query` собран под
+    /// анализаторы этого сегмента.
+    pub fn prune(
+        &self,
+        seg_path: &str,
+        query: &CompiledQuery,
+        cursor_docid: Option<u64>,
+    ) -> Option<SegmentTaskOutput> {
+        let summary = match SegmentSummary::load(Path::new(seg_path)) {
+            Ok(Some(s)) => s,
+            Ok(None) => return None,
+            Err(e) => {
+                tracing::warn!(seg = seg_path, error = %e, "segment summary unreadable, not pruning");
+                return None;
+            }
+        };
+        // каждый `must`/`should` сверяется со сводкой; `must_not` не отсекает.
+        // Ошибку разбора шаблона вернёт сам поиск по сегменту
+        let may_match =
+            query.may_match(|grams, field| summary.may_match(grams, field, &self.filters));
+        if may_match {
+            return None;
+        }
+        let mut out = SegmentTaskOutput::empty(seg_path.to_string());
+        let last = summary.doc_count.checked_sub(1).map(u64::from);
+        out.last_docid = Some(last.max(cursor_docid).unwrap_or(0));
+        Some(out)
+    }
+}
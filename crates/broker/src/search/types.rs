@@ -1,3 +1,7 @@
+use grepzilla_segment::doc_values::RangeFilter;
+use grepzilla_segment::multi::Pattern;
+use grepzilla_segment::normalizer::Sensitivity;
+use grepzilla_segment::query::{BoolQuery, Clause, InvalidQuery};
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, time::Duration};
 
@@ -20,6 +24,11 @@ pub struct SearchLimits {
     pub deadline_ms: Option<u64>,
     #[serde(default)]
     pub max_candidates: Option<u64>,
+    /// Пункт без грамм (`*`, короткий литерал без индекса 1–2-грамм)
+    /// просматривает сегмент целиком, не больше `max_candidates` документов
+    /// на страницу, вместо ошибки
+    #[serde(default)]
+    pub full_scan: Option<bool>,
 }
 
 impl SearchLimits {
@@ -32,18 +41,82 @@ impl SearchLimits {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub struct SearchRequest {
+    /// Один wildcard; взаимоисключается с `regex` и `query`
+    #[serde(default)]
     pub wildcard: String,
+    /// Регулярное выражение вместо wildcard (синтаксис `regex`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
     #[serde(default)]
     pub field: Option<String>, // опционально
+    /// Булев запрос по полям (`must`/`should`/`must_not`), см.
+    /// [`grepzilla_segment::query`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<BoolQuery>,
+    /// `case_sensitive` / `accent_sensitive` / `width_sensitive`: строгий
+    /// verify по исходному тексту, префильтр — по свёрнутым граммам
+    #[serde(flatten)]
+    pub sensitivity: Sensitivity,
+    /// Движок verify для этого запроса (`regex`, `pcre2`, ...); без него —
+    /// движок брокера (`GZ_VERIFY`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify_engine: Option<String>,
     #[serde(default)]
     pub segments: Vec<String>, // B5
     #[serde(default)]
     pub shards: Option<Vec<u64>>, // B6
+    /// Фильтры по doc-values (AND), применяются битмапами до verify:
+    /// `[{"field":"ts","gte":"2025-08-20T12:00:00Z"}]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<RangeFilter>,
     pub page: PageIn,
     #[serde(default)]
     pub limits: Option<SearchLimits>,
 }
 
+/// Запрос `/msearch`: много wildcard по одним сегментам за один проход.
+/// Хит помечается id всех совпавших паттернов (`Hit::patterns`).
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct MultiSearchRequest {
+    pub patterns: Vec<Pattern>,
+    #[serde(default)]
+    pub segments: Vec<String>,
+    #[serde(default)]
+    pub shards: Option<Vec<u64>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<RangeFilter>,
+    pub page: PageIn,
+    #[serde(default)]
+    pub limits: Option<SearchLimits>,
+}
+
+impl SearchRequest {
+    /// Запрос в виде дерева: `query` как есть либо `wildcard`/`regex` +
+    /// `field` единственным `must`. Форма проверяется здесь же
+    /// ([`BoolQuery::validate`]): неверная — ошибка клиента.
+    pub fn bool_query(&self) -> Result<BoolQuery, InvalidQuery> {
+        let field = self.field.as_deref().filter(|f| !f.is_empty());
+        let invalid = |msg: &str| Err(InvalidQuery(msg.to_string()));
+        let query = match (&self.query, self.wildcard.is_empty(), &self.regex) {
+            (None, false, None) => BoolQuery::single(Clause::wildcard(&self.wildcard, field)),
+            (None, true, Some(rx)) => BoolQuery::single(Clause::regex(rx, field)),
+            (Some(_), true, None) if field.is_some() => {
+                return invalid(
+                    "`field` goes with `wildcard`/`regex`; set it per clause in `query`",
+                )
+            }
+            (Some(q), true, None) => q.clone(),
+            (None, true, None) => {
+                return invalid("one of `wildcard`, `regex` or `query` is required")
+            }
+            _ => return invalid("use only one of `wildcard`, `regex` or `query`"),
+        };
+        query.validate()?;
+        Ok(query)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub struct PerSegPos {
@@ -66,6 +139,17 @@ pub struct SearchMetrics {
     pub deadline_hit: bool,
     pub saturated_sem: u64,
     pub dedup_dropped: u64,
+    /// Сегменты, отсечённые по сводке (`summary.bin`) без открытия
+    #[serde(default)]
+    pub segments_pruned: u64,
+    /// Сегменты, которые не удалось искать (например, не читается схема
+    /// анализаторов); в `segments_pruned` не входят, курсор по ним не
+    /// продвигается
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments_failed: Vec<SegmentFailure>,
+    /// Движок verify, которым проверялись кандидаты
+    #[serde(default)]
+    pub verify_engine: String,
 
     // NEW (optional, агрегированные по всем сегам):
     #[serde(default)]
@@ -78,6 +162,13 @@ pub struct SearchMetrics {
     pub warmed_docs: Option<u64>,
 }
 
+/// Сегмент, пропущенный из-за ошибки, и её текст.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SegmentFailure {
+    pub segment: String,
+    pub error: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub struct SearchResponse {
@@ -93,4 +184,7 @@ pub struct Hit {
     pub doc_id: u32,
     pub matched_field: String,
     pub preview: String,
+    /// id совпавших паттернов (только в ответе `/msearch`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
 }
@@ -3,24 +3,22 @@
 use anyhow::Result;
 use std::path::Path;
 
-use grepzilla_segment::common::preview::{build_preview, PreviewOpts};
-use grepzilla_segment::gram::{required_grams_from_wildcard, BooleanOp};
+use grepzilla_segment::analyzer::Analyzers;
+use grepzilla_segment::common::preview::{build_preview, snippet_for_match, PreviewOpts};
+use grepzilla_segment::query::{CompiledClause, PrefilterOpts};
 use grepzilla_segment::segjson::JsonSegmentReader;
 use grepzilla_segment::v2::reader::BinSegmentReader;
 use grepzilla_segment::SegmentReader;
 
-use crate::search::executor::{SegmentTaskInput, SegmentTaskOutput};
+use crate::search::executor::{DocMatch, SegmentTaskInput, SegmentTaskOutput};
 use crate::search::types::Hit;
 
 pub async fn search_one_segment(
     input: SegmentTaskInput,
     _ctok: tokio_util::sync::CancellationToken,
 ) -> Result<SegmentTaskOutput> {
-    // normalize wildcard (StoredDoc.fields уже нормализованы)
-    let nq = grepzilla_segment::normalizer::normalize(&input.wildcard);
-    let grams = required_grams_from_wildcard(&nq)?;
-    // движок верификации берём из input (Arc<dyn VerifyEngine>)
-    let eng = input.verify_engine.clone();
+    // запрос уже нормализован и собран координатором (StoredDoc.fields тоже нормализованы)
+    let query = input.query.clone();
 
     let mut hits: Vec<Hit> = Vec::new();
     let mut candidates: u64 = 0;
@@ -40,16 +38,23 @@ pub async fn search_one_segment(
         let reader = BinSegmentReader::open_segment(&input.seg_path)?;
 
         let t0 = std::time::Instant::now();
-        let bm = reader.prefilter(BooleanOp::And, &grams, non_empty(&input.field))?;
+        // блоки постингов до курсора не декодируются; соседние граммы
+        // литерала проверяются на смежность по позиционным маскам;
+        // пункты запроса сводятся по must (AND) / should (OR)
+        let opts = PrefilterOpts {
+            short_grams: reader.has_short_grams(),
+            full_scan: input.full_scan,
+        };
+        let mut bm = query.prefilter(opts, |runs, field| {
+            reader.prefilter_adjacent_after(runs, field, input.cursor_docid)
+        })?;
+        // фильтры по doc-values — битмапами, до verify
+        reader.apply_range_filters(&mut bm, &input.filters)?;
         prefilter_ms += t0.elapsed().as_millis() as u64;
 
         // прогрев OnceCell: page_size * 4 (cap 5000) после курсора
         let warm_cap = (input.page_size.saturating_mul(4)).min(5_000);
-        let warm_vec: Vec<u32> = bm
-            .iter()
-            .skip(skip_from_cursor(input.cursor_docid))
-            .take(warm_cap)
-            .collect();
+        let warm_vec: Vec<u32> = bm.iter().take(warm_cap).collect();
         let tpf0 = std::time::Instant::now();
         warmed_docs = warm_vec.len() as u64;
         reader.prefetch_docs(warm_vec.into_iter());
@@ -62,67 +67,29 @@ pub async fn search_one_segment(
                 }
             }
 
-            // отметим, что этот doc действительно просмотрен
-            last_scanned = Some(doc_id);
-
-            candidates += 1;
-            if candidates > input.max_candidates {
+            // бюджет исчерпан — документ останется следующей странице
+            if candidates >= input.max_candidates {
                 break;
             }
+            candidates += 1;
+
+            // отметим, что этот doc действительно просмотрен
+            last_scanned = Some(doc_id);
 
             let tv0 = std::time::Instant::now();
-            let matched_field = match non_empty(&input.field) {
-                Some(f) => {
-                    if let Some(doc) = reader.get_doc(doc_id) {
-                        let ok = doc.fields.get(f).map(|t| eng.is_match(t)).unwrap_or(false);
-                        if ok {
-                            Some(f.to_string())
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                }
-                None => {
-                    if let Some(doc) = reader.get_doc(doc_id) {
-                        doc.fields
-                            .iter()
-                            .find(|(_, t)| eng.is_match(t))
-                            .map(|(k, _)| k.clone())
-                    } else {
-                        None
-                    }
-                }
-            };
+            let matched = reader.get_doc(doc_id).and_then(|doc| query.matches(doc));
             verify_ms += tv0.elapsed().as_millis() as u64;
 
-            if let Some(mf) = matched_field {
+            if let Some(DocMatch { field: mf, clause, patterns }) = matched {
                 if let Some(doc) = reader.get_doc(doc_id) {
-                    // Пытаемся взять точный матч-спан для превью из verify-движка
-                    let preview = if let Some(txt) = doc.fields.get(&mf) {
-                        if let Some((s, e)) = eng.find(txt) {
-                            snippet_with_highlight_utf8(txt, s, e, 180)
-                        } else {
-                            // fallback — старый универсальный билд
-                            build_preview(
-                                doc,
-                                PreviewOpts {
-                                    preferred_fields: &["text.title", "text.body", "title", "body"],
-                                    max_len: 180,
-                                    highlight_needle: longest_literal_needle(&nq).as_deref(),
-                                },
-                            )
+                    // Пытаемся взять точный матч-спан для превью из verify-движка пункта
+                    let preview = match doc.fields.get(&mf).and_then(|t| clause.engine_for(&mf).find(t)) {
+                        Some((s, e)) => {
+                            let a = reader.analyzers().for_field(&mf);
+                            snippet_for_match(doc, &mf, s, e, 180, a.as_ref())
                         }
-                    } else {
-                        build_preview(
-                            doc,
-                            PreviewOpts {
-                                preferred_fields: &["text.title", "text.body", "title", "body"],
-                                max_len: 180,
-                                highlight_needle: longest_literal_needle(&nq).as_deref(),
-                            },
-                        )
+                        // fallback — старый универсальный билд
+                        None => fallback_preview(doc, clause, reader.analyzers()),
                     };
 
                     hits.push(Hit {
@@ -130,6 +97,7 @@ pub async fn search_one_segment(
                         doc_id: doc.doc_id,
                         matched_field: mf,
                         preview,
+                        patterns,
                     });
                     if hits.len() >= 1024 {
                         break;
@@ -142,7 +110,15 @@ pub async fn search_one_segment(
         let reader = JsonSegmentReader::open_segment(&input.seg_path)?;
 
         let t0 = std::time::Instant::now();
-        let bm = reader.prefilter(BooleanOp::And, &grams, non_empty(&input.field))?;
+        let opts = PrefilterOpts {
+            short_grams: false,
+            full_scan: input.full_scan,
+        };
+        let mut bm = query.prefilter(opts, |runs, field| reader.prefilter_runs(runs, field))?;
+        // в V1 нет doc-values: с фильтрами по диапазону документы не проходят
+        if !input.filters.is_empty() {
+            bm.clear();
+        }
         prefilter_ms += t0.elapsed().as_millis() as u64;
 
         for doc_id in bm.iter() {
@@ -152,86 +128,35 @@ pub async fn search_one_segment(
                 }
             }
 
-            // отметим просмотренный doc
-            last_scanned = Some(doc_id);
-
-            candidates += 1;
-            if candidates > input.max_candidates {
+            if candidates >= input.max_candidates {
                 break;
             }
+            candidates += 1;
+
+            // отметим просмотренный doc
+            last_scanned = Some(doc_id);
 
             let tv0 = std::time::Instant::now();
-            let (matched, matched_field) = match non_empty(&input.field) {
-                Some(f) => {
-                    if let Some(doc) = reader.get_doc(doc_id) {
-                        let ok = doc.fields.get(f).map(|t| eng.is_match(t)).unwrap_or(false);
-                        (ok, ok.then(|| f.to_string()))
-                    } else {
-                        (false, None)
-                    }
-                }
-                None => {
-                    if let Some(doc) = reader.get_doc(doc_id) {
-                        if let Some((k, _)) = doc.fields.iter().find(|(_, t)| eng.is_match(t)) {
-                            (true, Some(k.clone()))
-                        } else {
-                            (false, None)
-                        }
-                    } else {
-                        (false, None)
-                    }
-                }
-            };
+            let matched = reader.get_doc(doc_id).and_then(|doc| query.matches(doc));
             verify_ms += tv0.elapsed().as_millis() as u64;
 
-            if matched {
+            if let Some(DocMatch { field: mf, clause, patterns }) = matched {
                 if let Some(doc) = reader.get_doc(doc_id) {
                     // Аналогичный путь: пытаемся из matched_field взять точный матч
-                    let preview = if let Some(mf) = matched_field.as_ref() {
-                        if let Some(txt) = doc.fields.get(mf) {
-                            if let Some((s, e)) = eng.find(txt) {
-                                snippet_with_highlight_utf8(txt, s, e, 180)
-                            } else {
-                                build_preview(
-                                    doc,
-                                    PreviewOpts {
-                                        preferred_fields: &[
-                                            "text.title",
-                                            "text.body",
-                                            "title",
-                                            "body",
-                                        ],
-                                        max_len: 180,
-                                        highlight_needle: longest_literal_needle(&nq).as_deref(),
-                                    },
-                                )
-                            }
-                        } else {
-                            build_preview(
-                                doc,
-                                PreviewOpts {
-                                    preferred_fields: &["text.title", "text.body", "title", "body"],
-                                    max_len: 180,
-                                    highlight_needle: longest_literal_needle(&nq).as_deref(),
-                                },
-                            )
+                    let preview = match doc.fields.get(&mf).and_then(|t| clause.engine_for(&mf).find(t)) {
+                        Some((s, e)) => {
+                            let a = reader.analyzers().for_field(&mf);
+                            snippet_for_match(doc, &mf, s, e, 180, a.as_ref())
                         }
-                    } else {
-                        build_preview(
-                            doc,
-                            PreviewOpts {
-                                preferred_fields: &["text.title", "text.body", "title", "body"],
-                                max_len: 180,
-                                highlight_needle: longest_literal_needle(&nq).as_deref(),
-                            },
-                        )
+                        None => fallback_preview(doc, clause, reader.analyzers()),
                     };
 
                     hits.push(Hit {
                         ext_id: doc.ext_id.clone(),
                         doc_id: doc.doc_id,
-                        matched_field: matched_field.unwrap_or_default(),
+                        matched_field: mf,
                         preview,
+                        patterns,
                     });
                     if hits.len() >= 1024 {
                         break;
@@ -256,128 +181,22 @@ pub async fn search_one_segment(
         verify_ms,
         prefetch_ms,
         warmed_docs,
+        error: None,
     })
 }
 
-#[inline]
-fn non_empty(s: &str) -> Option<&str> {
-    if s.is_empty() {
-        None
-    } else {
-        Some(s)
-    }
-}
-
-#[inline]
-fn skip_from_cursor(cur: Option<u64>) -> usize {
-    match cur {
-        Some(v) if v > 0 => v as usize,
-        _ => 0,
-    }
-}
-
-#[inline]
-fn longest_literal_needle(wc: &str) -> Option<String> {
-    let mut best = String::new();
-    let mut cur = String::new();
-    for ch in wc.chars() {
-        match ch {
-            '*' | '?' => {
-                if cur.len() > best.len() {
-                    best = std::mem::take(&mut cur);
-                } else {
-                    cur.clear();
-                }
-            }
-            _ => cur.push(ch),
-        }
-    }
-    if cur.len() > best.len() {
-        best = cur;
-    }
-    if best.is_empty() {
-        None
-    } else {
-        Some(best)
-    }
-}
-
-/// Построить сниппет по байтовым смещениям матча с корректными границами UTF-8.
-/// Всегда вставляет `[` и `]` вокруг матча; добавляет `…` если фрагмент усечён.
-fn snippet_with_highlight_utf8(
-    s: &str,
-    m_start_b: usize,
-    m_end_b: usize,
-    max_chars: usize,
+fn fallback_preview(
+    doc: &grepzilla_segment::StoredDoc,
+    clause: &CompiledClause,
+    analyzers: &Analyzers,
 ) -> String {
-    if max_chars == 0 || s.is_empty() {
-        return String::new();
-    }
-
-    let (byte_of_char, total_chars) = index_chars(s);
-
-    // Переводим байтовые индексы в индексы по символам
-    let m_start_c = byte_to_char_idx(&byte_of_char, m_start_b);
-    let m_end_c = byte_to_char_idx(&byte_of_char, m_end_b);
-    let match_len_c = m_end_c.saturating_sub(m_start_c);
-
-    let budget = max_chars.saturating_sub(match_len_c + 2); // +2 на скобки
-    let ctx = budget / 2;
-
-    let from_c = m_start_c.saturating_sub(ctx);
-    let to_c = (m_end_c + ctx).min(total_chars);
-
-    let from_b = byte_of_char[from_c];
-    let to_b = byte_of_char[to_c];
-
-    let mut out = String::new();
-    if from_c > 0 {
-        out.push('…');
-    }
-    out.push_str(&s[from_b..m_start_b]);
-    out.push('[');
-    out.push_str(&s[m_start_b..m_end_b]);
-    out.push(']');
-    out.push_str(&s[m_end_b..to_b]);
-    if to_c < total_chars {
-        out.push('…');
-    }
-
-    ensure_max_chars(&out, max_chars + 4)
-}
-
-/// Таблица «индекс символа → байтовое смещение», с завершающим s.len().
-fn index_chars(s: &str) -> (Vec<usize>, usize) {
-    let mut byte_of_char = Vec::with_capacity(s.len() + 1);
-    for (b, _) in s.char_indices() {
-        byte_of_char.push(b);
-    }
-    byte_of_char.push(s.len());
-    let total_chars = byte_of_char.len() - 1;
-    (byte_of_char, total_chars)
-}
-
-/// Перевод байтового индекса в индекс символа (ближайшая левая граница).
-fn byte_to_char_idx(byte_of_char: &[usize], byte_idx: usize) -> usize {
-    match byte_of_char.binary_search(&byte_idx) {
-        Ok(i) => i,
-        Err(pos) => pos.saturating_sub(1),
-    }
-}
-
-/// Жёсткая страховка по длине в символах (без паник).
-fn ensure_max_chars(s: &str, max_chars: usize) -> String {
-    let mut it = s.chars();
-    let mut out = String::new();
-    for _ in 0..max_chars {
-        if let Some(ch) = it.next() {
-            out.push(ch);
-        } else {
-            return out;
-        }
-    }
-    if it.next().is_some() {
-        out.push('…');
-    }
-    out
+    build_preview(
+        doc,
+        PreviewOpts {
+            preferred_fields: &["text.title", "text.body", "title", "body"],
+            max_len: 180,
+            highlight_needle: clause.needle().as_deref(),
+            analyzers,
+        },
+    )
 }
@@ -13,6 +13,8 @@ mod tests {
         std::env::set_var("GZ_HOT_CAP", "1234");
         std::env::set_var("GZ_MANIFEST", "/tmp/manifest.json");
         std::env::set_var("GZ_SHARD", "77");
+        std::env::set_var("GZ_VERIFY", "pcre2");
+        std::env::set_var("GZ_MAX_PATTERN_LEN", "256");
 
         let cfg = BrokerConfig::from_env();
 
@@ -23,6 +25,8 @@ mod tests {
         assert_eq!(cfg.hot_cap, 1234);
         assert_eq!(cfg.manifest_path.as_deref(), Some("/tmp/manifest.json"));
         assert_eq!(cfg.shard, 77);
+        assert_eq!(cfg.verify_engine, "pcre2");
+        assert_eq!(cfg.query_limits.max_pattern_len, 256);
 
         // cleanup (по желанию)
         std::env::remove_var("GZ_ADDR");
@@ -32,5 +36,7 @@ mod tests {
         std::env::remove_var("GZ_HOT_CAP");
         std::env::remove_var("GZ_MANIFEST");
         std::env::remove_var("GZ_SHARD");
+        std::env::remove_var("GZ_VERIFY");
+        std::env::remove_var("GZ_MAX_PATTERN_LEN");
     }
 }
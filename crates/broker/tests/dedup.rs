@@ -13,6 +13,7 @@ fn mk_seg(seg_path: &str, hits: Vec<Hit>) -> SegmentTaskOutput {
         verify_ms: 0,
         prefetch_ms: 0,
         warmed_docs: 0,
+        error: None,
     }
 }
 
@@ -23,12 +24,14 @@ fn merge_dedups_by_ext_id_across_segments() {
         doc_id: 10,
         matched_field: "text.body".into(),
         preview: "first".into(),
+        patterns: Vec::new(),
     };
     let h2 = Hit {
         ext_id: "same-ext-id".into(),
         doc_id: 11,
         matched_field: "text.body".into(),
         preview: "second".into(),
+        patterns: Vec::new(),
     };
 
     let parts = vec![
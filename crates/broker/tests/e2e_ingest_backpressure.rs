@@ -27,6 +27,9 @@ async fn returns_503_when_hotmem_hard_cap_reached() {
                 .to_string(),
         ),
         shard: 1,
+        analyzers_path: None,
+        verify_engine: "regex".into(),
+        query_limits: Default::default(),
     };
 
     let app = make_router_with_config(cfg);
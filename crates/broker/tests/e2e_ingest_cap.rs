@@ -24,6 +24,9 @@ async fn hotmem_respects_cap() {
         hot_cap: 3, // cap = 3, чтобы "1","2" выкинулись
         manifest_path: None,
         shard: 0,
+        analyzers_path: None,
+        verify_engine: "regex".into(),
+        query_limits: Default::default(),
     };
 
     let app = make_router_with_config(cfg);
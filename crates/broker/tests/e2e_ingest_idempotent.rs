@@ -28,6 +28,9 @@ async fn idempotent_post_is_not_duplicated() {
                 .to_string(),
         ),
         shard: 1,
+        analyzers_path: None,
+        verify_engine: "regex".into(),
+        query_limits: Default::default(),
     };
 
     let app = make_router_with_config(cfg);
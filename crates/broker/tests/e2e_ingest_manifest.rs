@@ -33,6 +33,9 @@ async fn ingest_appends_segment_to_manifest_for_single_shard() {
         hot_cap: 10_000,
         manifest_path: Some(tmp.path().join("manifest.json").to_string_lossy().to_string()),
         shard: 42,
+        analyzers_path: None,
+        verify_engine: "regex".into(),
+        query_limits: Default::default(),
     };
 
     let app = make_router_with_config(cfg.clone());
@@ -65,6 +68,9 @@ async fn second_ingest_increments_generation() {
         hot_cap: 10_000,
         manifest_path: Some(tmp.path().join("manifest.json").to_string_lossy().to_string()),
         shard: 7,
+        analyzers_path: None,
+        verify_engine: "regex".into(),
+        query_limits: Default::default(),
     };
 
     let app = make_router_with_config(cfg);
@@ -15,6 +15,9 @@ async fn corrupt_wal_sidecar_yields_segment_error_but_ok() {
         parallelism:1, hot_cap:10_000,
         manifest_path: Some(tmp.path().join("manifest.json").to_string_lossy().to_string()),
         shard: 1,
+        analyzers_path: None,
+        verify_engine: "regex".into(),
+        query_limits: Default::default(),
     };
     let app = make_router_with_config(cfg);
 
@@ -7,14 +7,19 @@ use broker::search::SearchCoordinator;
 use std::sync::Arc;
 
 pub fn make_router_with_config(cfg: BrokerConfig) -> Router {
-    // создаём HotMem с нужной ёмкостью
-    let hot = HotMem::new().with_cap(cfg.hot_cap);
+    // создаём HotMem с нужной ёмкостью и схемой анализаторов
+    let hot = HotMem::new()
+        .with_cap(cfg.hot_cap)
+        .with_analyzers(cfg.analyzers().expect("analyzers schema"));
 
     // ВАЖНО: прокинуть hot в координатор поиска,
     // чтобы /search видел документы, которые мы только что залили через /ingest
     let coord = Arc::new(
         SearchCoordinator::new(cfg.parallelism)
-            .with_hot(hot.clone())
+            .with_verify_engine(&cfg.verify_engine)
+            .expect("verify engine")
+            .with_query_limits(cfg.query_limits)
+            .with_hot(hot.clone()),
     );
 
     let state = AppState { coord, cfg, hot };
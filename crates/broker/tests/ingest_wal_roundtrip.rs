@@ -29,5 +29,8 @@ fn test_cfg(tmp: &tempfile::TempDir, parallelism: usize) -> BrokerConfig {
         hot_cap: 10_000,
         manifest_path: Some(tmp.path().join("manifest.json").to_string_lossy().to_string()),
         shard: 0,
+        analyzers_path: None,
+        verify_engine: "regex".into(),
+        query_limits: Default::default(),
     }
 }
@@ -20,6 +20,7 @@ fn mk_seg(
         verify_ms: verify,
         prefetch_ms: prefetch,
         warmed_docs: warmed,
+        error: None,
     }
 }
 
@@ -30,6 +31,7 @@ fn paginator_aggregates_metrics_and_reports_dedup() {
         doc_id: 1,
         matched_field: "text.body".into(),
         preview: "...".into(),
+        patterns: Vec::new(),
     };
 
     let parts = vec![
@@ -50,6 +52,9 @@ fn paginator_aggregates_metrics_and_reports_dedup() {
             deadline_hit: false,
             saturated_sem: 0,
             dedup_dropped,
+            segments_pruned: 0,
+            segments_failed: Vec::new(),
+            verify_engine: "regex".into(),
             prefilter_ms: Some(prefilter_ms_total),
             verify_ms: Some(verify_ms_total),
             prefetch_ms: Some(prefetch_ms_total),
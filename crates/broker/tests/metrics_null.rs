@@ -27,6 +27,9 @@ fn metrics_are_null_when_empty() {
             deadline_hit: false,
             saturated_sem: 0,
             dedup_dropped,
+            segments_pruned: 0,
+            segments_failed: Vec::new(),
+            verify_engine: "regex".into(),
             prefilter_ms: if has_any_metrics {
                 Some(prefilter_ms_total)
             } else {
@@ -0,0 +1,247 @@
+// broker/tests/msearch.rs
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use broker::ingest::hot::HotMem;
+use broker::search::types::*;
+use broker::search::SearchCoordinator;
+use grepzilla_segment::limits::{LimitExceeded, QueryLimits};
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::SegmentWriter;
+use serde_json::{json, Value};
+use std::path::Path;
+use tower::util::ServiceExt;
+
+mod helpers;
+use helpers::make_router_with_parallelism;
+
+const DOCS: &[&str] = &[
+    r#"{"_id":"a","text":{"title":"Connection timeout","body":"retry later"}}"#,
+    r#"{"_id":"b","text":{"title":"Disk full","body":"connection refused"}}"#,
+    r#"{"_id":"c","text":{"title":"All good","body":"nothing to see"}}"#,
+];
+
+fn build(dir: &Path, name: &str, docs: &[&str]) -> String {
+    let input = dir.join(format!("{name}.jsonl"));
+    std::fs::write(&input, docs.join("\n")).unwrap();
+    let out = dir.join(name).to_str().unwrap().to_string();
+    BinSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), &out)
+        .unwrap();
+    out
+}
+
+fn request(segments: &[&str], patterns: Value) -> MultiSearchRequest {
+    serde_json::from_value(json!({
+        "patterns": patterns,
+        "segments": segments,
+        "page": { "size": 10, "cursor": null }
+    }))
+    .unwrap()
+}
+
+fn tagged(resp: &SearchResponse) -> Vec<(&str, Vec<&str>)> {
+    let mut v: Vec<_> = resp
+        .hits
+        .iter()
+        .map(|h| {
+            let ids = h.patterns.iter().map(String::as_str).collect();
+            (h.ext_id.as_str(), ids)
+        })
+        .collect();
+    v.sort();
+    v
+}
+
+#[tokio::test]
+async fn hits_carry_matching_pattern_ids() {
+    let tmp = tempfile::tempdir().unwrap();
+    let s1 = build(tmp.path(), "s1", &DOCS[..2]);
+    let s2 = build(tmp.path(), "s2", &DOCS[2..]);
+    let coord = SearchCoordinator::new(2);
+
+    let resp = coord
+        .handle_multi(request(
+            &[&s1, &s2],
+            json!([
+                { "id": "timeout", "wildcard": "*timeout*" },
+                { "id": "conn", "wildcard": "*connection*" },
+                { "id": "conn-body", "wildcard": "*connection*", "field": "text.body" },
+                { "id": "good", "wildcard": "*good*" }
+            ]),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        tagged(&resp),
+        [
+            ("a", vec!["timeout", "conn"]),
+            ("b", vec!["conn", "conn-body"]),
+            ("c", vec!["good"]),
+        ]
+    );
+    assert_eq!(resp.metrics.verify_engine, "regex_set");
+    // курсор — как у /search: по сегментам
+    let cursor = resp.cursor.unwrap();
+    assert!(cursor.per_seg.contains_key(&s1) && cursor.per_seg.contains_key(&s2));
+}
+
+#[tokio::test]
+async fn hot_docs_are_tagged_too() {
+    let hot = HotMem::new();
+    let docs = DOCS
+        .iter()
+        .map(|d| serde_json::from_str(d).unwrap())
+        .collect();
+    assert!(hot.apply(docs, None).is_ok());
+    let coord = SearchCoordinator::new(1).with_hot(hot);
+
+    let resp = coord
+        .handle_multi(request(
+            &[],
+            json!([
+                { "id": "refused", "wildcard": "*refused*" },
+                { "id": "disk", "wildcard": "disk*", "field": "text.title" }
+            ]),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(tagged(&resp), [("b", vec!["refused", "disk"])]);
+}
+
+async fn post(body: Value) -> (StatusCode, Value) {
+    let app = make_router_with_parallelism(2);
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/msearch")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = resp.status();
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+async fn http_msearch_and_limits() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build(tmp.path(), "seg", DOCS);
+
+    let (status, body) = post(json!({
+        "patterns": [
+            { "id": "t", "wildcard": "*timeout*" },
+            { "id": "r", "wildcard": "*retry*" }
+        ],
+        "segments": [seg],
+        "page": { "size": 10, "cursor": null }
+    }))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["hits"][0]["ext_id"], "a");
+    assert_eq!(body["hits"][0]["patterns"], json!(["t", "r"]));
+
+    let (status, body) = post(json!({
+        "patterns": [
+            { "id": "ok", "wildcard": "*timeout*" },
+            { "id": "huge", "wildcard": "x".repeat(5000) }
+        ],
+        "segments": [seg],
+        "page": { "size": 10, "cursor": null }
+    }))
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "query_too_complex");
+    assert_eq!(body["clause"], "patterns[1]");
+}
+
+#[tokio::test]
+async fn http_rejects_bad_pattern_sets_with_400() {
+    for (patterns, message) in [
+        (json!([]), "at least one pattern"),
+        (
+            json!([
+                { "id": "x", "wildcard": "*timeout*" },
+                { "id": "x", "wildcard": "*retry*" }
+            ]),
+            "duplicate pattern id `x`",
+        ),
+    ] {
+        let (status, body) = post(json!({
+            "patterns": patterns,
+            "segments": ["does/not/exist"],
+            "page": { "size": 10, "cursor": null }
+        }))
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+        assert_eq!(body["error"], "invalid_query");
+        assert!(
+            body["message"].as_str().unwrap().contains(message),
+            "{body}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn weak_pattern_is_400_not_empty_page() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build(tmp.path(), "seg", DOCS);
+    let patterns = json!([
+        { "id": "weak", "wildcard": "*ab*" },
+        { "id": "r", "wildcard": "*retry*" }
+    ]);
+
+    let (status, body) = post(json!({
+        "patterns": patterns,
+        "segments": [seg],
+        "page": { "size": 10, "cursor": null }
+    }))
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    assert_eq!(body["error"], "invalid_query");
+    assert_eq!(
+        body["message"],
+        "patterns[0]: pattern too weak; need ≥3 consecutive literal chars"
+    );
+
+    // с полным просмотром ищутся все паттерны
+    let (status, body) = post(json!({
+        "patterns": patterns,
+        "segments": [seg],
+        "page": { "size": 10, "cursor": null },
+        "limits": { "full_scan": true }
+    }))
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["hits"][0]["ext_id"], "a");
+    assert_eq!(body["hits"][0]["patterns"], json!(["r"]));
+}
+
+#[tokio::test]
+async fn pattern_count_is_limited() {
+    let coord = SearchCoordinator::new(2).with_query_limits(QueryLimits {
+        max_patterns: 2,
+        ..Default::default()
+    });
+    let patterns = json!([
+        { "id": "a", "wildcard": "*timeout*" },
+        { "id": "b", "wildcard": "*retry*" },
+        { "id": "c", "wildcard": "*refused*" }
+    ]);
+    let err = coord
+        .handle_multi(request(&["does/not/exist"], patterns))
+        .await
+        .unwrap_err();
+    let limit = err.downcast_ref::<LimitExceeded>().expect("limit error");
+    assert_eq!(
+        (limit.limit, limit.clause.as_str(), limit.actual),
+        ("max_patterns", "patterns", Some(3))
+    );
+}
@@ -14,6 +14,7 @@ fn mk_seg(seg_path: &str, hits: Vec<Hit>, last_docid: u64) -> SegmentTaskOutput
         verify_ms: 0,
         prefetch_ms: 0,
         warmed_docs: 0,
+        error: None,
     }
 }
 
@@ -29,12 +30,14 @@ fn two_pages_do_not_overlap() {
                     doc_id: 1,
                     matched_field: "text".into(),
                     preview: "...".into(),
+                    patterns: Vec::new(),
                 },
                 Hit {
                     ext_id: "id-2".into(),
                     doc_id: 2,
                     matched_field: "text".into(),
                     preview: "...".into(),
+                    patterns: Vec::new(),
                 },
             ],
             /*last_docid*/ 2,
@@ -46,6 +49,7 @@ fn two_pages_do_not_overlap() {
                 doc_id: 7,
                 matched_field: "text".into(),
                 preview: "...".into(),
+                patterns: Vec::new(),
             }],
             /*last_docid*/ 7,
         ),
@@ -63,6 +67,7 @@ fn two_pages_do_not_overlap() {
                 doc_id: 3,
                 matched_field: "text".into(),
                 preview: "...".into(),
+                patterns: Vec::new(),
             }],
             /*last_docid*/ 3,
         ),
@@ -73,6 +78,7 @@ fn two_pages_do_not_overlap() {
                 doc_id: 8,
                 matched_field: "text".into(),
                 preview: "...".into(),
+                patterns: Vec::new(),
             }],
             /*last_docid*/ 8,
         ),
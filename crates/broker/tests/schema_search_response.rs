@@ -13,6 +13,9 @@ fn search_response_schema_minimal() {
             deadline_hit: false,
             saturated_sem: 0,
             dedup_dropped: 0,
+            segments_pruned: 0,
+            segments_failed: Vec::new(),
+            verify_engine: "regex".into(),
             prefilter_ms: None,
             verify_ms: None,
             prefetch_ms: None,
@@ -35,6 +38,7 @@ fn search_response_schema_minimal() {
         "deadline_hit",
         "saturated_sem",
         "dedup_dropped",
+        "segments_pruned",
         "prefilter_ms",
         "verify_ms",
         "prefetch_ms",
@@ -65,6 +69,7 @@ fn search_response_schema_full_hit() {
             doc_id: 123,
             matched_field: "text.body".into(),
             preview: "...".into(),
+            patterns: Vec::new(),
         }],
         cursor: Some(SearchCursor {
             per_seg: std::iter::once((
@@ -80,6 +85,9 @@ fn search_response_schema_full_hit() {
             deadline_hit: false,
             saturated_sem: 0,
             dedup_dropped: 1,
+            segments_pruned: 0,
+            segments_failed: Vec::new(),
+            verify_engine: "regex".into(),
             prefilter_ms: Some(5),
             verify_ms: Some(4),
             prefetch_ms: Some(1),
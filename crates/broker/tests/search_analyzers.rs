@@ -0,0 +1,118 @@
+// broker/tests/search_analyzers.rs
+use broker::ingest::hot::HotMem;
+use broker::search::types::*;
+use broker::search::SearchCoordinator;
+use grepzilla_segment::analyzer::{AnalyzerSchema, Analyzers};
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::SegmentWriter;
+use serde_json::json;
+use std::path::Path;
+
+const DOCS: &[&str] = &[
+    r#"{"_id":"a","text":{"body":"привет"}}"#,
+    r#"{"_id":"b","text":{"body":"ghbdtn"}}"#,
+];
+
+fn layout_schema() -> Analyzers {
+    let schema: AnalyzerSchema = serde_json::from_value(json!({
+        "analyzers": { "layout": ["lowercase", "keyboard_layout"] },
+        "fields": { "text.body": "layout" }
+    }))
+    .unwrap();
+    Analyzers::new(schema).unwrap()
+}
+
+fn build(dir: &Path, name: &str, analyzers: Analyzers) -> String {
+    let input = dir.join("docs.jsonl");
+    std::fs::write(&input, DOCS.join("\n")).unwrap();
+    let out = dir.join(name).to_str().unwrap().to_string();
+    BinSegmentWriter::default()
+        .with_analyzers(analyzers)
+        .write_segment(input.to_str().unwrap(), &out)
+        .unwrap();
+    out
+}
+
+fn request(segments: &[&str], wildcard: &str) -> SearchRequest {
+    serde_json::from_value(json!({
+        "segments": segments,
+        "wildcard": wildcard,
+        "field": "text.body",
+        "page": { "size": 10, "cursor": null }
+    }))
+    .unwrap()
+}
+
+fn ids(resp: &SearchResponse) -> Vec<&str> {
+    let mut v: Vec<_> = resp.hits.iter().map(|h| h.ext_id.as_str()).collect();
+    v.sort();
+    v
+}
+
+#[tokio::test]
+async fn query_follows_segment_analyzers() {
+    let tmp = tempfile::tempdir().unwrap();
+    let layout = build(tmp.path(), "layout", layout_schema());
+    let plain = build(tmp.path(), "plain", Analyzers::default());
+    let coord = SearchCoordinator::new(2);
+
+    // в одном запросе паттерн приводится схемой каждого сегмента
+    // (одинаковые _id из разных сегментов схлопываются)
+    let resp = coord
+        .handle(request(&[&plain, &layout], "*привет*"))
+        .await
+        .unwrap();
+    assert_eq!(ids(&resp), ["a", "b"]);
+
+    let resp = coord.handle(request(&[&plain], "*привет*")).await.unwrap();
+    assert_eq!(ids(&resp), ["a"]);
+    let resp = coord.handle(request(&[&layout], "*ghbdtn*")).await.unwrap();
+    assert_eq!(ids(&resp), ["a", "b"]);
+}
+
+#[tokio::test]
+async fn hot_docs_use_configured_analyzers() {
+    let hot = HotMem::new().with_analyzers(layout_schema());
+    let docs = DOCS
+        .iter()
+        .map(|d| serde_json::from_str(d).unwrap())
+        .collect();
+    assert!(hot.apply(docs, None).is_ok());
+    let coord = SearchCoordinator::new(1).with_hot(hot);
+
+    let resp = coord.handle(request(&[], "*ghbdtn*")).await.unwrap();
+    assert_eq!(ids(&resp), ["a", "b"]);
+}
+
+#[tokio::test]
+async fn unreadable_schema_is_a_failure_not_a_prune() {
+    let tmp = tempfile::tempdir().unwrap();
+    let plain = build(tmp.path(), "plain", Analyzers::default());
+    let broken = build(tmp.path(), "broken", Analyzers::default());
+    std::fs::write(Path::new(&broken).join("analyzers.json"), "{not json").unwrap();
+    let coord = SearchCoordinator::new(2);
+
+    let resp = coord
+        .handle(request(&[&plain, &broken], "*привет*"))
+        .await
+        .unwrap();
+    assert_eq!(ids(&resp), ["a"]);
+    assert_eq!(resp.metrics.segments_pruned, 0);
+    let failed: Vec<_> = resp
+        .metrics
+        .segments_failed
+        .iter()
+        .map(|f| f.segment.as_str())
+        .collect();
+    assert_eq!(failed, [broken.as_str()]);
+    // сегмент не просмотрен: курсора по нему нет
+    let cursor = resp.cursor.unwrap();
+    assert!(cursor.per_seg.contains_key(&plain));
+    assert!(!cursor.per_seg.contains_key(&broken));
+
+    // позиция из входного курсора не сдвигается
+    let mut req = request(&[&broken], "*привет*");
+    req.page.cursor = Some(json!({ "per_seg": { broken.clone(): { "last_docid": 7 } } }));
+    let resp = coord.handle(req).await.unwrap();
+    assert_eq!(resp.cursor.unwrap().per_seg[&broken].last_docid, 7);
+}
@@ -0,0 +1,203 @@
+// broker/tests/search_bool_query.rs
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use broker::search::types::*;
+use broker::search::SearchCoordinator;
+use grepzilla_segment::segjson::JsonSegmentWriter;
+use grepzilla_segment::v2::writer::{BinSegmentWriter, FieldGrams};
+use grepzilla_segment::SegmentWriter;
+use serde_json::{json, Value};
+use std::path::Path;
+use tower::util::ServiceExt;
+
+mod helpers;
+use helpers::make_router_with_parallelism;
+
+const DOCS: &[&str] = &[
+    r#"{"_id":"a","text":{"title":"Timeout on upload","body":"gave up"}}"#,
+    r#"{"_id":"b","text":{"title":"timeout","body":"will retry later"}}"#,
+    r#"{"_id":"c","text":{"title":"ok","body":"timeout in body only"}}"#,
+    r#"{"_id":"d","text":{"title":"disk timeout","body":"disk full"}}"#,
+    r#"{"_id":"e","text":{"title":"network","body":"retry"}}"#,
+];
+
+fn build(dir: &Path) -> (String, String) {
+    let input = dir.join("docs.jsonl");
+    std::fs::write(&input, DOCS.join("\n")).unwrap();
+    let v1 = dir.join("v1").to_str().unwrap().to_string();
+    let v2 = dir.join("v2").to_str().unwrap().to_string();
+    JsonSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), &v1)
+        .unwrap();
+    BinSegmentWriter::default()
+        .with_field_grams(FieldGrams::All)
+        .write_segment(input.to_str().unwrap(), &v2)
+        .unwrap();
+    (v1, v2)
+}
+
+fn request(seg: &str, query: serde_json::Value) -> SearchRequest {
+    serde_json::from_value(json!({
+        "query": query,
+        "segments": [seg],
+        "page": { "size": 10, "cursor": null }
+    }))
+    .unwrap()
+}
+
+fn ids(resp: &SearchResponse) -> Vec<(&str, &str)> {
+    resp.hits
+        .iter()
+        .map(|h| (h.ext_id.as_str(), h.matched_field.as_str()))
+        .collect()
+}
+
+#[tokio::test]
+async fn must_and_must_not_across_fields() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+    let q = json!({
+        "must": [{ "field": "text.title", "wildcard": "*timeout*" }],
+        "must_not": [{ "field": "text.body", "wildcard": "*retry*" }]
+    });
+    for seg in [&v1, &v2] {
+        let resp = coord.handle(request(seg, q.clone())).await.unwrap();
+        assert_eq!(
+            ids(&resp),
+            [("a", "text.title"), ("d", "text.title")],
+            "{seg}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_needs_one_clause() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+    let q = json!({
+        "must": [{ "wildcard": "*timeout*" }],
+        "should": [
+            { "field": "text.body", "wildcard": "*disk*" },
+            { "field": "text.title", "wildcard": "*upload*" }
+        ]
+    });
+    for seg in [&v1, &v2] {
+        let resp = coord.handle(request(seg, q.clone())).await.unwrap();
+        assert_eq!(
+            ids(&resp),
+            [("a", "text.title"), ("d", "text.title")],
+            "{seg}"
+        );
+    }
+
+    // только should: хватает любого
+    let q = json!({
+        "should": [
+            { "field": "text.body", "wildcard": "*retry*" },
+            { "field": "text.body", "wildcard": "*full*" }
+        ]
+    });
+    let resp = coord.handle(request(&v2, q)).await.unwrap();
+    assert_eq!(
+        ids(&resp),
+        [("b", "text.body"), ("d", "text.body"), ("e", "text.body")]
+    );
+}
+
+#[tokio::test]
+async fn pruner_follows_the_tree() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (_, v2) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+
+    // must с граммой, которой нет в сегменте, — сегмент не открывается
+    let q = json!({
+        "must": [{ "wildcard": "*timeout*" }, { "wildcard": "*zebra*" }]
+    });
+    let resp = coord.handle(request(&v2, q)).await.unwrap();
+    assert!(resp.hits.is_empty());
+    assert_eq!(resp.metrics.segments_pruned, 1);
+
+    // should: достаточно одного пункта, который может совпасть
+    let q = json!({
+        "should": [{ "wildcard": "*zebra*" }, { "wildcard": "*network*" }]
+    });
+    let resp = coord.handle(request(&v2, q)).await.unwrap();
+    assert_eq!(ids(&resp), [("e", "text.title")]);
+    assert_eq!(resp.metrics.segments_pruned, 0);
+
+    // must_not сегмент не отсекает
+    let q = json!({
+        "must": [{ "wildcard": "*network*" }],
+        "must_not": [{ "wildcard": "*zebra*" }]
+    });
+    let resp = coord.handle(request(&v2, q)).await.unwrap();
+    assert_eq!(resp.hits.len(), 1);
+    assert_eq!(resp.metrics.segments_pruned, 0);
+}
+
+#[tokio::test]
+async fn rejects_ambiguous_requests() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, _) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+
+    let mut req = request(&v1, json!({ "must": [{ "wildcard": "*timeout*" }] }));
+    req.wildcard = "*retry*".into();
+    assert!(coord.handle(req).await.is_err());
+
+    let mut req = request(&v1, json!({ "must": [{ "wildcard": "*timeout*" }] }));
+    req.field = Some("text.body".into());
+    assert!(coord.handle(req).await.is_err());
+
+    // нет ни must, ни should
+    let req = request(&v1, json!({ "must_not": [{ "wildcard": "*retry*" }] }));
+    assert!(coord.handle(req).await.is_err());
+
+    let mut req = request(&v1, json!({}));
+    req.query = None;
+    assert!(coord.handle(req).await.is_err());
+}
+
+#[tokio::test]
+async fn http_rejects_malformed_query_with_400() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, _) = build(tmp.path());
+    let app = make_router_with_parallelism(2);
+
+    for (query, message) in [
+        (
+            json!({ "must": [], "should": [] }),
+            "query needs at least one must or should clause",
+        ),
+        (
+            json!({ "must": [{ "wildcard": "*timeout*", "regex": "timeout" }] }),
+            "must[0]: clause needs exactly one of `wildcard` or `regex`",
+        ),
+    ] {
+        let body = serde_json::to_vec(&request(&v1, query)).unwrap();
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/search")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(v["error"], "invalid_query");
+        assert_eq!(v["message"], message);
+    }
+}
@@ -0,0 +1,100 @@
+// broker/tests/search_limits.rs
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use broker::search::types::*;
+use broker::search::SearchCoordinator;
+use grepzilla_segment::limits::{LimitExceeded, QueryLimits};
+use serde_json::{json, Value};
+use tower::util::ServiceExt;
+
+mod helpers;
+use helpers::make_router_with_parallelism;
+
+fn request(wildcard: &str) -> SearchRequest {
+    // сегмента нет на диске: лимиты проверяются раньше, чем его откроют
+    serde_json::from_value(json!({
+        "wildcard": wildcard,
+        "segments": ["does/not/exist"],
+        "page": { "size": 10, "cursor": null }
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn coordinator_rejects_before_opening_segments() {
+    let coord = SearchCoordinator::new(2).with_query_limits(QueryLimits {
+        max_wildcard_segments: 3,
+        ..Default::default()
+    });
+    let err = coord.handle(request("*a*b*c*d*")).await.unwrap_err();
+    let limit = err.downcast_ref::<LimitExceeded>().expect("limit error");
+    assert_eq!(limit.limit, "max_wildcard_segments");
+    assert_eq!(limit.actual, Some(4));
+
+    // в пределах лимита — обычный поиск (ошибка отсутствующего сегмента
+    // остаётся ошибкой сегмента)
+    let resp = coord.handle(request("*a*b*c*")).await.unwrap();
+    assert!(resp.hits.is_empty());
+}
+
+#[tokio::test]
+async fn regex_size_is_limited_by_the_engines() {
+    let coord = SearchCoordinator::new(2).with_query_limits(QueryLimits {
+        max_regex_size: 10_000,
+        ..Default::default()
+    });
+    let req: SearchRequest = serde_json::from_value(json!({
+        "query": { "must": [{ "wildcard": "*ok*" }, { "regex": r"\w{50}timeout" }] },
+        "segments": ["does/not/exist"],
+        "page": { "size": 10, "cursor": null }
+    }))
+    .unwrap();
+    let err = coord.handle(req).await.unwrap_err();
+    let limit = err.downcast_ref::<LimitExceeded>().expect("limit error");
+    assert_eq!((limit.limit, limit.max), ("max_regex_size", 10_000));
+    assert_eq!(limit.clause, "must[1]");
+
+    let msearch: MultiSearchRequest = serde_json::from_value(json!({
+        "patterns": (0..64)
+            .map(|i| json!({ "id": i.to_string(), "wildcard": format!("*a?b?c?{i}*") }))
+            .collect::<Vec<_>>(),
+        "segments": ["does/not/exist"],
+        "page": { "size": 10, "cursor": null }
+    }))
+    .unwrap();
+    let err = coord.handle_multi(msearch).await.unwrap_err();
+    let limit = err.downcast_ref::<LimitExceeded>().expect("limit error");
+    assert_eq!(
+        (limit.limit, limit.clause.as_str()),
+        ("max_regex_size", "patterns")
+    );
+}
+
+#[tokio::test]
+async fn http_returns_structured_400() {
+    let app = make_router_with_parallelism(2);
+    let body = serde_json::to_vec(&request(&"x".repeat(5000))).unwrap();
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/search")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let v: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(v["error"], "query_too_complex");
+    assert_eq!(v["limit"], "max_pattern_len");
+    assert_eq!(v["clause"], "must[0]");
+    assert_eq!(v["max"], 1024);
+    assert_eq!(v["actual"], 5000);
+}
@@ -8,6 +8,10 @@ async fn fills_page_and_cancels_rest() {
     let req = SearchRequest {
         wildcard: "*игра*".into(),
         field: Some("text.body".to_string()),
+        regex: None,
+        query: None,
+        sensitivity: Default::default(),
+        verify_engine: None,
         segments: vec![
             "segments/000001".into(),
             "segments/000002".into(),
@@ -21,8 +25,10 @@ async fn fills_page_and_cancels_rest() {
             parallelism: Some(3),
             deadline_ms: Some(500),
             max_candidates: Some(200_000),
+            full_scan: None,
         }),
         shards: None,
+        filters: Vec::new(),
     };
     let resp = coord.handle(req).await.unwrap();
     assert!(resp.hits.len() <= 50);
@@ -0,0 +1,102 @@
+// broker/tests/search_pruning.rs
+use broker::search::types::*;
+use broker::search::SearchCoordinator;
+use grepzilla_segment::doc_values::{RangeFilter, RangeValue};
+use grepzilla_segment::segjson::JsonSegmentWriter;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::SegmentWriter;
+use std::path::Path;
+
+fn build_v2(dir: &Path, name: &str, lines: &[&str]) -> String {
+    let input = dir.join(format!("{name}.jsonl"));
+    std::fs::write(&input, lines.join("\n")).unwrap();
+    let out = dir.join(name);
+    BinSegmentWriter::default()
+        .with_doc_values(vec!["ts:timestamp".parse().unwrap()])
+        .write_segment(input.to_str().unwrap(), out.to_str().unwrap())
+        .unwrap();
+    out.to_str().unwrap().to_string()
+}
+
+fn request(segments: Vec<String>, filters: Vec<RangeFilter>) -> SearchRequest {
+    SearchRequest {
+        wildcard: "*ошибка*".into(),
+        field: None,
+        regex: None,
+        query: None,
+        sensitivity: Default::default(),
+        verify_engine: None,
+        segments,
+        shards: None,
+        filters,
+        page: PageIn {
+            size: 10,
+            cursor: None,
+        },
+        limits: None,
+    }
+}
+
+#[tokio::test]
+async fn prunes_segments_by_summary_and_advances_cursor() {
+    let tmp = tempfile::tempdir().unwrap();
+    let hit = build_v2(
+        tmp.path(),
+        "hit",
+        &[
+            r#"{"_id":"a","ts":"2025-08-20T12:00:00Z","text":{"body":"ошибка диска"}}"#,
+            r#"{"_id":"b","ts":"2025-08-20T12:05:00Z","text":{"body":"всё хорошо"}}"#,
+        ],
+    );
+    // нет нужных грамм
+    let other = build_v2(
+        tmp.path(),
+        "other",
+        &[
+            r#"{"_id":"c","ts":"2025-08-20T12:00:00Z","text":{"body":"всё хорошо"}}"#,
+            r#"{"_id":"d","ts":"2025-08-20T12:01:00Z","text":{"body":"тишина"}}"#,
+            r#"{"_id":"e","ts":"2025-08-20T12:02:00Z","text":{"body":"покой"}}"#,
+        ],
+    );
+    // граммы есть, но время вне фильтра
+    let old = build_v2(
+        tmp.path(),
+        "old",
+        &[r#"{"_id":"f","ts":"2025-08-19T00:00:00Z","text":{"body":"ошибка сети"}}"#],
+    );
+    // V1-сегмент тоже публикует сводку
+    let v1_input = tmp.path().join("v1.jsonl");
+    std::fs::write(&v1_input, r#"{"_id":"g","text":{"body":"тишина"}}"#).unwrap();
+    let v1 = tmp.path().join("v1");
+    JsonSegmentWriter::default()
+        .write_segment(v1_input.to_str().unwrap(), v1.to_str().unwrap())
+        .unwrap();
+    let v1 = v1.to_str().unwrap().to_string();
+
+    let coord = SearchCoordinator::new(2);
+    let all = vec![hit.clone(), other.clone(), old.clone(), v1.clone()];
+
+    let resp = coord
+        .handle(request(all.clone(), Vec::new()))
+        .await
+        .unwrap();
+    let mut ids: Vec<_> = resp.hits.iter().map(|h| h.ext_id.as_str()).collect();
+    ids.sort();
+    assert_eq!(ids, ["a", "f"]);
+    assert_eq!(resp.metrics.segments_pruned, 2);
+    // отсечённые сегменты — в курсоре как просмотренные до конца
+    let cursor = resp.cursor.unwrap();
+    assert_eq!(cursor.per_seg[&other].last_docid, 2);
+    assert_eq!(cursor.per_seg[&v1].last_docid, 0);
+
+    let recent = RangeFilter {
+        field: "ts".into(),
+        gte: Some(RangeValue::Text("2025-08-20T00:00:00Z".into())),
+        ..Default::default()
+    };
+    let resp = coord.handle(request(all, vec![recent])).await.unwrap();
+    let ids: Vec<_> = resp.hits.iter().map(|h| h.ext_id.as_str()).collect();
+    assert_eq!(ids, ["a"]);
+    assert_eq!(resp.metrics.segments_pruned, 3);
+    assert!(resp.cursor.unwrap().per_seg.contains_key(&old));
+}
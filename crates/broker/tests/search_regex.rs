@@ -0,0 +1,192 @@
+// broker/tests/search_regex.rs
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use broker::search::types::*;
+use broker::search::SearchCoordinator;
+use grepzilla_segment::query::InvalidQuery;
+use grepzilla_segment::segjson::JsonSegmentWriter;
+use grepzilla_segment::v2::writer::{BinSegmentWriter, FieldGrams};
+use grepzilla_segment::SegmentWriter;
+use serde_json::{json, Value};
+use std::path::Path;
+use tower::util::ServiceExt;
+
+mod helpers;
+use helpers::make_router_with_parallelism;
+
+const DOCS: &[&str] = &[
+    r#"{"_id":"a","text":{"title":"Upload error 504","body":"gave up"}}"#,
+    r#"{"_id":"b","text":{"title":"errno 13","body":"permission denied"}}"#,
+    r#"{"_id":"c","text":{"title":"ok","body":"error in body only"}}"#,
+    r#"{"_id":"d","text":{"title":"TimeOut after 30s","body":"retry"}}"#,
+    r#"{"_id":"e","text":{"title":"Ошибка сети","body":"timeout"}}"#,
+];
+
+fn build(dir: &Path) -> (String, String) {
+    let input = dir.join("docs.jsonl");
+    std::fs::write(&input, DOCS.join("\n")).unwrap();
+    let v1 = dir.join("v1").to_str().unwrap().to_string();
+    let v2 = dir.join("v2").to_str().unwrap().to_string();
+    JsonSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), &v1)
+        .unwrap();
+    BinSegmentWriter::default()
+        .with_field_grams(FieldGrams::All)
+        .write_segment(input.to_str().unwrap(), &v2)
+        .unwrap();
+    (v1, v2)
+}
+
+fn request(seg: &str, body: serde_json::Value) -> SearchRequest {
+    let mut req = json!({
+        "segments": [seg],
+        "page": { "size": 10, "cursor": null }
+    });
+    for (k, v) in body.as_object().unwrap() {
+        req[k] = v.clone();
+    }
+    serde_json::from_value(req).unwrap()
+}
+
+fn ids(resp: &SearchResponse) -> Vec<(&str, &str)> {
+    resp.hits
+        .iter()
+        .map(|h| (h.ext_id.as_str(), h.matched_field.as_str()))
+        .collect()
+}
+
+#[tokio::test]
+async fn regex_alternation_on_v1_and_v2() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+    for seg in [&v1, &v2] {
+        let req = request(
+            seg,
+            json!({ "regex": r"err(or|no) \d+", "field": "text.title" }),
+        );
+        let resp = coord.handle(req).await.unwrap();
+        assert_eq!(
+            ids(&resp),
+            [("a", "text.title"), ("b", "text.title")],
+            "{seg}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn regex_matches_normalized_text() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+    for seg in [&v1, &v2] {
+        // регистр нормализуется и в тексте, и в выражении
+        let resp = coord
+            .handle(request(seg, json!({ "regex": r"TimeOut after \d+s" })))
+            .await
+            .unwrap();
+        assert_eq!(ids(&resp), [("d", "text.title")], "{seg}");
+
+        let resp = coord
+            .handle(request(seg, json!({ "regex": "ОШИБКА (сети|диска)" })))
+            .await
+            .unwrap();
+        assert_eq!(ids(&resp), [("e", "text.title")], "{seg}");
+    }
+}
+
+#[tokio::test]
+async fn regex_clause_inside_bool_query() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+    let q = json!({
+        "must": [{ "regex": "err(or|no)" }],
+        "must_not": [{ "field": "text.title", "wildcard": "*upload*" }]
+    });
+    for seg in [&v1, &v2] {
+        let resp = coord
+            .handle(request(seg, json!({ "query": q.clone() })))
+            .await
+            .unwrap();
+        assert_eq!(
+            ids(&resp),
+            [("b", "text.title"), ("c", "text.body")],
+            "{seg}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn bad_and_weak_regexes() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+
+    // синтаксическая ошибка — ошибка запроса (клиента)
+    let req = request(&v1, json!({ "regex": "err(or" }));
+    let err = coord.handle(req).await.unwrap_err();
+    let msg = err.downcast_ref::<InvalidQuery>().map(|e| e.0.as_str());
+    assert!(msg.is_some_and(|m| m.starts_with("bad regex")), "{err:#}");
+
+    // wildcard и regex одновременно нельзя
+    let req = request(&v1, json!({ "regex": "error", "wildcard": "*error*" }));
+    assert!(coord.handle(req).await.is_err());
+
+    // без обязательного литерала ≥3 символов сегменты не сканируются:
+    // запрос отвергается до их открытия
+    for seg in [&v1, &v2] {
+        let req = request(seg, json!({ "regex": r"\d+|ok" }));
+        let err = coord.handle(req).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InvalidQuery>().map(|e| e.0.as_str()),
+            Some("must[0]: regex too weak; need a required literal of ≥3 chars"),
+            "{seg}"
+        );
+        // ...если полный просмотр не разрешён явно
+        let req = request(
+            seg,
+            json!({ "regex": r"\d+|ok", "limits": { "full_scan": true } }),
+        );
+        let resp = coord.handle(req).await.unwrap();
+        assert_eq!(resp.hits.len(), 4, "{seg}");
+    }
+}
+
+#[tokio::test]
+async fn http_bad_and_weak_regexes_are_400() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, _) = build(tmp.path());
+    let app = make_router_with_parallelism(2);
+
+    for (regex, message) in [
+        ("(unclosed", "bad regex"),
+        (".*", "must[0]: regex too weak"),
+    ] {
+        let body = serde_json::to_vec(&request(&v1, json!({ "regex": regex }))).unwrap();
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/search")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{regex}");
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(v["error"], "invalid_query", "{regex}");
+        assert!(
+            v["message"].as_str().unwrap().starts_with(message),
+            "{regex}: {v}"
+        );
+    }
+}
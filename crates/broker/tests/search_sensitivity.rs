@@ -0,0 +1,142 @@
+// broker/tests/search_sensitivity.rs
+use broker::search::types::*;
+use broker::search::SearchCoordinator;
+use grepzilla_segment::segjson::JsonSegmentWriter;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::SegmentWriter;
+use serde_json::json;
+use std::path::Path;
+
+const DOCS: &[&str] = &[
+    r#"{"_id":"a","text":{"title":"NullPointerException in Café"}}"#,
+    r#"{"_id":"b","text":{"title":"nullpointerexception in cafe"}}"#,
+    r#"{"_id":"c","text":{"title":"NULLPOINTEREXCEPTION ＣＡＦＥ"}}"#,
+];
+
+/// (V1, V2) с исходным текстом
+fn build(dir: &Path) -> (String, String) {
+    let input = dir.join("docs.jsonl");
+    std::fs::write(&input, DOCS.join("\n")).unwrap();
+    let v1 = dir.join("v1").to_str().unwrap().to_string();
+    let v2 = dir.join("v2").to_str().unwrap().to_string();
+    JsonSegmentWriter::default()
+        .with_raw_text(true)
+        .write_segment(input.to_str().unwrap(), &v1)
+        .unwrap();
+    BinSegmentWriter::default()
+        .with_raw_text(true)
+        .write_segment(input.to_str().unwrap(), &v2)
+        .unwrap();
+    (v1, v2)
+}
+
+fn request(seg: &str, body: serde_json::Value) -> SearchRequest {
+    let mut req = json!({
+        "segments": [seg],
+        "page": { "size": 10, "cursor": null }
+    });
+    for (k, v) in body.as_object().unwrap() {
+        req[k] = v.clone();
+    }
+    serde_json::from_value(req).unwrap()
+}
+
+fn ids(resp: &SearchResponse) -> Vec<&str> {
+    resp.hits.iter().map(|h| h.ext_id.as_str()).collect()
+}
+
+#[tokio::test]
+async fn folded_by_default_strict_on_request() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+    for seg in [&v1, &v2] {
+        let search = |body| coord.handle(request(seg, body));
+
+        let resp = search(json!({ "wildcard": "*NullPointer*" }))
+            .await
+            .unwrap();
+        assert_eq!(ids(&resp), ["a", "b", "c"], "{seg}");
+
+        let resp = search(json!({ "wildcard": "*NullPointer*", "case_sensitive": true }))
+            .await
+            .unwrap();
+        assert_eq!(ids(&resp), ["a"], "{seg}");
+        // кандидаты — по свёрнутым граммам, отсеивает verify
+        assert_eq!(resp.metrics.candidates_total, 3, "{seg}");
+
+        let resp = search(json!({ "wildcard": "*café*", "accent_sensitive": true }))
+            .await
+            .unwrap();
+        assert_eq!(ids(&resp), ["a"], "{seg}");
+
+        let resp = search(json!({ "regex": "in cafe$", "accent_sensitive": true }))
+            .await
+            .unwrap();
+        assert_eq!(ids(&resp), ["b"], "{seg}");
+
+        let resp = search(json!({ "wildcard": "*ＣＡＦＥ*", "width_sensitive": true }))
+            .await
+            .unwrap();
+        assert_eq!(ids(&resp), ["c"], "{seg}");
+    }
+}
+
+#[tokio::test]
+async fn strict_modes_apply_to_bool_query() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (_, v2) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+    let resp = coord
+        .handle(request(
+            &v2,
+            json!({
+                "query": {
+                    "must": [{ "field": "text.title", "wildcard": "*exception*" }],
+                    "must_not": [{ "wildcard": "*NULL*" }]
+                },
+                "case_sensitive": true
+            }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(ids(&resp), ["b"]);
+}
+
+#[tokio::test]
+async fn segment_without_raw_text_is_reported() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("docs.jsonl");
+    std::fs::write(&input, DOCS.join("\n")).unwrap();
+    let plain = tmp.path().join("plain").to_str().unwrap().to_string();
+    BinSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), &plain)
+        .unwrap();
+    let (_, raw) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+
+    // строгий запрос: сегмент без исходного текста не ищется, а помечается
+    let strict = json!({ "wildcard": "*NullPointer*", "case_sensitive": true });
+    let mut req = request(&raw, strict);
+    req.segments.push(plain.clone());
+    let resp = coord.handle(req).await.unwrap();
+    assert_eq!(ids(&resp), ["a"]);
+    assert_eq!(resp.metrics.segments_failed.len(), 1);
+    assert_eq!(resp.metrics.segments_failed[0].segment, plain);
+    assert!(
+        resp.metrics.segments_failed[0]
+            .error
+            .contains("built without raw text"),
+        "{:?}",
+        resp.metrics.segments_failed
+    );
+    assert!(!resp.cursor.unwrap().per_seg.contains_key(&plain));
+
+    // без строгости такой сегмент ищется как обычно
+    let resp = coord
+        .handle(request(&plain, json!({ "wildcard": "*NullPointer*" })))
+        .await
+        .unwrap();
+    assert_eq!(ids(&resp), ["a", "b", "c"]);
+    assert!(resp.metrics.segments_failed.is_empty());
+}
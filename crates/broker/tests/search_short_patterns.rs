@@ -0,0 +1,126 @@
+// broker/tests/search_short_patterns.rs
+use broker::search::types::*;
+use broker::search::SearchCoordinator;
+use grepzilla_segment::segjson::JsonSegmentWriter;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::SegmentWriter;
+use serde_json::json;
+use std::path::Path;
+
+const DOCS: &[&str] = &[
+    r#"{"_id":"a","text":{"title":"ID 42"}}"#,
+    r#"{"_id":"b","text":{"title":"order 4"}}"#,
+    r#"{"_id":"c","text":{"title":"user id"}}"#,
+    r#"{"_id":"d","text":{"title":"no match"}}"#,
+    r#"{"_id":"e","text":{"title":"42 again"}}"#,
+];
+
+/// (V1, V2 без короткого индекса, V2 с ним)
+fn build(dir: &Path) -> (String, String, String) {
+    let input = dir.join("docs.jsonl");
+    std::fs::write(&input, DOCS.join("\n")).unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let (v1, v2, short) = (path("v1"), path("v2"), path("short"));
+    JsonSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), &v1)
+        .unwrap();
+    BinSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), &v2)
+        .unwrap();
+    BinSegmentWriter::default()
+        .with_short_grams(true)
+        .write_segment(input.to_str().unwrap(), &short)
+        .unwrap();
+    (v1, v2, short)
+}
+
+fn request(seg: &str, wildcard: &str, limits: serde_json::Value) -> SearchRequest {
+    serde_json::from_value(json!({
+        "wildcard": wildcard,
+        "segments": [seg],
+        "page": { "size": 10, "cursor": null },
+        "limits": limits
+    }))
+    .unwrap()
+}
+
+fn ids(resp: &SearchResponse) -> Vec<&str> {
+    resp.hits.iter().map(|h| h.ext_id.as_str()).collect()
+}
+
+#[tokio::test]
+async fn short_index_answers_short_patterns() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (_, _, short) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+
+    let resp = coord
+        .handle(request(&short, "*42*", json!({})))
+        .await
+        .unwrap();
+    assert_eq!(ids(&resp), ["a", "e"]);
+    // кандидаты — только документы с биграммой
+    assert_eq!(resp.metrics.candidates_total, 2);
+
+    let resp = coord
+        .handle(request(&short, "*ID*", json!({})))
+        .await
+        .unwrap();
+    assert_eq!(ids(&resp), ["a", "c"]);
+}
+
+#[tokio::test]
+async fn full_scan_only_when_allowed() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2, _) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+
+    for seg in [&v1, &v2] {
+        // без индекса коротких грамм сегмент не ищется: он в segments_failed,
+        // а курсор по нему не сдвигается
+        let resp = coord.handle(request(seg, "*42*", json!({}))).await.unwrap();
+        assert!(resp.hits.is_empty(), "{seg}");
+        let failed = &resp.metrics.segments_failed;
+        assert_eq!(failed.len(), 1, "{seg}");
+        assert_eq!(&failed[0].segment, seg);
+        assert!(
+            failed[0].error.contains("pattern too weak"),
+            "{seg}: {failed:?}"
+        );
+        assert!(!resp.cursor.unwrap().per_seg.contains_key(seg.as_str()));
+
+        let mut req = request(seg, "*42*", json!({}));
+        req.page.cursor = Some(json!({ "per_seg": { seg.as_str(): { "last_docid": 3 } } }));
+        let resp = coord.handle(req).await.unwrap();
+        assert_eq!(resp.cursor.unwrap().per_seg[seg.as_str()].last_docid, 3);
+
+        let resp = coord
+            .handle(request(seg, "*42*", json!({ "full_scan": true })))
+            .await
+            .unwrap();
+        assert_eq!(ids(&resp), ["a", "e"], "{seg}");
+        assert_eq!(resp.metrics.candidates_total, 5, "{seg}");
+    }
+}
+
+#[tokio::test]
+async fn full_scan_budget_pages_through_segment() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (_, v2, _) = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+
+    // по 2 документа за страницу; курсор продолжает с непроверенного
+    let mut req = request(
+        &v2,
+        "*4*",
+        json!({ "full_scan": true, "max_candidates": 2 }),
+    );
+    let mut seen = Vec::new();
+    for _ in 0..3 {
+        let resp = coord.handle(req.clone()).await.unwrap();
+        assert!(resp.metrics.candidates_total <= 2);
+        seen.extend(resp.hits.iter().map(|h| h.ext_id.clone()));
+        req.page.cursor = Some(serde_json::to_value(resp.cursor.unwrap()).unwrap());
+    }
+    assert_eq!(seen, ["a", "b", "e"]);
+}
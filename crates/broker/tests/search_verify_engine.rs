@@ -0,0 +1,136 @@
+// broker/tests/search_verify_engine.rs
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use broker::search::types::*;
+use broker::search::SearchCoordinator;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::SegmentWriter;
+use serde_json::json;
+use std::path::Path;
+use tower::util::ServiceExt;
+
+mod helpers;
+use helpers::make_router_with_parallelism;
+
+const DOCS: &[&str] = &[
+    r#"{"_id":"a","text":{"title":"Connection timeout"}}"#,
+    r#"{"_id":"b","text":{"title":"timeout after retry"}}"#,
+];
+
+fn build(dir: &Path) -> String {
+    let input = dir.join("docs.jsonl");
+    std::fs::write(&input, DOCS.join("\n")).unwrap();
+    let out = dir.join("seg").to_str().unwrap().to_string();
+    BinSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), &out)
+        .unwrap();
+    out
+}
+
+fn request(seg: &str, engine: Option<&str>) -> SearchRequest {
+    serde_json::from_value(json!({
+        "wildcard": "*timeout",
+        "segments": [seg],
+        "verify_engine": engine,
+        "page": { "size": 10, "cursor": null }
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn engine_is_reported_in_metrics() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build(tmp.path());
+    let coord = SearchCoordinator::new(2);
+
+    let resp = coord.handle(request(&seg, None)).await.unwrap();
+    assert_eq!(resp.metrics.verify_engine, "regex");
+    let ids: Vec<_> = resp.hits.iter().map(|h| h.ext_id.as_str()).collect();
+    assert_eq!(ids, ["a"]);
+
+    let resp = coord.handle(request(&seg, Some("REGEX"))).await.unwrap();
+    assert_eq!(resp.metrics.verify_engine, "regex");
+}
+
+#[cfg(feature = "engine-pcre2")]
+#[tokio::test]
+async fn pcre2_per_request_and_by_default() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build(tmp.path());
+
+    let coord = SearchCoordinator::new(2);
+    let resp = coord.handle(request(&seg, Some("pcre2"))).await.unwrap();
+    assert_eq!(resp.metrics.verify_engine, "pcre2");
+    let ids: Vec<_> = resp.hits.iter().map(|h| h.ext_id.as_str()).collect();
+    assert_eq!(ids, ["a"]);
+
+    let coord = SearchCoordinator::new(2)
+        .with_verify_engine("pcre2")
+        .unwrap();
+    let resp = coord.handle(request(&seg, None)).await.unwrap();
+    assert_eq!(resp.metrics.verify_engine, "pcre2");
+}
+
+#[cfg(feature = "engine-pcre2")]
+#[tokio::test]
+async fn pcre2_regex_clause_uses_pcre2_syntax() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build(tmp.path());
+    let request = |engine: &str| -> SearchRequest {
+        serde_json::from_value(json!({
+            "regex": "(?<=Connection )timeout",
+            "segments": [seg],
+            "verify_engine": engine,
+            "page": { "size": 10, "cursor": null }
+        }))
+        .unwrap()
+    };
+
+    let coord = SearchCoordinator::new(2);
+    let resp = coord.handle(request("pcre2")).await.unwrap();
+    assert_eq!(resp.metrics.verify_engine, "pcre2");
+    let ids: Vec<_> = resp.hits.iter().map(|h| h.ext_id.as_str()).collect();
+    assert_eq!(ids, ["a"]);
+
+    // ретроспективной проверки в синтаксисе `regex` нет
+    let err = coord.handle(request("regex")).await.unwrap_err();
+    assert!(err.to_string().contains("bad regex"), "{err:#}");
+}
+
+#[tokio::test]
+async fn unknown_engine_fails_loudly() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build(tmp.path());
+
+    assert!(SearchCoordinator::new(2)
+        .with_verify_engine("hyperscan")
+        .is_err());
+    let err = SearchCoordinator::new(2)
+        .handle(request(&seg, Some("hyperscan")))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("unknown verify engine"), "{err:#}");
+
+    let app = make_router_with_parallelism(2);
+    let body = serde_json::to_vec(&request(&seg, Some("hyperscan"))).unwrap();
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/search")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(v["error"], "unknown_verify_engine");
+    assert!(v["message"].as_str().unwrap().contains("hyperscan"), "{v}");
+}
@@ -1,6 +1,7 @@
 // crates/grepzilla_segment/benches/preview_bench.rs
 use criterion::{Criterion, criterion_group, criterion_main};
 use grepzilla_segment::StoredDoc;
+use grepzilla_segment::analyzer::Analyzers;
 use grepzilla_segment::common::preview::{PreviewOpts, build_preview};
 use std::collections::BTreeMap;
 
@@ -15,6 +16,7 @@ fn mk_doc() -> StoredDoc {
         doc_id: 0,
         ext_id: "x".into(),
         fields,
+        raw: BTreeMap::new(),
     }
 }
 
@@ -28,6 +30,7 @@ fn bench_preview(c: &mut Criterion) {
                     preferred_fields: &["text.title", "text.body"],
                     max_len: 80,
                     highlight_needle: Some("игра"),
+                    analyzers: &Analyzers::default(),
                 },
             );
         })
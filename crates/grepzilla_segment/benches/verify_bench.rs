@@ -0,0 +1,22 @@
+// crates/grepzilla_segment/benches/verify_bench.rs
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use grepzilla_segment::verify::{LiteralVerify, RegexVerify, VerifyEngine};
+
+const TEXT: &str = "2024-05-01 12:00:01 worker-17 request id=af93 upstream connection \
+                    reset by peer after 30s, retrying with backoff; last status 502 timeout";
+
+fn bench_verify(c: &mut Criterion) {
+    for pattern in ["*timeout*", "*upstream*502*", "*missing*"] {
+        let rx = RegexVerify::compile_wildcard(pattern).unwrap();
+        let lit = LiteralVerify::compile(pattern, true).unwrap();
+        c.bench_function(&format!("regex {pattern}"), |b| {
+            b.iter(|| rx.is_match(black_box(TEXT)))
+        });
+        c.bench_function(&format!("literal {pattern}"), |b| {
+            b.iter(|| lit.is_match(black_box(TEXT)))
+        });
+    }
+}
+
+criterion_group!(benches, bench_verify);
+criterion_main!(benches);
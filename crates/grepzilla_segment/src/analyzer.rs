@@ -0,0 +1,401 @@
+// crates/grepzilla_segment/src/analyzer.rs
+//! Анализаторы текста: во что превращаются строковые поля при индексации и
+//! паттерны при поиске. Анализатор — цепочка стадий ([`Stage`]); встроенный
+//! `default` повторяет [`crate::normalizer::normalize`] (нижний регистр, NFKC,
+//! снятие диакритики U+0300–U+036F).
+//!
+//! Схема индекса ([`AnalyzerSchema`]) задаёт именованные анализаторы и
+//! назначает их путям полей; остальные поля получают анализатор `default`
+//! схемы. Индексы массивов в пути не учитываются: `tags` относится и к
+//! `tags[0]`.
+//!
+//! ```json
+//! {"analyzers": {"ru": ["case_fold", "nfkc", "yo", "strip_marks"]},
+//!  "fields":    {"text.title": "ru"}}
+//! ```
+//!
+//! Схема записывается в сегмент (`analyzers.json`, только если отличается от
+//! стандартной), и паттерн пункта приводится анализатором того поля
+//! сегмента, по которому ищется.
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+use crate::normalizer::strip_accents;
+
+/// Встроенный анализатор, совпадающий с прежней нормализацией.
+pub const DEFAULT_ANALYZER: &str = "default";
+
+/// Файл схемы анализаторов в каталоге сегмента (V1 и V2).
+pub const ANALYZERS_FILE: &str = "analyzers.json";
+
+/// Приведение текста к виду индекса.
+pub trait Analyzer: Send + Sync {
+    /// Идентификатор анализатора в схеме.
+    fn id(&self) -> &str;
+    /// Текст поля или литералы паттерна → вид, в котором они индексируются.
+    fn analyze(&self, text: &str) -> String;
+}
+
+/// Стадия анализатора.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Нижний регистр (`str::to_lowercase`).
+    Lowercase,
+    /// Нижний регистр и полная свёртка там, где она от него отличается
+    /// (`ß` → `ss`, конечная `ς` → `σ`, `ſ` → `s` и т.п.).
+    CaseFold,
+    /// Совместимая нормализация NFKC (ширина, лигатуры, надстрочные).
+    Nfkc,
+    /// `ё` → `е` (в том числе `е` + U+0308), с сохранением регистра.
+    Yo,
+    /// Снятие диакритики U+0300–U+036F (как в `default`).
+    StripAccents,
+    /// Снятие всех комбинирующих знаков Unicode.
+    StripMarks,
+    /// Свёртка раскладки: кириллица ЙЦУКЕН → латиница QWERTY тех же клавиш
+    /// (`привет` и `ghbdtn` дают одно и то же).
+    KeyboardLayout,
+}
+
+impl Stage {
+    pub fn apply(self, s: &str) -> String {
+        match self {
+            Stage::Lowercase => s.to_lowercase(),
+            Stage::CaseFold => case_fold(s),
+            Stage::Nfkc => s.nfkc().collect(),
+            Stage::Yo => yo(s),
+            Stage::StripAccents => strip_accents(s),
+            Stage::StripMarks => s.nfd().filter(|c| !is_combining_mark(*c)).collect(),
+            Stage::KeyboardLayout => s.chars().map(layout_key).collect(),
+        }
+    }
+}
+
+fn case_fold(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.to_lowercase().chars() {
+        match c {
+            'ß' => out.push_str("ss"),
+            'ς' => out.push('σ'),
+            'ſ' => out.push('s'),
+            'ϐ' => out.push('β'),
+            'ϑ' => out.push('θ'),
+            'ϕ' => out.push('φ'),
+            'ϖ' => out.push('π'),
+            'ϰ' => out.push('κ'),
+            'ϱ' => out.push('ρ'),
+            'ϵ' => out.push('ε'),
+            'ẛ' => out.push('ṡ'),
+            '\u{1FBE}' => out.push('ι'),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn yo(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            'ё' => out.push('е'),
+            'Ё' => out.push('Е'),
+            'е' | 'Е' => {
+                out.push(c);
+                // декомпозированная ё: диерезис снимается
+                if chars.peek() == Some(&'\u{308}') {
+                    chars.next();
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Кириллица ЙЦУКЕН → символ той же клавиши в QWERTY.
+fn layout_key(c: char) -> char {
+    const RU: &str = "йцукенгшщзхъфывапролджэячсмитьбюё";
+    const EN: &str = "qwertyuiop[]asdfghjkl;'zxcvbnm,.`";
+    const RU_UP: &str = "ЙЦУКЕНГШЩЗХЪФЫВАПРОЛДЖЭЯЧСМИТЬБЮЁ";
+    const EN_UP: &str = "QWERTYUIOP{}ASDFGHJKL:\"ZXCVBNM<>~";
+    let find = |from: &str, to: &str| {
+        from.chars()
+            .position(|x| x == c)
+            .and_then(|i| to.chars().nth(i))
+    };
+    find(RU, EN).or_else(|| find(RU_UP, EN_UP)).unwrap_or(c)
+}
+
+/// Анализатор из цепочки стадий.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipeline {
+    id: String,
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    pub fn new(id: &str, stages: Vec<Stage>) -> Self {
+        Self {
+            id: id.to_string(),
+            stages,
+        }
+    }
+
+    /// Встроенный `default`.
+    pub fn standard() -> Self {
+        Self::new(
+            DEFAULT_ANALYZER,
+            vec![Stage::Lowercase, Stage::Nfkc, Stage::StripAccents],
+        )
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+}
+
+impl Analyzer for Pipeline {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn analyze(&self, text: &str) -> String {
+        let mut out = text.to_string();
+        for st in &self.stages {
+            out = st.apply(&out);
+        }
+        out
+    }
+}
+
+/// Схема анализаторов индекса (то, что пишется в `analyzers.json`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnalyzerSchema {
+    /// Именованные анализаторы: id → стадии
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub analyzers: BTreeMap<String, Vec<Stage>>,
+    /// Анализатор полей без назначения (по умолчанию `default`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// Путь поля → id анализатора
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Разрешённая схема: анализаторы готовы к применению.
+#[derive(Clone)]
+pub struct Analyzers {
+    schema: AnalyzerSchema,
+    default: Arc<dyn Analyzer>,
+    fields: HashMap<String, Arc<dyn Analyzer>>,
+}
+
+impl Default for Analyzers {
+    fn default() -> Self {
+        Self {
+            schema: AnalyzerSchema::default(),
+            default: Arc::new(Pipeline::standard()),
+            fields: HashMap::new(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Analyzers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.schema.fmt(f)
+    }
+}
+
+impl PartialEq for Analyzers {
+    fn eq(&self, other: &Self) -> bool {
+        (self.is_standard() && other.is_standard()) || self.schema == other.schema
+    }
+}
+
+impl Analyzers {
+    /// Проверить схему и собрать анализаторы. Ошибка — неизвестный id или
+    /// переопределение встроенного `default`.
+    pub fn new(schema: AnalyzerSchema) -> Result<Self> {
+        if schema.analyzers.contains_key(DEFAULT_ANALYZER) {
+            bail!("analyzer `{DEFAULT_ANALYZER}` is built in and cannot be redefined");
+        }
+        let mut built: HashMap<&str, Arc<dyn Analyzer>> = HashMap::new();
+        built.insert(DEFAULT_ANALYZER, Arc::new(Pipeline::standard()));
+        for (id, stages) in &schema.analyzers {
+            built.insert(id, Arc::new(Pipeline::new(id, stages.clone())));
+        }
+        let get = |id: &str| {
+            built
+                .get(id)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("unknown analyzer `{id}`"))
+        };
+        let default = get(schema.default.as_deref().unwrap_or(DEFAULT_ANALYZER))?;
+        let fields = schema
+            .fields
+            .iter()
+            .map(|(f, id)| Ok((f.clone(), get(id)?)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            schema,
+            default,
+            fields,
+        })
+    }
+
+    /// Схема из JSON-файла.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("read analyzers {}", path.display()))?;
+        let schema: AnalyzerSchema = serde_json::from_str(&text)
+            .with_context(|| format!("bad analyzers {}", path.display()))?;
+        Self::new(schema)
+    }
+
+    /// Схема сегмента `dir`: `analyzers.json` либо стандартная.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(ANALYZERS_FILE);
+        if path.exists() {
+            Self::from_file(&path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Записать схему в каталог сегмента; стандартная не пишется (и
+    /// оставшийся от прошлой сборки файл удаляется).
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(ANALYZERS_FILE);
+        if self.is_standard() {
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+            return Ok(());
+        }
+        std::fs::write(&path, serde_json::to_vec_pretty(&self.schema)?)?;
+        Ok(())
+    }
+
+    pub fn schema(&self) -> &AnalyzerSchema {
+        &self.schema
+    }
+
+    /// Все поля анализируются встроенным `default`.
+    pub fn is_standard(&self) -> bool {
+        self.distinct().iter().all(|a| a.id() == DEFAULT_ANALYZER)
+    }
+
+    /// Анализатор поля `field` (полный путь, индексы массивов допустимы).
+    pub fn for_field(&self, field: &str) -> &Arc<dyn Analyzer> {
+        if self.fields.is_empty() {
+            return &self.default;
+        }
+        self.fields
+            .get(schema_path(field).as_ref())
+            .unwrap_or(&self.default)
+    }
+
+    /// Анализатор полей без назначения.
+    pub fn default_analyzer(&self) -> &Arc<dyn Analyzer> {
+        &self.default
+    }
+
+    /// Различные анализаторы схемы: сначала `default`, затем по id.
+    pub fn distinct(&self) -> Vec<Arc<dyn Analyzer>> {
+        let mut out = vec![self.default.clone()];
+        let mut ids: Vec<_> = self.fields.values().collect();
+        ids.sort_by(|a, b| a.id().cmp(b.id()));
+        for a in ids {
+            if out.iter().all(|x| x.id() != a.id()) {
+                out.push(a.clone());
+            }
+        }
+        out
+    }
+}
+
+/// Путь поля без индексов массивов: `items[1].name` → `items.name`.
+fn schema_path(field: &str) -> Cow<'_, str> {
+    if !field.contains('[') {
+        return Cow::Borrowed(field);
+    }
+    let mut out = String::with_capacity(field.len());
+    let mut depth = 0usize;
+    for c in field.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            c if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normalizer::normalize;
+
+    #[test]
+    fn standard_matches_normalize() {
+        let a = Pipeline::standard();
+        for s in ["КоШКи", "Café", "Ёлка", "ＡＢ", "\u{FB01}nal", "e\u{301}"] {
+            assert_eq!(a.analyze(s), normalize(s), "{s}");
+        }
+    }
+
+    #[test]
+    fn stages() {
+        assert_eq!(Stage::CaseFold.apply("Straße ΣΟΦΟΣ"), "strasse σοφοσ");
+        assert_eq!(Stage::Lowercase.apply("ΣΟΦΟΣ"), "σοφος");
+        assert_eq!(Stage::Yo.apply("Ёлка и е\u{308}ж"), "Елка и еж");
+        assert_eq!(Stage::StripMarks.apply("a\u{20D7}é"), "ae");
+        assert_eq!(Stage::StripAccents.apply("a\u{20D7}é"), "a\u{20D7}e");
+        assert_eq!(Stage::KeyboardLayout.apply("привет"), "ghbdtn");
+        assert_eq!(Stage::KeyboardLayout.apply("Хм, ok"), "{v, ok");
+    }
+
+    #[test]
+    fn schema_resolves_fields() {
+        let schema: AnalyzerSchema = serde_json::from_str(
+            r#"{"analyzers":{"kb":["lowercase","keyboard_layout"]},
+                "fields":{"text.title":"kb","tags":"default"}}"#,
+        )
+        .unwrap();
+        let a = Analyzers::new(schema).unwrap();
+        assert!(!a.is_standard());
+        assert_eq!(a.for_field("text.title").analyze("Привет"), "ghbdtn");
+        assert_eq!(a.for_field("text.body").id(), DEFAULT_ANALYZER);
+        assert_eq!(a.for_field("tags[2]").id(), DEFAULT_ANALYZER);
+        let ids: Vec<_> = a.distinct().iter().map(|x| x.id().to_string()).collect();
+        assert_eq!(ids, ["default", "kb"]);
+
+        assert!(Analyzers::default().is_standard());
+        let only_default = AnalyzerSchema {
+            fields: BTreeMap::from([("x".into(), DEFAULT_ANALYZER.into())]),
+            ..Default::default()
+        };
+        assert!(Analyzers::new(only_default).unwrap().is_standard());
+
+        let unknown = AnalyzerSchema {
+            default: Some("nope".into()),
+            ..Default::default()
+        };
+        assert!(Analyzers::new(unknown).is_err());
+        let redefined = AnalyzerSchema {
+            analyzers: BTreeMap::from([(DEFAULT_ANALYZER.into(), vec![Stage::Lowercase])]),
+            ..Default::default()
+        };
+        assert!(Analyzers::new(redefined).is_err());
+    }
+}
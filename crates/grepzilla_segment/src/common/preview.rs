@@ -1,5 +1,7 @@
 // crates/grepzilla_segment/src/common/preview.rs
 use crate::StoredDoc;
+use crate::analyzer::{Analyzer, Analyzers};
+use crate::normalizer::OffsetMap;
 
 /// Опции превью.
 pub struct PreviewOpts<'a> {
@@ -10,17 +12,22 @@ pub struct PreviewOpts<'a> {
     /// Подсветить первую встречу иглы (уже нормализованную).
     /// Если None — просто обрезка.
     pub highlight_needle: Option<&'a str>,
+    /// Анализаторы полей документа: подсветка переносится на исходный текст
+    /// тем анализатором, которым поле приведено.
+    pub analyzers: &'a Analyzers,
 }
 
 /// Построить превью с подсветкой вокруг первого вхождения `highlight_needle`
 /// (нечувствительно к регистру, с fallback по укорачиванию до длины >= 3),
 /// или усечённый текст, если игла не найдена. Всегда по границам UTF-8.
+/// Если у документа сохранён исходный текст поля, показывается он.
 pub fn build_preview(doc: &StoredDoc, opts: PreviewOpts<'_>) -> String {
     // 1) выбрать источник текста
-    let binding = String::new();
-    let text = pick_field_text(doc, opts.preferred_fields)
-        .or_else(|| doc.fields.values().next())
-        .unwrap_or(&binding);
+    let Some((field, text)) = pick_field_text(doc, opts.preferred_fields)
+        .or_else(|| doc.fields.iter().next().map(|(k, v)| (k.as_str(), v)))
+    else {
+        return String::new();
+    };
 
     // 2) если игла есть и найдена — делаем сниппет по матчу; иначе — просто усечение
     match opts
@@ -28,17 +35,48 @@ pub fn build_preview(doc: &StoredDoc, opts: PreviewOpts<'_>) -> String {
         .and_then(|n| find_ci_with_fallback(text, n, 3))
     {
         Some((m_start_b, m_end_b)) => {
-            snippet_with_highlight(text, m_start_b, m_end_b, opts.max_len)
+            let a = opts.analyzers.for_field(field);
+            snippet_for_match(doc, field, m_start_b, m_end_b, opts.max_len, a.as_ref())
         }
-        None => truncate_chars_with_ellipsis(text, opts.max_len),
+        None => truncate_chars_with_ellipsis(display_text(doc, field), opts.max_len),
+    }
+}
+
+/// Текст поля для показа: исходный, если сохранён, иначе нормализованный.
+pub fn display_text<'a>(doc: &'a StoredDoc, field: &str) -> &'a str {
+    doc.raw
+        .get(field)
+        .or_else(|| doc.fields.get(field))
+        .map_or("", String::as_str)
+}
+
+/// Сниппет по матчу `[m_start_b, m_end_b)` в нормализованном тексте поля.
+/// Если сохранён исходный текст, подсветка переносится на него через
+/// [`OffsetMap`] по анализатору поля `a`; иначе (или если карта не
+/// строится) — нормализованный текст.
+pub fn snippet_for_match(
+    doc: &StoredDoc,
+    field: &str,
+    m_start_b: usize,
+    m_end_b: usize,
+    max_chars: usize,
+    a: &dyn Analyzer,
+) -> String {
+    let text = doc.fields.get(field).map_or("", String::as_str);
+    if let Some(raw) = doc.raw.get(field)
+        && let Some(map) = OffsetMap::build(raw, text, a)
+    {
+        let (s, e) = map.to_raw(m_start_b, m_end_b);
+        return snippet_with_highlight(raw, s, e, max_chars);
     }
+    snippet_with_highlight(text, m_start_b, m_end_b, max_chars)
 }
 
-fn pick_field_text<'a>(doc: &'a StoredDoc, preferred: &[&str]) -> Option<&'a String> {
+fn pick_field_text<'a>(doc: &'a StoredDoc, preferred: &[&'a str]) -> Option<(&'a str, &'a String)> {
     for f in preferred {
         if let Some(t) = doc.fields.get(*f) {
             if !t.is_empty() {
-                return Some(t);
+                return Some((f, t));
             }
         }
     }
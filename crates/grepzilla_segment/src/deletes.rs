@@ -0,0 +1,155 @@
+// crates/grepzilla_segment/src/deletes.rs
+//! Живые документы сегмента: sidecar `deletes.roaring` рядом с сегментом.
+//!
+//! Сегменты неизменяемы, поэтому удаление пишется отдельным файлом — Roaring
+//! локальных DocId — и не требует пересборки. Каждая запись увеличивает
+//! `generation`: читатель фиксирует снимок удалений при открытии, а брокер по
+//! поколению понимает, что вид сегмента устарел и его пора переоткрыть.
+//!
+//! Формат (LE):
+//! ```text
+//! [magic "GZDEL1\0\0"][u64 generation][u64 cardinality][roaring portable][u64 CRC64]
+//! ```
+//! Файл заменяется атомарно (запись во временный файл + rename).
+use anyhow::{Result, bail};
+use croaring::{Bitmap, Portable};
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+
+use crate::v2::crc::crc64_ecma;
+
+pub const DELETES_FILE: &str = "deletes.roaring";
+const DELETES_MAGIC: &[u8; 8] = b"GZDEL1\0\0";
+const HEADER_LEN: usize = 8 + 8 + 8;
+
+/// Снимок удалений сегмента. Отсутствие файла — поколение 0, удалений нет.
+#[derive(Debug, Clone, Default)]
+pub struct LiveDocs {
+    generation: u64,
+    deleted: Bitmap,
+}
+
+impl LiveDocs {
+    /// Прочитать `deletes.roaring` из каталога сегмента.
+    pub fn load(seg_dir: &Path) -> Result<Self> {
+        let bytes = match fs::read(seg_dir.join(DELETES_FILE)) {
+            Ok(b) => b,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        if bytes.len() < HEADER_LEN + 8 || &bytes[0..8] != DELETES_MAGIC {
+            bail!("deletes.roaring bad magic");
+        }
+        let body = &bytes[..bytes.len() - 8];
+        let crc_expect = u64::from_le_bytes(bytes[bytes.len() - 8..].try_into().unwrap());
+        if crc64_ecma(body) != crc_expect {
+            bail!("deletes.roaring CRC mismatch");
+        }
+        let generation = u64::from_le_bytes(body[8..16].try_into().unwrap());
+        let cardinality = u64::from_le_bytes(body[16..24].try_into().unwrap());
+        let deleted = Bitmap::try_deserialize::<Portable>(&body[HEADER_LEN..])
+            .ok_or_else(|| anyhow::anyhow!("deletes.roaring bad bitmap"))?;
+        if deleted.cardinality() != cardinality {
+            bail!("deletes.roaring cardinality mismatch");
+        }
+        Ok(Self {
+            generation,
+            deleted,
+        })
+    }
+
+    /// Поколение удалений (0 — удалений не было).
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Удалённые DocId.
+    pub fn deleted(&self) -> &Bitmap {
+        &self.deleted
+    }
+
+    pub fn is_deleted(&self, doc_id: u32) -> bool {
+        self.deleted.contains(doc_id)
+    }
+
+    /// Убрать удалённые документы из кандидатов.
+    pub fn apply(&self, acc: &mut Bitmap) {
+        if !self.deleted.is_empty() {
+            acc.andnot_inplace(&self.deleted);
+        }
+    }
+}
+
+/// Текущее поколение удалений на диске (читает только заголовок).
+pub fn read_generation(seg_dir: &Path) -> Result<u64> {
+    let mut f = match File::open(seg_dir.join(DELETES_FILE)) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut hdr = [0u8; 16];
+    f.read_exact(&mut hdr)?;
+    if &hdr[0..8] != DELETES_MAGIC {
+        bail!("deletes.roaring bad magic");
+    }
+    Ok(u64::from_le_bytes(hdr[8..16].try_into().unwrap()))
+}
+
+/// Пометить документы удалёнными: добавить `doc_ids` к текущим удалениям и
+/// записать новое поколение. DocId за пределами `doc_count` — ошибка.
+pub fn delete_docs(
+    seg_dir: &Path,
+    doc_count: u32,
+    doc_ids: impl IntoIterator<Item = u32>,
+) -> Result<LiveDocs> {
+    let mut live = LiveDocs::load(seg_dir)?;
+    for id in doc_ids {
+        if id >= doc_count {
+            bail!("doc_id {id} out of range (doc_count={doc_count})");
+        }
+        live.deleted.add(id);
+    }
+    live.generation += 1;
+
+    let payload = live.deleted.serialize::<Portable>();
+    let mut buf = Vec::with_capacity(HEADER_LEN + payload.len() + 8);
+    buf.extend_from_slice(DELETES_MAGIC);
+    buf.extend_from_slice(&live.generation.to_le_bytes());
+    buf.extend_from_slice(&live.deleted.cardinality().to_le_bytes());
+    buf.extend_from_slice(&payload);
+    let crc = crc64_ecma(&buf);
+    buf.extend_from_slice(&crc.to_le_bytes());
+
+    let tmp = seg_dir.join(format!("{DELETES_FILE}.tmp"));
+    {
+        let mut f = File::create(&tmp)?;
+        f.write_all(&buf)?;
+        f.sync_all()?;
+    }
+    fs::rename(&tmp, seg_dir.join(DELETES_FILE))?;
+    Ok(live)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generations_accumulate() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(read_generation(tmp.path()).unwrap(), 0);
+        assert!(LiveDocs::load(tmp.path()).unwrap().deleted().is_empty());
+
+        delete_docs(tmp.path(), 10, [1, 3]).unwrap();
+        let live = delete_docs(tmp.path(), 10, [3, 7]).unwrap();
+        assert_eq!(live.generation(), 2);
+
+        let back = LiveDocs::load(tmp.path()).unwrap();
+        assert_eq!(back.generation(), 2);
+        assert_eq!(back.deleted().iter().collect::<Vec<_>>(), vec![1, 3, 7]);
+        assert_eq!(read_generation(tmp.path()).unwrap(), 2);
+
+        assert!(delete_docs(tmp.path(), 10, [10]).is_err());
+    }
+}
@@ -0,0 +1,375 @@
+// crates/grepzilla_segment/src/doc_values.rs
+//! Doc-values: числовые и временные значения полей и фильтры по диапазону.
+//!
+//! Значения всех типов приводятся к ключу `i64`, порядок которого совпадает
+//! с порядком значений, поэтому фильтр по диапазону — это сравнение ключей:
+//! - `i64` — как есть (`true`/`false` → 1/0);
+//! - `f64` — биты IEEE-754 с инверсией отрицательных (монотонно);
+//! - `timestamp` — миллисекунды Unix; из JSON принимается число (уже мс)
+//!   или строка RFC 3339 (`2025-08-20T12:00:00Z`, `2025-08-20`).
+//!
+//! Хранение колонок в сегменте V2 — см. [`crate::v2::columns`].
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+/// Тип колонки doc-values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocValueKind {
+    I64,
+    F64,
+    Timestamp,
+}
+
+impl DocValueKind {
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            DocValueKind::I64 => 1,
+            DocValueKind::F64 => 2,
+            DocValueKind::Timestamp => 3,
+        }
+    }
+
+    pub(crate) fn from_code(c: u8) -> Result<Self> {
+        Ok(match c {
+            1 => DocValueKind::I64,
+            2 => DocValueKind::F64,
+            3 => DocValueKind::Timestamp,
+            _ => bail!("unknown doc-value kind {c}"),
+        })
+    }
+
+    /// Ключ значения из JSON; `None` — значение не подходит к типу.
+    pub fn key_of(self, v: &Value) -> Option<i64> {
+        match (self, v) {
+            (DocValueKind::I64 | DocValueKind::Timestamp, Value::Number(n)) => n
+                .as_i64()
+                .or_else(|| n.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64)),
+            (DocValueKind::I64, Value::Bool(b)) => Some(*b as i64),
+            (DocValueKind::F64, Value::Number(n)) => n.as_f64().map(f64_key),
+            (DocValueKind::Timestamp, Value::String(s)) => parse_timestamp_ms(s),
+            _ => None,
+        }
+    }
+
+    /// Значение по ключу — для показа.
+    pub fn value_of(self, key: i64) -> Value {
+        match self {
+            DocValueKind::I64 | DocValueKind::Timestamp => Value::from(key),
+            DocValueKind::F64 => Value::from(f64_from_key(key)),
+        }
+    }
+}
+
+impl fmt::Display for DocValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DocValueKind::I64 => "i64",
+            DocValueKind::F64 => "f64",
+            DocValueKind::Timestamp => "timestamp",
+        })
+    }
+}
+
+/// Поле, для которого writer строит колонку: `name:kind`, например `ts:timestamp`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocValueField {
+    pub name: String,
+    pub kind: DocValueKind,
+}
+
+impl DocValueField {
+    pub fn new(name: &str, kind: DocValueKind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+        }
+    }
+}
+
+impl FromStr for DocValueField {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((name, kind)) = s.rsplit_once(':') else {
+            bail!("doc-value field must be `name:kind`: {s}");
+        };
+        let kind = match kind {
+            "i64" | "int" => DocValueKind::I64,
+            "f64" | "float" => DocValueKind::F64,
+            "timestamp" | "ts" => DocValueKind::Timestamp,
+            _ => bail!("unknown doc-value kind `{kind}` (i64|f64|timestamp)"),
+        };
+        if name.is_empty() {
+            bail!("doc-value field name is empty: {s}");
+        }
+        Ok(Self::new(name, kind))
+    }
+}
+
+/// Граница диапазона: число или строка (время RFC 3339).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RangeValue {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// Фильтр `field` по диапазону; заданные границы объединяются по AND.
+/// Документ без значения поля фильтр не проходит.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RangeFilter {
+    pub field: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gt: Option<RangeValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gte: Option<RangeValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lt: Option<RangeValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lte: Option<RangeValue>,
+}
+
+impl RangeFilter {
+    /// Границы как замкнутый интервал ключей колонки типа `kind`;
+    /// `None` — интервал пуст.
+    pub fn key_range(&self, kind: DocValueKind) -> Result<Option<(i64, i64)>> {
+        let mut lo = i64::MIN;
+        let mut hi = i64::MAX;
+        if let Some(v) = &self.gte {
+            lo = lo.max(self.bound_key(kind, v, Side::Lower)?);
+        }
+        // x > 1.5 ⇔ x >= floor(1.5) + 1 для целых; для f64 +1 — соседнее число
+        if let Some(v) = &self.gt {
+            match self.bound_key(kind, v, Side::Upper)?.checked_add(1) {
+                Some(k) => lo = lo.max(k),
+                None => return Ok(None),
+            }
+        }
+        if let Some(v) = &self.lte {
+            hi = hi.min(self.bound_key(kind, v, Side::Upper)?);
+        }
+        if let Some(v) = &self.lt {
+            match self.bound_key(kind, v, Side::Lower)?.checked_sub(1) {
+                Some(k) => hi = hi.min(k),
+                None => return Ok(None),
+            }
+        }
+        Ok((lo <= hi).then_some((lo, hi)))
+    }
+
+    /// Проверка значения из JSON без знания типа колонки (горячая память):
+    /// числа сравниваются как числа, строки разбираются как время.
+    pub fn matches_json(&self, v: &Value) -> bool {
+        let Some(x) = loose_number(v) else {
+            return false;
+        };
+        let ok = |b: &Option<RangeValue>, f: fn(f64, f64) -> bool| {
+            b.as_ref()
+                .is_none_or(|b| loose_bound(b).is_some_and(|b| f(x, b)))
+        };
+        ok(&self.gt, |x, b| x > b)
+            && ok(&self.gte, |x, b| x >= b)
+            && ok(&self.lt, |x, b| x < b)
+            && ok(&self.lte, |x, b| x <= b)
+    }
+
+    fn bound_key(&self, kind: DocValueKind, v: &RangeValue, side: Side) -> Result<i64> {
+        let key = match (kind, v) {
+            (DocValueKind::I64 | DocValueKind::Timestamp, RangeValue::Int(i)) => Some(*i),
+            // дробная граница целой колонки: x >= 1.5 ⇔ x >= 2
+            (DocValueKind::I64 | DocValueKind::Timestamp, RangeValue::Float(f)) => {
+                let r = match side {
+                    Side::Lower => f.ceil(),
+                    Side::Upper => f.floor(),
+                };
+                r.is_finite().then_some(r as i64)
+            }
+            (DocValueKind::F64, RangeValue::Int(i)) => Some(f64_key(*i as f64)),
+            (DocValueKind::F64, RangeValue::Float(f)) => Some(f64_key(*f)),
+            (DocValueKind::Timestamp, RangeValue::Text(s)) => parse_timestamp_ms(s),
+            _ => None,
+        };
+        match key {
+            Some(k) => Ok(k),
+            None => bail!(
+                "range bound {v:?} does not fit {kind} field `{}`",
+                self.field
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Side {
+    Lower,
+    Upper,
+}
+
+fn loose_number(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        Value::Bool(b) => Some(*b as i64 as f64),
+        Value::String(s) => parse_timestamp_ms(s).map(|ms| ms as f64),
+        _ => None,
+    }
+}
+
+fn loose_bound(b: &RangeValue) -> Option<f64> {
+    match b {
+        RangeValue::Int(i) => Some(*i as f64),
+        RangeValue::Float(f) => Some(*f),
+        RangeValue::Text(s) => parse_timestamp_ms(s).map(|ms| ms as f64),
+    }
+}
+
+/// Монотонный ключ f64: порядок ключей совпадает с порядком чисел.
+pub fn f64_key(f: f64) -> i64 {
+    let b = f.to_bits() as i64;
+    if b < 0 { b ^ i64::MAX } else { b }
+}
+
+pub fn f64_from_key(k: i64) -> f64 {
+    let b = if k < 0 { k ^ i64::MAX } else { k };
+    f64::from_bits(b as u64)
+}
+
+/// Разбор времени RFC 3339 в миллисекунды Unix. Принимает также дату без
+/// времени, пробел вместо `T` и любой регистр `T`/`Z` (нормализованный текст).
+pub fn parse_timestamp_ms(s: &str) -> Option<i64> {
+    let b = s.trim().as_bytes();
+    let num = |r: std::ops::Range<usize>| -> Option<i64> {
+        let d = b.get(r)?;
+        if d.is_empty() || !d.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        std::str::from_utf8(d).ok()?.parse().ok()
+    };
+    if b.len() < 10 || b[4] != b'-' || b[7] != b'-' {
+        return None;
+    }
+    let (y, mo, d) = (num(0..4)?, num(5..7)?, num(8..10)?);
+    if !(1..=12).contains(&mo) || !(1..=days_in_month(y, mo)).contains(&d) {
+        return None;
+    }
+    let mut ms = days_from_civil(y, mo, d) * 86_400_000;
+    if b.len() == 10 {
+        return Some(ms);
+    }
+    if !matches!(b[10], b'T' | b't' | b' ') || b.len() < 19 || b[13] != b':' || b[16] != b':' {
+        return None;
+    }
+    let (h, mi, sec) = (num(11..13)?, num(14..16)?, num(17..19)?);
+    if h > 23 || mi > 59 || sec > 60 {
+        return None;
+    }
+    ms += (h * 3600 + mi * 60 + sec) * 1000;
+
+    let mut p = 19;
+    if b.get(p) == Some(&b'.') {
+        let start = p + 1;
+        p = start;
+        while p < b.len() && b[p].is_ascii_digit() {
+            p += 1;
+        }
+        if p == start {
+            return None;
+        }
+        // миллисекунды: первые три цифры дроби
+        let frac = &b[start..p.min(start + 3)];
+        let mut f = std::str::from_utf8(frac).ok()?.parse::<i64>().ok()?;
+        for _ in frac.len()..3 {
+            f *= 10;
+        }
+        ms += f;
+    }
+    match b.get(p) {
+        None => Some(ms), // без зоны — UTC
+        Some(b'Z' | b'z') if p + 1 == b.len() => Some(ms),
+        Some(&sign @ (b'+' | b'-')) if b.len() == p + 6 && b[p + 3] == b':' => {
+            let off = (num(p + 1..p + 3)? * 60 + num(p + 4..p + 6)?) * 60_000;
+            Some(if sign == b'+' { ms - off } else { ms + off })
+        }
+        _ => None,
+    }
+}
+
+fn is_leap(y: i64) -> bool {
+    (y % 4 == 0 && y % 100 != 0) || y % 400 == 0
+}
+
+fn days_in_month(y: i64, m: i64) -> i64 {
+    match m {
+        2 if is_leap(y) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Дни от 1970-01-01 (алгоритм Howard Hinnant, пролептический григорианский).
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp_ms("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_timestamp_ms("2025-08-20T12:00:00Z"),
+            Some(1_755_691_200_000)
+        );
+        assert_eq!(
+            parse_timestamp_ms("2025-08-20t15:00:00.5+03:00"),
+            Some(1_755_691_200_500)
+        );
+        assert_eq!(parse_timestamp_ms("2024-02-29"), Some(1_709_164_800_000));
+        assert_eq!(parse_timestamp_ms("2023-02-29"), None);
+        assert_eq!(parse_timestamp_ms("вчера"), None);
+    }
+
+    #[test]
+    fn f64_keys_keep_order() {
+        let xs = [f64::NEG_INFINITY, -2.5, -0.0, 0.0, 1e-9, 3.0, f64::INFINITY];
+        for w in xs.windows(2) {
+            assert!(f64_key(w[0]) <= f64_key(w[1]), "{w:?}");
+        }
+        assert_eq!(f64_from_key(f64_key(-2.5)), -2.5);
+    }
+
+    #[test]
+    fn key_ranges() {
+        let f = RangeFilter {
+            field: "n".into(),
+            gt: Some(RangeValue::Float(1.5)),
+            lt: Some(RangeValue::Int(5)),
+            ..Default::default()
+        };
+        assert_eq!(f.key_range(DocValueKind::I64).unwrap(), Some((2, 4)));
+
+        let ts = RangeFilter {
+            field: "ts".into(),
+            gte: Some(RangeValue::Text("2025-08-20T12:00:00Z".into())),
+            ..Default::default()
+        };
+        let (lo, _) = ts.key_range(DocValueKind::Timestamp).unwrap().unwrap();
+        assert_eq!(lo, 1_755_691_200_000);
+        assert!(ts.key_range(DocValueKind::I64).is_err());
+        assert!(ts.matches_json(&Value::from("2025-08-20T12:30:00Z")));
+        assert!(!ts.matches_json(&Value::from(0)));
+    }
+}
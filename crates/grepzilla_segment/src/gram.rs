@@ -1,5 +1,7 @@
 use crate::normalizer::normalize;
+use crate::wildcard;
 use anyhow::{Result, bail};
+use croaring::Bitmap;
 
 #[derive(Debug, Clone, Copy)]
 pub enum BooleanOp {
@@ -17,38 +19,212 @@ pub fn trigrams(s: &str) -> Vec<String> {
         .collect()
 }
 
-/// Извлечь обязательные 3-граммы из wildcard-паттерна (\* и ?)
+/// Ошибка паттерна без литерала из ≥3 символов подряд.
+pub const WEAK_WILDCARD: &str = "pattern too weak; need ≥3 consecutive literal chars";
+
+/// Извлечь обязательные 3-граммы из wildcard-паттерна (`*`, `?` и
+/// экранирование `\`, см. [`crate::wildcard`])
 pub fn required_grams_from_wildcard(pattern: &str) -> Result<Vec<String>> {
-    let pat = normalize(pattern);
-    let mut out = Vec::new();
-    let mut buf = String::new();
-    for ch in pat.chars() {
-        match ch {
-            '*' | '?' => {
-                if buf.chars().count() >= 3 {
-                    push_tris(&buf, &mut out);
+    Ok(required_gram_runs_from_wildcard(pattern)?.concat())
+}
+
+/// Обязательные 3-граммы, сгруппированные по литералам паттерна: внутри
+/// цепочки граммы идут подряд со сдвигом в один символ (для проверки
+/// смежности по позиционным маскам постингов).
+pub fn required_gram_runs_from_wildcard(pattern: &str) -> Result<Vec<Vec<String>>> {
+    let out: Vec<Vec<String>> = wildcard::literals(&wildcard::map_literals(pattern, normalize))
+        .iter()
+        .filter(|l| l.chars().count() >= 3)
+        .map(|l| tris(l))
+        .collect();
+    if out.is_empty() {
+        bail!("{WEAK_WILDCARD}");
+    }
+    Ok(out)
+}
+
+/// Формула wildcard-паттерна: цепочки литералов из ≥3 символов, а если
+/// таких нет — короткие литералы ([`GramQuery::Short`]); паттерн вовсе без
+/// литералов (`*`, `??`) — `All`. Паттерн уже приведён анализатором поля
+/// (см. [`crate::analyzer`]).
+pub fn wildcard_gram_query(pattern: &str) -> GramQuery {
+    let lits = wildcard::literals(pattern);
+    if lits.iter().any(|l| l.chars().count() >= 3) {
+        return lits
+            .iter()
+            .map(|l| GramQuery::literal(l))
+            .fold(GramQuery::All, GramQuery::and);
+    }
+    lits.into_iter()
+        .map(GramQuery::Short)
+        .fold(GramQuery::All, GramQuery::and)
+}
+
+/// Булева формула по цепочкам 3-грамм — обязательное условие совпадения
+/// паттерна. Лист — цепочка грамм одного литерала (граммы идут подряд со
+/// сдвигом в один символ, как в [`required_gram_runs_from_wildcard`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GramQuery {
+    /// условия нет — подходит любой документ
+    All,
+    Run(Vec<String>),
+    /// литерал из 1–2 символов: отбирается индексом коротких грамм
+    /// (`short_grams.*`), без него — не сужает (см. [`Self::without_short`])
+    Short(String),
+    And(Vec<GramQuery>),
+    /// `Or(vec![])` — не подходит ни один документ
+    Or(Vec<GramQuery>),
+}
+
+impl GramQuery {
+    /// Литерал: цепочка его грамм; короче трёх символов — без условия.
+    pub fn literal(s: &str) -> Self {
+        if s.chars().count() < 3 {
+            Self::All
+        } else {
+            Self::Run(tris(s))
+        }
+    }
+
+    /// Формула wildcard-паттерна: AND цепочек его литералов.
+    pub fn from_runs(runs: Vec<Vec<String>>) -> Self {
+        runs.into_iter().map(Self::Run).fold(Self::All, Self::and)
+    }
+
+    pub fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::All, x) | (x, Self::All) => x,
+            (Self::Or(v), _) | (_, Self::Or(v)) if v.is_empty() => Self::Or(v),
+            (Self::And(mut a), Self::And(b)) => {
+                for x in b {
+                    push_unique(&mut a, x);
                 }
-                buf.clear();
+                Self::And(a)
+            }
+            (Self::And(mut a), x) | (x, Self::And(mut a)) => {
+                push_unique(&mut a, x);
+                Self::And(a)
             }
-            _ => buf.push(ch),
+            (a, b) if a == b => a,
+            (a, b) => Self::And(vec![a, b]),
         }
     }
-    if buf.chars().count() >= 3 {
-        push_tris(&buf, &mut out);
+
+    pub fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::All, _) | (_, Self::All) => Self::All,
+            (Self::Or(v), x) | (x, Self::Or(v)) if v.is_empty() => x,
+            (Self::Or(mut a), Self::Or(b)) => {
+                for x in b {
+                    push_unique(&mut a, x);
+                }
+                Self::Or(a)
+            }
+            (Self::Or(mut a), x) | (x, Self::Or(mut a)) => {
+                push_unique(&mut a, x);
+                Self::Or(a)
+            }
+            (a, b) if a == b => a,
+            (a, b) => Self::Or(vec![a, b]),
+        }
     }
-    if out.is_empty() {
-        bail!("pattern too weak; need ≥3 consecutive literal chars");
+
+    /// Граммы, обязательные при любом исходе (листья, достижимые только
+    /// через AND) — для плана и отладки.
+    pub fn required_grams(&self) -> Vec<String> {
+        match self {
+            Self::Run(r) => r.clone(),
+            Self::And(xs) => xs.iter().flat_map(Self::required_grams).collect(),
+            Self::All | Self::Short(_) | Self::Or(_) => Vec::new(),
+        }
+    }
+
+    /// Формула для сегмента без индекса коротких грамм: `Short` → `All`.
+    pub fn without_short(&self) -> Self {
+        match self {
+            Self::Short(_) => Self::All,
+            Self::And(xs) => xs
+                .iter()
+                .map(Self::without_short)
+                .fold(Self::All, Self::and),
+            Self::Or(xs) => xs
+                .iter()
+                .map(Self::without_short)
+                .fold(Self::Or(Vec::new()), Self::or),
+            x => x.clone(),
+        }
+    }
+
+    /// Вычислить формулу: `runs` отбирает документы, где есть все цепочки
+    /// (AND); листья одного AND уходят одним вызовом, OR — объединение.
+    /// `All` — вызов без цепочек, `Short` — цепочка из одного литерала
+    /// короче трёх символов.
+    pub fn eval(&self, runs: &mut impl FnMut(&[Vec<String>]) -> Result<Bitmap>) -> Result<Bitmap> {
+        match self {
+            Self::All => runs(&[]),
+            Self::Run(r) => runs(std::slice::from_ref(r)),
+            Self::Short(s) => runs(&[vec![s.clone()]]),
+            Self::And(xs) => {
+                let leaves: Vec<Vec<String>> = xs
+                    .iter()
+                    .filter_map(|x| match x {
+                        Self::Run(r) => Some(r.clone()),
+                        Self::Short(s) => Some(vec![s.clone()]),
+                        _ => None,
+                    })
+                    .collect();
+                let mut acc = (!leaves.is_empty()).then(|| runs(&leaves)).transpose()?;
+                for x in xs
+                    .iter()
+                    .filter(|x| !matches!(x, Self::Run(_) | Self::Short(_)))
+                {
+                    if acc.as_ref().is_some_and(|a| a.is_empty()) {
+                        break;
+                    }
+                    let bm = x.eval(runs)?;
+                    acc = Some(match acc {
+                        Some(mut a) => {
+                            a.and_inplace(&bm);
+                            a
+                        }
+                        None => bm,
+                    });
+                }
+                Ok(acc.unwrap_or_default())
+            }
+            Self::Or(xs) => {
+                let mut acc = Bitmap::new();
+                for x in xs {
+                    acc.or_inplace(&x.eval(runs)?);
+                }
+                Ok(acc)
+            }
+        }
+    }
+
+    /// Та же логика для грубой проверки «может ли совпасть» (сводка сегмента).
+    /// Короткие литералы проверяются как `All`: в сводке только 3-граммы.
+    pub fn may_match(&self, runs: &mut impl FnMut(&[String]) -> bool) -> bool {
+        match self {
+            Self::All | Self::Short(_) => runs(&[]),
+            Self::Run(r) => runs(r),
+            Self::And(xs) => xs.iter().all(|x| x.may_match(runs)),
+            Self::Or(xs) => xs.iter().any(|x| x.may_match(runs)),
+        }
     }
-    Ok(out)
 }
 
-fn push_tris(s: &str, out: &mut Vec<String>) {
-    let cs: Vec<char> = s.chars().collect();
-    for w in cs.windows(3) {
-        out.push(w.iter().collect());
+fn push_unique(v: &mut Vec<GramQuery>, x: GramQuery) {
+    if !v.contains(&x) {
+        v.push(x);
     }
 }
 
+fn tris(s: &str) -> Vec<String> {
+    let cs: Vec<char> = s.chars().collect();
+    cs.windows(3).map(|w| w.iter().collect()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -60,4 +236,34 @@ mod tests {
             vec!["кот".to_string(), "оти".to_string(), "тик".to_string()]
         );
     }
+
+    #[test]
+    fn gram_runs_follow_literals() {
+        let runs = required_gram_runs_from_wildcard("*abcd*xyz?q*").unwrap();
+        assert_eq!(runs, vec![vec!["abc", "bcd"], vec!["xyz"]]);
+        assert_eq!(
+            required_grams_from_wildcard("*abcd*xyz?q*").unwrap(),
+            vec!["abc", "bcd", "xyz"]
+        );
+    }
+
+    #[test]
+    fn short_literals_only_without_long_ones() {
+        let short = |s: &str| GramQuery::Short(s.into());
+        assert_eq!(
+            wildcard_gram_query("*abcd*x?q*"),
+            GramQuery::Run(vec!["abc".into(), "bcd".into()])
+        );
+        assert_eq!(
+            wildcard_gram_query("*id*4?2*"),
+            GramQuery::And(vec![short("id"), short("4"), short("2")])
+        );
+        assert_eq!(wildcard_gram_query("*?*"), GramQuery::All);
+        assert_eq!(
+            wildcard_gram_query("*id*")
+                .and(GramQuery::Short("x".into()).or(GramQuery::literal("abc")))
+                .without_short(),
+            GramQuery::All
+        );
+    }
 }
@@ -0,0 +1,852 @@
+// crates/grepzilla_segment/src/integrity.rs
+//! Глубокая проверка целостности сегмента (V1 и V2), `gzctl verify-seg`.
+//!
+//! При открытии сегмента проверяются только CRC64 файлов. Здесь сегмент
+//! обходится целиком и собираются все структурные нарушения, а не первое:
+//! - постинги строго возрастают, DocId `< doc_count`, `doc_count` записей
+//!   совпадает с декодированным, записи `grams.dat` не перекрываются;
+//! - ключи словаря строго возрастают и являются 3-граммами (1–2-граммами
+//!   в `short_grams.*`);
+//! - таблица offsets `docs.dat` монотонна и закрывается длиной payload;
+//! - FieldId в записях `docs.dat` известны `fields.idx`, а маски полей
+//!   совпадают с набором полей документов;
+//! - колонки doc-values, удаления и сводка согласованы с сегментом, схема
+//!   анализаторов разбирается.
+//!
+//! Файл с неверным CRC64 дальше не разбирается: нарушение уже найдено,
+//! а разбор мусора ничего не добавит.
+use anyhow::{Result, bail};
+use croaring::{Bitmap, Portable};
+use memmap2::Mmap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::analyzer::{ANALYZERS_FILE, Analyzers};
+use crate::deletes::{DELETES_FILE, LiveDocs};
+use crate::summary::{SUMMARY_FILE, SegmentSummary};
+use crate::v2::codec::get_varint;
+use crate::v2::columns::{ColumnsIndex, DOC_VALUES_FILE};
+use crate::v2::crc::crc64_ecma;
+use crate::v2::gram_dict::{FIELD_GRAM_SEP, GRAMS_IDX_V1, GramDict};
+use crate::v2::postings::decode_postings_raw;
+use crate::v2::types::{META_MAGIC, META_VERSION, MetaHeader};
+use crate::v2::writer::SHORT_GRAMS;
+use crate::{SegmentMetaV1, StoredDoc};
+
+/// Сколько нарушений хранить в отчёте; остальные только считаются.
+pub const MAX_VIOLATIONS: usize = 1000;
+
+/// Нарушение: файл сегмента и описание.
+#[derive(Debug, Clone, Serialize)]
+pub struct Violation {
+    pub file: String,
+    pub message: String,
+}
+
+/// Итог проверки сегмента.
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    pub segment: String,
+    /// `v1` или `v2`
+    pub format: &'static str,
+    pub doc_count: u32,
+    pub violations: Vec<Violation>,
+    /// Нарушения сверх [`MAX_VIOLATIONS`] (в `violations` не попали).
+    pub suppressed: u64,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    fn push(&mut self, file: &str, message: impl Into<String>) {
+        if self.violations.len() >= MAX_VIOLATIONS {
+            self.suppressed += 1;
+            return;
+        }
+        self.violations.push(Violation {
+            file: file.to_string(),
+            message: message.into(),
+        });
+    }
+}
+
+/// Проверить сегмент V1 или V2 (формат — по `meta.bin`/`meta.json`).
+/// Ошибка — только если каталог не похож на сегмент; нарушения — в отчёте.
+pub fn verify_segment(dir: &Path) -> Result<IntegrityReport> {
+    let format = if dir.join("meta.bin").exists() {
+        "v2"
+    } else if dir.join("meta.json").exists() {
+        "v1"
+    } else {
+        bail!("not a segment (no meta.bin/meta.json): {}", dir.display());
+    };
+    let mut r = IntegrityReport {
+        segment: dir.display().to_string(),
+        format,
+        doc_count: 0,
+        violations: Vec::new(),
+        suppressed: 0,
+    };
+    let fields = match format {
+        "v2" => check_v2(dir, &mut r),
+        _ => check_v1(dir, &mut r),
+    };
+    if let Some(fields) = fields {
+        check_sidecars(dir, &fields, &mut r);
+    }
+    Ok(r)
+}
+
+// ---------------------------------------------------------------------------
+// V2
+
+/// Проверить V2; `Some(имена полей)`, если `meta.bin` читается.
+fn check_v2(dir: &Path, r: &mut IntegrityReport) -> Option<Vec<String>> {
+    let meta = open_with_crc(dir, "meta.bin", r)?;
+    let hdr = match MetaHeader::parse(&meta[..meta.len() - 8]) {
+        Ok(h) => h,
+        Err(e) => {
+            r.push("meta.bin", e.to_string());
+            return None;
+        }
+    };
+    if hdr.magic != META_MAGIC || hdr.version != META_VERSION {
+        r.push(
+            "meta.bin",
+            format!("bad magic/version {:#x}/{}", hdr.magic, hdr.version),
+        );
+        return None;
+    }
+    let Ok(doc_count) = u32::try_from(hdr.doc_count) else {
+        r.push(
+            "meta.bin",
+            format!("doc_count {} does not fit u32", hdr.doc_count),
+        );
+        return None;
+    };
+    r.doc_count = doc_count;
+    let gram_count = hdr.gram_count;
+
+    // длины тел файлов из meta.bin
+    let files = [
+        ("grams.idx", hdr.grams_idx_len),
+        ("grams.dat", hdr.grams_dat_len),
+        ("fields.idx", hdr.fields_idx_len),
+        ("fields.dat", hdr.fields_dat_len),
+        ("docs.dat", hdr.docs_dat_len),
+    ];
+    let mut maps: HashMap<&str, Mmap> = HashMap::new();
+    for (name, body_len) in files {
+        if let Some(m) = open_with_crc(dir, name, r) {
+            let actual = (m.len() - 8) as u64;
+            if actual != body_len {
+                r.push(name, format!("body length {actual} != meta.bin {body_len}"));
+            }
+            maps.insert(name, m);
+        }
+    }
+
+    // fields.idx / fields.dat
+    let fields = match (maps.get("fields.idx"), maps.get("fields.dat")) {
+        (Some(idx), Some(dat)) => check_fields(idx, dat, doc_count, r),
+        _ => None,
+    };
+    let names: Vec<String> = fields
+        .as_ref()
+        .map(|f| f.iter().map(|(n, _)| n.clone()).collect())
+        .unwrap_or_default();
+
+    // docs.dat (+ сверка с масками полей)
+    if let Some(docs) = maps.get("docs.dat") {
+        check_docs(docs, doc_count, fields.as_deref(), r);
+    }
+
+    // grams.idx / grams.dat
+    if let (Some(idx), Some(dat)) = (maps.remove("grams.idx"), maps.remove("grams.dat")) {
+        let n = check_grams("grams", idx, &dat, doc_count, None, 3..=3, r);
+        if n.is_some_and(|n| n != gram_count) {
+            r.push(
+                "grams.idx",
+                format!("{} keys != meta.bin gram_count {gram_count}", n.unwrap()),
+            );
+        }
+    }
+
+    // field_grams.* (опционально)
+    if dir.join("field_grams.idx").exists() || dir.join("field_grams.dat").exists() {
+        let idx = open_with_crc(dir, "field_grams.idx", r);
+        let dat = open_with_crc(dir, "field_grams.dat", r);
+        if let (Some(idx), Some(dat), Some(fields)) = (idx, dat, fields.as_deref()) {
+            check_grams("field_grams", idx, &dat, doc_count, Some(fields), 3..=3, r);
+        }
+    }
+
+    // short_grams.* (опционально)
+    let (short_idx, short_dat) = (format!("{SHORT_GRAMS}.idx"), format!("{SHORT_GRAMS}.dat"));
+    if dir.join(&short_idx).exists() || dir.join(&short_dat).exists() {
+        let idx = open_with_crc(dir, &short_idx, r);
+        let dat = open_with_crc(dir, &short_dat, r);
+        if let (Some(idx), Some(dat)) = (idx, dat) {
+            check_grams(SHORT_GRAMS, idx, &dat, doc_count, None, 1..=2, r);
+        }
+    }
+
+    // doc_values.dat (опционально)
+    if dir.join(DOC_VALUES_FILE).exists()
+        && let Some(m) = open_with_crc(dir, DOC_VALUES_FILE, r)
+    {
+        let body = &m[..m.len() - 8];
+        match ColumnsIndex::parse(body, doc_count) {
+            Err(e) => r.push(DOC_VALUES_FILE, e.to_string()),
+            Ok(idx) => {
+                for f in idx.fields() {
+                    match idx.column(body, &f.name) {
+                        Err(e) => r.push(DOC_VALUES_FILE, e.to_string()),
+                        Ok(None) => {}
+                        Ok(Some(col)) => {
+                            for v in col.violations() {
+                                r.push(DOC_VALUES_FILE, format!("column `{}`: {v}", f.name));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Some(names)
+}
+
+/// Разобрать `fields.idx` и маски `fields.dat`: (имя, маска) по FieldId.
+fn check_fields(
+    idx: &Mmap,
+    dat: &Mmap,
+    doc_count: u32,
+    r: &mut IntegrityReport,
+) -> Option<Vec<(String, Bitmap)>> {
+    const F: &str = "fields.idx";
+    let body = &idx[..idx.len() - 8];
+    if body.len() < 16 || u32::from_le_bytes(body[0..4].try_into().unwrap()) != 0x475A4649 {
+        r.push(F, "bad magic");
+        return None;
+    }
+    let count = u32::from_le_bytes(body[8..12].try_into().unwrap()) as usize;
+    let dict_len = u32::from_le_bytes(body[12..16].try_into().unwrap()) as usize;
+    let dict_end = 16 + dict_len;
+    if dict_end > body.len() || dict_end + count * 20 != body.len() {
+        r.push(
+            F,
+            format!("length {} does not fit {count} fields", body.len()),
+        );
+        return None;
+    }
+
+    // словарь имён
+    let mut names = Vec::with_capacity(count);
+    let mut p = &body[16..dict_end];
+    for fid in 0..count {
+        let Some((len, rest)) = get_varint(p).filter(|(l, rest)| *l as usize <= rest.len()) else {
+            r.push(F, format!("name dict truncated at field {fid}"));
+            return None;
+        };
+        let (name, rest) = rest.split_at(len as usize);
+        p = rest;
+        match std::str::from_utf8(name) {
+            Ok(s) => names.push(s.to_string()),
+            Err(_) => {
+                r.push(F, format!("field {fid}: name is not UTF-8"));
+                names.push(String::new());
+            }
+        }
+    }
+    if !p.is_empty() {
+        r.push(F, format!("{} trailing bytes in name dict", p.len()));
+    }
+    let mut seen = HashSet::new();
+    for n in &names {
+        if !seen.insert(n) {
+            r.push(F, format!("duplicate field name `{n}`"));
+        }
+    }
+
+    // записи (fid, off, len) → маски
+    let dat_body = &dat[..dat.len() - 8];
+    let mut masks: Vec<Option<Bitmap>> = vec![None; count];
+    for i in 0..count {
+        let at = dict_end + i * 20;
+        let fid = u32::from_le_bytes(body[at..at + 4].try_into().unwrap()) as usize;
+        let off = u64::from_le_bytes(body[at + 4..at + 12].try_into().unwrap());
+        let len = u64::from_le_bytes(body[at + 12..at + 20].try_into().unwrap());
+        if fid >= count {
+            r.push(
+                F,
+                format!("record {i}: field id {fid} >= field_count {count}"),
+            );
+            continue;
+        }
+        if masks[fid].is_some() {
+            r.push(F, format!("record {i}: field id {fid} repeated"));
+            continue;
+        }
+        let Some(rec) = slice_at(dat_body, off, len) else {
+            r.push(
+                F,
+                format!(
+                    "field `{}`: mask [{off}+{len}] outside fields.dat",
+                    names[fid]
+                ),
+            );
+            continue;
+        };
+        match parse_field_mask(rec) {
+            Err(e) => r.push("fields.dat", format!("field `{}`: {e}", names[fid])),
+            Ok(bm) => {
+                if let Some(mx) = bm.maximum()
+                    && mx >= doc_count
+                {
+                    r.push(
+                        "fields.dat",
+                        format!(
+                            "field `{}`: doc_id {mx} >= doc_count {doc_count}",
+                            names[fid]
+                        ),
+                    );
+                }
+                masks[fid] = Some(bm);
+            }
+        }
+    }
+    for (fid, m) in masks.iter().enumerate() {
+        if m.is_none() {
+            r.push(F, format!("field `{}` has no mask record", names[fid]));
+        }
+    }
+    Some(
+        names
+            .into_iter()
+            .zip(masks)
+            .map(|(n, m)| (n, m.unwrap_or_default()))
+            .collect(),
+    )
+}
+
+fn parse_field_mask(rec: &[u8]) -> Result<Bitmap> {
+    match rec.first() {
+        Some(1) if rec.len() >= 5 => {
+            let plen = u32::from_le_bytes(rec[1..5].try_into().unwrap()) as usize;
+            if 5 + plen != rec.len() {
+                bail!("roaring length {plen} does not match record");
+            }
+            Bitmap::try_deserialize::<Portable>(&rec[5..])
+                .ok_or_else(|| anyhow::anyhow!("bad roaring bitmap"))
+        }
+        Some(k) => bail!("unknown mask kind {k}"),
+        None => bail!("empty mask record"),
+    }
+}
+
+/// Проверить `docs.dat`: заголовок, offsets и каждую запись; сверить поля
+/// документов с масками `fields.dat`.
+fn check_docs(
+    m: &Mmap,
+    doc_count: u32,
+    fields: Option<&[(String, Bitmap)]>,
+    r: &mut IntegrityReport,
+) {
+    const F: &str = "docs.dat";
+    let body = &m[..m.len() - 8];
+    if body.len() < 24 || &body[0..8] != b"GZDOCS2\0" {
+        r.push(F, "bad magic");
+        return;
+    }
+    let dc = u64::from_le_bytes(body[8..16].try_into().unwrap());
+    let offsets_count = u64::from_le_bytes(body[16..24].try_into().unwrap());
+    if dc != doc_count as u64 {
+        r.push(F, format!("doc_count {dc} != meta.bin {doc_count}"));
+        return;
+    }
+    if offsets_count != dc + 1 {
+        r.push(F, format!("offsets_count {offsets_count} != doc_count + 1"));
+        return;
+    }
+    let Some(payload_start) = (offsets_count as usize)
+        .checked_mul(8)
+        .map(|n| n + 24)
+        .filter(|&s| s <= body.len())
+    else {
+        r.push(F, "offsets table OOB");
+        return;
+    };
+    let offs: Vec<u64> = body[24..payload_start]
+        .chunks_exact(8)
+        .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    let payload = &body[payload_start..];
+    if offs[0] != 0 {
+        r.push(F, format!("first offset {} != 0", offs[0]));
+    }
+    if *offs.last().unwrap() != payload.len() as u64 {
+        r.push(
+            F,
+            format!(
+                "last offset {} != payload length {}",
+                offs.last().unwrap(),
+                payload.len()
+            ),
+        );
+    }
+
+    let field_count = fields.map(|f| f.len());
+    let mut doc_fields: Vec<Bitmap> = vec![Bitmap::new(); field_count.unwrap_or(0)];
+    for doc in 0..doc_count {
+        let (from, to) = (offs[doc as usize], offs[doc as usize + 1]);
+        if from > to {
+            r.push(
+                F,
+                format!("doc {doc}: offsets not monotonic ({from} > {to})"),
+            );
+            continue;
+        }
+        let Some(rec) = slice_at(payload, from, to - from) else {
+            r.push(
+                F,
+                format!("doc {doc}: record [{from}..{to}] outside payload"),
+            );
+            continue;
+        };
+        match parse_doc_record(rec, field_count) {
+            Err(e) => r.push(F, format!("doc {doc}: {e}")),
+            Ok(fids) => {
+                for fid in fids {
+                    if let Some(bm) = doc_fields.get_mut(fid as usize) {
+                        bm.add(doc);
+                    }
+                }
+            }
+        }
+    }
+
+    // маски полей ⇔ поля документов
+    for ((name, mask), docs) in fields.unwrap_or_default().iter().zip(&doc_fields) {
+        let extra = mask.andnot(docs);
+        let missing = docs.andnot(mask);
+        if !extra.is_empty() || !missing.is_empty() {
+            r.push(
+                "fields.dat",
+                format!(
+                    "field `{name}`: mask has {} docs without the field (first {:?}), \
+                     {} docs with the field are not in the mask (first {:?})",
+                    extra.cardinality(),
+                    extra.minimum(),
+                    missing.cardinality(),
+                    missing.minimum(),
+                ),
+            );
+        }
+    }
+}
+
+/// Разобрать запись документа; FieldId полей (без хвоста исходного текста).
+fn parse_doc_record(rec: &[u8], field_count: Option<usize>) -> Result<Vec<u32>> {
+    let mut p = rec;
+    let varint = |p: &mut &[u8], what: &str| -> Result<u64> {
+        let (v, rest) = get_varint(p).ok_or_else(|| anyhow::anyhow!("{what}: truncated varint"))?;
+        *p = rest;
+        Ok(v)
+    };
+    let bytes = |p: &mut &[u8], len: u64, what: &str| -> Result<()> {
+        if len > p.len() as u64 {
+            bail!("{what}: OOB");
+        }
+        let (s, rest) = p.split_at(len as usize);
+        std::str::from_utf8(s).map_err(|_| anyhow::anyhow!("{what}: not UTF-8"))?;
+        *p = rest;
+        Ok(())
+    };
+    let check_fid = |fid: u64, what: &str| -> Result<u32> {
+        match field_count {
+            Some(n) if fid >= n as u64 => bail!("{what}: field id {fid} >= field_count {n}"),
+            _ => Ok(fid as u32),
+        }
+    };
+
+    let ext_len = varint(&mut p, "ext_id")?;
+    bytes(&mut p, ext_len, "ext_id")?;
+    let n = varint(&mut p, "fields_len")?;
+    let mut fids = Vec::new();
+    for _ in 0..n {
+        let fid = check_fid(varint(&mut p, "field")?, "field")?;
+        if fids.last().is_some_and(|&prev| prev >= fid) {
+            bail!("field ids not strictly ascending");
+        }
+        fids.push(fid);
+        let len = varint(&mut p, "field")?;
+        bytes(&mut p, len, "field value")?;
+    }
+    if !p.is_empty() {
+        let n = varint(&mut p, "raw_len")?;
+        for _ in 0..n {
+            let fid = check_fid(varint(&mut p, "raw")?, "raw")?;
+            if fids.binary_search(&fid).is_err() {
+                bail!("raw text for field id {fid} without normalized value");
+            }
+            let len = varint(&mut p, "raw")?;
+            bytes(&mut p, len, "raw value")?;
+        }
+        if !p.is_empty() {
+            bail!("{} trailing bytes", p.len());
+        }
+    }
+    Ok(fids)
+}
+
+/// Проверить словарь грамм и его постинги. `fields` задан для
+/// `field_grams.*`: ключ — `поле\0грамма`, DocId должны входить в маску поля.
+/// `gram_len` — допустимая длина граммы в символах.
+/// Возвращает число ключей, если словарь удалось пройти целиком.
+fn check_grams(
+    name: &str,
+    idx: Mmap,
+    dat: &Mmap,
+    doc_count: u32,
+    fields: Option<&[(String, Bitmap)]>,
+    gram_len: RangeInclusive<usize>,
+    r: &mut IntegrityReport,
+) -> Option<u64> {
+    let idx_f = format!("{name}.idx");
+    let dat_f = format!("{name}.dat");
+    let dict = match GramDict::open(idx) {
+        Ok(d) => d,
+        Err(e) => {
+            r.push(&idx_f, e.to_string());
+            return None;
+        }
+    };
+    let masks: HashMap<&str, &Bitmap> = fields
+        .unwrap_or_default()
+        .iter()
+        .map(|(n, m)| (n.as_str(), m))
+        .collect();
+    let dat_body = &dat[..dat.len() - 8];
+    let mut prev_key: Option<Vec<u8>> = None;
+    let mut prev_end = 0u64;
+    let mut n = 0u64;
+    for e in dict.iter() {
+        let e = match e {
+            Ok(e) => e,
+            Err(err) => {
+                r.push(&idx_f, format!("entry {n}: {err}"));
+                return None;
+            }
+        };
+        n += 1;
+        let shown = String::from_utf8_lossy(&e.key).into_owned();
+        if prev_key.as_ref().is_some_and(|p| *p >= e.key) {
+            r.push(&idx_f, format!("key {shown:?} not strictly ascending"));
+        }
+
+        // ключ: 3-грамма (в v1 — усечённые 3 байта, не проверяем)
+        let mut mask = None;
+        match std::str::from_utf8(&e.key) {
+            Err(_) => r.push(&idx_f, format!("key {shown:?} is not UTF-8")),
+            Ok(_) if dict.version() == GRAMS_IDX_V1 => {}
+            Ok(key) => {
+                let gram = match fields {
+                    None => key,
+                    Some(_) => match key.split_once(FIELD_GRAM_SEP) {
+                        Some((field, gram)) => {
+                            mask = masks.get(field).copied();
+                            if mask.is_none() {
+                                r.push(&idx_f, format!("key {shown:?}: unknown field `{field}`"));
+                            }
+                            gram
+                        }
+                        None => {
+                            r.push(&idx_f, format!("key {shown:?}: no field separator"));
+                            ""
+                        }
+                    },
+                };
+                // маркер поля в field_grams — пустая грамма
+                let marker = fields.is_some() && gram.is_empty();
+                if !marker && !gram_len.contains(&gram.chars().count()) {
+                    let n = match (gram_len.start(), gram_len.end()) {
+                        (lo, hi) if lo == hi => lo.to_string(),
+                        (lo, hi) => format!("{lo}–{hi}"),
+                    };
+                    r.push(&idx_f, format!("key {shown:?} is not a {n}-gram"));
+                }
+            }
+        }
+        prev_key = Some(e.key);
+
+        if e.off < prev_end {
+            r.push(
+                &idx_f,
+                format!("key {shown:?}: postings overlap previous entry"),
+            );
+        }
+        prev_end = prev_end.max(e.off.saturating_add(e.len));
+        let Some(rec) = slice_at(dat_body, e.off, e.len) else {
+            r.push(
+                &idx_f,
+                format!(
+                    "key {shown:?}: postings [{}+{}] outside {dat_f}",
+                    e.off, e.len
+                ),
+            );
+            continue;
+        };
+        let ids = match decode_postings_raw(rec) {
+            Ok(ids) => ids,
+            Err(err) => {
+                r.push(&dat_f, format!("key {shown:?}: {err}"));
+                continue;
+            }
+        };
+        if let Some(w) = ids.windows(2).find(|w| w[0] >= w[1]) {
+            r.push(
+                &dat_f,
+                format!(
+                    "key {shown:?}: postings not strictly ascending ({} then {})",
+                    w[0], w[1]
+                ),
+            );
+        }
+        if let Some(&bad) = ids.iter().find(|&&d| d >= doc_count) {
+            r.push(
+                &dat_f,
+                format!("key {shown:?}: doc_id {bad} >= doc_count {doc_count}"),
+            );
+        }
+        if let Some(mask) = mask
+            && let Some(&d) = ids.iter().find(|&&d| !mask.contains(d))
+        {
+            r.push(
+                &dat_f,
+                format!("key {shown:?}: doc {d} does not have the field"),
+            );
+        }
+    }
+    if n != dict.len() as u64 {
+        r.push(
+            &idx_f,
+            format!("{n} entries != header count {}", dict.len()),
+        );
+    }
+    Some(n)
+}
+
+// ---------------------------------------------------------------------------
+// V1
+
+/// Проверить V1; `Some(имена полей)`, если `meta.json` читается.
+fn check_v1(dir: &Path, r: &mut IntegrityReport) -> Option<Vec<String>> {
+    let meta: SegmentMetaV1 = match read_json(dir, "meta.json") {
+        Ok(m) => m,
+        Err(e) => {
+            r.push("meta.json", e.to_string());
+            return None;
+        }
+    };
+    r.doc_count = meta.doc_count;
+    let doc_count = meta.doc_count;
+
+    // docs.jsonl: doc_id = номер строки
+    let mut doc_fields: HashMap<String, Bitmap> = HashMap::new();
+    match std::fs::read_to_string(dir.join("docs.jsonl")) {
+        Err(e) => r.push("docs.jsonl", e.to_string()),
+        Ok(text) => {
+            let mut n = 0u32;
+            for (i, line) in text.lines().filter(|l| !l.trim().is_empty()).enumerate() {
+                n += 1;
+                match serde_json::from_str::<StoredDoc>(line) {
+                    Err(e) => r.push("docs.jsonl", format!("line {}: {e}", i + 1)),
+                    Ok(d) => {
+                        if d.doc_id as usize != i {
+                            r.push(
+                                "docs.jsonl",
+                                format!("line {}: doc_id {} != {i}", i + 1, d.doc_id),
+                            );
+                        }
+                        for f in d.fields.keys() {
+                            doc_fields.entry(f.clone()).or_default().add(i as u32);
+                        }
+                        if let Some(f) = d.raw.keys().find(|f| !d.fields.contains_key(*f)) {
+                            r.push(
+                                "docs.jsonl",
+                                format!(
+                                    "line {}: raw text for field `{f}` without normalized value",
+                                    i + 1
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+            if n != doc_count {
+                r.push(
+                    "docs.jsonl",
+                    format!("{n} docs != meta.json doc_count {doc_count}"),
+                );
+            }
+        }
+    }
+
+    // grams.json
+    match read_json::<HashMap<String, Vec<u32>>>(dir, "grams.json") {
+        Err(e) => r.push("grams.json", e.to_string()),
+        Ok(grams) => {
+            if grams.len() as u64 != meta.gram_count as u64 {
+                r.push(
+                    "grams.json",
+                    format!(
+                        "{} grams != meta.json gram_count {}",
+                        grams.len(),
+                        meta.gram_count
+                    ),
+                );
+            }
+            let mut keys: Vec<&String> = grams.keys().collect();
+            keys.sort();
+            for k in keys {
+                if k.chars().count() != 3 {
+                    r.push("grams.json", format!("key {k:?} is not a 3-gram"));
+                }
+                check_id_list("grams.json", &format!("key {k:?}"), &grams[k], doc_count, r);
+            }
+        }
+    }
+
+    // field_masks.json (может отсутствовать у сегментов до A2)
+    let mut names: Vec<String> = doc_fields.keys().cloned().collect();
+    if dir.join("field_masks.json").exists() {
+        match read_json::<HashMap<String, Vec<u32>>>(dir, "field_masks.json") {
+            Err(e) => r.push("field_masks.json", e.to_string()),
+            Ok(masks) => {
+                let mut keys: Vec<&String> = masks.keys().chain(doc_fields.keys()).collect();
+                keys.sort();
+                keys.dedup();
+                for k in keys {
+                    let ids = masks.get(k).map(Vec::as_slice).unwrap_or_default();
+                    check_id_list(
+                        "field_masks.json",
+                        &format!("field `{k}`"),
+                        ids,
+                        doc_count,
+                        r,
+                    );
+                    let mask = Bitmap::of(ids);
+                    let docs = doc_fields.get(k).cloned().unwrap_or_default();
+                    if mask != docs {
+                        r.push(
+                            "field_masks.json",
+                            format!(
+                                "field `{k}`: mask has {} docs, docs.jsonl has the field in {}",
+                                mask.cardinality(),
+                                docs.cardinality()
+                            ),
+                        );
+                    }
+                }
+                names = masks.into_keys().collect();
+            }
+        }
+    }
+    Some(names)
+}
+
+fn check_id_list(file: &str, what: &str, ids: &[u32], doc_count: u32, r: &mut IntegrityReport) {
+    if let Some(w) = ids.windows(2).find(|w| w[0] >= w[1]) {
+        r.push(
+            file,
+            format!(
+                "{what}: doc ids not strictly ascending ({} then {})",
+                w[0], w[1]
+            ),
+        );
+    }
+    if let Some(&bad) = ids.iter().find(|&&d| d >= doc_count) {
+        r.push(
+            file,
+            format!("{what}: doc_id {bad} >= doc_count {doc_count}"),
+        );
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(dir: &Path, name: &str) -> Result<T> {
+    Ok(serde_json::from_reader(std::io::BufReader::new(
+        File::open(dir.join(name))?,
+    ))?)
+}
+
+// ---------------------------------------------------------------------------
+// общее для V1 и V2
+
+/// Удаления, сводка и схема анализаторов: должны относиться к этому сегменту.
+fn check_sidecars(dir: &Path, fields: &[String], r: &mut IntegrityReport) {
+    if let Err(e) = Analyzers::load(dir) {
+        r.push(ANALYZERS_FILE, format!("{e:#}"));
+    }
+    match LiveDocs::load(dir) {
+        Err(e) => r.push(DELETES_FILE, e.to_string()),
+        Ok(live) => {
+            if let Some(mx) = live.deleted().maximum()
+                && mx >= r.doc_count
+            {
+                r.push(
+                    DELETES_FILE,
+                    format!("doc_id {mx} >= doc_count {}", r.doc_count),
+                );
+            }
+        }
+    }
+    match SegmentSummary::load(dir) {
+        Err(e) => r.push(SUMMARY_FILE, e.to_string()),
+        Ok(None) => {}
+        Ok(Some(s)) => {
+            if s.doc_count != r.doc_count {
+                r.push(
+                    SUMMARY_FILE,
+                    format!("doc_count {} != {}", s.doc_count, r.doc_count),
+                );
+            }
+            let mut fields = fields.to_vec();
+            fields.sort();
+            if s.fields != fields {
+                r.push(SUMMARY_FILE, "field set differs from the segment");
+            }
+        }
+    }
+}
+
+/// mmap файла с проверкой CRC64 в футере; `None` — файл не разбираем дальше.
+fn open_with_crc(dir: &Path, name: &str, r: &mut IntegrityReport) -> Option<Mmap> {
+    let m = match File::open(dir.join(name)).and_then(|f| unsafe { Mmap::map(&f) }) {
+        Ok(m) => m,
+        Err(e) => {
+            r.push(name, e.to_string());
+            return None;
+        }
+    };
+    if m.len() < 8 {
+        r.push(name, "too small for CRC64 footer");
+        return None;
+    }
+    let expect = u64::from_le_bytes(m[m.len() - 8..].try_into().unwrap());
+    if crc64_ecma(&m[..m.len() - 8]) != expect {
+        r.push(name, "CRC64 mismatch");
+        return None;
+    }
+    Some(m)
+}
+
+fn slice_at(body: &[u8], off: u64, len: u64) -> Option<&[u8]> {
+    let end = off.checked_add(len)?;
+    (end <= body.len() as u64).then(|| &body[off as usize..end as usize])
+}
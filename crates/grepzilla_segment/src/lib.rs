@@ -1,3 +1,4 @@
+pub mod analyzer;
 pub mod gram;
 pub mod normalizer;
 pub mod segjson;
@@ -8,11 +9,20 @@ use std::collections::BTreeMap;
 
 pub mod common;
 pub mod cursor;
+pub mod deletes;
+pub mod doc_values;
+pub mod integrity;
+pub mod limits;
 pub mod manifest;
 pub mod manifest_store;
+pub mod multi;
+pub mod query;
+pub mod regex_grams;
 pub mod search;
+pub mod summary;
 pub mod v2;
 pub mod verify;
+pub mod wildcard;
 
 /// Внешняя модель документа при ingest
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +34,10 @@ pub struct StoredDoc {
     pub doc_id: u32,                      // локальный id в сегменте
     pub ext_id: String,                   // внешний _id
     pub fields: BTreeMap<String, String>, // только строковые поля, уже нормализованные
+    /// Исходный текст полей для превью — только там, где он отличается
+    /// от нормализованного и сегмент собран с сохранением исходника.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub raw: BTreeMap<String, String>,
 }
 
 /// Метаданные сегмента (минимум для MVP)
@@ -32,6 +46,23 @@ pub struct SegmentMetaV1 {
     pub version: u32, // 1
     pub doc_count: u32,
     pub gram_count: u32,
+    /// Сохранён исходный текст полей (`--raw-text`); у сегментов, собранных
+    /// до появления флага, — `false`
+    #[serde(default)]
+    pub raw_text: bool,
+}
+
+/// Сохранён ли в сегменте `dir` исходный текст полей: флаг `meta.bin` (V2)
+/// или `meta.json` (V1). Читается только заголовок, без открытия сегмента.
+pub fn segment_has_raw_text(dir: &std::path::Path) -> Result<bool> {
+    let meta_bin = dir.join("meta.bin");
+    if meta_bin.exists() {
+        let b = std::fs::read(&meta_bin)?;
+        let body = b.get(..b.len().saturating_sub(8)).unwrap_or_default();
+        return Ok(v2::types::MetaHeader::parse(body)?.has_raw_text());
+    }
+    let meta: SegmentMetaV1 = serde_json::from_slice(&std::fs::read(dir.join("meta.json"))?)?;
+    Ok(meta.raw_text)
 }
 
 /// Точки расширения: читатель/писатель сегмента
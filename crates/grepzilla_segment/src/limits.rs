@@ -0,0 +1,201 @@
+// crates/grepzilla_segment/src/limits.rs
+//! Лимиты сложности запроса: длина паттерна, число кусков wildcard между
+//! `*`, размер скомпилированного regex и число паттернов `/msearch`.
+//! Длина, куски и число паттернов проверяются по
+//! исходному запросу до открытия сегментов — патологический паттерн
+//! (`*a*b*c*…` с десятками звёздочек, паттерн на мегабайт) не доходит до
+//! verify по `max_candidates` документам каждого сегмента. Размер regex
+//! ограничивает сама сборка движков (`RegexBuilder::size_limit` фабрики,
+//! см. [`crate::verify::EngineRegistry::with_regex_size_limit`]); это тоже
+//! до открытия сегментов, пункт ошибки заполняет сборка запроса.
+use serde::{Deserialize, Serialize};
+
+use crate::multi::Pattern;
+use crate::query::{BoolQuery, Clause};
+use crate::wildcard::{self, Token};
+
+pub const DEFAULT_MAX_PATTERN_LEN: usize = 1024;
+pub const DEFAULT_MAX_WILDCARD_SEGMENTS: usize = 16;
+/// байт скомпилированной программы regex (`RegexBuilder::size_limit`)
+pub const DEFAULT_MAX_REGEX_SIZE: usize = 1 << 20;
+pub const DEFAULT_MAX_PATTERNS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct QueryLimits {
+    /// символов в wildcard или regex одного пункта
+    pub max_pattern_len: usize,
+    /// непустых кусков wildcard между `*` (`*a*b?c*` — два)
+    pub max_wildcard_segments: usize,
+    /// байт скомпилированного regex (и wildcard в виде regex); у
+    /// мультипоиска — всего набора
+    pub max_regex_size: usize,
+    /// паттернов в одном запросе мультипоиска
+    pub max_patterns: usize,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        Self {
+            max_pattern_len: DEFAULT_MAX_PATTERN_LEN,
+            max_wildcard_segments: DEFAULT_MAX_WILDCARD_SEGMENTS,
+            max_regex_size: DEFAULT_MAX_REGEX_SIZE,
+            max_patterns: DEFAULT_MAX_PATTERNS,
+        }
+    }
+}
+
+/// Нарушенный лимит; сериализуется в тело ответа `400`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LimitExceeded {
+    /// имя лимита (`max_pattern_len`, `max_wildcard_segments`,
+    /// `max_regex_size`, `max_patterns`)
+    pub limit: &'static str,
+    /// пункт запроса: `must[0]`, `should[1]`, `patterns[2]`, `patterns`, ...
+    pub clause: String,
+    pub max: usize,
+    /// фактическое значение; для `max_regex_size` неизвестно
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<usize>,
+}
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: query exceeds {} = {}",
+            self.clause, self.limit, self.max
+        )?;
+        if let Some(actual) = self.actual {
+            write!(f, " (got {actual})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl LimitExceeded {
+    /// Превышен `max_regex_size`; пункт заполняет вызывающий.
+    pub fn regex_size(max: usize) -> Self {
+        Self {
+            limit: "max_regex_size",
+            clause: String::new(),
+            max,
+            actual: None,
+        }
+    }
+
+    /// Ошибка `e` с пунктом `clause(прежний пункт)`, если это нарушение
+    /// лимита; прочие ошибки — как есть.
+    pub fn in_clause(e: anyhow::Error, clause: impl FnOnce(&str) -> String) -> anyhow::Error {
+        match e.downcast::<Self>() {
+            Ok(l) => Self {
+                clause: clause(&l.clause),
+                ..l
+            }
+            .into(),
+            Err(e) => e,
+        }
+    }
+
+    /// Тело ответа: `{"error": "query_too_complex", "message": ..., "limit": ...,
+    /// "clause": ..., "max": ..., "actual": ...}` — одно у `/search` и `gzctl`.
+    pub fn body(&self) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "error": "query_too_complex",
+            "message": self.to_string(),
+        });
+        if let (Some(obj), Ok(serde_json::Value::Object(fields))) =
+            (body.as_object_mut(), serde_json::to_value(self))
+        {
+            obj.extend(fields);
+        }
+        body
+    }
+}
+
+impl QueryLimits {
+    /// Проверить все пункты запроса; первое нарушение — ошибка. Размер
+    /// regex здесь не проверяется — см. описание модуля.
+    pub fn check(&self, query: &BoolQuery) -> Result<(), LimitExceeded> {
+        for (kind, clauses) in [
+            ("must", &query.must),
+            ("should", &query.should),
+            ("must_not", &query.must_not),
+        ] {
+            for (i, c) in clauses.iter().enumerate() {
+                self.check_clause(c)
+                    .map_err(|(limit, max, actual)| LimitExceeded {
+                        limit,
+                        clause: format!("{kind}[{i}]"),
+                        max,
+                        actual,
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Паттерны мультипоиска: их число и каждый как пункт (`patterns[i]`).
+    pub fn check_multi(&self, patterns: &[Pattern]) -> Result<(), LimitExceeded> {
+        if patterns.len() > self.max_patterns {
+            return Err(LimitExceeded {
+                limit: "max_patterns",
+                clause: "patterns".to_string(),
+                max: self.max_patterns,
+                actual: Some(patterns.len()),
+            });
+        }
+        for (i, p) in patterns.iter().enumerate() {
+            self.check_clause(&Clause::wildcard(&p.wildcard, p.field.as_deref()))
+                .map_err(|(limit, max, actual)| LimitExceeded {
+                    limit,
+                    clause: format!("patterns[{i}]"),
+                    max,
+                    actual,
+                })?;
+        }
+        Ok(())
+    }
+
+    fn check_clause(&self, c: &Clause) -> Result<(), (&'static str, usize, Option<usize>)> {
+        let pattern = c.regex.as_deref().unwrap_or(&c.wildcard);
+        let len = pattern.chars().count();
+        if len > self.max_pattern_len {
+            return Err(("max_pattern_len", self.max_pattern_len, Some(len)));
+        }
+        if c.regex.is_none() {
+            let segments = wildcard_segments(&c.wildcard);
+            if segments > self.max_wildcard_segments {
+                return Err((
+                    "max_wildcard_segments",
+                    self.max_wildcard_segments,
+                    Some(segments),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Непустые куски wildcard между `*`.
+fn wildcard_segments(pattern: &str) -> usize {
+    wildcard::parse(pattern)
+        .split(|t| *t == Token::AnyString)
+        .filter(|s| !s.is_empty())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments() {
+        assert_eq!(wildcard_segments("*"), 0);
+        assert_eq!(wildcard_segments("foo"), 1);
+        assert_eq!(wildcard_segments("**a*b?c**"), 2);
+        assert_eq!(wildcard_segments(r"*a\*b*"), 1);
+    }
+}
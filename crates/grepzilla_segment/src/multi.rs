@@ -0,0 +1,205 @@
+// crates/grepzilla_segment/src/multi.rs
+//! Мультипоиск: N wildcard-паттернов по одним и тем же сегментам за один
+//! проход (`/msearch` брокера). Паттерны собираются под схему анализаторов
+//! сегмента, как пункты `should` булева запроса, но:
+//!
+//! * префильтр — один на сегмент: формулы грамм паттернов сводятся OR, по
+//!   одной формуле на поле;
+//! * verify — один [`MultiVerifyEngine`] на анализатор схемы (паттерны,
+//!   приведённые им), то есть один проход по тексту поля на все паттерны;
+//! * совпадение документа — все совпавшие паттерны, а не первый.
+//!
+//! Префильтр общий, поэтому паттерн без литерала из ≥3 символов сорвал бы
+//! поиск по сегменту для всех: без полного просмотра такой паттерн — ошибка
+//! запроса ([`CompiledMulti::check_strength`]), а не сегмента.
+use anyhow::Result;
+use croaring::Bitmap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::StoredDoc;
+use crate::analyzer::{Analyzers, DEFAULT_ANALYZER};
+use crate::gram::GramQuery;
+use crate::limits::LimitExceeded;
+use crate::normalizer::Sensitivity;
+use crate::query::{BoolQuery, Clause, CompiledClause, CompiledQuery, InvalidQuery, PrefilterOpts};
+use crate::verify::{MultiVerifyEngine, VerifyFactory};
+use crate::wildcard::map_literals;
+
+/// Паттерн мультипоиска; `id` возвращается в хитах, которые он нашёл.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Pattern {
+    pub id: String,
+    pub wildcard: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+/// Форма набора паттернов: хотя бы один, id без повторов. Нарушение —
+/// ошибка клиента (`400` у `/msearch`).
+pub fn validate(patterns: &[Pattern]) -> Result<(), InvalidQuery> {
+    if patterns.is_empty() {
+        return Err(InvalidQuery("msearch needs at least one pattern".into()));
+    }
+    let mut seen = BTreeSet::new();
+    if let Some(p) = patterns.iter().find(|p| !seen.insert(p.id.as_str())) {
+        return Err(InvalidQuery(format!("duplicate pattern id `{}`", p.id)));
+    }
+    Ok(())
+}
+
+pub struct CompiledMulti {
+    ids: Vec<String>,
+    /// паттерны пунктами `should`: граммы, подсветка, отсев по сводке
+    query: CompiledQuery,
+    /// id анализатора → движок по приведённым им паттернам и их номера
+    engines: BTreeMap<String, (MultiVerifyEngine, Vec<usize>)>,
+    analyzers: Analyzers,
+}
+
+impl CompiledMulti {
+    /// Собрать паттерны под схему `analyzers`; `factory` — движок
+    /// подсветки отдельного паттерна.
+    pub fn compile(
+        patterns: &[Pattern],
+        factory: &dyn VerifyFactory,
+        analyzers: &Analyzers,
+    ) -> Result<Self> {
+        validate(patterns)?;
+        let query = BoolQuery {
+            should: patterns
+                .iter()
+                .map(|p| Clause::wildcard(&p.wildcard, p.field.as_deref()))
+                .collect(),
+            ..Default::default()
+        }
+        .compile_with(factory, Sensitivity::default(), analyzers)
+        // пункт `should[i]` запроса — паттерн `patterns[i]`
+        .map_err(|e| LimitExceeded::in_clause(e, |c| c.replacen("should", "patterns", 1)))?;
+
+        let mut engines = BTreeMap::new();
+        for a in analyzers.distinct() {
+            let members: Vec<usize> = (0..patterns.len())
+                .filter(|&i| {
+                    query.should[i]
+                        .field
+                        .as_deref()
+                        .is_none_or(|f| analyzers.for_field(f).id() == a.id())
+                })
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            let wildcards: Vec<String> = members
+                .iter()
+                .map(|&i| map_literals(&patterns[i].wildcard, |s| a.analyze(s)))
+                .collect();
+            // как у одиночного пункта: регистр сворачивает только `default`
+            let engine = MultiVerifyEngine::compile(
+                &wildcards,
+                a.id() != DEFAULT_ANALYZER,
+                factory.regex_size_limit(),
+            )
+            .map_err(|e| LimitExceeded::in_clause(e, |_| "patterns".to_string()))?;
+            engines.insert(a.id().to_string(), (engine, members));
+        }
+
+        Ok(Self {
+            ids: patterns.iter().map(|p| p.id.clone()).collect(),
+            query,
+            engines,
+            analyzers: analyzers.clone(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// `id` паттерна номер `i`.
+    pub fn id(&self, i: usize) -> &str {
+        &self.ids[i]
+    }
+
+    /// Пункт паттерна номер `i` (подсветка, игла для превью).
+    pub fn clause(&self, i: usize) -> &CompiledClause {
+        &self.query.should[i]
+    }
+
+    /// Паттерны как булев запрос «хотя бы один» — для отсева сегментов по
+    /// сводке ([`CompiledQuery::may_match`]).
+    pub fn query(&self) -> &CompiledQuery {
+        &self.query
+    }
+
+    /// Каждому паттерну нужен литерал из ≥3 символов (индекс коротких грамм
+    /// есть не у всех сегментов), если полный просмотр не разрешён; ошибка
+    /// называет паттерн (`patterns[1]: pattern too weak; ...`).
+    pub fn check_strength(&self, full_scan: bool) -> Result<(), InvalidQuery> {
+        let opts = PrefilterOpts {
+            short_grams: false,
+            full_scan,
+        };
+        for (i, c) in self.query.should.iter().enumerate() {
+            if let Err(e) = c.gram_query_for(opts) {
+                return Err(InvalidQuery(format!("patterns[{i}]: {e}")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Кандидаты: OR формул грамм всех паттернов, одна формула на поле;
+    /// `runs` — как у [`CompiledQuery::prefilter`].
+    pub fn prefilter(
+        &self,
+        opts: PrefilterOpts,
+        mut runs: impl FnMut(&[Vec<String>], Option<&str>) -> Result<Bitmap>,
+    ) -> Result<Bitmap> {
+        let mut by_field: BTreeMap<Option<&str>, GramQuery> = BTreeMap::new();
+        for c in &self.query.should {
+            let q = c.gram_query_for(opts)?;
+            let key = c.field.as_deref();
+            let q = match by_field.remove(&key) {
+                Some(prev) => prev.or(q),
+                None => q,
+            };
+            by_field.insert(key, q);
+        }
+        let mut bm = Bitmap::new();
+        for (field, q) in by_field {
+            bm.or_inplace(&q.eval(&mut |r| runs(r, field))?);
+        }
+        Ok(bm)
+    }
+
+    /// Совпавшие паттерны документа по возрастанию номера: `(номер, поле)`,
+    /// поле — первое, где паттерн совпал.
+    pub fn matches<'d>(&self, doc: &'d StoredDoc) -> Vec<(usize, &'d str)> {
+        let mut found: Vec<Option<&str>> = vec![None; self.ids.len()];
+        for (field, text) in &doc.fields {
+            let id = self.analyzers.for_field(field).id();
+            let Some((engine, members)) = self.engines.get(id) else {
+                continue;
+            };
+            for k in engine.matches(text) {
+                let i = members[k];
+                let own_field = self.query.should[i]
+                    .field
+                    .as_deref()
+                    .is_none_or(|f| f == field);
+                if found[i].is_none() && own_field {
+                    found[i] = Some(field);
+                }
+            }
+        }
+        found
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, f)| Some((i, f?)))
+            .collect()
+    }
+}
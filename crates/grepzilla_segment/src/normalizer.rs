@@ -1,12 +1,54 @@
+use serde::{Deserialize, Serialize};
 use unicode_normalization::UnicodeNormalization;
 
+use crate::analyzer::Analyzer;
+use unicode_normalization::char::is_combining_mark;
+
+/// Строгость сравнения при verify. По умолчанию всё сворачивается, как в индексе.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sensitivity {
+    /// Различать регистр.
+    pub case_sensitive: bool,
+    /// Различать диакритику (é ≠ e, ё ≠ е).
+    pub accent_sensitive: bool,
+    /// Не применять NFKC: полноширинные формы, лигатуры и т.п. различаются.
+    pub width_sensitive: bool,
+}
+
+impl Sensitivity {
+    /// Совпадает со свёрткой индекса — строгая проверка не нужна.
+    pub fn is_folded(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 pub fn normalize(s: &str) -> String {
-    let lower = s.to_lowercase();
-    let nfkc = lower.nfkc().collect::<String>();
-    strip_accents(&nfkc)
+    normalize_with(s, Sensitivity::default())
 }
 
-fn strip_accents(s: &str) -> String {
+/// Нормализация с частично отключённой свёрткой. Диакритика, если она
+/// сохраняется, приводится к составной форме (NFC): `é` и `e` + U+0301
+/// сравниваются одинаково, а классы вида `[eé]` остаются односимвольными.
+pub fn normalize_with(s: &str, sens: Sensitivity) -> String {
+    let lower = if sens.case_sensitive {
+        s.to_string()
+    } else {
+        s.to_lowercase()
+    };
+    let nfkc = if sens.width_sensitive {
+        lower
+    } else {
+        lower.nfkc().collect::<String>()
+    };
+    if sens.accent_sensitive {
+        nfkc.nfc().collect()
+    } else {
+        strip_accents(&nfkc)
+    }
+}
+
+pub(crate) fn strip_accents(s: &str) -> String {
     s.nfd().filter(|c| !is_mark(*c)).collect()
 }
 
@@ -14,11 +56,130 @@ fn is_mark(c: char) -> bool {
     ('\u{0300}'..='\u{036F}').contains(&c)
 }
 
+/// Соответствие байтовых смещений нормализованной строки смещениям исходной.
+///
+/// Исходный текст режется на кластеры «символ + комбинирующие знаки» и
+/// каждый кластер приводится анализатором поля отдельно; точки `(norm_off, raw_off)` —
+/// начала кластеров. Смещение внутри кластера сводится к его границам, так
+/// что подсветка в исходном тексте захватывает кластер целиком.
+#[derive(Debug, Clone)]
+pub struct OffsetMap {
+    norm: Vec<usize>,
+    raw: Vec<usize>,
+}
+
+impl OffsetMap {
+    /// Построить карту для `raw`, приведённого анализатором `a` в
+    /// `normalized`. `None`, если покластерное приведение не совпало с
+    /// `normalized` (редкие случаи NFKC, склеивающие кластеры).
+    pub fn build(raw: &str, normalized: &str, a: &dyn Analyzer) -> Option<Self> {
+        let mut norm = Vec::new();
+        let mut raw_offs = Vec::new();
+        let mut out = String::with_capacity(normalized.len());
+        let mut start = 0usize;
+        for (i, c) in raw.char_indices().skip(1) {
+            if is_combining_mark(c) {
+                continue;
+            }
+            norm.push(out.len());
+            raw_offs.push(start);
+            out.push_str(&a.analyze(&raw[start..i]));
+            start = i;
+        }
+        if !raw.is_empty() {
+            norm.push(out.len());
+            raw_offs.push(start);
+            out.push_str(&a.analyze(&raw[start..]));
+        }
+        norm.push(out.len());
+        raw_offs.push(raw.len());
+        (out == normalized).then_some(Self {
+            norm,
+            raw: raw_offs,
+        })
+    }
+
+    /// Перевести диапазон `[s, e)` нормализованной строки в диапазон исходной.
+    pub fn to_raw(&self, s: usize, e: usize) -> (usize, usize) {
+        // начало — кластер, содержащий s; конец — первый кластер, начинающийся не раньше e
+        let si = self.norm.partition_point(|&n| n <= s).saturating_sub(1);
+        let ei = self
+            .norm
+            .partition_point(|&n| n < e)
+            .min(self.norm.len() - 1);
+        let (rs, re) = (self.raw[si], self.raw[ei]);
+        (rs, re.max(rs))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::analyzer::{Pipeline, Stage};
     #[test]
     fn test_normalize_basic() {
         assert_eq!(normalize("КоШКи"), "кошки");
     }
+
+    #[test]
+    fn normalize_with_keeps_requested_distinctions() {
+        let case = Sensitivity {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        assert_eq!(normalize_with("Café", case), "Cafe");
+        let accent = Sensitivity {
+            accent_sensitive: true,
+            ..Default::default()
+        };
+        assert_eq!(normalize_with("Café", accent), "café");
+        assert_eq!(normalize_with("Cafe\u{301}", accent), "café");
+        let width = Sensitivity {
+            width_sensitive: true,
+            ..Default::default()
+        };
+        assert_eq!(normalize_with("ＡＢ", width), "ａｂ");
+        assert_eq!(normalize("ＡＢ"), "ab");
+        assert!(Sensitivity::default().is_folded() && !width.is_folded());
+    }
+
+    #[test]
+    fn offset_map_follows_case_and_accents() {
+        let raw = "Ошибка в модуле API: Café";
+        let norm = normalize(raw);
+        let std = Pipeline::standard();
+        let map = OffsetMap::build(raw, &norm, &std).unwrap();
+
+        let s = norm.find("api").unwrap();
+        let (rs, re) = map.to_raw(s, s + 3);
+        assert_eq!(&raw[rs..re], "API");
+
+        let s = norm.find("cafe").unwrap();
+        let (rs, re) = map.to_raw(s, s + 4);
+        assert_eq!(&raw[rs..re], "Café");
+
+        // декомпозированный акцент: "e" + U+0301 — один кластер
+        let raw = "Cafe\u{301} ok";
+        let map = OffsetMap::build(raw, &normalize(raw), &std).unwrap();
+        let (rs, re) = map.to_raw(3, 4);
+        assert_eq!(&raw[rs..re], "e\u{301}");
+
+        // лигатура раскрывается в два символа: подсветка — вся лигатура
+        let raw = "\u{FB01}nal";
+        let map = OffsetMap::build(raw, &normalize(raw), &std).unwrap();
+        assert_eq!(map.to_raw(1, 3), (0, 4));
+    }
+
+    #[test]
+    fn offset_map_follows_field_analyzer() {
+        let raw = "Ёлка в модуле API";
+        let layout = Pipeline::new("layout", vec![Stage::Lowercase, Stage::KeyboardLayout]);
+        let text = layout.analyze(raw);
+        // стандартная нормализация дала бы другой текст — карты нет
+        assert!(OffsetMap::build(raw, &text, &Pipeline::standard()).is_none());
+        let map = OffsetMap::build(raw, &text, &layout).unwrap();
+        let s = text.find(&layout.analyze("модуле")).unwrap();
+        let (rs, re) = map.to_raw(s, s + "vjlekt".len());
+        assert_eq!(&raw[rs..re], "модуле");
+    }
 }
@@ -0,0 +1,679 @@
+// crates/grepzilla_segment/src/query.rs
+//! Булев запрос по полям: пункты `must` / `should` / `must_not`, каждый —
+//! wildcard или регулярное выражение по одному полю (или по всем). Один и тот же JSON принимают
+//! `/search` брокера (поле `query`) и `gzctl search-seg --query-file`:
+//!
+//! ```json
+//! {"must":     [{"field": "text.title", "wildcard": "*timeout*"}],
+//!  "must_not": [{"field": "text.body",  "wildcard": "*retry*"}]}
+//! ```
+//!
+//! Документ подходит, если совпали все `must`, хотя бы один `should` (если
+//! они заданы) и ни один `must_not`. Префильтр собирает битмапы пунктов по
+//! той же логике: `must` — AND, `should` — OR. Отбор по граммам шире
+//! точного, поэтому `must_not` из него не вычитается и проверяется только
+//! на verify.
+//!
+//! Wildcard сопоставляется со всем значением поля: якорь снимает только
+//! `*` на краю (`foo*` — префикс, `*foo*` — вхождение), `\` экранирует `*` и
+//! `?` (см. [`crate::wildcard`]).
+//!
+//! Для `regex` формула грамм выводится из разобранного выражения
+//! (см. [`crate::regex_grams`]), verify — полным regex по нормализованному тексту.
+//!
+//! Wildcard без литерала из ≥3 символов отбирается по коротким литералам,
+//! если у сегмента есть индекс 1–2-грамм, а пункт без грамм вовсе — полным
+//! просмотром сегмента, если он разрешён ([`PrefilterOpts`]).
+//!
+//! Запрос со строгим сравнением ([`Sensitivity`]) отбирается по тем же
+//! свёрнутым граммам; совпавший по свёрнутому тексту документ проверяется
+//! ещё раз — по исходному тексту поля (`StoredDoc::raw`), нормализованному
+//! без отключённых свёрток. Поля без записи в `raw` (текст не менялся
+//! нормализацией) сверяются по хранимому тексту. Сегмент, собранный без
+//! `--raw-text`, такой запрос искать не может — хранимый текст уже свёрнут:
+//! [`CompiledQuery::check_raw_text`].
+//!
+//! Паттерн приводится анализатором поля из схемы сегмента
+//! ([`crate::analyzer`]), поэтому запрос собирается под схему:
+//! [`BoolQuery::compile_with`]. У пункта без поля столько вариантов, сколько
+//! различных анализаторов в схеме: граммы — OR вариантов, verify — вариантом
+//! анализатора проверяемого поля.
+use anyhow::{Result, bail};
+use croaring::Bitmap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::StoredDoc;
+use crate::analyzer::{Analyzer, Analyzers, DEFAULT_ANALYZER};
+use crate::gram::{GramQuery, WEAK_WILDCARD, wildcard_gram_query};
+use crate::limits::LimitExceeded;
+use crate::normalizer::{Sensitivity, normalize_with};
+use crate::regex_grams::WEAK_REGEX;
+use crate::verify::{VerifyEngine, VerifyFactory};
+use crate::wildcard::map_literals;
+
+/// Пункт запроса: `wildcard` либо `regex` по полю; без поля — по любому
+/// полю документа.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Clause {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub wildcard: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+impl Clause {
+    pub fn wildcard(wildcard: &str, field: Option<&str>) -> Self {
+        Self {
+            wildcard: wildcard.to_string(),
+            field: field.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    pub fn regex(regex: &str, field: Option<&str>) -> Self {
+        Self {
+            regex: Some(regex.to_string()),
+            field: field.map(str::to_string),
+            ..Default::default()
+        }
+    }
+}
+
+/// Запрос неверной формы (нет ни `must`, ни `should`, у пункта и `wildcard`,
+/// и `regex`, ...) — ошибка клиента, а не сервера: `/search` отвечает `400`
+/// с телом [`InvalidQuery::body`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidQuery(pub String);
+
+impl std::fmt::Display for InvalidQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidQuery {}
+
+impl InvalidQuery {
+    /// Тело ответа: `{"error": "invalid_query", "message": ...}`.
+    pub fn body(&self) -> serde_json::Value {
+        serde_json::json!({ "error": "invalid_query", "message": self.0 })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoolQuery {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub must: Vec<Clause>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub should: Vec<Clause>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub must_not: Vec<Clause>,
+}
+
+impl BoolQuery {
+    /// Запрос из одного пункта (прежний вид `wildcard` + `field`).
+    pub fn single(clause: Clause) -> Self {
+        Self {
+            must: vec![clause],
+            ..Default::default()
+        }
+    }
+
+    /// Проверить форму запроса без сборки: есть `must` или `should`, у
+    /// каждого пункта ровно одно из `wildcard` / `regex`.
+    pub fn validate(&self) -> Result<(), InvalidQuery> {
+        if self.must.is_empty() && self.should.is_empty() {
+            return Err(InvalidQuery(
+                "query needs at least one must or should clause".into(),
+            ));
+        }
+        for (kind, clauses) in [
+            ("must", &self.must),
+            ("should", &self.should),
+            ("must_not", &self.must_not),
+        ] {
+            for (i, c) in clauses.iter().enumerate() {
+                if c.wildcard.is_empty() == c.regex.is_none() {
+                    return Err(InvalidQuery(format!(
+                        "{kind}[{i}]: clause needs exactly one of `wildcard` or `regex`"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Разобрать граммы и собрать движки verify для всех пунктов.
+    /// `must` и `should` дают кандидатов, поэтому их паттерны должны
+    /// содержать граммы; `must_not` только проверяется и может быть любым.
+    /// Слишком короткий паттерн — ошибка префильтра (как и у одиночного
+    /// wildcard, она возникает при поиске по сегменту), а не сборки.
+    pub fn compile(&self, factory: &dyn VerifyFactory) -> Result<CompiledQuery> {
+        self.compile_with(factory, Sensitivity::default(), &Analyzers::default())
+    }
+
+    /// То же со строгостью сравнения `sens` для всех пунктов; паттерны
+    /// приводятся анализаторами полей сегмента `analyzers`.
+    pub fn compile_with(
+        &self,
+        factory: &dyn VerifyFactory,
+        sens: Sensitivity,
+        analyzers: &Analyzers,
+    ) -> Result<CompiledQuery> {
+        self.validate()?;
+        let compile = |kind: &str, clauses: &[Clause], grams: bool| {
+            clauses
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    CompiledClause::new(c, factory, sens, analyzers, grams)
+                        .map_err(|e| LimitExceeded::in_clause(e, |_| format!("{kind}[{i}]")))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(CompiledQuery {
+            must: compile("must", &self.must, true)?,
+            should: compile("should", &self.should, true)?,
+            must_not: compile("must_not", &self.must_not, false)?,
+        })
+    }
+}
+
+pub struct CompiledClause {
+    /// `None` — любое поле (пустая строка трактуется так же)
+    pub field: Option<String>,
+    /// нормализованный wildcard либо verify-паттерн regex (анализатор поля,
+    /// у пункта без поля — анализатор полей без назначения)
+    pub pattern: String,
+    /// формула обязательных грамм (`All` у `must_not`)
+    grams: GramQuery,
+    /// ошибка префильтра, если формула не сужает кандидатов
+    weak: &'static str,
+    pub engine: Arc<dyn VerifyEngine>,
+    /// пункт без поля: движки остальных анализаторов схемы по их id
+    others: BTreeMap<String, Arc<dyn VerifyEngine>>,
+    analyzers: Analyzers,
+    /// verify по исходному тексту (`None` — достаточно свёрнутого)
+    strict: Option<Arc<dyn VerifyEngine>>,
+    sens: Sensitivity,
+}
+
+/// Чем сегмент может ответить на слабые пункты.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrefilterOpts {
+    /// у сегмента есть индекс коротких грамм (`short_grams.*`)
+    pub short_grams: bool,
+    /// пункт без грамм берёт все документы сегмента вместо ошибки
+    /// (число проверяемых ограничивает вызывающий, например `max_candidates`)
+    pub full_scan: bool,
+}
+
+impl CompiledClause {
+    fn new(
+        c: &Clause,
+        factory: &dyn VerifyFactory,
+        sens: Sensitivity,
+        analyzers: &Analyzers,
+        grams: bool,
+    ) -> Result<Self> {
+        let field = c.field.clone().filter(|f| !f.is_empty());
+        // пункт без поля ищет по полям с разными анализаторами: граммы — OR
+        // по вариантам, verify — движком анализатора совпавшего поля
+        let targets = match &field {
+            Some(f) => vec![analyzers.for_field(f).clone()],
+            None => analyzers.distinct(),
+        };
+        let mut variants = targets
+            .iter()
+            .map(|a| Ok((a.id().to_string(), Self::analyzed(c, factory, a.as_ref())?)))
+            .collect::<Result<Vec<_>>>()?;
+        let (_, (pattern, mut gram_query, weak, engine)) = variants.remove(0);
+        let mut others = BTreeMap::new();
+        for (id, (_, q, _, e)) in variants {
+            gram_query = gram_query.or(q);
+            others.insert(id, e);
+        }
+        let strict = match (&c.regex, sens.is_folded()) {
+            (_, true) => None,
+            (None, false) => {
+                let wildcard = map_literals(&c.wildcard, |s| normalize_with(s, sens));
+                Some(factory.compile_sensitive(&wildcard, sens)?)
+            }
+            (Some(regex), false) => Some(factory.compile_regex_sensitive(regex, sens)?),
+        };
+        Ok(Self {
+            field,
+            pattern,
+            grams: if grams { gram_query } else { GramQuery::All },
+            weak,
+            engine,
+            others,
+            analyzers: analyzers.clone(),
+            strict,
+            sens,
+        })
+    }
+
+    /// Паттерн, формула, ошибка слабого паттерна и движок для анализатора `a`.
+    fn analyzed(
+        c: &Clause,
+        factory: &dyn VerifyFactory,
+        a: &dyn Analyzer,
+    ) -> Result<(String, GramQuery, &'static str, Arc<dyn VerifyEngine>)> {
+        match (c.wildcard.is_empty(), &c.regex) {
+            (false, None) => {
+                let wildcard = map_literals(&c.wildcard, |s| a.analyze(s));
+                let q = wildcard_gram_query(&wildcard);
+                // текст и паттерн приведены одним анализатором: регистр
+                // сверяется как есть, если анализатор его сохраняет
+                let engine = if a.id() == DEFAULT_ANALYZER {
+                    factory.compile(&wildcard)?
+                } else {
+                    let exact = Sensitivity {
+                        case_sensitive: true,
+                        ..Default::default()
+                    };
+                    factory.compile_sensitive(&wildcard, exact)?
+                };
+                Ok((wildcard, q, WEAK_WILDCARD, engine))
+            }
+            (true, Some(regex)) => {
+                // синтаксическая ошибка — сразу, слабый паттерн — при префильтре
+                let (rq, engine) = factory.compile_regex_clause(regex, a)?;
+                Ok((rq.verify, rq.grams, WEAK_REGEX, engine))
+            }
+            _ => bail!("clause needs exactly one of `wildcard` or `regex`"),
+        }
+    }
+
+    /// Движок verify для поля `field` (анализатор поля в схеме сегмента).
+    pub fn engine_for(&self, field: &str) -> &Arc<dyn VerifyEngine> {
+        if self.others.is_empty() {
+            return &self.engine;
+        }
+        let id = self.analyzers.for_field(field).id();
+        self.others.get(id).unwrap_or(&self.engine)
+    }
+
+    /// Формула грамм пункта (с короткими литералами).
+    pub fn gram_query(&self) -> &GramQuery {
+        &self.grams
+    }
+
+    /// Формула для сегмента с возможностями `opts`; ошибка — паттерн
+    /// слишком слабый, а полный просмотр не разрешён.
+    pub fn gram_query_for(&self, opts: PrefilterOpts) -> Result<GramQuery> {
+        let q = if opts.short_grams {
+            self.grams.clone()
+        } else {
+            self.grams.without_short()
+        };
+        if q == GramQuery::All && !opts.full_scan {
+            bail!("{}", self.weak);
+        }
+        Ok(q)
+    }
+
+    /// Формула не сужает кандидатов ни в каком сегменте (нет литерала даже
+    /// из коротких грамм), а полный просмотр не разрешён, — ошибка клиента,
+    /// видная до открытия сегментов.
+    pub fn check_strength(&self, full_scan: bool) -> Result<(), InvalidQuery> {
+        if self.grams == GramQuery::All && !full_scan {
+            return Err(InvalidQuery(self.weak.to_string()));
+        }
+        Ok(())
+    }
+
+    /// 3-граммы, обязательные при любом исходе (для плана AND).
+    pub fn grams(&self) -> Vec<String> {
+        self.grams.required_grams()
+    }
+
+    /// Самый длинный обязательный литерал — подсветка в превью, когда
+    /// verify-движок не дал точного спана.
+    pub fn needle(&self) -> Option<String> {
+        fn literals(q: &GramQuery, out: &mut Vec<String>) {
+            match q {
+                GramQuery::Run(r) => {
+                    let mut s = r.first().cloned().unwrap_or_default();
+                    s.extend(r.iter().skip(1).filter_map(|g| g.chars().last()));
+                    out.push(s);
+                }
+                GramQuery::Short(s) => out.push(s.clone()),
+                GramQuery::And(xs) | GramQuery::Or(xs) => xs.iter().for_each(|x| literals(x, out)),
+                GramQuery::All => {}
+            }
+        }
+        let mut all = Vec::new();
+        literals(&self.grams, &mut all);
+        all.into_iter()
+            .filter(|s| !s.is_empty())
+            .max_by_key(|s| s.chars().count())
+    }
+
+    /// Поле документа, в котором пункт совпал.
+    pub fn matched_field<'a>(&self, doc: &'a StoredDoc) -> Option<&'a str> {
+        match &self.field {
+            Some(f) => doc
+                .fields
+                .get_key_value(f)
+                .filter(|(k, t)| self.is_match(doc, k, t))
+                .map(|(k, _)| k.as_str()),
+            None => doc
+                .fields
+                .iter()
+                .find(|(k, t)| self.is_match(doc, k, t))
+                .map(|(k, _)| k.as_str()),
+        }
+    }
+
+    /// Нужен ли пункту исходный текст полей (строгое сравнение).
+    pub fn is_strict(&self) -> bool {
+        self.strict.is_some()
+    }
+
+    /// Свёрнутый текст поля, затем (при строгом сравнении) исходный.
+    fn is_match(&self, doc: &StoredDoc, field: &str, text: &str) -> bool {
+        self.engine_for(field).is_match(text)
+            && self.strict.as_ref().is_none_or(|e| {
+                let raw = doc.raw.get(field).map_or(text, String::as_str);
+                e.is_match(&normalize_with(raw, self.sens))
+            })
+    }
+}
+
+pub struct CompiledQuery {
+    pub must: Vec<CompiledClause>,
+    pub should: Vec<CompiledClause>,
+    pub must_not: Vec<CompiledClause>,
+}
+
+impl CompiledQuery {
+    /// Пункты, дающие кандидатов: сначала `must`, затем `should`.
+    pub fn positive(&self) -> impl Iterator<Item = &CompiledClause> {
+        self.must.iter().chain(&self.should)
+    }
+
+    /// Нужен ли запросу исходный текст полей (строгое сравнение).
+    pub fn needs_raw_text(&self) -> bool {
+        self.positive()
+            .chain(&self.must_not)
+            .any(CompiledClause::is_strict)
+    }
+
+    /// [`CompiledClause::check_strength`] для `must` и `should`; ошибка
+    /// называет пункт (`must[0]: regex too weak; ...`).
+    pub fn check_strength(&self, full_scan: bool) -> Result<(), InvalidQuery> {
+        for (kind, clauses) in [("must", &self.must), ("should", &self.should)] {
+            for (i, c) in clauses.iter().enumerate() {
+                c.check_strength(full_scan)
+                    .map_err(|e| InvalidQuery(format!("{kind}[{i}]: {}", e.0)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Строгий запрос к сегменту `seg_dir`, собранному без исходного текста, —
+    /// ошибка: по свёрнутому тексту verify молча не нашёл бы ничего.
+    pub fn check_raw_text(&self, seg_dir: &std::path::Path) -> Result<()> {
+        if self.needs_raw_text() && !crate::segment_has_raw_text(seg_dir)? {
+            bail!(
+                "segment {} was built without raw text (--raw-text); \
+                 case/accent/width-sensitive search needs it",
+                seg_dir.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Может ли запрос совпасть, если `grams` отвечает, могут ли все граммы
+    /// встретиться в поле (например, по сводке сегмента). `must_not` не
+    /// учитывается; слабый паттерн проверяется без грамм.
+    pub fn may_match(&self, mut grams: impl FnMut(&[String], Option<&str>) -> bool) -> bool {
+        let mut clause =
+            |c: &CompiledClause| c.grams.may_match(&mut |g| grams(g, c.field.as_deref()));
+        self.must.iter().all(&mut clause)
+            && (self.should.is_empty() || self.should.iter().any(&mut clause))
+    }
+
+    /// Кандидаты запроса. `runs` отбирает документы, где в поле есть все
+    /// цепочки грамм (AND, см. [`GramQuery::eval`]); пункты сводятся AND по
+    /// `must` и OR по `should`. Пересечение обрывается, как только стало пусто.
+    /// Короткие литералы доходят до `runs` только при `opts.short_grams`.
+    pub fn prefilter(
+        &self,
+        opts: PrefilterOpts,
+        mut runs: impl FnMut(&[Vec<String>], Option<&str>) -> Result<Bitmap>,
+    ) -> Result<Bitmap> {
+        let mut clause = |c: &CompiledClause| {
+            c.gram_query_for(opts)?
+                .eval(&mut |r| runs(r, c.field.as_deref()))
+        };
+        let mut acc: Option<Bitmap> = None;
+        for c in &self.must {
+            let bm = clause(c)?;
+            let cur = match acc.take() {
+                Some(mut a) => {
+                    a.and_inplace(&bm);
+                    a
+                }
+                None => bm,
+            };
+            if cur.is_empty() {
+                return Ok(cur);
+            }
+            acc = Some(cur);
+        }
+        if !self.should.is_empty() {
+            let mut any = Bitmap::new();
+            for c in &self.should {
+                any.or_inplace(&clause(c)?);
+            }
+            acc = Some(match acc {
+                Some(mut a) => {
+                    a.and_inplace(&any);
+                    a
+                }
+                None => any,
+            });
+        }
+        Ok(acc.unwrap_or_default())
+    }
+
+    /// Verify документа. `Some((поле, пункт))` — совпадение: первый
+    /// совпавший положительный пункт и его поле (для превью).
+    pub fn matches<'a>(&'a self, doc: &StoredDoc) -> Option<(String, &'a CompiledClause)> {
+        let mut first: Option<(String, &CompiledClause)> = None;
+        for c in &self.must {
+            let f = c.matched_field(doc)?;
+            first.get_or_insert_with(|| (f.to_string(), c));
+        }
+        if !self.should.is_empty() {
+            let (f, c) = self
+                .should
+                .iter()
+                .find_map(|c| c.matched_field(doc).map(|f| (f, c)))?;
+            first.get_or_insert_with(|| (f.to_string(), c));
+        }
+        if self.must_not.iter().any(|c| c.matched_field(doc).is_some()) {
+            return None;
+        }
+        first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify::EnvVerifyFactory;
+    use std::collections::BTreeMap;
+
+    fn doc(fields: &[(&str, &str)]) -> StoredDoc {
+        StoredDoc {
+            doc_id: 0,
+            ext_id: "x".into(),
+            fields: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            raw: BTreeMap::new(),
+        }
+    }
+
+    fn compile(json: &str) -> Result<CompiledQuery> {
+        serde_json::from_str::<BoolQuery>(json)?.compile(&EnvVerifyFactory::from_env())
+    }
+
+    #[test]
+    fn must_should_must_not() {
+        let q = compile(
+            r#"{"must":[{"field":"title","wildcard":"*timeout*"}],
+                "should":[{"wildcard":"*disk*"},{"field":"body","wildcard":"*net*"}],
+                "must_not":[{"field":"body","wildcard":"*retry*"}]}"#,
+        )
+        .unwrap();
+        let hit = doc(&[("title", "timeout"), ("body", "network down")]);
+        let (f, c) = q.matches(&hit).unwrap();
+        assert_eq!((f.as_str(), c.pattern.as_str()), ("title", "*timeout*"));
+        assert_eq!(c.needle().as_deref(), Some("timeout"));
+        // нет ни одного should
+        assert!(
+            q.matches(&doc(&[("title", "timeout"), ("body", "ok")]))
+                .is_none()
+        );
+        // must_not
+        let retry = doc(&[("title", "timeout"), ("body", "net retry")]);
+        assert!(q.matches(&retry).is_none());
+        // must — только в своём поле
+        assert!(q.matches(&doc(&[("body", "timeout on disk")])).is_none());
+    }
+
+    #[test]
+    fn prefilter_combines_clauses() {
+        let q = compile(
+            r#"{"must":[{"wildcard":"*aaa*"},{"wildcard":"*bbb*"}],
+                "should":[{"wildcard":"*ccc*"},{"wildcard":"*ddd*"}]}"#,
+        )
+        .unwrap();
+        let sets = BTreeMap::from([
+            ("aaa", vec![1, 2, 3, 4]),
+            ("bbb", vec![2, 3, 4, 5]),
+            ("ccc", vec![3]),
+            ("ddd", vec![4, 9]),
+        ]);
+        let bm = q
+            .prefilter(PrefilterOpts::default(), |runs, _| {
+                Ok(Bitmap::of(&sets[runs[0][0].as_str()]))
+            })
+            .unwrap();
+        assert_eq!(bm.to_vec(), [3, 4]);
+        assert!(q.may_match(|g, _| g != ["ccc"]));
+        assert!(!q.may_match(|g, _| g == ["ccc"]));
+    }
+
+    #[test]
+    fn rejects_empty_and_weak_clauses() {
+        let err = compile(r#"{"must_not":[{"wildcard":"*retry*"}]}"#)
+            .err()
+            .unwrap();
+        assert!(err.downcast_ref::<InvalidQuery>().is_some(), "{err:#}");
+        let err = compile(r#"{"must":[{"wildcard":"*abc*","regex":"abc"}]}"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.downcast_ref::<InvalidQuery>().map(|e| e.0.as_str()),
+            Some("must[0]: clause needs exactly one of `wildcard` or `regex`")
+        );
+        assert!(compile(r#"{"must":[{"wildcard":"*abc*"}],"mustnt":[]}"#).is_err());
+        // короткий must — ошибка префильтра, если сегменту нечем его сузить
+        let q = compile(r#"{"must":[{"wildcard":"*ab*"}]}"#).unwrap();
+        let none = PrefilterOpts::default();
+        assert!(q.prefilter(none, |_, _| Ok(Bitmap::new())).is_err());
+        // ...но до сегментов его не отвергаем: короткие граммы могут быть;
+        // пункт без литералов отвергается сразу
+        assert_eq!(q.check_strength(false), Ok(()));
+        let q = compile(r#"{"must":[{"wildcard":"*abc*"}],"should":[{"regex":".*"}]}"#).unwrap();
+        assert_eq!(
+            q.check_strength(false).map_err(|e| e.0),
+            Err("should[0]: regex too weak; need a required literal of ≥3 chars".into())
+        );
+        assert_eq!(q.check_strength(true), Ok(()));
+        // must_not только проверяется — граммы ему не нужны
+        let q =
+            compile(r#"{"must":[{"wildcard":"*abc*"}],"must_not":[{"wildcard":"*x*"}]}"#).unwrap();
+        assert_eq!(q.must_not[0].gram_query(), &GramQuery::All);
+        assert!(q.matches(&doc(&[("t", "abc x")])).is_none());
+        assert!(q.matches(&doc(&[("t", "abc")])).is_some());
+    }
+
+    #[test]
+    fn short_literals_and_full_scan() {
+        let q = compile(r#"{"must":[{"wildcard":"*ab*"}],"should":[{"wildcard":"*"}]}"#).unwrap();
+        let seen = |opts| {
+            let mut seen = Vec::new();
+            q.prefilter(opts, |runs, _| {
+                seen.push(runs.to_vec());
+                Ok(Bitmap::of(&[1]))
+            })
+            .map(|_| seen)
+        };
+        let short = PrefilterOpts {
+            short_grams: true,
+            ..Default::default()
+        };
+        // `*` в should сузить нечем
+        assert!(seen(short).is_err());
+        let scan = PrefilterOpts {
+            full_scan: true,
+            ..short
+        };
+        assert_eq!(seen(scan).unwrap(), [vec![vec!["ab".to_string()]], vec![]]);
+        // без индекса коротких грамм `*ab*` — тоже полный просмотр
+        let scan = PrefilterOpts {
+            short_grams: false,
+            ..scan
+        };
+        assert_eq!(seen(scan).unwrap(), [Vec::<Vec<String>>::new(), vec![]]);
+    }
+
+    #[test]
+    fn sensitive_verify_uses_raw_text() {
+        let case = Sensitivity {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let q = BoolQuery::single(Clause::wildcard("*NullPointer*", None))
+            .compile_with(&EnvVerifyFactory::from_env(), case, &Analyzers::default())
+            .unwrap();
+        // граммы — свёрнутые, как в индексе
+        assert_eq!(q.must[0].grams()[0], "nul");
+        let mut hit = doc(&[("t", "a nullpointer b")]);
+        hit.raw.insert("t".into(), "a NullPointer b".into());
+        assert!(q.matches(&hit).is_some());
+        let mut miss = doc(&[("t", "a nullpointer b")]);
+        miss.raw.insert("t".into(), "a NULLPOINTER b".into());
+        assert!(q.matches(&miss).is_none());
+
+        let accent = Sensitivity {
+            accent_sensitive: true,
+            ..Default::default()
+        };
+        let q = BoolQuery::single(Clause::regex("caf[eé]s? ouvert", None))
+            .compile_with(&EnvVerifyFactory::from_env(), accent, &Analyzers::default())
+            .unwrap();
+        let mut hit = doc(&[("t", "cafe ouvert")]);
+        hit.raw.insert("t".into(), "Café ouvert".into());
+        assert!(q.matches(&hit).is_some());
+        let q = BoolQuery::single(Clause::regex("cafe ouvert", None))
+            .compile_with(&EnvVerifyFactory::from_env(), accent, &Analyzers::default())
+            .unwrap();
+        assert!(q.matches(&hit).is_none());
+        // без исходного текста сравнивается хранимый
+        assert!(q.matches(&doc(&[("t", "cafe ouvert")])).is_some());
+    }
+}
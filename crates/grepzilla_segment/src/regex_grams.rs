@@ -0,0 +1,406 @@
+// crates/grepzilla_segment/src/regex_grams.rs
+//! Регулярные выражения как запрос: разбор `regex-syntax`, приведение
+//! литералов к виду нормализованного текста и вывод обязательной формулы
+//! 3-грамм по HIR (подход codesearch, Russ Cox, «Regular Expression Matching
+//! with a Trigram Index»).
+//!
+//! Для каждого узла считаются: `exact` — все строки, которыми узел может
+//! совпасть (если их немного), `prefix`/`suffix` — множества, с которых
+//! совпадение обязательно начинается / которыми заканчивается, и `query` —
+//! формула грамм, обязательная для совпадения. Слишком большие множества
+//! переводятся в формулу и усекаются до двух символов.
+use anyhow::{Result, bail};
+use regex_syntax::ParserBuilder;
+use regex_syntax::hir::{
+    Capture, Class, ClassUnicode, ClassUnicodeRange, Hir, HirKind, Repetition,
+};
+use std::collections::BTreeSet;
+
+use crate::analyzer::{Analyzer, Pipeline};
+use crate::gram::GramQuery;
+use crate::normalizer::{Sensitivity, normalize_with};
+use crate::query::InvalidQuery;
+
+/// Класс символов не длиннее — раскрывается в множество строк.
+const MAX_CLASS: u32 = 16;
+/// Предел размера `exact`/`prefix`/`suffix` до перевода в формулу.
+const MAX_SET: usize = 32;
+/// Классы не длиннее дополняются нормализованными символами (`[Ёё]` → `[Ёёе]`).
+const MAX_CLASS_NORMALIZE: u32 = 256;
+/// Ошибка выражения без обязательного литерала из ≥3 символов.
+pub const WEAK_REGEX: &str = "regex too weak; need a required literal of ≥3 chars";
+
+/// Разобранный regex-запрос.
+#[derive(Debug, Clone)]
+pub struct RegexQuery {
+    /// Паттерн для verify по нормализованному тексту (синтаксис `regex`)
+    pub verify: String,
+    /// Обязательная формула грамм для префильтра
+    pub grams: GramQuery,
+}
+
+impl RegexQuery {
+    /// Формула для префильтра. Паттерн без обязательного литерала из трёх
+    /// символов отвергается — префильтр для него бесполезен.
+    pub fn gram_query(&self) -> Result<GramQuery> {
+        if self.grams == GramQuery::All {
+            bail!("{WEAK_REGEX}");
+        }
+        Ok(self.grams.clone())
+    }
+}
+
+/// Разобрать `pattern`: литералы и небольшие классы приводятся к виду
+/// нормализованного текста (нижний регистр, без диакритики), затем из HIR
+/// выводится формула грамм.
+pub fn parse_regex(pattern: &str) -> Result<RegexQuery> {
+    parse_regex_with(pattern, &Pipeline::standard())
+}
+
+/// То же для поля с анализатором `analyzer`.
+pub fn parse_regex_with(pattern: &str, analyzer: &dyn Analyzer) -> Result<RegexQuery> {
+    let fold = |s: &str| analyzer.analyze(s);
+    let hir = parse(pattern)?;
+    let hir = normalize_hir(&hir, &fold);
+    let mut info = analyze(&hir, &fold);
+    info.simplify(true);
+    let grams = info.into_query();
+    Ok(RegexQuery {
+        verify: hir.to_string(),
+        grams,
+    })
+}
+
+/// Verify-паттерн для строгого сравнения: литералы и классы приводятся
+/// к `normalize_with(_, sens)`, а не к полной свёртке.
+pub fn verify_pattern_with(pattern: &str, sens: Sensitivity) -> Result<String> {
+    let hir = parse(pattern)?;
+    Ok(normalize_hir(&hir, &|s: &str| normalize_with(s, sens)).to_string())
+}
+
+/// Формула грамм для уже нормализованного HIR.
+pub fn required_grams(hir: &Hir) -> GramQuery {
+    let standard = Pipeline::standard();
+    let mut info = analyze(hir, &|s: &str| standard.analyze(s));
+    info.simplify(true);
+    info.into_query()
+}
+
+/// Приведение строки к виду текста поля (анализатор или строгая нормализация).
+type Fold<'a> = &'a dyn Fn(&str) -> String;
+
+/// Синтаксическая ошибка — [`InvalidQuery`]: ошибка клиента.
+fn parse(pattern: &str) -> Result<Hir> {
+    ParserBuilder::new()
+        .build()
+        .parse(pattern)
+        .map_err(|e| InvalidQuery(format!("bad regex: {e}")).into())
+}
+
+fn normalize_hir(h: &Hir, fold: Fold) -> Hir {
+    let rec = |h: &Hir| normalize_hir(h, fold);
+    match h.kind() {
+        HirKind::Literal(lit) => match std::str::from_utf8(&lit.0) {
+            Ok(s) => Hir::literal(fold(s).into_bytes()),
+            Err(_) => h.clone(),
+        },
+        HirKind::Class(Class::Unicode(cls)) if class_len(cls) <= MAX_CLASS_NORMALIZE => {
+            let mut out = cls.clone();
+            for c in class_chars(cls) {
+                let n = fold(&c.to_string());
+                let mut n = n.chars();
+                if let (Some(n), None) = (n.next(), n.next()) {
+                    out.push(ClassUnicodeRange::new(n, n));
+                }
+            }
+            Hir::class(Class::Unicode(out))
+        }
+        HirKind::Repetition(r) => Hir::repetition(Repetition {
+            sub: Box::new(rec(&r.sub)),
+            ..r.clone()
+        }),
+        HirKind::Capture(c) => Hir::capture(Capture {
+            sub: Box::new(rec(&c.sub)),
+            ..c.clone()
+        }),
+        HirKind::Concat(xs) => Hir::concat(xs.iter().map(rec).collect()),
+        HirKind::Alternation(xs) => Hir::alternation(xs.iter().map(rec).collect()),
+        HirKind::Empty | HirKind::Look(_) | HirKind::Class(_) => h.clone(),
+    }
+}
+
+fn class_len(cls: &ClassUnicode) -> u32 {
+    cls.ranges()
+        .iter()
+        .map(|r| r.end() as u32 - r.start() as u32 + 1)
+        .sum()
+}
+
+fn class_chars(cls: &ClassUnicode) -> impl Iterator<Item = char> + '_ {
+    cls.ranges().iter().flat_map(|r| r.start()..=r.end())
+}
+
+type Set = BTreeSet<String>;
+
+struct Info {
+    exact: Option<Set>,
+    prefix: Set,
+    suffix: Set,
+    query: GramQuery,
+}
+
+impl Info {
+    fn exact(set: Set) -> Self {
+        Self {
+            exact: Some(set),
+            prefix: Set::new(),
+            suffix: Set::new(),
+            query: GramQuery::All,
+        }
+    }
+
+    /// Совпадение — любая строка (в том числе пустая).
+    fn any() -> Self {
+        let empty = Set::from([String::new()]);
+        Self {
+            exact: None,
+            prefix: empty.clone(),
+            suffix: empty,
+            query: GramQuery::All,
+        }
+    }
+
+    /// Перевести `exact` в формулу и префикс/суффикс.
+    fn drop_exact(&mut self) {
+        if let Some(ex) = self.exact.take() {
+            self.query = std::mem::replace(&mut self.query, GramQuery::All).and(or_literals(&ex));
+            self.prefix = ex.clone();
+            self.suffix = ex;
+        }
+    }
+
+    fn simplify(&mut self, force: bool) {
+        if let Some(ex) = &self.exact {
+            let min_len = ex.iter().map(|s| s.chars().count()).min().unwrap_or(0);
+            if ex.len() > MAX_SET || min_len >= 4 || (force && min_len >= 3) {
+                self.drop_exact();
+            }
+        }
+        if self.exact.is_none() {
+            shrink(&mut self.query, &mut self.prefix, |s| take_chars(s, 2));
+            shrink(&mut self.query, &mut self.suffix, |s| last_chars(s, 2));
+        }
+    }
+
+    fn into_query(self) -> GramQuery {
+        match &self.exact {
+            Some(ex) => self.query.and(or_literals(ex)),
+            None => self
+                .query
+                .and(or_literals(&self.prefix))
+                .and(or_literals(&self.suffix)),
+        }
+    }
+}
+
+/// Множество длиннее двух символов или слишком большое: его литералы
+/// уходят в формулу, строки усекаются `cut`.
+fn shrink(query: &mut GramQuery, set: &mut Set, cut: impl Fn(&str) -> String) {
+    if set.len() <= MAX_SET && set.iter().all(|s| s.chars().count() <= 2) {
+        return;
+    }
+    *query = std::mem::replace(query, GramQuery::All).and(or_literals(set));
+    let cut: Set = set.iter().map(|s| cut(s)).collect();
+    *set = if cut.len() > MAX_SET {
+        Set::from([String::new()])
+    } else {
+        cut
+    };
+}
+
+fn or_literals(set: &Set) -> GramQuery {
+    set.iter()
+        .map(|s| GramQuery::literal(s))
+        .fold(GramQuery::Or(Vec::new()), GramQuery::or)
+}
+
+fn take_chars(s: &str, n: usize) -> String {
+    s.chars().take(n).collect()
+}
+
+fn last_chars(s: &str, n: usize) -> String {
+    let cs: Vec<char> = s.chars().collect();
+    cs[cs.len().saturating_sub(n)..].iter().collect()
+}
+
+fn cross(a: &Set, b: &Set) -> Set {
+    a.iter()
+        .flat_map(|x| b.iter().map(move |y| format!("{x}{y}")))
+        .collect()
+}
+
+fn analyze(h: &Hir, fold: Fold) -> Info {
+    let mut info = match h.kind() {
+        HirKind::Empty | HirKind::Look(_) => Info::exact(Set::from([String::new()])),
+        HirKind::Literal(lit) => match std::str::from_utf8(&lit.0) {
+            Ok(s) => Info::exact(Set::from([s.to_string()])),
+            Err(_) => Info::any(),
+        },
+        // в нормализованном тексте встречаются только нормализованные символы:
+        // `(?i)t` → `[Tt]` даёт одну строку «t»
+        HirKind::Class(Class::Unicode(cls)) if class_len(cls) <= MAX_CLASS => Info::exact(
+            class_chars(cls)
+                .map(String::from)
+                .filter(|c| fold(c) == *c)
+                .collect(),
+        ),
+        HirKind::Class(_) => Info::any(),
+        HirKind::Capture(c) => analyze(&c.sub, fold),
+        HirKind::Repetition(r) => match (r.min, r.max) {
+            (1, Some(1)) => analyze(&r.sub, fold),
+            (0, Some(1)) => alternate(
+                Info::exact(Set::from([String::new()])),
+                analyze(&r.sub, fold),
+            ),
+            (0, _) => Info::any(),
+            // x{n,m}, n ≥ 1: хотя бы одно вхождение x, но строки не точные
+            _ => {
+                let mut x = analyze(&r.sub, fold);
+                x.drop_exact();
+                x
+            }
+        },
+        HirKind::Concat(xs) => {
+            let mut it = xs.iter();
+            let first = it.next().map_or_else(
+                || Info::exact(Set::from([String::new()])),
+                |x| analyze(x, fold),
+            );
+            it.fold(first, |acc, x| concat(acc, analyze(x, fold)))
+        }
+        HirKind::Alternation(xs) => {
+            let mut it = xs.iter();
+            let first = it
+                .next()
+                .map_or_else(|| Info::exact(Set::new()), |x| analyze(x, fold));
+            it.fold(first, |acc, x| alternate(acc, analyze(x, fold)))
+        }
+    };
+    info.simplify(false);
+    info
+}
+
+fn concat(x: Info, y: Info) -> Info {
+    let mut query = x.query.and(y.query);
+    let (prefix, suffix) = match (&x.exact, &y.exact) {
+        (Some(a), Some(b)) => {
+            let mut out = Info::exact(cross(a, b));
+            out.query = query;
+            return out;
+        }
+        (Some(a), None) => (cross(a, &y.prefix), y.suffix),
+        (None, Some(b)) => (x.prefix, cross(&x.suffix, b)),
+        (None, None) => {
+            // совпадение проходит через стык: суффикс x, сразу за ним префикс y
+            query = query.and(or_literals(&cross(&x.suffix, &y.prefix)));
+            (x.prefix, y.suffix)
+        }
+    };
+    Info {
+        exact: None,
+        prefix,
+        suffix,
+        query,
+    }
+}
+
+fn alternate(mut x: Info, mut y: Info) -> Info {
+    if let (Some(a), Some(b)) = (&x.exact, &y.exact) {
+        let mut out = Info::exact(a.union(b).cloned().collect());
+        out.query = std::mem::replace(&mut x.query, GramQuery::All)
+            .or(std::mem::replace(&mut y.query, GramQuery::All));
+        return out;
+    }
+    x.drop_exact();
+    y.drop_exact();
+    Info {
+        exact: None,
+        prefix: x.prefix.union(&y.prefix).cloned().collect(),
+        suffix: x.suffix.union(&y.suffix).cloned().collect(),
+        query: x.query.or(y.query),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::Stage;
+    use crate::normalizer::normalize;
+
+    fn q(p: &str) -> GramQuery {
+        parse_regex(p).unwrap().gram_query().unwrap()
+    }
+
+    fn run(s: &str) -> GramQuery {
+        GramQuery::literal(s)
+    }
+
+    #[test]
+    fn literals_and_concat() {
+        assert_eq!(q("timeout"), run("timeout"));
+        assert_eq!(q("Ошибка"), run("ошибка"));
+        assert_eq!(q(r"timeout\s+\d+ms"), run("timeout"));
+        assert_eq!(q("abc.*xyz"), run("abc").and(run("xyz")));
+        // за последним «abc» сразу идёт «def»
+        assert_eq!(q("(abc)+def"), run("abc").and(run("bcdef")));
+        assert_eq!(q("(?i)TimeOut"), run("timeout"));
+    }
+
+    #[test]
+    fn alternation_and_classes() {
+        assert_eq!(q("error|warning"), run("error").or(run("warning")));
+        assert_eq!(q("err(or|no)"), run("errno").or(run("error")));
+        // небольшой класс раскрывается: ba[rz]qux
+        let g = q("ba[rz]qux");
+        assert_eq!(g, run("barqux").or(run("bazqux")));
+        // в одной ветке нет литерала — условия нет
+        assert!(parse_regex("abc|x").unwrap().gram_query().is_err());
+    }
+
+    #[test]
+    fn verify_pattern_matches_normalized_text() {
+        let rq = parse_regex("ЁЛКА [А-Я]+").unwrap();
+        let rx = regex::Regex::new(&rq.verify).unwrap();
+        assert!(rx.is_match(&normalize("Ёлка ЗЕЛЁНАЯ")));
+        assert_eq!(rq.grams, run("елка "));
+    }
+
+    #[test]
+    fn regex_follows_field_analyzer() {
+        let kb = Pipeline::new("kb", vec![Stage::Lowercase, Stage::KeyboardLayout]);
+        let rq = parse_regex_with("Привет|ghbdtn", &kb).unwrap();
+        assert_eq!(rq.grams, run("ghbdtn"));
+        let rx = regex::Regex::new(&rq.verify).unwrap();
+        assert!(rx.is_match(&kb.analyze("ПРИВЕТ")));
+    }
+
+    #[test]
+    fn strict_verify_pattern_keeps_case() {
+        let case = Sensitivity {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let rx = regex::Regex::new(&verify_pattern_with("Ёлка", case).unwrap()).unwrap();
+        assert!(rx.is_match(&normalize_with("Ёлка", case)));
+        assert!(!rx.is_match(&normalize_with("ёлка", case)));
+    }
+
+    #[test]
+    fn weak_and_invalid() {
+        for p in [".*", r"\d+", "ab", "a.c", "(abc)?", "[a-z]{3}"] {
+            assert!(parse_regex(p).unwrap().gram_query().is_err(), "{p}");
+        }
+        let e = parse_regex("abc(").unwrap_err().to_string();
+        assert!(e.starts_with("bad regex"), "{e}");
+    }
+}
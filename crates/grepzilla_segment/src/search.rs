@@ -5,6 +5,7 @@ use crate::gram::{BooleanOp, required_grams_from_wildcard};
 use crate::normalizer::normalize;
 use crate::segjson::JsonSegmentReader;
 use crate::verify::compile_wildcard_engine; // NEW
+use crate::wildcard::map_literals;
 
 use crate::SegmentReader;
 
@@ -30,7 +31,7 @@ pub fn search_one_segment(
     max_candidates: u64,
 ) -> Result<SegSearchOut> {
     // 1) нормализуем запрос и извлекаем обязательные 3-граммы
-    let norm_wc = normalize(wildcard);
+    let norm_wc = map_literals(wildcard, normalize);
     let grams = required_grams_from_wildcard(&norm_wc)?;
 
     // 2) открываем сегмент V1 (JSON) и считаем префильтр
@@ -1,21 +1,48 @@
 // Файл: crates/grepzilla_segment/src/segjson.rs
+use crate::analyzer::Analyzers;
+use crate::deletes::LiveDocs;
 use crate::gram::{self, BooleanOp};
-use crate::normalizer::normalize;
+use crate::summary::{GramBloom, SegmentSummary};
 use crate::{SegmentMetaV1, SegmentReader, SegmentWriter, StoredDoc};
 
 use anyhow::Result;
 use croaring::Bitmap;
 use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
 
 /// JSON-реализация сегмента V1:
 /// - grams.json      : { trigram -> [doc_id, ...] }
 /// - field_masks.json: { field   -> [doc_id, ...] }   // добавлено в A2
 /// - docs.jsonl      : StoredDoc по строке (с doc_id)
 /// - meta.json       : SegmentMetaV1
+/// - summary.bin     : сводка для отсечения сегмента (см. [`crate::summary`])
+/// - analyzers.json  : схема анализаторов, если она не стандартная
+///
+/// `docs.jsonl` пишется по мере чтения входа; битмапы грамм и масок полей
+/// копятся в памяти до конца (`grams.json` — один объект). Ограниченная по
+/// памяти сборка больших входов — только у V2
+/// ([`crate::v2::writer::BinSegmentWriter::with_memory_budget`]).
 #[derive(Default)]
-pub struct JsonSegmentWriter;
+pub struct JsonSegmentWriter {
+    raw_text: bool,
+    analyzers: Analyzers,
+}
+
+impl JsonSegmentWriter {
+    /// Сохранять исходный (ненормализованный) текст полей для превью.
+    pub fn with_raw_text(mut self, on: bool) -> Self {
+        self.raw_text = on;
+        self
+    }
+
+    /// Анализаторы полей (см. [`crate::analyzer`]).
+    pub fn with_analyzers(mut self, analyzers: Analyzers) -> Self {
+        self.analyzers = analyzers;
+        self
+    }
+}
 
 impl SegmentWriter for JsonSegmentWriter {
     fn write_segment(&mut self, input_jsonl: &str, out_dir: &str) -> Result<()> {
@@ -24,10 +51,13 @@ impl SegmentWriter for JsonSegmentWriter {
         let f = File::open(input_jsonl)?;
         let br = BufReader::new(f);
 
+        // docs.jsonl — потоком, документы в памяти не копятся
+        let docs_path = format!("{}/docs.jsonl", out_dir);
+        let mut df = BufWriter::new(File::create(&docs_path)?);
+
         let mut next_id: u32 = 0;
         let mut grams: HashMap<String, Bitmap> = HashMap::new();
         let mut field_masks: HashMap<String, Bitmap> = HashMap::new();
-        let mut docs: Vec<StoredDoc> = Vec::new();
 
         for line in br.lines() {
             let line = line?;
@@ -44,9 +74,13 @@ impl SegmentWriter for JsonSegmentWriter {
 
             // Собираем строковые поля, нормализуем и индексируем
             let mut stored: BTreeMap<String, String> = BTreeMap::new();
+            let mut raw: BTreeMap<String, String> = BTreeMap::new();
             collect_strings("", &v, &mut |path, s| {
-                let ns = normalize(s);
+                let ns = self.analyzers.for_field(path).analyze(s);
                 stored.insert(path.to_string(), ns.clone());
+                if self.raw_text && ns != s {
+                    raw.insert(path.to_string(), s.to_string());
+                }
 
                 // n-gram индекс
                 for g in gram::trigrams(&ns) {
@@ -59,13 +93,17 @@ impl SegmentWriter for JsonSegmentWriter {
                     .add(next_id);
             });
 
-            docs.push(StoredDoc {
+            let doc = StoredDoc {
                 doc_id: next_id,
                 ext_id,
                 fields: stored,
-            });
+                raw,
+            };
+            serde_json::to_writer(&mut df, &doc)?;
+            df.write_all(b"\n")?;
             next_id += 1;
         }
+        df.flush()?;
 
         // grams.json
         let grams_path = format!("{}/grams.json", out_dir);
@@ -85,23 +123,26 @@ impl SegmentWriter for JsonSegmentWriter {
         let mut mf = File::create(&masks_path)?;
         serde_json::to_writer_pretty(&mut mf, &masks_dump)?;
 
-        // docs.jsonl
-        let docs_path = format!("{}/docs.jsonl", out_dir);
-        let mut df = File::create(&docs_path)?;
-        for d in &docs {
-            serde_json::to_writer(&mut df, d)?;
-            df.write_all(b"\n")?;
-        }
-
         // meta.json
         let meta = SegmentMetaV1 {
             version: 1,
             doc_count: next_id,
             gram_count: grams.len() as u32,
+            raw_text: self.raw_text,
         };
         let meta_path = format!("{}/meta.json", out_dir);
         let mut meta_f = File::create(&meta_path)?;
         serde_json::to_writer_pretty(&mut meta_f, &meta)?;
+
+        // summary.bin (doc-values в V1 нет)
+        SegmentSummary::new(
+            next_id,
+            field_masks.into_keys(),
+            Vec::new(),
+            GramBloom::from_grams(grams.keys().map(String::as_str)),
+        )
+        .write(Path::new(out_dir))?;
+        self.analyzers.write(Path::new(out_dir))?;
         Ok(())
     }
 }
@@ -111,6 +152,8 @@ pub struct JsonSegmentReader {
     grams: HashMap<String, Bitmap>,
     field_masks: HashMap<String, Bitmap>,
     docs: Vec<StoredDoc>,
+    live: LiveDocs,
+    analyzers: Analyzers,
 }
 
 impl SegmentReader for JsonSegmentReader {
@@ -149,6 +192,8 @@ impl SegmentReader for JsonSegmentReader {
             grams,
             field_masks,
             docs,
+            live: LiveDocs::load(std::path::Path::new(path))?,
+            analyzers: Analyzers::load(std::path::Path::new(path))?,
         })
     }
 
@@ -205,6 +250,8 @@ impl SegmentReader for JsonSegmentReader {
                 acc.clear();
             }
         }
+        // Удалённые документы не доходят до verify
+        self.live.apply(&mut acc);
         Ok(acc)
     }
 
@@ -213,6 +260,40 @@ impl SegmentReader for JsonSegmentReader {
     }
 }
 
+impl JsonSegmentReader {
+    /// Удаления, действующие для этого читателя (снимок при открытии).
+    pub fn live_docs(&self) -> &LiveDocs {
+        &self.live
+    }
+
+    /// Анализаторы, которыми собран сегмент.
+    pub fn analyzers(&self) -> &Analyzers {
+        &self.analyzers
+    }
+
+    /// Собран ли сегмент с исходным текстом полей (`--raw-text`).
+    pub fn has_raw_text(&self) -> bool {
+        self.meta.raw_text
+    }
+
+    /// AND всех 3-грамм цепочек `runs` (как [`crate::query::CompiledQuery::prefilter`]
+    /// передаёт их читателю). Коротких грамм V1 не хранит — короткие литералы
+    /// не сужают; без 3-грамм — все живые документы поля.
+    pub fn prefilter_runs(&self, runs: &[Vec<String>], field: Option<&str>) -> Result<Bitmap> {
+        let grams: Vec<String> = runs
+            .iter()
+            .flatten()
+            .filter(|g| g.chars().count() >= 3)
+            .cloned()
+            .collect();
+        if grams.is_empty() {
+            self.prefilter(BooleanOp::Not, &[], field)
+        } else {
+            self.prefilter(BooleanOp::And, &grams, field)
+        }
+    }
+}
+
 // -------- helpers --------
 fn read_json<T: for<'de> serde::Deserialize<'de>>(path: &str) -> Result<T> {
     let f = File::open(path)?;
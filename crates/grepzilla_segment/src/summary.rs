@@ -0,0 +1,338 @@
+// crates/grepzilla_segment/src/summary.rs
+//! Сводка сегмента: `summary.bin` рядом с сегментом (V1 и V2).
+//!
+//! Маленький файл, по которому планировщик брокера решает, может ли сегмент
+//! вообще дать совпадения, — не открывая сам сегмент: число документов,
+//! набор полей, Bloom-фильтр 3-грамм и диапазоны колонок doc-values
+//! (в том числе время). Ошибиться сводка может только в сторону «может
+//! совпасть»: Bloom-фильтр не даёт ложных отрицаний.
+//!
+//! Формат (LE):
+//! ```text
+//! [magic "GZSUM1\0\0"][u64 doc_count]
+//! [varint n_fields] n_fields × [varint len][name]
+//! [varint n_ranges] n_ranges × [varint len][name][u8 kind][i64 min][i64 max]
+//! [u32 bloom_k][u32 bloom_words] bloom_words × u64
+//! [u64 CRC64]
+//! ```
+use anyhow::{Result, anyhow, bail};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::doc_values::{DocValueField, DocValueKind, RangeFilter};
+use crate::v2::codec::{get_varint, put_varint};
+use crate::v2::crc::crc64_ecma;
+
+pub const SUMMARY_FILE: &str = "summary.bin";
+const SUMMARY_MAGIC: &[u8; 8] = b"GZSUM1\0\0";
+/// Бит фильтра на грамму и число хешей: ~1% ложных срабатываний.
+const BLOOM_BITS_PER_KEY: usize = 10;
+const BLOOM_K: u32 = 7;
+
+/// Bloom-фильтр 3-грамм сегмента (double hashing поверх FNV-1a).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GramBloom {
+    k: u32,
+    words: Vec<u64>,
+}
+
+impl GramBloom {
+    /// Фильтр по хешам грамм ([`gram_hash`]).
+    pub fn from_hashes(hashes: &[u64]) -> Self {
+        let bits = (hashes.len() * BLOOM_BITS_PER_KEY).max(64);
+        let mut bloom = Self {
+            k: BLOOM_K,
+            words: vec![0; bits.div_ceil(64)],
+        };
+        for &h in hashes {
+            for bit in bloom.bits(h) {
+                bloom.words[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        bloom
+    }
+
+    pub fn from_grams<'a>(grams: impl IntoIterator<Item = &'a str>) -> Self {
+        let hashes: Vec<u64> = grams.into_iter().map(gram_hash).collect();
+        Self::from_hashes(&hashes)
+    }
+
+    /// `false` — граммы в сегменте точно нет.
+    pub fn may_contain(&self, gram: &str) -> bool {
+        self.bits(gram_hash(gram))
+            .all(|bit| self.words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    fn bits(&self, h: u64) -> impl Iterator<Item = usize> + use<> {
+        let m = (self.words.len() * 64) as u64;
+        let h1 = h;
+        let h2 = h.rotate_left(32) | 1;
+        (0..self.k as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % m) as usize)
+    }
+}
+
+/// Хеш граммы для Bloom-фильтра (FNV-1a 64): стабилен между процессами.
+pub fn gram_hash(gram: &str) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in gram.bytes() {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    h
+}
+
+/// Диапазон значений колонки doc-values (ключи, см. [`crate::doc_values`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueRange {
+    pub field: DocValueField,
+    pub min: i64,
+    pub max: i64,
+}
+
+/// Сводка сегмента для отсечения на этапе планирования.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentSummary {
+    pub doc_count: u32,
+    /// Имена полей сегмента (отсортированы).
+    pub fields: Vec<String>,
+    /// Колонки doc-values, у которых есть хотя бы одно значение.
+    pub ranges: Vec<ValueRange>,
+    pub grams: GramBloom,
+}
+
+impl SegmentSummary {
+    pub fn new(
+        doc_count: u32,
+        fields: impl IntoIterator<Item = String>,
+        ranges: Vec<ValueRange>,
+        grams: GramBloom,
+    ) -> Self {
+        let mut fields: Vec<String> = fields.into_iter().collect();
+        fields.sort();
+        fields.dedup();
+        Self {
+            doc_count,
+            fields,
+            ranges,
+            grams,
+        }
+    }
+
+    /// Прочитать `summary.bin`; `None` — сводки нет (сегмент старой сборки).
+    pub fn load(seg_dir: &Path) -> Result<Option<Self>> {
+        let bytes = match fs::read(seg_dir.join(SUMMARY_FILE)) {
+            Ok(b) => b,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if bytes.len() < 16 + 8 || &bytes[0..8] != SUMMARY_MAGIC {
+            bail!("summary.bin bad magic");
+        }
+        let body = &bytes[..bytes.len() - 8];
+        let crc_expect = u64::from_le_bytes(bytes[bytes.len() - 8..].try_into().unwrap());
+        if crc64_ecma(body) != crc_expect {
+            bail!("summary.bin CRC mismatch");
+        }
+        Self::parse(&body[8..]).map(Some)
+    }
+
+    fn parse(b: &[u8]) -> Result<Self> {
+        let truncated = || anyhow!("summary.bin truncated");
+        let take = |b: &mut &[u8], n: usize| -> Result<Vec<u8>> {
+            if b.len() < n {
+                return Err(truncated());
+            }
+            let (head, tail) = b.split_at(n);
+            *b = tail;
+            Ok(head.to_vec())
+        };
+        let varint = |b: &mut &[u8]| -> Result<usize> {
+            let (v, rest) = get_varint(b).ok_or_else(truncated)?;
+            *b = rest;
+            Ok(v as usize)
+        };
+        let string = |b: &mut &[u8]| -> Result<String> {
+            let n = varint(b)?;
+            Ok(String::from_utf8(take(b, n)?)?)
+        };
+        let i64_at = |b: &mut &[u8]| -> Result<i64> {
+            Ok(i64::from_le_bytes(take(b, 8)?.try_into().unwrap()))
+        };
+
+        let mut b = b;
+        let doc_count = u64::from_le_bytes(take(&mut b, 8)?.try_into().unwrap());
+        let doc_count = u32::try_from(doc_count)?;
+        let n_fields = varint(&mut b)?;
+        let mut fields = Vec::with_capacity(n_fields.min(1024));
+        for _ in 0..n_fields {
+            fields.push(string(&mut b)?);
+        }
+        let n_ranges = varint(&mut b)?;
+        let mut ranges = Vec::with_capacity(n_ranges.min(1024));
+        for _ in 0..n_ranges {
+            let name = string(&mut b)?;
+            let kind = DocValueKind::from_code(take(&mut b, 1)?[0])?;
+            let min = i64_at(&mut b)?;
+            let max = i64_at(&mut b)?;
+            ranges.push(ValueRange {
+                field: DocValueField::new(&name, kind),
+                min,
+                max,
+            });
+        }
+        let k = u32::from_le_bytes(take(&mut b, 4)?.try_into().unwrap());
+        let n_words = u32::from_le_bytes(take(&mut b, 4)?.try_into().unwrap()) as usize;
+        if k == 0 || n_words == 0 || b.len() != n_words * 8 {
+            bail!("summary.bin bad bloom");
+        }
+        let words = b
+            .chunks_exact(8)
+            .map(|w| u64::from_le_bytes(w.try_into().unwrap()))
+            .collect();
+        Ok(Self {
+            doc_count,
+            fields,
+            ranges,
+            grams: GramBloom { k, words },
+        })
+    }
+
+    /// Записать `summary.bin` в каталог сегмента (временный файл + rename).
+    pub fn write(&self, seg_dir: &Path) -> Result<()> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(SUMMARY_MAGIC);
+        buf.extend_from_slice(&(self.doc_count as u64).to_le_bytes());
+        put_varint(self.fields.len() as u64, &mut buf);
+        for f in &self.fields {
+            put_varint(f.len() as u64, &mut buf);
+            buf.extend_from_slice(f.as_bytes());
+        }
+        put_varint(self.ranges.len() as u64, &mut buf);
+        for r in &self.ranges {
+            put_varint(r.field.name.len() as u64, &mut buf);
+            buf.extend_from_slice(r.field.name.as_bytes());
+            buf.push(r.field.kind.code());
+            buf.extend_from_slice(&r.min.to_le_bytes());
+            buf.extend_from_slice(&r.max.to_le_bytes());
+        }
+        buf.extend_from_slice(&self.grams.k.to_le_bytes());
+        buf.extend_from_slice(&(self.grams.words.len() as u32).to_le_bytes());
+        for w in &self.grams.words {
+            buf.extend_from_slice(&w.to_le_bytes());
+        }
+        let crc = crc64_ecma(&buf);
+        buf.extend_from_slice(&crc.to_le_bytes());
+
+        let tmp = seg_dir.join(format!("{SUMMARY_FILE}.tmp"));
+        fs::write(&tmp, &buf)?;
+        fs::rename(&tmp, seg_dir.join(SUMMARY_FILE))?;
+        Ok(())
+    }
+
+    /// Диапазон времени сегмента (объединение колонок `timestamp`), мс Unix.
+    pub fn time_range(&self) -> Option<(i64, i64)> {
+        self.ranges
+            .iter()
+            .filter(|r| r.field.kind == DocValueKind::Timestamp)
+            .map(|r| (r.min, r.max))
+            .reduce(|(a, b), (c, d)| (a.min(c), b.max(d)))
+    }
+
+    pub fn range(&self, field: &str) -> Option<&ValueRange> {
+        self.ranges.iter().find(|r| r.field.name == field)
+    }
+
+    /// Может ли сегмент дать совпадение для запроса с обязательными
+    /// граммами `grams` (AND), полем `field` и фильтрами `filters`.
+    /// `false` — сегмент можно не открывать.
+    pub fn may_match(
+        &self,
+        grams: &[String],
+        field: Option<&str>,
+        filters: &[RangeFilter],
+    ) -> bool {
+        if self.doc_count == 0 {
+            return false;
+        }
+        if let Some(f) = field
+            && self.fields.binary_search_by(|x| x.as_str().cmp(f)).is_err()
+        {
+            return false;
+        }
+        if !grams.iter().all(|g| self.grams.may_contain(g)) {
+            return false;
+        }
+        filters.iter().all(|flt| match self.range(&flt.field) {
+            // нет значений поля — фильтр не пройдёт ни один документ
+            None => false,
+            Some(r) => match flt.key_range(r.field.kind) {
+                Ok(Some((lo, hi))) => lo <= r.max && hi >= r.min,
+                Ok(None) => false,
+                // ошибку границы покажет сам сегмент
+                Err(_) => true,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::doc_values::RangeValue;
+
+    #[test]
+    fn roundtrip_and_pruning() {
+        let tmp = tempfile::tempdir().unwrap();
+        let grams = ["оши", "шиб", "ибк", "бка"];
+        let s = SegmentSummary::new(
+            10,
+            ["text.body".to_string(), "text.title".to_string()],
+            vec![ValueRange {
+                field: DocValueField::new("ts", DocValueKind::Timestamp),
+                min: 1_000,
+                max: 2_000,
+            }],
+            GramBloom::from_grams(grams),
+        );
+        s.write(tmp.path()).unwrap();
+        let back = SegmentSummary::load(tmp.path()).unwrap().unwrap();
+        assert_eq!(back, s);
+        assert_eq!(back.time_range(), Some((1_000, 2_000)));
+        assert!(
+            SegmentSummary::load(&tmp.path().join("nope"))
+                .unwrap()
+                .is_none()
+        );
+
+        let g = |v: &[&str]| v.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        assert!(s.may_match(&g(&grams), Some("text.body"), &[]));
+        assert!(!s.may_match(&g(&["оши", "сет"]), None, &[]));
+        assert!(!s.may_match(&[], Some("text.other"), &[]));
+
+        let ts = |gte: i64| RangeFilter {
+            field: "ts".into(),
+            gte: Some(RangeValue::Int(gte)),
+            ..Default::default()
+        };
+        assert!(s.may_match(&[], None, &[ts(2_000)]));
+        assert!(!s.may_match(&[], None, &[ts(2_001)]));
+        let other = RangeFilter {
+            field: "latency".into(),
+            ..ts(0)
+        };
+        assert!(!s.may_match(&[], None, &[other]));
+    }
+
+    #[test]
+    fn bloom_has_no_false_negatives() {
+        let grams: Vec<String> = (0..5000).map(|i| format!("{i:03x}")).collect();
+        let bloom = GramBloom::from_grams(grams.iter().map(String::as_str));
+        assert!(grams.iter().all(|g| bloom.may_contain(g)));
+        let fp = (0..5000)
+            .map(|i| format!("z{i:02x}"))
+            .filter(|g| bloom.may_contain(g))
+            .count();
+        assert!(fp < 200, "false positives: {fp}");
+    }
+}
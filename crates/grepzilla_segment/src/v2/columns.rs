@@ -0,0 +1,410 @@
+// crates/grepzilla_segment/src/v2/columns.rs
+//! `doc_values.dat` — колонки doc-values сегмента V2 (опционально).
+//!
+//! Формат (LE), CRC64 в футере:
+//! ```text
+//! header:  [magic "GZDV1\0\0\0"][u64 doc_count][u32 n_cols][u32 block_docs]
+//! dir × n_cols:
+//!          [varint name_len][name][u8 kind][i64 min][i64 max][u64 off][u64 len]
+//! column (off/len от начала файла):
+//!          [u32 present_len][present: Roaring portable]
+//!          [n_blocks × (i64 min, i64 max)]   n_blocks = ceil(doc_count / block_docs)
+//!          [doc_count × i64 key]             нет значения — 0 (смотри present)
+//! ```
+//! Ключи — см. [`crate::doc_values`]. Сводки min/max по блокам позволяют
+//! фильтру по диапазону пропускать блоки целиком (логи пишутся почти по
+//! возрастанию времени).
+use anyhow::{Result, bail};
+use croaring::{Bitmap, Portable};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::doc_values::{DocValueField, DocValueKind, RangeFilter};
+use crate::summary::ValueRange;
+use crate::v2::codec::put_varint;
+use crate::v2::crc::crc64_ecma_reader;
+
+pub const DOC_VALUES_FILE: &str = "doc_values.dat";
+const DV_MAGIC: &[u8; 8] = b"GZDV1\0\0\0";
+/// Документов в блоке сводки min/max.
+pub const DV_BLOCK_DOCS: u32 = 1024;
+
+/// Потоковая запись колонок: значения уходят во временные файлы по мере
+/// поступления документов, в памяти — только present и сводки блоков.
+pub(crate) struct ColumnsWriter {
+    cols: Vec<ColumnBuf>,
+}
+
+struct ColumnBuf {
+    field: DocValueField,
+    tmp: PathBuf,
+    values: BufWriter<File>,
+    written: u32,
+    present: Bitmap,
+    blocks: Vec<(i64, i64)>,
+}
+
+impl ColumnsWriter {
+    pub(crate) fn new(tmp_dir: &Path, fields: &[DocValueField]) -> Result<Self> {
+        let mut cols = Vec::with_capacity(fields.len());
+        for (i, f) in fields.iter().enumerate() {
+            if fields[..i].iter().any(|g| g.name == f.name) {
+                bail!("doc-value field `{}` configured twice", f.name);
+            }
+            let tmp = tmp_dir.join(format!("dv.{i}"));
+            cols.push(ColumnBuf {
+                field: f.clone(),
+                values: BufWriter::new(File::create(&tmp)?),
+                tmp,
+                written: 0,
+                present: Bitmap::new(),
+                blocks: Vec::new(),
+            });
+        }
+        Ok(Self { cols })
+    }
+
+    /// Значение колонки `col` для документа `doc`. DocId не убывают; повтор
+    /// того же документа игнорируется (берётся первое значение).
+    pub(crate) fn put(&mut self, col: usize, doc: u32, key: i64) -> Result<()> {
+        let c = &mut self.cols[col];
+        if doc < c.written {
+            return Ok(());
+        }
+        c.pad_to(doc)?;
+        c.values.write_all(&key.to_le_bytes())?;
+        c.written += 1;
+        c.present.add(doc);
+        let b = (doc / DV_BLOCK_DOCS) as usize;
+        if c.blocks.len() <= b {
+            c.blocks.resize(b + 1, (i64::MAX, i64::MIN));
+        }
+        let (mn, mx) = &mut c.blocks[b];
+        (*mn, *mx) = ((*mn).min(key), (*mx).max(key));
+        Ok(())
+    }
+
+    /// Записать `doc_values.dat` для `doc_count` документов. Возвращает
+    /// диапазоны непустых колонок (для сводки сегмента).
+    pub(crate) fn finish(mut self, doc_count: u32, path: &Path) -> Result<Vec<ValueRange>> {
+        let n_blocks = doc_count.div_ceil(DV_BLOCK_DOCS) as usize;
+        let mut presents = Vec::with_capacity(self.cols.len());
+        for c in &mut self.cols {
+            c.pad_to(doc_count)?;
+            c.values.flush()?;
+            c.blocks.resize(n_blocks, (i64::MAX, i64::MIN));
+            presents.push(c.present.serialize::<Portable>());
+        }
+
+        // каталог: смещения колонок считаются заранее
+        let dir_len: usize = self
+            .cols
+            .iter()
+            .map(|c| {
+                let mut v = Vec::new();
+                put_varint(c.field.name.len() as u64, &mut v);
+                v.len() + c.field.name.len() + 1 + 8 * 4
+            })
+            .sum();
+        let mut off = (8 + 8 + 4 + 4 + dir_len) as u64;
+
+        let mut f = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        {
+            let mut w = BufWriter::new(&mut f);
+            w.write_all(DV_MAGIC)?;
+            w.write_all(&(doc_count as u64).to_le_bytes())?;
+            w.write_all(&(self.cols.len() as u32).to_le_bytes())?;
+            w.write_all(&DV_BLOCK_DOCS.to_le_bytes())?;
+            for (c, present) in self.cols.iter().zip(&presents) {
+                let len = (4 + present.len() + n_blocks * 16) as u64 + doc_count as u64 * 8;
+                let mut name = Vec::new();
+                put_varint(c.field.name.len() as u64, &mut name);
+                name.extend_from_slice(c.field.name.as_bytes());
+                w.write_all(&name)?;
+                w.write_all(&[c.field.kind.code()])?;
+                let (mn, mx) = c.min_max();
+                w.write_all(&mn.to_le_bytes())?;
+                w.write_all(&mx.to_le_bytes())?;
+                w.write_all(&off.to_le_bytes())?;
+                w.write_all(&len.to_le_bytes())?;
+                off += len;
+            }
+            for (c, present) in self.cols.iter().zip(&presents) {
+                w.write_all(&(present.len() as u32).to_le_bytes())?;
+                w.write_all(present)?;
+                for (mn, mx) in &c.blocks {
+                    w.write_all(&mn.to_le_bytes())?;
+                    w.write_all(&mx.to_le_bytes())?;
+                }
+                std::io::copy(&mut File::open(&c.tmp)?, &mut w)?;
+            }
+            w.flush()?;
+        }
+        finalize_crc(&mut f)?;
+        Ok(self
+            .cols
+            .iter()
+            .filter(|c| !c.present.is_empty())
+            .map(|c| {
+                let (min, max) = c.min_max();
+                ValueRange {
+                    field: c.field.clone(),
+                    min,
+                    max,
+                }
+            })
+            .collect())
+    }
+}
+
+impl ColumnBuf {
+    fn pad_to(&mut self, doc: u32) -> Result<()> {
+        while self.written < doc {
+            self.values.write_all(&0i64.to_le_bytes())?;
+            self.written += 1;
+        }
+        Ok(())
+    }
+
+    fn min_max(&self) -> (i64, i64) {
+        self.blocks
+            .iter()
+            .fold((i64::MAX, i64::MIN), |(a, b), (mn, mx)| {
+                (a.min(*mn), b.max(*mx))
+            })
+    }
+}
+
+fn finalize_crc(f: &mut File) -> Result<()> {
+    use std::io::{Seek, SeekFrom};
+    f.seek(SeekFrom::Start(0))?;
+    let crc = crc64_ecma_reader(f)?;
+    f.seek(SeekFrom::End(0))?;
+    f.write_all(&crc.to_le_bytes())?;
+    f.flush()?;
+    Ok(())
+}
+
+/// Колонки открытого сегмента (поверх mmap `doc_values.dat` без футера).
+pub(crate) struct ColumnsIndex {
+    doc_count: u32,
+    block_docs: u32,
+    cols: Vec<ColumnMeta>,
+}
+
+struct ColumnMeta {
+    field: DocValueField,
+    min: i64,
+    max: i64,
+    off: usize,
+    len: usize,
+}
+
+impl ColumnsIndex {
+    /// Разобрать заголовок; `body` — файл без CRC-футера.
+    pub(crate) fn parse(body: &[u8], doc_count: u32) -> Result<Self> {
+        if body.len() < 24 || &body[0..8] != DV_MAGIC {
+            bail!("doc_values.dat bad magic");
+        }
+        let dc = u64::from_le_bytes(body[8..16].try_into().unwrap());
+        if dc != doc_count as u64 {
+            bail!("doc_values.dat doc_count mismatch");
+        }
+        let n = u32::from_le_bytes(body[16..20].try_into().unwrap()) as usize;
+        let block_docs = u32::from_le_bytes(body[20..24].try_into().unwrap());
+        if block_docs == 0 {
+            bail!("doc_values.dat zero block size");
+        }
+        let n_blocks = doc_count.div_ceil(block_docs) as usize;
+        let mut p = 24usize;
+        let mut cols = Vec::with_capacity(n);
+        for _ in 0..n {
+            let (name_len, adv) = crate::v2::codec::get_varint(&body[p.min(body.len())..])
+                .ok_or_else(|| anyhow::anyhow!("doc_values.dat dir truncated"))
+                .map(|(v, rest)| (v as usize, body.len() - p - rest.len()))?;
+            p += adv;
+            let end = p + name_len + 1 + 32;
+            if end > body.len() {
+                bail!("doc_values.dat dir truncated");
+            }
+            let name = std::str::from_utf8(&body[p..p + name_len])?.to_string();
+            p += name_len;
+            let kind = DocValueKind::from_code(body[p])?;
+            p += 1;
+            let rd = |at: usize| i64::from_le_bytes(body[at..at + 8].try_into().unwrap());
+            let (min, max) = (rd(p), rd(p + 8));
+            let (off, len) = (rd(p + 16) as usize, rd(p + 24) as usize);
+            p = end;
+            if off.checked_add(len).is_none_or(|e| e > body.len())
+                || len < 4 + n_blocks * 16 + doc_count as usize * 8
+            {
+                bail!("doc_values.dat column `{name}` OOB");
+            }
+            cols.push(ColumnMeta {
+                field: DocValueField { name, kind },
+                min,
+                max,
+                off,
+                len,
+            });
+        }
+        Ok(Self {
+            doc_count,
+            block_docs,
+            cols,
+        })
+    }
+
+    pub(crate) fn fields(&self) -> impl Iterator<Item = &DocValueField> {
+        self.cols.iter().map(|c| &c.field)
+    }
+
+    pub(crate) fn column<'a>(&self, body: &'a [u8], field: &str) -> Result<Option<Column<'a>>> {
+        let Some(c) = self.cols.iter().find(|c| c.field.name == field) else {
+            return Ok(None);
+        };
+        let data = &body[c.off..c.off + c.len];
+        let plen = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+        let n_blocks = self.doc_count.div_ceil(self.block_docs) as usize;
+        if 4 + plen + n_blocks * 16 + self.doc_count as usize * 8 != c.len {
+            bail!("doc_values.dat column `{field}` length mismatch");
+        }
+        let present = Bitmap::try_deserialize::<Portable>(&data[4..4 + plen])
+            .ok_or_else(|| anyhow::anyhow!("doc_values.dat bad present bitmap"))?;
+        let blocks_at = 4 + plen;
+        let values_at = blocks_at + n_blocks * 16;
+        Ok(Some(Column {
+            kind: c.field.kind,
+            min: c.min,
+            max: c.max,
+            block_docs: self.block_docs,
+            doc_count: self.doc_count,
+            present,
+            blocks: &data[blocks_at..values_at],
+            values: &data[values_at..],
+        }))
+    }
+}
+
+/// Колонка doc-values одного поля.
+pub struct Column<'a> {
+    kind: DocValueKind,
+    min: i64,
+    max: i64,
+    block_docs: u32,
+    doc_count: u32,
+    present: Bitmap,
+    blocks: &'a [u8],
+    values: &'a [u8],
+}
+
+impl Column<'_> {
+    pub fn kind(&self) -> DocValueKind {
+        self.kind
+    }
+
+    /// Наименьший и наибольший ключ колонки (`None` — значений нет).
+    pub fn min_max(&self) -> Option<(i64, i64)> {
+        (self.min <= self.max).then_some((self.min, self.max))
+    }
+
+    /// Документы, у которых есть значение.
+    pub fn present(&self) -> &Bitmap {
+        &self.present
+    }
+
+    /// Ключ значения документа.
+    pub fn get(&self, doc: u32) -> Option<i64> {
+        if !self.present.contains(doc) {
+            return None;
+        }
+        Some(self.key_at(doc))
+    }
+
+    fn key_at(&self, doc: u32) -> i64 {
+        let i = doc as usize * 8;
+        i64::from_le_bytes(self.values[i..i + 8].try_into().unwrap())
+    }
+
+    fn block(&self, b: usize) -> (i64, i64) {
+        let rd = |at: usize| i64::from_le_bytes(self.blocks[at..at + 8].try_into().unwrap());
+        (rd(b * 16), rd(b * 16 + 8))
+    }
+
+    /// Документы с ключом в `[lo, hi]`. Блоки целиком вне диапазона
+    /// пропускаются, целиком внутри — берутся без чтения значений.
+    pub fn range(&self, lo: i64, hi: i64) -> Bitmap {
+        let mut out = Bitmap::new();
+        if lo > hi || self.min > hi || self.max < lo {
+            return out;
+        }
+        let n_blocks = self.doc_count.div_ceil(self.block_docs) as usize;
+        for b in 0..n_blocks {
+            let (mn, mx) = self.block(b);
+            if mn > hi || mx < lo {
+                continue;
+            }
+            let from = b as u32 * self.block_docs;
+            let to = (from + self.block_docs).min(self.doc_count);
+            if lo <= mn && mx <= hi {
+                let mut all = Bitmap::new();
+                all.add_range(from..to);
+                all.and_inplace(&self.present);
+                out.or_inplace(&all);
+                continue;
+            }
+            for doc in from..to {
+                let k = self.key_at(doc);
+                if lo <= k && k <= hi && self.present.contains(doc) {
+                    out.add(doc);
+                }
+            }
+        }
+        out
+    }
+
+    /// Структурные нарушения колонки: present за пределами `doc_count`,
+    /// значения вне сводок своего блока и колонки.
+    pub(crate) fn violations(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if let Some(mx) = self.present.maximum()
+            && mx >= self.doc_count
+        {
+            out.push(format!(
+                "present doc_id {mx} >= doc_count {}",
+                self.doc_count
+            ));
+            return out;
+        }
+        for doc in self.present.iter() {
+            let k = self.key_at(doc);
+            let (mn, mx) = self.block((doc / self.block_docs) as usize);
+            if k < mn || k > mx {
+                out.push(format!(
+                    "doc {doc}: value {k} outside block min/max [{mn}, {mx}]"
+                ));
+            }
+            if k < self.min || k > self.max {
+                out.push(format!(
+                    "doc {doc}: value {k} outside column min/max [{}, {}]",
+                    self.min, self.max
+                ));
+            }
+        }
+        out
+    }
+
+    /// Документы, проходящие фильтр.
+    pub fn filter(&self, f: &RangeFilter) -> anyhow::Result<Bitmap> {
+        Ok(match f.key_range(self.kind)? {
+            Some((lo, hi)) => self.range(lo, hi),
+            None => Bitmap::new(),
+        })
+    }
+}
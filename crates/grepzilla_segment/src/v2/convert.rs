@@ -0,0 +1,210 @@
+// crates/grepzilla_segment/src/v2/convert.rs
+//! Перевод сегмента V1 (JSON) в V2 без исходного JSONL и сверка хитов.
+//!
+//! Документы читаются через [`JsonSegmentReader`] и переносятся в том же
+//! порядке, поэтому DocId сохраняются и `deletes.roaring` копируется как есть.
+//! Граммы строятся заново из сохранённого нормализованного текста: в V1 нет
+//! позиционных масок, а так V2 получает их полностью. Колонок doc-values у V1
+//! нет, поэтому их нет и у результата.
+//!
+//! [`compare_hits`] прогоняет набор wildcard-запросов по двум сегментам
+//! и сравнивает множества хитов (RFC-0002 §13: паритет V1/V2).
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::SegmentReader;
+use crate::deletes::DELETES_FILE;
+use crate::normalizer::Sensitivity;
+use crate::query::{BoolQuery, Clause, PrefilterOpts};
+use crate::segjson::JsonSegmentReader;
+use crate::v2::reader::BinSegmentReader;
+use crate::v2::spill::GramSpill;
+use crate::v2::writer::{
+    DEFAULT_MEMORY_BUDGET, FieldGrams, SHORT_GRAMS, SegmentSink, index_short_text, index_text,
+};
+use crate::verify::EnvVerifyFactory;
+
+/// Параметры конвертации.
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    /// Для каких полей строить `field_grams.*`.
+    pub field_grams: FieldGrams,
+    /// Строить `short_grams.*` (индекс 1–2-грамм).
+    pub short_grams: bool,
+    /// Бюджет памяти под постинги (байт), как у [`crate::v2::writer::BinSegmentWriter`].
+    pub memory_budget: usize,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            field_grams: FieldGrams::Off,
+            short_grams: false,
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+        }
+    }
+}
+
+/// Итог конвертации.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConvertStats {
+    pub docs: u64,
+    pub fields: u64,
+    /// документов, помеченных удалёнными (перенесены вместе с DocId)
+    pub deleted: u64,
+}
+
+/// Записать сегмент V1 `v1_dir` в формате V2 в `out_dir`.
+pub fn convert_v1_to_v2(
+    v1_dir: &str,
+    out_dir: &str,
+    opts: &ConvertOptions,
+) -> Result<ConvertStats> {
+    let src = Path::new(v1_dir);
+    let out = Path::new(out_dir);
+    if out.exists() && src.canonicalize()? == out.canonicalize()? {
+        bail!("convert output must differ from input: {v1_dir}");
+    }
+    let reader = JsonSegmentReader::open_segment(v1_dir)?;
+
+    let mut sink = SegmentSink::create(out)?;
+    sink.set_analyzers(reader.analyzers().clone());
+    sink.set_raw_text(reader.has_raw_text());
+    let mut grams = GramSpill::new(sink.tmp_dir.clone(), "grams");
+    let mut field_grams = GramSpill::new(sink.tmp_dir.clone(), "field_grams");
+    let mut short_grams = GramSpill::new(sink.tmp_dir.clone(), SHORT_GRAMS);
+
+    for doc_id in 0..reader.doc_count() {
+        let Some(doc) = reader.get_doc(doc_id) else {
+            bail!("doc {doc_id} missing in docs.jsonl");
+        };
+        if doc.doc_id != doc_id {
+            bail!(
+                "docs.jsonl line {doc_id}: doc_id {} out of order",
+                doc.doc_id
+            );
+        }
+        let mut fields = Vec::with_capacity(doc.fields.len());
+        for (path, ns) in &doc.fields {
+            // текст в V1 уже нормализован
+            let per_field = opts.field_grams.covers(path);
+            index_text(path, ns, doc_id, per_field, &mut grams, &mut field_grams);
+            if opts.short_grams {
+                index_short_text(ns, doc_id, &mut short_grams);
+            }
+            fields.push((sink.field_id(path), ns.clone()));
+        }
+        let raw = doc
+            .raw
+            .iter()
+            .map(|(path, s)| (sink.field_id(path), s.clone()))
+            .collect();
+        sink.push_doc(&doc.ext_id, fields, raw)?;
+
+        if grams.mem_bytes() + field_grams.mem_bytes() + short_grams.mem_bytes()
+            > opts.memory_budget
+        {
+            grams.spill()?;
+            field_grams.spill()?;
+            short_grams.spill()?;
+        }
+    }
+
+    let stats = ConvertStats {
+        docs: sink.doc_count as u64,
+        fields: sink.field_count() as u64,
+        deleted: reader.live_docs().deleted().cardinality(),
+    };
+    let field_grams = match opts.field_grams {
+        FieldGrams::Off => None,
+        _ => Some(field_grams.finish()?),
+    };
+    let short_grams = if opts.short_grams {
+        Some(short_grams.finish()?)
+    } else {
+        None
+    };
+    sink.finish(grams.finish()?, field_grams, short_grams)?;
+
+    // DocId совпадают — удаления (вместе с поколением) переносятся как есть
+    let deletes = out.join(DELETES_FILE);
+    if src.join(DELETES_FILE).exists() {
+        std::fs::copy(src.join(DELETES_FILE), &deletes)?;
+    } else if deletes.exists() {
+        std::fs::remove_file(&deletes)?;
+    }
+    Ok(stats)
+}
+
+/// Расхождение хитов одного запроса между сегментами.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HitDiff {
+    pub query: String,
+    pub v1_hits: u64,
+    pub v2_hits: u64,
+    /// `_id`, найденные только в V1 / только в V2
+    pub only_v1: Vec<String>,
+    pub only_v2: Vec<String>,
+}
+
+impl HitDiff {
+    pub fn is_equal(&self) -> bool {
+        self.only_v1.is_empty() && self.only_v2.is_empty()
+    }
+}
+
+/// Прогнать `queries` по сегментам V1 и V2 (тем же путём, что и поиск:
+/// префильтр по граммам, затем verify) и сравнить хиты по `_id`.
+pub fn compare_hits(
+    v1_dir: &str,
+    v2_dir: &str,
+    queries: &[String],
+    field: Option<&str>,
+) -> Result<Vec<HitDiff>> {
+    let v1 = JsonSegmentReader::open_segment(v1_dir)?;
+    let v2 = BinSegmentReader::open_segment(v2_dir)?;
+    let factory = EnvVerifyFactory::from_env();
+
+    let mut out = Vec::with_capacity(queries.len());
+    for q in queries {
+        // каждый сегмент — со своими анализаторами (у результата
+        // конвертации они те же, что у исходника)
+        let query = BoolQuery::single(Clause::wildcard(q, field));
+        let compile = |a| query.compile_with(&factory, Sensitivity::default(), a);
+        let (q1, q2) = (compile(v1.analyzers())?, compile(v2.analyzers())?);
+
+        let cand1 = q1.prefilter(PrefilterOpts::default(), |runs, f| {
+            v1.prefilter_runs(runs, f)
+        })?;
+        let mut hits1 = BTreeSet::new();
+        for doc_id in cand1.iter() {
+            if let Some(doc) = v1.get_doc(doc_id)
+                && q1.matches(doc).is_some()
+            {
+                hits1.insert(doc.ext_id.clone());
+            }
+        }
+
+        let cand2 = q2.prefilter(PrefilterOpts::default(), |runs, f| {
+            v2.prefilter_adjacent_after(runs, f, None)
+        })?;
+        let mut hits2 = BTreeSet::new();
+        for doc_id in cand2.iter() {
+            let doc = v2.read_doc(doc_id)?;
+            if q2.matches(&doc).is_some() {
+                hits2.insert(doc.ext_id);
+            }
+        }
+
+        out.push(HitDiff {
+            query: q.clone(),
+            v1_hits: hits1.len() as u64,
+            v2_hits: hits2.len() as u64,
+            only_v1: hits1.difference(&hits2).cloned().collect(),
+            only_v2: hits2.difference(&hits1).cloned().collect(),
+        });
+    }
+    Ok(out)
+}
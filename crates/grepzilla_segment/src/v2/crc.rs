@@ -8,3 +8,18 @@ pub fn crc64_ecma(data: &[u8]) -> u64 {
     d.write(data);
     d.sum64()
 }
+
+/// CRC64-ECMA всего потока (читает кусками, без загрузки в память).
+pub fn crc64_ecma_reader<R: std::io::Read>(r: &mut R) -> std::io::Result<u64> {
+    use crc64fast::Digest;
+    let mut d = Digest::new();
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let n = r.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        d.write(&buf[..n]);
+    }
+    Ok(d.sum64())
+}
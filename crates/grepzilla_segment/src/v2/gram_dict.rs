@@ -0,0 +1,596 @@
+// crates/grepzilla_segment/src/v2/gram_dict.rs
+//! Словарь грамм `grams.idx`: gram → (offset, length) в `grams.dat`.
+//!
+//! Версии:
+//! - v1: записи по 24 байта `[u8;3 key][u64 off][u64 len][pad 5]`. Ключ —
+//!   первые 3 БАЙТА граммы, поэтому для не-ASCII текста разные триграммы
+//!   склеиваются в один ключ. Только чтение (старые сегменты).
+//! - v2: ключ — полная UTF-8 строка граммы. Таблица записей фиксированной
+//!   ширины `[u32 key_off][u32 key_len][u64 off][u64 len]` + куча ключей.
+//!   Только чтение.
+//! - v3: front-coded блоки по [`FC_BLOCK_KEYS`] ключей + таблица смещений
+//!   блоков. Первый ключ блока хранится целиком, остальные — как
+//!   (общий префикс с предыдущим, суффикс); offset кодируется дельтой от конца
+//!   предыдущего постинга в блоке. Поиск — бинарный по первым ключам блоков, затем
+//!   линейный скан внутри блока.
+//!
+//! Во всех версиях записи отсортированы по байтам ключа, поэтому кроме точного
+//! поиска доступны префиксные и диапазонные сканы ([`GramDict::range`]).
+use anyhow::{Result, anyhow, bail};
+use memmap2::Mmap;
+use std::cmp::Ordering;
+use std::io::Write;
+
+use crate::v2::codec::{get_varint, put_varint};
+
+pub const GRAMS_IDX_MAGIC: u32 = 0x475A4944; // "GZID"
+pub const GRAMS_IDX_V1: u16 = 1;
+pub const GRAMS_IDX_V2: u16 = 2;
+pub const GRAMS_IDX_V3: u16 = 3;
+
+/// Ключей в одном front-coded блоке (v3).
+pub const FC_BLOCK_KEYS: usize = 16;
+
+/// Разделитель поля и граммы в ключах `field_grams.idx`.
+pub const FIELD_GRAM_SEP: char = '\0';
+
+/// Ключ пофилдового индекса: `"<field>\0<gram>"`. Пустая грамма даёт маркер
+/// поля — он означает, что поле проиндексировано отдельно.
+pub fn field_gram_key(field: &str, gram: &str) -> String {
+    let mut k = String::with_capacity(field.len() + 1 + gram.len());
+    k.push_str(field);
+    k.push(FIELD_GRAM_SEP);
+    k.push_str(gram);
+    k
+}
+
+const HEADER_LEN: usize = 4 + 2 + 2 + 4 + 4;
+const V1_RECORD_LEN: usize = 24;
+const V2_RECORD_LEN: usize = 4 + 4 + 8 + 8;
+
+/// Запись словаря.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GramEntry {
+    pub key: Vec<u8>,
+    pub off: u64,
+    pub len: u64,
+}
+
+impl GramEntry {
+    pub fn key_str(&self) -> &str {
+        std::str::from_utf8(&self.key).unwrap_or("\u{FFFD}")
+    }
+}
+
+/// Записать `grams.idx` v3 (без CRC-футера — его добавляет вызывающий).
+/// `entries` MUST быть отсортированы по байтам ключа, а постинги — идти в
+/// `grams.dat` в том же порядке (offset не убывает).
+pub fn write_grams_idx<W: Write>(w: &mut W, entries: &[(&str, u64, u64)]) -> Result<()> {
+    let mut blocks: Vec<u8> = Vec::new();
+    let mut block_offs: Vec<u32> = Vec::new();
+
+    let mut prev_key: &[u8] = &[];
+    let mut prev_end = 0u64;
+    for (i, (key, off, len)) in entries.iter().enumerate() {
+        let key = key.as_bytes();
+        if i > 0 && key <= prev_key {
+            bail!("grams.idx keys must be strictly sorted");
+        }
+        if i.is_multiple_of(FC_BLOCK_KEYS) {
+            // блок декодируется независимо: первый offset — абсолютный
+            block_offs.push(u32::try_from(blocks.len())?);
+            prev_end = 0;
+            put_varint(key.len() as u64, &mut blocks);
+            blocks.extend_from_slice(key);
+        } else {
+            let shared = common_prefix(prev_key, key);
+            put_varint(shared as u64, &mut blocks);
+            put_varint((key.len() - shared) as u64, &mut blocks);
+            blocks.extend_from_slice(&key[shared..]);
+        }
+        if *off < prev_end {
+            bail!("grams.idx postings offsets must not decrease");
+        }
+        put_varint(off - prev_end, &mut blocks);
+        put_varint(*len, &mut blocks);
+        prev_key = key;
+        prev_end = off + len;
+    }
+
+    w.write_all(&GRAMS_IDX_MAGIC.to_le_bytes())?;
+    w.write_all(&GRAMS_IDX_V3.to_le_bytes())?;
+    w.write_all(&0u16.to_le_bytes())?; // flags
+    w.write_all(&(entries.len() as u32).to_le_bytes())?;
+    w.write_all(&(FC_BLOCK_KEYS as u32).to_le_bytes())?;
+    for o in &block_offs {
+        w.write_all(&o.to_le_bytes())?;
+    }
+    w.write_all(&blocks)?;
+    Ok(())
+}
+
+/// Читатель `grams.idx` поверх mmap (CRC проверяется при открытии сегмента).
+pub struct GramDict {
+    m: Mmap,
+    version: u16,
+    count: usize,
+    /// v1/v2: длина записи; v3: ключей в блоке
+    rec_len: usize,
+    /// v2: начало кучи ключей; v3: начало блоков
+    heap_start: usize,
+    /// v3: число блоков
+    block_count: usize,
+}
+
+impl GramDict {
+    pub fn open(m: Mmap) -> Result<Self> {
+        if m.len() < HEADER_LEN + 8 {
+            bail!("grams.idx too small");
+        }
+        let body_len = m.len() - 8; // без CRC64
+        let magic = u32::from_le_bytes(m[0..4].try_into().unwrap());
+        if magic != GRAMS_IDX_MAGIC {
+            bail!("grams.idx bad magic");
+        }
+        let version = u16::from_le_bytes(m[4..6].try_into().unwrap());
+        let count = u32::from_le_bytes(m[8..12].try_into().unwrap()) as usize;
+        let rec_len = u32::from_le_bytes(m[12..16].try_into().unwrap()) as usize;
+
+        let (heap_start, block_count) = match version {
+            GRAMS_IDX_V1 | GRAMS_IDX_V2 => {
+                let expect = if version == GRAMS_IDX_V1 {
+                    V1_RECORD_LEN
+                } else {
+                    V2_RECORD_LEN
+                };
+                if rec_len != expect {
+                    bail!("grams.idx bad record_len {rec_len}");
+                }
+                (HEADER_LEN + count * rec_len, 0)
+            }
+            GRAMS_IDX_V3 => {
+                if rec_len == 0 {
+                    bail!("grams.idx bad block size");
+                }
+                let block_count = count.div_ceil(rec_len);
+                (HEADER_LEN + block_count * 4, block_count)
+            }
+            v => bail!("grams.idx unsupported version {v}"),
+        };
+        if heap_start > body_len {
+            bail!("grams.idx records OOB");
+        }
+        Ok(Self {
+            m,
+            version,
+            count,
+            rec_len,
+            heap_start,
+            block_count,
+        })
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Найти (offset, length) постинга граммы.
+    pub fn lookup(&self, gram: &str) -> Result<Option<(u64, u64)>> {
+        let needle = gram.as_bytes();
+        match self.version {
+            GRAMS_IDX_V1 => {
+                // старая раскладка: сравниваем только первые 3 байта
+                if needle.len() < 3 {
+                    return Ok(None);
+                }
+                self.lookup_fixed(&needle[..3])
+            }
+            GRAMS_IDX_V2 => self.lookup_fixed(needle),
+            _ => match self.range(needle, None)?.next().transpose()? {
+                Some(e) if e.key == needle => Ok(Some((e.off, e.len))),
+                _ => Ok(None),
+            },
+        }
+    }
+
+    /// Все записи словаря по порядку ключей.
+    pub fn iter(&self) -> GramRange<'_> {
+        GramRange::at(self, 0, None)
+    }
+
+    /// Записи с ключами из `[from, to)` (`to = None` — до конца словаря).
+    pub fn range(&self, from: &[u8], to: Option<&[u8]>) -> Result<GramRange<'_>> {
+        let start = match self.version {
+            GRAMS_IDX_V3 => {
+                // последний блок, чей первый ключ <= from
+                let (mut lo, mut hi) = (0usize, self.block_count);
+                while lo < hi {
+                    let mid = (lo + hi) / 2;
+                    if self.block_first_key(mid)? <= from {
+                        lo = mid + 1;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                lo.saturating_sub(1) * self.rec_len
+            }
+            _ => {
+                let (mut lo, mut hi) = (0usize, self.count);
+                while lo < hi {
+                    let mid = (lo + hi) / 2;
+                    if self.fixed_key_at(mid)? < from {
+                        lo = mid + 1;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                lo
+            }
+        };
+        let mut it = GramRange::at(self, start, to.map(|t| t.to_vec()));
+        // v3: докрутить внутри блока до первого ключа >= from
+        while let Some(e) = it.next_raw()? {
+            if e.key.as_slice() >= from {
+                it.pending = Some(e);
+                break;
+            }
+        }
+        Ok(it)
+    }
+
+    /// Записи, ключи которых начинаются с `prefix`.
+    pub fn prefix(&self, prefix: &[u8]) -> Result<GramRange<'_>> {
+        let end = prefix_successor(prefix);
+        self.range(prefix, end.as_deref())
+    }
+
+    fn lookup_fixed(&self, needle: &[u8]) -> Result<Option<(u64, u64)>> {
+        let (mut lo, mut hi) = (0usize, self.count);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match self.fixed_key_at(mid)?.cmp(needle) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => return Ok(Some(self.fixed_postings_ref(mid))),
+            }
+        }
+        Ok(None)
+    }
+
+    /// v1/v2: ключ i-й записи (для v1 — 3 байта).
+    fn fixed_key_at(&self, i: usize) -> Result<&[u8]> {
+        let base = HEADER_LEN + i * self.rec_len;
+        match self.version {
+            GRAMS_IDX_V1 => Ok(&self.m[base..base + 3]),
+            _ => {
+                let k_off = u32::from_le_bytes(self.m[base..base + 4].try_into().unwrap()) as usize;
+                let k_len =
+                    u32::from_le_bytes(self.m[base + 4..base + 8].try_into().unwrap()) as usize;
+                let from = self.heap_start + k_off;
+                let to = from + k_len;
+                if to > self.m.len() - 8 {
+                    bail!("grams.idx key OOB");
+                }
+                Ok(&self.m[from..to])
+            }
+        }
+    }
+
+    /// v1/v2: (offset, length) i-й записи в `grams.dat`.
+    fn fixed_postings_ref(&self, i: usize) -> (u64, u64) {
+        let base = HEADER_LEN + i * self.rec_len;
+        let p = match self.version {
+            GRAMS_IDX_V1 => base + 3,
+            _ => base + 8,
+        };
+        let off = u64::from_le_bytes(self.m[p..p + 8].try_into().unwrap());
+        let len = u64::from_le_bytes(self.m[p + 8..p + 16].try_into().unwrap());
+        (off, len)
+    }
+
+    /// v3: байтовое начало блока.
+    fn block_start(&self, b: usize) -> Result<usize> {
+        let p = HEADER_LEN + b * 4;
+        let rel = u32::from_le_bytes(self.m[p..p + 4].try_into().unwrap()) as usize;
+        let at = self.heap_start + rel;
+        if at >= self.m.len() - 8 {
+            bail!("grams.idx block OOB");
+        }
+        Ok(at)
+    }
+
+    /// v3: первый ключ блока (хранится целиком).
+    fn block_first_key(&self, b: usize) -> Result<&[u8]> {
+        let at = self.block_start(b)?;
+        let body = &self.m[..self.m.len() - 8];
+        let (klen, rest) = get_varint(&body[at..]).ok_or_else(|| anyhow!("truncated varint"))?;
+        let from = body.len() - rest.len();
+        let to = from + klen as usize;
+        if to > body.len() {
+            bail!("grams.idx key OOB");
+        }
+        Ok(&body[from..to])
+    }
+}
+
+/// Итератор по записям словаря (см. [`GramDict::iter`], [`GramDict::range`]).
+pub struct GramRange<'a> {
+    d: &'a GramDict,
+    /// индекс следующей записи
+    idx: usize,
+    /// исключающая верхняя граница ключа
+    end: Option<Vec<u8>>,
+    /// запись, прочитанная при позиционировании
+    pending: Option<GramEntry>,
+    // --- состояние декодера v3 ---
+    p: usize,
+    key: Vec<u8>,
+    prev_end: u64,
+}
+
+impl<'a> GramRange<'a> {
+    fn at(d: &'a GramDict, idx: usize, end: Option<Vec<u8>>) -> Self {
+        Self {
+            d,
+            idx,
+            end,
+            pending: None,
+            p: 0,
+            key: Vec::new(),
+            prev_end: 0,
+        }
+    }
+
+    /// Следующая запись без проверки верхней границы.
+    fn next_raw(&mut self) -> Result<Option<GramEntry>> {
+        if self.idx >= self.d.count {
+            return Ok(None);
+        }
+        let i = self.idx;
+        self.idx += 1;
+        if self.d.version != GRAMS_IDX_V3 {
+            let (off, len) = self.d.fixed_postings_ref(i);
+            return Ok(Some(GramEntry {
+                key: self.d.fixed_key_at(i)?.to_vec(),
+                off,
+                len,
+            }));
+        }
+
+        let body = &self.d.m[..self.d.m.len() - 8];
+        let block_head = i.is_multiple_of(self.d.rec_len);
+        let mut cur = if block_head {
+            let at = self.d.block_start(i / self.d.rec_len)?;
+            self.key.clear();
+            self.prev_end = 0;
+            &body[at..]
+        } else {
+            &body[self.p..]
+        };
+        let mut next = || -> Result<u64> {
+            let (v, rest) = get_varint(cur).ok_or_else(|| anyhow!("truncated varint"))?;
+            cur = rest;
+            Ok(v)
+        };
+        let (shared, suffix) = if block_head {
+            (0, next()? as usize)
+        } else {
+            (next()? as usize, next()? as usize)
+        };
+        if shared > self.key.len() || suffix > cur.len() {
+            bail!("grams.idx front-coding OOB");
+        }
+        self.key.truncate(shared);
+        self.key.extend_from_slice(&cur[..suffix]);
+        cur = &cur[suffix..];
+        let (gap, rest) = get_varint(cur).ok_or_else(|| anyhow!("truncated varint"))?;
+        let (len, rest) = get_varint(rest).ok_or_else(|| anyhow!("truncated varint"))?;
+        let off = self.prev_end + gap;
+        self.prev_end = off + len;
+        self.p = body.len() - rest.len();
+        Ok(Some(GramEntry {
+            key: self.key.clone(),
+            off,
+            len,
+        }))
+    }
+}
+
+impl Iterator for GramRange<'_> {
+    type Item = Result<GramEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let e = match self.pending.take() {
+            Some(e) => e,
+            None => match self.next_raw() {
+                Ok(Some(e)) => e,
+                Ok(None) => return None,
+                Err(err) => {
+                    self.idx = self.d.count;
+                    return Some(Err(err));
+                }
+            },
+        };
+        if let Some(end) = &self.end
+            && e.key.as_slice() >= end.as_slice()
+        {
+            self.idx = self.d.count;
+            return None;
+        }
+        Some(Ok(e))
+    }
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// Наименьшая строка байт, большая всех строк с данным префиксом.
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut out = prefix.to_vec();
+    while let Some(last) = out.pop() {
+        if last < u8::MAX {
+            out.push(last + 1);
+            return Some(out);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(entries: &[(&str, u64, u64)]) -> GramDict {
+        let mut buf = Vec::new();
+        write_grams_idx(&mut buf, entries).unwrap();
+        open_buf(buf)
+    }
+
+    fn open_buf(mut buf: Vec<u8>) -> GramDict {
+        let crc = crate::v2::crc::crc64_ecma(&buf);
+        buf.extend_from_slice(&crc.to_le_bytes());
+        let mut mm = memmap2::MmapMut::map_anon(buf.len()).unwrap();
+        mm.copy_from_slice(&buf);
+        GramDict::open(mm.make_read_only().unwrap()).unwrap()
+    }
+
+    /// Отсортированные ключи с непрерывными постингами (как пишет writer).
+    fn sample(keys: &[String]) -> Vec<(&str, u64, u64)> {
+        let mut off = 0;
+        keys.iter()
+            .enumerate()
+            .map(|(i, k)| {
+                let len = 5 + i as u64 % 7;
+                off += len;
+                (k.as_str(), off - len, len)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cyrillic_trigrams_do_not_collide() {
+        // "кот"/"кош"/"коф" совпадают по первым 3 байтам
+        let mut keys: Vec<String> = ["кот", "кош", "коф", "abc"].map(String::from).to_vec();
+        keys.sort();
+        let entries = sample(&keys);
+        let d = build(&entries);
+        assert_eq!(d.version(), GRAMS_IDX_V3);
+        for (k, off, len) in &entries {
+            assert_eq!(d.lookup(k).unwrap(), Some((*off, *len)));
+        }
+        assert_eq!(d.lookup("кок").unwrap(), None);
+    }
+
+    #[test]
+    fn lookup_prefix_and_range_across_blocks() {
+        let mut keys: Vec<String> = Vec::new();
+        for a in ['a', 'b', 'ж'] {
+            for b in 'a'..='z' {
+                keys.push(format!("{a}{b}x"));
+            }
+        }
+        keys.sort();
+        let entries = sample(&keys);
+        let d = build(&entries);
+        assert_eq!(d.len(), keys.len());
+
+        for (k, off, len) in &entries {
+            assert_eq!(d.lookup(k).unwrap(), Some((*off, *len)), "{k}");
+        }
+        assert_eq!(d.lookup("aax0").unwrap(), None);
+        assert_eq!(d.lookup("zzz").unwrap(), None);
+        assert_eq!(d.lookup("").unwrap(), None);
+
+        let all: Vec<GramEntry> = d.iter().map(|e| e.unwrap()).collect();
+        assert_eq!(all.len(), keys.len());
+        assert_eq!(all[17].key_str(), keys[17]);
+
+        let pref: Vec<String> = d
+            .prefix("ж".as_bytes())
+            .unwrap()
+            .map(|e| e.unwrap().key_str().to_string())
+            .collect();
+        assert_eq!(pref.len(), 26);
+        assert!(pref.iter().all(|k| k.starts_with('ж')));
+
+        let rng: Vec<String> = d
+            .range(b"bm", Some(b"bp"))
+            .unwrap()
+            .map(|e| e.unwrap().key_str().to_string())
+            .collect();
+        assert_eq!(rng, vec!["bmx", "bnx", "box"]);
+    }
+
+    #[test]
+    fn front_coding_is_smaller_than_fixed_records() {
+        let mut keys: Vec<String> = Vec::new();
+        for a in 'а'..='я' {
+            for b in 'а'..='я' {
+                keys.push(format!("{a}{b}т"));
+            }
+        }
+        keys.sort();
+        let entries = sample(&keys);
+        let mut buf = Vec::new();
+        write_grams_idx(&mut buf, &entries).unwrap();
+        let fixed =
+            HEADER_LEN + keys.len() * V2_RECORD_LEN + keys.iter().map(|k| k.len()).sum::<usize>();
+        assert!(buf.len() * 2 < fixed, "{} vs {}", buf.len(), fixed);
+    }
+
+    #[test]
+    fn reads_legacy_v2_layout() {
+        let keys = ["abc", "кот", "кош"];
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&GRAMS_IDX_MAGIC.to_le_bytes());
+        buf.extend_from_slice(&GRAMS_IDX_V2.to_le_bytes());
+        buf.extend_from_slice(&0u16.to_le_bytes());
+        buf.extend_from_slice(&(keys.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&(V2_RECORD_LEN as u32).to_le_bytes());
+        let mut key_off = 0u32;
+        for (i, k) in keys.iter().enumerate() {
+            buf.extend_from_slice(&key_off.to_le_bytes());
+            buf.extend_from_slice(&(k.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&(i as u64 * 10).to_le_bytes());
+            buf.extend_from_slice(&3u64.to_le_bytes());
+            key_off += k.len() as u32;
+        }
+        for k in keys {
+            buf.extend_from_slice(k.as_bytes());
+        }
+        let d = open_buf(buf);
+        assert_eq!(d.version(), GRAMS_IDX_V2);
+        assert_eq!(d.lookup("кош").unwrap(), Some((20, 3)));
+        assert_eq!(d.lookup("коф").unwrap(), None);
+        assert_eq!(d.prefix("ко".as_bytes()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn reads_legacy_v1_layout() {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&GRAMS_IDX_MAGIC.to_le_bytes());
+        buf.extend_from_slice(&GRAMS_IDX_V1.to_le_bytes());
+        buf.extend_from_slice(&0u16.to_le_bytes());
+        buf.extend_from_slice(&2u32.to_le_bytes());
+        buf.extend_from_slice(&(V1_RECORD_LEN as u32).to_le_bytes());
+        for (key, off, len) in [(b"abc", 0u64, 7u64), (b"xyz", 7, 9)] {
+            buf.extend_from_slice(key);
+            buf.extend_from_slice(&off.to_le_bytes());
+            buf.extend_from_slice(&len.to_le_bytes());
+            buf.extend_from_slice(&[0u8; 5]);
+        }
+        let d = open_buf(buf);
+        assert_eq!(d.version(), GRAMS_IDX_V1);
+        assert_eq!(d.lookup("xyz").unwrap(), Some((7, 9)));
+        assert_eq!(d.lookup("abd").unwrap(), None);
+    }
+}
@@ -0,0 +1,220 @@
+// crates/grepzilla_segment/src/v2/inspect.rs
+//! Статистика сегмента V2 для разбора медленных запросов: заголовок
+//! `meta.bin`, самые длинные постинги, мощность полей и размеры файлов
+//! и документов. Всё считается по заголовкам и смещениям, без декодирования
+//! постингов и разбора документов.
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::Path;
+
+use crate::SegmentReader;
+use crate::v2::reader::BinSegmentReader;
+use crate::v2::types::MetaHeader;
+
+/// Верхние границы корзин гистограммы размеров документов (байт);
+/// последняя корзина — всё, что больше.
+const DOC_SIZE_BUCKETS: [u64; 6] = [64, 256, 1 << 10, 4 << 10, 16 << 10, 64 << 10];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentInspection {
+    pub segment: String,
+    pub meta: MetaHeader,
+    /// помечено в `deletes.roaring`
+    pub deleted: u64,
+    /// файлы каталога сегмента по имени
+    pub files: Vec<FileSize>,
+    pub total_bytes: u64,
+    pub postings: PostingsStats,
+    /// самые длинные постинги `grams.dat` (по числу DocId)
+    pub top_grams: Vec<GramStat>,
+    pub fields: Vec<FieldStat>,
+    pub docs: DocSizeStats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileSize {
+    pub name: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PostingsStats {
+    pub grams: u64,
+    /// сумма длин постингов (DocId)
+    pub total_docs: u64,
+    pub total_bytes: u64,
+    /// граммы, встречающиеся в каждом документе — бесполезны для префильтра
+    pub in_all_docs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GramStat {
+    pub docs: u32,
+    pub bytes: u64,
+    pub gram: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldStat {
+    pub name: String,
+    /// документов с полем (по маске `fields.dat`)
+    pub docs: u64,
+    /// есть ли у поля собственный индекс грамм (`field_grams.*`)
+    pub field_grams: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DocSizeStats {
+    pub count: u64,
+    pub total_bytes: u64,
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub histogram: Vec<SizeBucket>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SizeBucket {
+    /// верхняя граница корзины; `None` — без границы
+    pub le: Option<u64>,
+    pub count: u64,
+}
+
+/// Собрать статистику сегмента V2 `dir`; `top_n` — сколько грамм показать.
+pub fn inspect_segment(dir: &str, top_n: usize) -> Result<SegmentInspection> {
+    if !Path::new(dir).join("meta.bin").exists() {
+        bail!("not a V2 segment (no meta.bin): {dir}");
+    }
+    let reader = BinSegmentReader::open_segment(dir)?;
+    let meta = reader.meta_header()?;
+
+    let mut files = Vec::new();
+    for e in std::fs::read_dir(Path::new(dir))? {
+        let e = e?;
+        let md = e.metadata()?;
+        if md.is_file() {
+            files.push(FileSize {
+                name: e.file_name().to_string_lossy().into_owned(),
+                bytes: md.len(),
+            });
+        }
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    let total_bytes = files.iter().map(|f| f.bytes).sum();
+
+    // top-N через min-heap размера N; при равенстве раньше идёт меньший ключ
+    let mut postings = PostingsStats::default();
+    let mut heap: BinaryHeap<Reverse<(u32, u64, Reverse<String>)>> =
+        BinaryHeap::with_capacity(top_n + 1);
+    for e in reader.gram_sizes() {
+        let (gram, docs, bytes) = e?;
+        postings.grams += 1;
+        postings.total_docs += docs as u64;
+        postings.total_bytes += bytes;
+        if docs as u64 == meta.doc_count && docs > 0 {
+            postings.in_all_docs += 1;
+        }
+        if top_n > 0 {
+            heap.push(Reverse((docs, bytes, Reverse(gram))));
+            if heap.len() > top_n {
+                heap.pop();
+            }
+        }
+    }
+    let top_grams = heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((docs, bytes, Reverse(gram)))| GramStat { docs, bytes, gram })
+        .collect();
+
+    let mut fields = Vec::new();
+    for name in reader.field_names() {
+        let docs = reader.field_docs(name)?.map_or(0, |m| m.cardinality());
+        fields.push(FieldStat {
+            name: name.clone(),
+            docs,
+            field_grams: reader.has_field_grams(name),
+        });
+    }
+    fields.sort_by(|a, b| b.docs.cmp(&a.docs).then_with(|| a.name.cmp(&b.name)));
+
+    let sizes: Vec<u64> = (0..reader.doc_count())
+        .filter_map(|d| reader.doc_len(d))
+        .collect();
+
+    Ok(SegmentInspection {
+        segment: dir.to_string(),
+        meta,
+        deleted: reader.live_docs().deleted().cardinality(),
+        files,
+        total_bytes,
+        postings,
+        top_grams,
+        fields,
+        docs: doc_size_stats(sizes),
+    })
+}
+
+fn doc_size_stats(mut sizes: Vec<u64>) -> DocSizeStats {
+    let mut histogram: Vec<SizeBucket> = DOC_SIZE_BUCKETS
+        .iter()
+        .map(|&le| SizeBucket {
+            le: Some(le),
+            count: 0,
+        })
+        .chain([SizeBucket { le: None, count: 0 }])
+        .collect();
+    for &s in &sizes {
+        let i = DOC_SIZE_BUCKETS
+            .iter()
+            .position(|&le| s <= le)
+            .unwrap_or(DOC_SIZE_BUCKETS.len());
+        histogram[i].count += 1;
+    }
+    if sizes.is_empty() {
+        return DocSizeStats {
+            histogram,
+            ..Default::default()
+        };
+    }
+    sizes.sort_unstable();
+    let n = sizes.len();
+    let total: u64 = sizes.iter().sum();
+    // ближайший ранг: наименьшее значение, не меньше доли q выборки
+    let pct = |q: f64| sizes[((q * n as f64).ceil() as usize).clamp(1, n) - 1];
+    DocSizeStats {
+        count: n as u64,
+        total_bytes: total,
+        min: sizes[0],
+        max: sizes[n - 1],
+        mean: total as f64 / n as f64,
+        p50: pct(0.50),
+        p90: pct(0.90),
+        p99: pct(0.99),
+        histogram,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doc_sizes_percentiles_and_buckets() {
+        let st = doc_size_stats((1..=100).map(|i| i * 10).collect());
+        assert_eq!((st.count, st.min, st.max), (100, 10, 1000));
+        assert_eq!((st.p50, st.p90, st.p99), (500, 900, 990));
+        assert_eq!(st.mean, 505.0);
+        let counts: Vec<u64> = st.histogram.iter().map(|b| b.count).collect();
+        assert_eq!(counts, [6, 19, 75, 0, 0, 0, 0]);
+
+        let empty = doc_size_stats(Vec::new());
+        assert_eq!(empty.count, 0);
+        assert_eq!(empty.histogram.len(), DOC_SIZE_BUCKETS.len() + 1);
+    }
+}
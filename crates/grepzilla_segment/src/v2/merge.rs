@@ -0,0 +1,247 @@
+// crates/grepzilla_segment/src/v2/merge.rs
+//! Слияние нескольких сегментов V2 в один.
+//!
+//! Документы входов склеиваются по порядку (DocId переназначаются подряд),
+//! постинги `grams.dat`/`field_grams.dat` переносятся с перенумерацией без
+//! повторной токенизации, маски полей строятся по перенесённым документам.
+//! Входы перечисляются от старых к новым: при совпадении `_id` остаётся
+//! последняя версия документа. Документы из `deletes.roaring` входов
+//! в результат не попадают, поэтому у результата удалений нет.
+//! Колонки doc-values переносятся по именам полей, индекс коротких грамм —
+//! если он есть у всех входов.
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::SegmentReader;
+use crate::doc_values::DocValueField;
+use crate::v2::gram_dict::FIELD_GRAM_SEP;
+use crate::v2::postings::MaskedDoc;
+use crate::v2::reader::BinSegmentReader;
+use crate::v2::spill::{GramStream, MergedGrams};
+use crate::v2::writer::SegmentSink;
+
+/// Параметры слияния.
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    /// Внешние `_id` удалённых документов — в результат не попадают.
+    pub tombstones: HashSet<String>,
+}
+
+/// Итог слияния.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MergeStats {
+    pub segments: usize,
+    pub input_docs: u64,
+    pub output_docs: u64,
+    /// вытеснены более новой версией с тем же `_id`
+    pub dropped_superseded: u64,
+    /// удалены по `tombstones`
+    pub dropped_tombstoned: u64,
+    /// помечены в `deletes.roaring` входа
+    pub dropped_deleted: u64,
+}
+
+const DROPPED: u32 = u32::MAX;
+
+/// Слить сегменты `inputs` (от старых к новым) в новый сегмент `out_dir`.
+pub fn merge_segments(inputs: &[&str], out_dir: &str, opts: &MergeOptions) -> Result<MergeStats> {
+    if inputs.is_empty() {
+        bail!("nothing to merge");
+    }
+    let out = Path::new(out_dir);
+    for inp in inputs {
+        if out.exists() && same_dir(Path::new(inp), out)? {
+            bail!("merge output must differ from inputs: {inp}");
+        }
+    }
+    let readers = inputs
+        .iter()
+        .map(|p| BinSegmentReader::open_segment(p))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut stats = MergeStats {
+        segments: readers.len(),
+        ..Default::default()
+    };
+
+    // 1) последняя версия каждого _id
+    let mut latest: HashMap<String, (usize, u32)> = HashMap::new();
+    for (si, r) in readers.iter().enumerate() {
+        for doc in 0..r.doc_count() {
+            latest.insert(r.ext_id(doc)?, (si, doc));
+        }
+        stats.input_docs += r.doc_count() as u64;
+    }
+
+    // 2) перенумерация и перенос документов (вместе со значениями колонок)
+    // текст уже проанализирован: смешивать схемы нельзя
+    let analyzers = readers
+        .first()
+        .map(|r| r.analyzers().clone())
+        .unwrap_or_default();
+    if readers.iter().any(|r| *r.analyzers() != analyzers) {
+        bail!("cannot merge segments built with different analyzers");
+    }
+    let mut sink = SegmentSink::create(out)?;
+    sink.set_analyzers(analyzers);
+    // исходный текст есть у результата, только если он есть у всех входов
+    sink.set_raw_text(readers.iter().all(BinSegmentReader::has_raw_text));
+    let dv_fields = doc_value_fields(&readers)?;
+    sink.set_columns(&dv_fields)?;
+    let mut dv_cols = Vec::with_capacity(readers.len());
+    for r in &readers {
+        let mut cols = Vec::with_capacity(dv_fields.len());
+        for f in &dv_fields {
+            cols.push(r.doc_values(&f.name)?);
+        }
+        dv_cols.push(cols);
+    }
+    let mut remaps: Vec<Vec<u32>> = Vec::with_capacity(readers.len());
+    for (si, r) in readers.iter().enumerate() {
+        let mut remap = vec![DROPPED; r.doc_count() as usize];
+        for doc in 0..r.doc_count() {
+            let stored = r.read_doc(doc)?;
+            if latest.get(&stored.ext_id) != Some(&(si, doc)) {
+                stats.dropped_superseded += 1;
+                continue;
+            }
+            if r.live_docs().is_deleted(doc) {
+                stats.dropped_deleted += 1;
+                continue;
+            }
+            if opts.tombstones.contains(&stored.ext_id) {
+                stats.dropped_tombstoned += 1;
+                continue;
+            }
+            let mut to_fids = |m: std::collections::BTreeMap<String, String>| {
+                m.into_iter()
+                    .map(|(name, val)| (sink.field_id(&name), val))
+                    .collect::<Vec<_>>()
+            };
+            let fields = to_fids(stored.fields);
+            let raw = to_fids(stored.raw);
+            for (col, c) in dv_cols[si].iter().enumerate() {
+                if let Some(key) = c.as_ref().and_then(|c| c.get(doc)) {
+                    sink.put_value(col, key)?;
+                }
+            }
+            remap[doc as usize] = sink.push_doc(&stored.ext_id, fields, raw)?;
+        }
+        remaps.push(remap);
+    }
+    stats.output_docs = sink.doc_count as u64;
+
+    // 3) постинги: k-way merge словарей с перенумерацией
+    let grams: Vec<GramStream<'_>> = readers
+        .iter()
+        .zip(&remaps)
+        .map(|(r, remap)| remapped(r.gram_postings(), remap))
+        .collect();
+    let grams = MergedGrams::new(grams)?.filter(|e| !matches!(e, Ok((_, l)) if l.is_empty()));
+
+    // Пофилдовый индекс поля переносим, только если он есть у каждого входа,
+    // где это поле встречается: иначе часть документов из него выпадет.
+    let scoped = field_grams_fields(&readers);
+    let field_grams =
+        if scoped.is_empty() {
+            None
+        } else {
+            let sources: Vec<GramStream<'_>> = readers
+                .iter()
+                .zip(&remaps)
+                .filter_map(|(r, remap)| r.field_gram_postings().map(|it| remapped(it, remap)))
+                .map(|it| -> GramStream<'_> {
+                    let scoped = &scoped;
+                    Box::new(it.filter(move |e| {
+                        match e {
+                            Ok((key, _)) => key
+                                .split_once(FIELD_GRAM_SEP)
+                                .is_some_and(|(f, _)| scoped.contains(f)),
+                            Err(_) => true,
+                        }
+                    }))
+                })
+                .collect();
+            // пустые списки оставляем только у маркеров полей
+            Some(MergedGrams::new(sources)?.filter(
+                |e| !matches!(e, Ok((k, l)) if l.is_empty() && !k.ends_with(FIELD_GRAM_SEP)),
+            ))
+        };
+
+    // индекс коротких грамм — только если он есть у всех входов
+    let short_grams = if readers.iter().all(BinSegmentReader::has_short_grams) {
+        let sources: Vec<GramStream<'_>> = readers
+            .iter()
+            .zip(&remaps)
+            .filter_map(|(r, remap)| r.short_gram_postings().map(|it| remapped(it, remap)))
+            .collect();
+        Some(MergedGrams::new(sources)?.filter(|e| !matches!(e, Ok((_, l)) if l.is_empty())))
+    } else {
+        None
+    };
+
+    sink.finish(grams, field_grams, short_grams)?;
+    Ok(stats)
+}
+
+/// Постинги сегмента с DocId, переведёнными в нумерацию результата.
+fn remapped<'a>(
+    it: impl Iterator<Item = Result<(String, Vec<MaskedDoc>)>> + 'a,
+    remap: &'a [u32],
+) -> GramStream<'a> {
+    Box::new(it.map(move |e| {
+        let (key, list) = e?;
+        let mut out = Vec::with_capacity(list.len());
+        for m in list {
+            match remap.get(m.doc as usize) {
+                Some(&DROPPED) => {}
+                Some(&doc) => out.push(MaskedDoc { doc, ..m }),
+                None => bail!("posting doc_id {} out of range", m.doc),
+            }
+        }
+        Ok((key, out))
+    }))
+}
+
+/// Объединение колонок doc-values входов (тип поля должен совпадать).
+fn doc_value_fields(readers: &[BinSegmentReader]) -> Result<Vec<DocValueField>> {
+    let mut out: Vec<DocValueField> = Vec::new();
+    for r in readers {
+        for f in r.doc_value_fields() {
+            match out.iter().find(|o| o.name == f.name) {
+                Some(o) if o.kind != f.kind => bail!(
+                    "doc-value field `{}` has different kinds: {} vs {}",
+                    f.name,
+                    o.kind,
+                    f.kind
+                ),
+                Some(_) => {}
+                None => out.push(f.clone()),
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Поля, пофилдовый индекс которых есть у всех входов с этим полем.
+fn field_grams_fields(readers: &[BinSegmentReader]) -> HashSet<String> {
+    let mut ok: HashSet<String> = HashSet::new();
+    let mut broken: HashSet<String> = HashSet::new();
+    for r in readers {
+        for f in r.field_names() {
+            if r.has_field_grams(f) {
+                ok.insert(f.clone());
+            } else {
+                broken.insert(f.clone());
+            }
+        }
+    }
+    ok.retain(|f| !broken.contains(f));
+    ok
+}
+
+fn same_dir(a: &Path, b: &Path) -> Result<bool> {
+    Ok(a.canonicalize()? == b.canonicalize()?)
+}
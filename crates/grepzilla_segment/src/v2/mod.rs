@@ -1,8 +1,16 @@
 pub mod codec;
+pub mod columns;
+pub mod convert;
 pub mod crc;
 pub mod docs_reader;
 pub mod docs_writer;
+pub mod gram_dict;
+pub mod inspect;
+pub mod merge;
+pub mod plan;
+pub mod postings;
 pub mod reader;
+pub mod spill;
 pub mod types;
 pub mod varint;
 pub mod writer;
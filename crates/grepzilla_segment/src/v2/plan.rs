@@ -0,0 +1,203 @@
+// crates/grepzilla_segment/src/v2/plan.rs
+//! План AND-префильтра: какие граммы декодировать и в каком порядке.
+//!
+//! Длина постинга известна из заголовка записи `grams.dat` без декодирования,
+//! поэтому граммы пересекаются от редких к частым: после первой диапазон
+//! DocId сужается, и следующие читают только нужные блоки. Граммы одного
+//! литерала перекрываются на два символа; грамма, перекрывающаяся с уже
+//! выбранной более редкой, почти ничего не добавляет к отбору и пропускается
+//! (точность восстанавливают проверка смежности по маскам и verify).
+//! Пересечение останавливается, когда кандидатов мало, а следующая грамма
+//! намного длиннее: проверить кандидатов дешевле, чем декодировать её.
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Кандидатов достаточно мало, чтобы не декодировать длинные постинги.
+pub const STOP_CANDIDATES: u64 = 64;
+/// ...если следующая грамма хотя бы во столько раз длиннее числа кандидатов.
+pub const STOP_COST_RATIO: u64 = 8;
+
+/// Шаг плана: грамма и длина её постинга.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedGram {
+    pub gram: String,
+    pub docs: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GramPlan {
+    /// граммы в порядке пересечения (от редких к частым)
+    pub steps: Vec<PlannedGram>,
+    /// перекрываются с выбранными более редкими — не декодируются
+    pub skipped: Vec<String>,
+    /// граммы нет в индексе — AND заведомо пуст
+    pub missing: Option<String>,
+}
+
+impl GramPlan {
+    /// Остановить пересечение перед шагом `next` при `candidates` кандидатах.
+    pub fn should_stop(&self, candidates: u64, next: usize) -> bool {
+        if candidates == 0 {
+            return true;
+        }
+        candidates <= STOP_CANDIDATES
+            && self
+                .steps
+                .get(next)
+                .is_some_and(|s| s.docs as u64 >= candidates.saturating_mul(STOP_COST_RATIO))
+    }
+}
+
+/// Построить план для AND по `grams` (в порядке паттерна, как их выдаёт
+/// [`crate::gram::required_grams_from_wildcard`]). `doc_freq` — длина
+/// постинга граммы или `None`, если её нет. Граммы короче 3 байт не участвуют.
+///
+/// Перекрытие определяется по соседству в списке: следующая грамма
+/// продолжает литерал, если начинается с двух последних символов предыдущей.
+pub fn plan_and(
+    grams: &[String],
+    mut doc_freq: impl FnMut(&str) -> Result<Option<u32>>,
+) -> Result<GramPlan> {
+    // (грамма, позиция первого символа в «склейке» литералов)
+    let mut spans: Vec<(&str, i64)> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut prev: Option<(&str, i64)> = None;
+    for g in grams.iter().filter(|g| g.len() >= 3) {
+        let pos = match prev {
+            Some((p, at)) if continues(p, g) => at + 1,
+            // новый литерал — заведомо не перекрывается с предыдущим
+            Some((_, at)) => at + 4,
+            None => 0,
+        };
+        prev = Some((g, pos));
+        if seen.insert(g.as_str()) {
+            spans.push((g, pos));
+        }
+    }
+
+    let mut costed = Vec::with_capacity(spans.len());
+    for (g, pos) in spans {
+        match doc_freq(g)? {
+            Some(docs) => costed.push((docs, pos, g)),
+            None => {
+                return Ok(GramPlan {
+                    missing: Some(g.to_string()),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    costed.sort_by_key(|&(docs, pos, _)| (docs, pos));
+
+    let mut plan = GramPlan::default();
+    let mut taken: Vec<i64> = Vec::new();
+    for (docs, pos, g) in costed {
+        if taken.iter().any(|&t| (t - pos).abs() <= 2) {
+            plan.skipped.push(g.to_string());
+            continue;
+        }
+        taken.push(pos);
+        plan.steps.push(PlannedGram {
+            gram: g.to_string(),
+            docs,
+        });
+    }
+    Ok(plan)
+}
+
+/// `b` начинается с двух последних символов `a`.
+fn continues(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let mut b = b.chars();
+    a.len() == 3 && b.next() == Some(a[1]) && b.next() == Some(a[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gram::required_grams_from_wildcard;
+    use std::collections::HashMap;
+
+    fn plan(q: &str, freq: &HashMap<&str, u32>, default: u32) -> GramPlan {
+        let grams = required_grams_from_wildcard(q).unwrap();
+        plan_and(&grams, |g| {
+            Ok(match freq.get(g) {
+                Some(0) => None,
+                Some(&n) => Some(n),
+                None => Some(default),
+            })
+        })
+        .unwrap()
+    }
+
+    fn steps(p: &GramPlan) -> Vec<&str> {
+        p.steps.iter().map(|s| s.gram.as_str()).collect()
+    }
+
+    #[test]
+    fn rarest_first_without_overlaps() {
+        let freq = HashMap::from([("nal", 3), ("ati", 50)]);
+        let p = plan("*internationalization*", &freq, 1000);
+        // 18 грамм, из них 15 различных («ati», «tio», «ion» — дважды)
+        assert_eq!(p.steps.len() + p.skipped.len(), 15);
+        assert_eq!(
+            p.steps[0],
+            PlannedGram {
+                gram: "nal".into(),
+                docs: 3
+            }
+        );
+        assert_eq!(p.steps[1].gram, "ati");
+        // остальные — по позиции, без перекрытий с выбранными
+        assert_eq!(steps(&p), ["nal", "ati", "int", "ern", "iza"]);
+        assert!(p.missing.is_none());
+    }
+
+    #[test]
+    fn literals_do_not_overlap_each_other() {
+        let p = plan("*abc*xyz*", &HashMap::new(), 10);
+        assert_eq!(steps(&p), ["abc", "xyz"]);
+        assert!(p.skipped.is_empty());
+        // сцепляющиеся литералы неотличимы от одного — лишь менее точный отбор
+        let p = plan("*abc*bcd*", &HashMap::new(), 10);
+        assert_eq!(steps(&p), ["abc"]);
+
+        let p = plan("*abcd*", &HashMap::from([("bcd", 2)]), 10);
+        assert_eq!(steps(&p), ["bcd"]);
+        assert_eq!(p.skipped, ["abc"]);
+    }
+
+    #[test]
+    fn missing_gram_short_circuits() {
+        let p = plan("*ошибка*", &HashMap::from([("шиб", 0)]), 10);
+        assert_eq!(p.missing.as_deref(), Some("шиб"));
+        assert!(p.steps.is_empty());
+    }
+
+    #[test]
+    fn stops_when_rest_is_much_longer() {
+        let p = GramPlan {
+            steps: vec![
+                PlannedGram {
+                    gram: "aaa".into(),
+                    docs: 10,
+                },
+                PlannedGram {
+                    gram: "bbb".into(),
+                    docs: 79,
+                },
+                PlannedGram {
+                    gram: "ccc".into(),
+                    docs: 10_000,
+                },
+            ],
+            ..Default::default()
+        };
+        assert!(!p.should_stop(10, 1));
+        assert!(p.should_stop(10, 2));
+        assert!(!p.should_stop(STOP_CANDIDATES + 1, 2));
+        assert!(p.should_stop(0, 1));
+        assert!(!p.should_stop(10, 3));
+    }
+}
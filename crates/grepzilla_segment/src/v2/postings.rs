@@ -0,0 +1,455 @@
+// crates/grepzilla_segment/src/v2/postings.rs
+//! Кодек записей `grams.dat` (RFC-0002 §6).
+//!
+//! Каждая запись начинается с `[u8 kind][u32 doc_count]`:
+//! - kind=1 (inline): `varint first` + `doc_count-1` varint-дельт;
+//! - kind=2 (блоки): `[u32 base][u16 n][u8 codec][u32 payload_len][payload]`,
+//!   где payload — varint-дельты для `n-1` элементов блока.
+//!
+//! Блочный вид позволяет читателю пропускать блоки вне интересующего
+//! диапазона DocId, не декодируя их payload.
+//!
+//! Старший бит kind ([`FLAG_MASKS`]) означает, что у каждого DocId есть пара
+//! позиционных масок (`loc`, `next`, RFC-0002 §6.3): для inline они идут после
+//! всех дельт, для блоков — после payload своего блока (`n` байт `loc`, затем
+//! `n` байт `next`).
+use anyhow::{Result, bail};
+use croaring::Bitmap;
+use std::ops::RangeInclusive;
+
+use crate::v2::codec::{get_varint, put_varint};
+
+pub const KIND_INLINE: u8 = 1;
+pub const KIND_BLOCKS: u8 = 2;
+pub const FLAG_MASKS: u8 = 0x80;
+pub const CODEC_DELTA_VARINT: u8 = 1;
+
+/// Inline-запись допустима при `doc_count ≤ 8` и payload ≤ 64 байт (RFC-0002 §10).
+pub const INLINE_MAX_DOCS: usize = 8;
+pub const INLINE_MAX_PAYLOAD: usize = 64;
+/// Рекомендуемый размер блока (RFC-0002 §6.2).
+pub const BLOCK_DOCS: usize = 8192;
+
+const HEAD_LEN: usize = 1 + 4;
+const BLOCK_HEAD_LEN: usize = 4 + 2 + 1 + 4;
+
+/// DocId с позиционными масками граммы в документе.
+///
+/// - `loc`: бит `pos % 8` для каждой позиции (в символах) вхождения граммы;
+/// - `next`: бит [`next_char_bit`] символа, следующего за вхождением.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaskedDoc {
+    pub doc: u32,
+    pub loc: u8,
+    pub next: u8,
+}
+
+/// Бит позиции в маске `loc`.
+#[inline]
+pub fn loc_bit(pos: usize) -> u8 {
+    1 << (pos % 8)
+}
+
+/// Бит символа в маске `next`.
+#[inline]
+pub fn next_char_bit(ch: char) -> u8 {
+    1 << (ch as u32 % 8)
+}
+
+/// Закодировать отсортированный список DocId без дублей.
+/// Вид записи (inline/блоки) выбирается по порогам RFC.
+pub fn encode_postings(ids: &[u32], out: &mut Vec<u8>) {
+    encode_postings_with_block(ids, BLOCK_DOCS, out)
+}
+
+/// То же, что [`encode_postings`], но с явным размером блока (для тестов/бенчей).
+pub fn encode_postings_with_block(ids: &[u32], block_docs: usize, out: &mut Vec<u8>) {
+    encode(ids, None, block_docs, out)
+}
+
+/// Закодировать постинги вместе с масками (kind с [`FLAG_MASKS`]).
+pub fn encode_masked_postings(docs: &[MaskedDoc], out: &mut Vec<u8>) {
+    encode_masked_postings_with_block(docs, BLOCK_DOCS, out)
+}
+
+/// То же, что [`encode_masked_postings`], но с явным размером блока.
+pub fn encode_masked_postings_with_block(docs: &[MaskedDoc], block_docs: usize, out: &mut Vec<u8>) {
+    let ids: Vec<u32> = docs.iter().map(|d| d.doc).collect();
+    encode(&ids, Some(docs), block_docs, out)
+}
+
+fn encode(ids: &[u32], masks: Option<&[MaskedDoc]>, block_docs: usize, out: &mut Vec<u8>) {
+    let block_docs = block_docs.clamp(1, u16::MAX as usize);
+    let flag = if masks.is_some() { FLAG_MASKS } else { 0 };
+    let put_masks = |from: usize, to: usize, out: &mut Vec<u8>| {
+        if let Some(m) = masks {
+            out.extend(m[from..to].iter().map(|d| d.loc));
+            out.extend(m[from..to].iter().map(|d| d.next));
+        }
+    };
+
+    if ids.len() <= INLINE_MAX_DOCS {
+        let mut payload = Vec::new();
+        put_delta_varints(ids, &mut payload);
+        if payload.len() <= INLINE_MAX_PAYLOAD {
+            out.push(KIND_INLINE | flag);
+            out.extend_from_slice(&(ids.len() as u32).to_le_bytes());
+            out.extend_from_slice(&payload);
+            put_masks(0, ids.len(), out);
+            return;
+        }
+    }
+
+    out.push(KIND_BLOCKS | flag);
+    out.extend_from_slice(&(ids.len() as u32).to_le_bytes());
+    let mut payload = Vec::new();
+    for (ci, chunk) in ids.chunks(block_docs).enumerate() {
+        payload.clear();
+        let mut prev = chunk[0];
+        for &d in &chunk[1..] {
+            put_varint((d - prev) as u64, &mut payload);
+            prev = d;
+        }
+        out.extend_from_slice(&chunk[0].to_le_bytes());
+        out.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+        out.push(CODEC_DELTA_VARINT);
+        out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        out.extend_from_slice(&payload);
+        let from = ci * block_docs;
+        put_masks(from, from + chunk.len(), out);
+    }
+}
+
+/// Декодировать запись целиком.
+pub fn decode_postings(body: &[u8]) -> Result<Bitmap> {
+    decode_postings_in(body, 0..=u32::MAX)
+}
+
+/// Декодировать только DocId из `range`.
+/// Для kind=2 блоки, целиком лежащие вне диапазона, пропускаются по заголовкам.
+pub fn decode_postings_in(body: &[u8], range: RangeInclusive<u32>) -> Result<Bitmap> {
+    let mut ids: Vec<u32> = Vec::new();
+    decode(body, range, &mut |_, d| ids.push(d))?;
+    let mut bm = Bitmap::new();
+    bm.add_many(&ids);
+    Ok(bm)
+}
+
+/// Есть ли у записи позиционные маски.
+pub fn has_masks(body: &[u8]) -> bool {
+    body.first().is_some_and(|k| k & FLAG_MASKS != 0)
+}
+
+/// Декодировать DocId из `range` вместе с масками (по возрастанию DocId).
+/// Для записи без масок возвращает `None`.
+pub fn decode_masked_postings_in(
+    body: &[u8],
+    range: RangeInclusive<u32>,
+) -> Result<Option<Vec<MaskedDoc>>> {
+    if !has_masks(body) {
+        return Ok(None);
+    }
+    let mut out = Vec::new();
+    decode(body, range, &mut |m, doc| {
+        let (loc, next) = m.unwrap_or_default();
+        out.push(MaskedDoc { doc, loc, next })
+    })?;
+    Ok(Some(out))
+}
+
+/// Все DocId записи в порядке хранения — без пропуска блоков и без
+/// предположений о сортировке (для проверки целостности). Заодно сверяет
+/// `doc_count` заголовка и отсутствие лишних байт.
+pub fn decode_postings_raw(body: &[u8]) -> Result<Vec<u32>> {
+    if body.len() < HEAD_LEN {
+        bail!("postings too small");
+    }
+    let masked = body[0] & FLAG_MASKS != 0;
+    let doc_cnt = u32::from_le_bytes(body[1..5].try_into().unwrap()) as usize;
+    let masks_len = |n: usize| if masked { 2 * n } else { 0 };
+    let mut ids = Vec::with_capacity(doc_cnt.min(body.len()));
+    match body[0] & !FLAG_MASKS {
+        KIND_INLINE => {
+            let rest = decode_run(&body[HEAD_LEN..], None, doc_cnt, u32::MAX, &mut ids)?;
+            if rest.len() != masks_len(doc_cnt) {
+                bail!("postings inline length mismatch");
+            }
+        }
+        KIND_BLOCKS => {
+            let mut p = HEAD_LEN;
+            while p < body.len() {
+                let blk = read_block_head(body, p)?;
+                if blk.n == 0 {
+                    bail!("postings empty block");
+                }
+                if blk.codec != CODEC_DELTA_VARINT {
+                    bail!("unknown postings block codec {}", blk.codec);
+                }
+                let payload_end = p + BLOCK_HEAD_LEN + blk.payload_len;
+                let rest = decode_run(
+                    &body[p + BLOCK_HEAD_LEN..payload_end],
+                    Some(blk.base),
+                    blk.n,
+                    u32::MAX,
+                    &mut ids,
+                )?;
+                if !rest.is_empty() {
+                    bail!("postings block payload length mismatch");
+                }
+                p = payload_end + masks_len(blk.n);
+                if p > body.len() {
+                    bail!("postings masks OOB");
+                }
+            }
+        }
+        k => bail!("unknown postings kind {}", k),
+    }
+    if ids.len() != doc_cnt {
+        bail!("postings doc_count {} != decoded {}", doc_cnt, ids.len());
+    }
+    Ok(ids)
+}
+
+/// Общий разбор записи: `emit(маски, doc)` для каждого DocId из `range`.
+fn decode(
+    body: &[u8],
+    range: RangeInclusive<u32>,
+    emit: &mut impl FnMut(Option<(u8, u8)>, u32),
+) -> Result<()> {
+    if range.is_empty() {
+        return Ok(());
+    }
+    let (lo, hi) = (*range.start(), *range.end());
+
+    if body.len() < HEAD_LEN {
+        bail!("postings too small");
+    }
+    let masked = body[0] & FLAG_MASKS != 0;
+    let kind = body[0] & !FLAG_MASKS;
+    let doc_cnt = u32::from_le_bytes(body[1..5].try_into().unwrap()) as usize;
+    let masks_len = |n: usize| if masked { 2 * n } else { 0 };
+
+    match kind {
+        KIND_INLINE => {
+            // inline короткий — читаем все дельты, чтобы найти начало масок
+            let mut ids = Vec::with_capacity(doc_cnt);
+            let rest = decode_run(&body[HEAD_LEN..], None, doc_cnt, u32::MAX, &mut ids)?;
+            let masks = if masked {
+                if rest.len() < masks_len(doc_cnt) {
+                    bail!("postings masks OOB");
+                }
+                Some(rest)
+            } else {
+                None
+            };
+            for (i, d) in ids.into_iter().enumerate() {
+                if (lo..=hi).contains(&d) {
+                    emit(masks.map(|m| (m[i], m[doc_cnt + i])), d);
+                }
+            }
+        }
+        KIND_BLOCKS => {
+            let mut p = HEAD_LEN;
+            let mut remaining = doc_cnt;
+            let mut ids = Vec::new();
+            while remaining > 0 {
+                let blk = read_block_head(body, p)?;
+                if blk.n == 0 || blk.n > remaining {
+                    bail!("postings block count mismatch");
+                }
+                if blk.codec != CODEC_DELTA_VARINT {
+                    bail!("unknown postings block codec {}", blk.codec);
+                }
+                if blk.base > hi {
+                    break;
+                }
+                let payload_end = p + BLOCK_HEAD_LEN + blk.payload_len;
+                let next = payload_end + masks_len(blk.n);
+                if next > body.len() {
+                    bail!("postings masks OOB");
+                }
+                remaining -= blk.n;
+
+                // Верхняя граница блока = base следующего блока - 1.
+                // Для последнего блока границу не знаем — декодируем.
+                let skip = remaining > 0 && read_block_head(body, next)?.base <= lo;
+                if !skip {
+                    ids.clear();
+                    let payload = &body[p + BLOCK_HEAD_LEN..payload_end];
+                    decode_run(payload, Some(blk.base), blk.n, hi, &mut ids)?;
+                    let masks = &body[payload_end..next];
+                    for (i, &d) in ids.iter().enumerate() {
+                        if d >= lo {
+                            emit(masked.then(|| (masks[i], masks[blk.n + i])), d);
+                        }
+                    }
+                }
+                p = next;
+            }
+            if remaining == 0 && p != body.len() {
+                bail!("postings trailing bytes");
+            }
+        }
+        k => bail!("unknown postings kind {}", k),
+    }
+    Ok(())
+}
+
+struct BlockHead {
+    base: u32,
+    n: usize,
+    codec: u8,
+    payload_len: usize,
+}
+
+fn read_block_head(body: &[u8], p: usize) -> Result<BlockHead> {
+    if p + BLOCK_HEAD_LEN > body.len() {
+        bail!("postings block header OOB");
+    }
+    let base = u32::from_le_bytes(body[p..p + 4].try_into().unwrap());
+    let n = u16::from_le_bytes(body[p + 4..p + 6].try_into().unwrap()) as usize;
+    let codec = body[p + 6];
+    let payload_len = u32::from_le_bytes(body[p + 7..p + 11].try_into().unwrap()) as usize;
+    if p + BLOCK_HEAD_LEN + payload_len > body.len() {
+        bail!("postings block payload OOB");
+    }
+    Ok(BlockHead {
+        base,
+        n,
+        codec,
+        payload_len,
+    })
+}
+
+/// Разобрать до `n` DocId (не больше `hi`): если `base` задан — это первый
+/// элемент, а в `bytes` лежат `n-1` дельт; иначе первый элемент тоже varint
+/// (inline-вид). Возвращает байты после последнего прочитанного значения.
+fn decode_run<'b>(
+    mut bytes: &'b [u8],
+    base: Option<u32>,
+    n: usize,
+    hi: u32,
+    out: &mut Vec<u32>,
+) -> Result<&'b [u8]> {
+    if n == 0 {
+        return Ok(bytes);
+    }
+    let mut cur = match base {
+        Some(b) => b,
+        None => {
+            let (v, rest) = get_varint(bytes).ok_or_else(|| anyhow::anyhow!("truncated varint"))?;
+            bytes = rest;
+            u32::try_from(v)?
+        }
+    };
+    for i in 0..n {
+        if i > 0 {
+            let (d, rest) = get_varint(bytes).ok_or_else(|| anyhow::anyhow!("truncated varint"))?;
+            bytes = rest;
+            cur = u32::try_from(d)
+                .ok()
+                .and_then(|d| cur.checked_add(d))
+                .ok_or_else(|| anyhow::anyhow!("postings delta overflow"))?;
+        }
+        if cur > hi {
+            break;
+        }
+        out.push(cur);
+    }
+    Ok(bytes)
+}
+
+fn put_delta_varints(ids: &[u32], out: &mut Vec<u8>) {
+    let mut prev = 0u32;
+    for (i, &d) in ids.iter().enumerate() {
+        let v = if i == 0 { d } else { d - prev };
+        put_varint(v as u64, out);
+        prev = d;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_list_is_inline() {
+        let ids = [1u32, 5, 9];
+        let mut buf = Vec::new();
+        encode_postings(&ids, &mut buf);
+        assert_eq!(buf[0], KIND_INLINE);
+        assert_eq!(decode_postings(&buf).unwrap().to_vec(), ids);
+    }
+
+    #[test]
+    fn more_than_inline_max_is_blocks() {
+        let ids: Vec<u32> = (0..=INLINE_MAX_DOCS as u32).collect();
+        let mut buf = Vec::new();
+        encode_postings(&ids, &mut buf);
+        assert_eq!(buf[0], KIND_BLOCKS);
+        assert_eq!(decode_postings(&buf).unwrap().to_vec(), ids);
+    }
+
+    #[test]
+    fn blocks_roundtrip_and_range() {
+        let ids: Vec<u32> = (0..1000).map(|i| i * 3).collect();
+        let mut buf = Vec::new();
+        encode_postings_with_block(&ids, 64, &mut buf);
+        assert_eq!(buf[0], KIND_BLOCKS);
+        assert_eq!(decode_postings(&buf).unwrap().to_vec(), ids);
+
+        let part = decode_postings_in(&buf, 1500..=1800).unwrap().to_vec();
+        let want: Vec<u32> = ids
+            .iter()
+            .copied()
+            .filter(|d| (1500..=1800).contains(d))
+            .collect();
+        assert_eq!(part, want);
+    }
+
+    #[test]
+    fn truncated_block_is_error() {
+        let ids: Vec<u32> = (0..100).collect();
+        let mut buf = Vec::new();
+        encode_postings_with_block(&ids, 16, &mut buf);
+        buf.truncate(buf.len() - 3);
+        assert!(decode_postings(&buf).is_err());
+    }
+
+    #[test]
+    fn masked_roundtrip_inline_and_blocks() {
+        for n in [3u32, 500] {
+            let docs: Vec<MaskedDoc> = (0..n)
+                .map(|i| MaskedDoc {
+                    doc: i * 2,
+                    loc: i as u8,
+                    next: !(i as u8),
+                })
+                .collect();
+            let mut buf = Vec::new();
+            encode_masked_postings_with_block(&docs, 64, &mut buf);
+            assert!(has_masks(&buf));
+
+            let ids: Vec<u32> = docs.iter().map(|d| d.doc).collect();
+            assert_eq!(decode_postings(&buf).unwrap().to_vec(), ids);
+
+            let all = decode_masked_postings_in(&buf, 0..=u32::MAX).unwrap();
+            assert_eq!(all.unwrap(), docs);
+
+            let part = decode_masked_postings_in(&buf, 300..=700).unwrap().unwrap();
+            let want: Vec<MaskedDoc> = docs
+                .iter()
+                .copied()
+                .filter(|d| (300..=700).contains(&d.doc))
+                .collect();
+            assert_eq!(part, want);
+        }
+    }
+
+    #[test]
+    fn unmasked_record_has_no_masks() {
+        let mut buf = Vec::new();
+        encode_postings(&[1, 2, 3], &mut buf);
+        assert!(decode_masked_postings_in(&buf, 0..=10).unwrap().is_none());
+    }
+}
@@ -6,19 +6,112 @@ use once_cell::sync::OnceCell;
 use std::{
     collections::{BTreeMap, HashMap},
     fs::File,
-    io::{Cursor, Read},
+    ops::RangeInclusive,
     path::{Path, PathBuf},
 };
 
+use crate::analyzer::Analyzers;
+use crate::deletes::LiveDocs;
+use crate::doc_values::{DocValueField, RangeFilter};
 use crate::gram::BooleanOp;
+use crate::v2::columns::{Column, ColumnsIndex, DOC_VALUES_FILE};
 use crate::v2::crc::crc64_ecma;
-use crate::v2::types::{META_HEADER_LEN, META_MAGIC, META_VERSION};
+use crate::v2::gram_dict::{GRAMS_IDX_V1, GramDict, field_gram_key};
+use crate::v2::plan::{GramPlan, plan_and};
+use crate::v2::postings::{
+    MaskedDoc, decode_masked_postings_in, decode_postings_in, next_char_bit,
+};
+use crate::v2::types::{META_HEADER_LEN, META_MAGIC, META_VERSION, MetaHeader};
+use crate::v2::writer::SHORT_GRAMS;
 use crate::{SegmentReader, StoredDoc}; // StoredDoc теперь используем
 
+/// Словарь грамм и его постинги (`grams.*` или `field_grams.*`).
+struct GramIndex {
+    dict: GramDict,
+    dat: Mmap,
+}
+
+impl GramIndex {
+    fn open(base: &Path, name: &str) -> Result<Self> {
+        Ok(Self {
+            dict: GramDict::open(mmap_with_crc(base.join(format!("{name}.idx")))?)?,
+            dat: mmap_with_crc(base.join(format!("{name}.dat")))?,
+        })
+    }
+
+    /// Постинги ключа в диапазоне DocId; `None`, если ключа нет.
+    fn postings(&self, key: &str, range: RangeInclusive<u32>) -> Result<Option<Bitmap>> {
+        match self.dict.lookup(key)? {
+            Some((off, len)) => Ok(Some(read_postings(&self.dat, off, len, range)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Длина постинга ключа из заголовка записи (без декодирования).
+    fn doc_freq(&self, key: &str) -> Result<Option<u32>> {
+        let Some((off, _)) = self.dict.lookup(key)? else {
+            return Ok(None);
+        };
+        let start = off as usize;
+        let Some(hdr) = self.dat.get(start..start + 5) else {
+            bail!("postings OOB");
+        };
+        Ok(Some(u32::from_le_bytes(hdr[1..5].try_into().unwrap())))
+    }
+
+    /// Все записи словаря: (ключ, постинги с масками) по возрастанию ключа.
+    /// Записи без масок получают `loc = next = 0xFF` (смежность не отсекает).
+    fn entries(&self) -> impl Iterator<Item = Result<(String, Vec<MaskedDoc>)>> + '_ {
+        self.dict.iter().map(move |e| {
+            let e = e?;
+            if self.dict.version() == GRAMS_IDX_V1 {
+                bail!("grams.idx v1 keys are truncated; rebuild the segment");
+            }
+            let (start, end) = (e.off as usize, (e.off + e.len) as usize);
+            if end > self.dat.len() {
+                bail!("postings OOB");
+            }
+            let body = &self.dat[start..end];
+            let list = match decode_masked_postings_in(body, 0..=u32::MAX)? {
+                Some(list) => list,
+                None => decode_postings_in(body, 0..=u32::MAX)?
+                    .iter()
+                    .map(|doc| MaskedDoc {
+                        doc,
+                        loc: 0xFF,
+                        next: 0xFF,
+                    })
+                    .collect(),
+            };
+            Ok((String::from_utf8(e.key)?, list))
+        })
+    }
+
+    /// Постинги с позиционными масками; `None`, если ключа нет или запись
+    /// собрана без масок.
+    fn masked_postings(
+        &self,
+        key: &str,
+        range: RangeInclusive<u32>,
+    ) -> Result<Option<Vec<MaskedDoc>>> {
+        let Some((off, len)) = self.dict.lookup(key)? else {
+            return Ok(None);
+        };
+        let (start, end) = (off as usize, (off + len) as usize);
+        if end > self.dat.len() {
+            bail!("postings OOB");
+        }
+        decode_masked_postings_in(&self.dat[start..end], range)
+    }
+}
+
 pub struct BinSegmentReader {
-    _meta_mmap: Mmap,
-    grams_idx: Mmap,
-    grams_dat: Mmap,
+    meta_mmap: Mmap,
+    grams: GramIndex,
+    // ключ (поле, грамма); есть только если сегмент собран с FieldGrams
+    field_grams: Option<GramIndex>,
+    // 1–2-граммы; есть только если сегмент собран с коротким индексом
+    short_grams: Option<GramIndex>,
     fields_idx: Mmap,
     fields_dat: Mmap,
 
@@ -31,6 +124,13 @@ pub struct BinSegmentReader {
     doc_count: u32,
     field_offsets: HashMap<String, (u64, u64)>, // field_name -> (off,len) в fields.dat
     field_names_by_id: Vec<String>,             // индекс = field_id
+
+    // снимок deletes.roaring на момент открытия
+    live: LiveDocs,
+    // doc_values.dat (если сегмент собран с колонками)
+    columns: Option<(Mmap, ColumnsIndex)>,
+    // analyzers.json (стандартная схема, если файла нет)
+    analyzers: Analyzers,
 }
 
 impl SegmentReader for BinSegmentReader {
@@ -65,9 +165,18 @@ impl SegmentReader for BinSegmentReader {
         u64buf.copy_from_slice(&meta_m[8..16]); // hdr.doc_count (u64)
         let doc_count = u64::from_le_bytes(u64buf) as u32;
 
-        // grams.idx/dat
-        let grams_idx_m = mmap_with_crc(base.join("grams.idx"))?;
-        let grams_dat_m = mmap_with_crc(base.join("grams.dat"))?;
+        // grams.idx/dat (+ опциональные field_grams.idx/dat, short_grams.idx/dat)
+        let grams = GramIndex::open(base, "grams")?;
+        let field_grams = if base.join("field_grams.idx").exists() {
+            Some(GramIndex::open(base, "field_grams")?)
+        } else {
+            None
+        };
+        let short_grams = if base.join(format!("{SHORT_GRAMS}.idx")).exists() {
+            Some(GramIndex::open(base, SHORT_GRAMS)?)
+        } else {
+            None
+        };
 
         // fields.idx/dat
         let fields_idx_m = mmap_with_crc(base.join("fields.idx"))?;
@@ -101,13 +210,23 @@ impl SegmentReader for BinSegmentReader {
         let docs_offsets_start = 24u64;
         let docs_payload_start = docs_offsets_start + offsets_count * 8;
 
+        // doc_values.dat (опционально)
+        let columns = if base.join(DOC_VALUES_FILE).exists() {
+            let m = mmap_with_crc(base.join(DOC_VALUES_FILE))?;
+            let idx = ColumnsIndex::parse(&m[..m.len() - 8], doc_count)?;
+            Some((m, idx))
+        } else {
+            None
+        };
+
         // инициализируем OnceCell на каждый документ (стабильная длина)
         let docs_cells = (0..doc_count as usize).map(|_| OnceCell::new()).collect();
 
         Ok(Self {
-            _meta_mmap: meta_m,
-            grams_idx: grams_idx_m,
-            grams_dat: grams_dat_m,
+            meta_mmap: meta_m,
+            grams,
+            field_grams,
+            short_grams,
             fields_idx: fields_idx_m,
             fields_dat: fields_dat_m,
 
@@ -119,6 +238,10 @@ impl SegmentReader for BinSegmentReader {
             doc_count,
             field_offsets,
             field_names_by_id,
+
+            live: LiveDocs::load(base)?,
+            columns,
+            analyzers: Analyzers::load(base)?,
         })
     }
 
@@ -127,57 +250,136 @@ impl SegmentReader for BinSegmentReader {
     }
 
     fn prefilter(&self, op: BooleanOp, grams: &[String], field: Option<&str>) -> Result<Bitmap> {
+        self.prefilter_after(op, grams, field, None)
+    }
+
+    fn get_doc(&self, doc_id: u32) -> Option<&StoredDoc> {
+        if doc_id >= self.doc_count {
+            return None;
+        }
+        if let Some(doc) = self.docs_cells[doc_id as usize].get() {
+            return Some(doc);
+        }
+        let (from, to) = self.doc_bounds(doc_id)?;
+        let parsed = match self.parse_doc(doc_id, from, to) {
+            Ok(d) => d,
+            Err(_) => return None,
+        };
+        let _ = self.docs_cells[doc_id as usize].set(parsed);
+        self.docs_cells[doc_id as usize].get()
+    }
+}
+
+// --- docs.dat helpers ---
+
+impl BinSegmentReader {
+    /// Префильтр, учитывающий курсор: DocId `<= after` не нужны вызывающему,
+    /// поэтому блоки постингов целиком до курсора не декодируются.
+    /// Для AND граммы пересекаются по плану (от редких к частым, без
+    /// перекрывающихся, с ранней остановкой — см. [`crate::v2::plan`]), поэтому
+    /// результат может быть шире точного пересечения всех грамм. Диапазон
+    /// сужается до [min..max] уже пересечённого, и следующие граммы читают только его.
+    ///
+    /// Если `field` проиндексировано отдельно (`field_grams.*`), граммы ищутся
+    /// только в этом поле, иначе — в общем индексе с маской поля.
+    pub fn prefilter_after(
+        &self,
+        op: BooleanOp,
+        grams: &[String],
+        field: Option<&str>,
+        after: Option<u64>,
+    ) -> Result<Bitmap> {
         use BooleanOp::*;
 
-        // Если не нашлось ни одной валидной 3-граммы — считаем «все документы»,
-        // а затем пересекаем с маской поля (если задана).
-        if grams.iter().all(|g| g.as_bytes().len() < 3) {
+        let lo = match after {
+            Some(a) if a >= self.doc_count as u64 => return Ok(Bitmap::new()),
+            Some(a) => a as u32 + 1,
+            None => 0,
+        };
+        // Вселенная: [lo..doc_count)
+        let universe = || {
             let mut all = Bitmap::new();
-            if self.doc_count > 0 {
-                all.add_range(0..self.doc_count);
-            }
-            if let Some(fname) = field {
-                if let Some((off, len)) = self.field_offsets.get(fname).copied() {
-                    let mask = read_field_bitmap(&self.fields_dat, off, len)?;
-                    all.and_inplace(&mask);
-                } else {
-                    all.clear();
-                }
+            if self.doc_count > lo {
+                all.add_range(lo..self.doc_count);
             }
+            all
+        };
+
+        // Если не нашлось ни одной валидной 3-граммы — считаем «все документы»,
+        // а затем пересекаем с маской поля (если задана).
+        if grams.iter().all(|g| g.len() < 3) {
+            let mut all = universe();
+            self.apply_field_mask(&mut all, field)?;
+            self.live.apply(&mut all);
             return Ok(all);
         }
 
+        let (index, scope) = self.gram_index(field);
+
+        // AND: только граммы плана, от редких к частым (см. [`crate::v2::plan`])
+        let plan = match op {
+            And => {
+                let plan = plan_in(index, scope, grams)?;
+                if plan.missing.is_some() {
+                    return Ok(Bitmap::new());
+                }
+                Some(plan)
+            }
+            Or | Not => None,
+        };
+        let planned: Vec<String>;
+        let grams = match &plan {
+            Some(p) => {
+                planned = p.steps.iter().map(|s| s.gram.clone()).collect();
+                &planned
+            }
+            None => grams,
+        };
+
+        let mut range = lo..=u32::MAX;
+        let mut acc: Option<Bitmap> = None;
         let mut vec_bm: Vec<Bitmap> = Vec::new();
 
-        for g in grams {
-            if g.as_bytes().len() < 3 {
+        for (step, g) in grams.iter().enumerate() {
+            if g.len() < 3 {
                 continue;
             }
-            let key = [g.as_bytes()[0], g.as_bytes()[1], g.as_bytes()[2]];
-            if let Some((off, len)) = lookup_gram(&self.grams_idx, key)? {
-                let bm = read_postings(&self.grams_dat, off, len)?;
-                vec_bm.push(bm);
-            } else {
+            let scoped_key = scope.map(|f| field_gram_key(f, g));
+            let key = scoped_key.as_deref().unwrap_or(g);
+            let Some(bm) = index.postings(key, range.clone())? else {
                 // нет такой 3-граммы — для AND это обнуляет всё
                 match op {
                     And => return Ok(Bitmap::new()),
-                    Or | Not => {} // ничего
+                    Or | Not => continue,
                 }
+            };
+            match op {
+                And => {
+                    let cur = match acc.take() {
+                        Some(mut a) => {
+                            a.and_inplace(&bm);
+                            a
+                        }
+                        None => bm,
+                    };
+                    match (cur.minimum(), cur.maximum()) {
+                        (Some(mn), Some(mx)) => range = mn..=mx,
+                        _ => return Ok(Bitmap::new()),
+                    }
+                    let stop = plan
+                        .as_ref()
+                        .is_some_and(|p| p.should_stop(cur.cardinality(), step + 1));
+                    acc = Some(cur);
+                    if stop {
+                        break;
+                    }
+                }
+                Or | Not => vec_bm.push(bm),
             }
         }
 
         let mut acc = match op {
-            And => {
-                let mut it = vec_bm.into_iter();
-                if let Some(mut first) = it.next() {
-                    for bm in it {
-                        first.and_inplace(&bm);
-                    }
-                    first
-                } else {
-                    Bitmap::new()
-                }
-            }
+            And => acc.unwrap_or_default(),
             Or => {
                 let mut out = Bitmap::new();
                 for bm in vec_bm {
@@ -186,11 +388,7 @@ impl SegmentReader for BinSegmentReader {
                 out
             }
             Not => {
-                // Вселенная: [0..doc_count)
-                let mut all = Bitmap::new();
-                if self.doc_count > 0 {
-                    all.add_range(0..self.doc_count);
-                }
+                let mut all = universe();
                 for bm in vec_bm {
                     all.andnot_inplace(&bm);
                 }
@@ -198,7 +396,130 @@ impl SegmentReader for BinSegmentReader {
             }
         };
 
-        // Маска поля (если задана)
+        // Маска поля (если задана) и удалённые документы
+        self.apply_field_mask(&mut acc, field)?;
+        self.live.apply(&mut acc);
+        Ok(acc)
+    }
+
+    /// План AND-префильтра по `grams` (для отладки и метрик).
+    pub fn plan_prefilter(&self, grams: &[String], field: Option<&str>) -> Result<GramPlan> {
+        let (index, scope) = self.gram_index(field);
+        plan_in(index, scope, grams)
+    }
+
+    /// Префильтр с проверкой смежности грамм. `runs` — цепочки грамм
+    /// литералов (см. [`crate::gram::required_gram_runs_from_wildcard`]).
+    /// Цепочка из одного литерала короче трёх символов — короткий литерал
+    /// ([`crate::gram::GramQuery::Short`]): он ищется в `short_grams.*`, а
+    /// без этого индекса не сужает кандидатов.
+    ///
+    /// Сначала обычный AND всех грамм, затем для каждой цепочки кандидат
+    /// остаётся, только если по маскам соседние граммы могут стоять подряд:
+    /// `loc` следующей граммы пересекается со сдвинутым на 1 `loc` предыдущей,
+    /// а `next` предыдущей содержит последний символ следующей. Маски лишь
+    /// огрубляют позиции, поэтому настоящие совпадения не теряются. Записи без
+    /// масок (старые сегменты) проверку пропускают.
+    pub fn prefilter_adjacent_after(
+        &self,
+        runs: &[Vec<String>],
+        field: Option<&str>,
+        after: Option<u64>,
+    ) -> Result<Bitmap> {
+        let (short, runs): (Vec<&Vec<String>>, Vec<&Vec<String>>) = runs
+            .iter()
+            .partition(|r| matches!(r.as_slice(), [g] if g.chars().count() < 3));
+        let grams: Vec<String> = runs.iter().flat_map(|r| r.iter().cloned()).collect();
+        let mut cand = self.prefilter_after(BooleanOp::And, &grams, field, after)?;
+        if let Some(index) = &self.short_grams {
+            for lit in short.iter().map(|r| &r[0]) {
+                let (Some(mn), Some(mx)) = (cand.minimum(), cand.maximum()) else {
+                    break;
+                };
+                match index.postings(lit, mn..=mx)? {
+                    Some(bm) => cand.and_inplace(&bm),
+                    None => cand.clear(),
+                }
+            }
+        }
+        let (index, scope) = self.gram_index(field);
+
+        'runs: for run in runs.iter().filter(|r| r.len() >= 2) {
+            let (Some(mn), Some(mx)) = (cand.minimum(), cand.maximum()) else {
+                break;
+            };
+            // (doc, возможные позиции последней граммы mod 8, её next)
+            let mut alive: Vec<(u32, u8, u8)> = Vec::new();
+            for (i, g) in run.iter().enumerate() {
+                let scoped_key = scope.map(|f| field_gram_key(f, g));
+                let key = scoped_key.as_deref().unwrap_or(g);
+                let Some(list) = index.masked_postings(key, mn..=mx)? else {
+                    continue 'runs;
+                };
+                if i == 0 {
+                    alive = list
+                        .iter()
+                        .filter(|m| cand.contains(m.doc))
+                        .map(|m| (m.doc, m.loc, m.next))
+                        .collect();
+                    continue;
+                }
+                let need_next = g.chars().last().map_or(0, next_char_bit);
+                let mut j = 0;
+                alive.retain_mut(|(doc, loc, next)| {
+                    while j < list.len() && list[j].doc < *doc {
+                        j += 1;
+                    }
+                    let Some(m) = list.get(j).filter(|m| m.doc == *doc) else {
+                        return false;
+                    };
+                    let pos = loc.rotate_left(1) & m.loc;
+                    if pos == 0 || *next & need_next == 0 {
+                        return false;
+                    }
+                    (*loc, *next) = (pos, m.next);
+                    true
+                });
+                if alive.is_empty() {
+                    break;
+                }
+            }
+            let mut kept = Bitmap::new();
+            kept.add_many(&alive.iter().map(|a| a.0).collect::<Vec<u32>>());
+            cand = kept;
+        }
+        Ok(cand)
+    }
+
+    /// Индекс грамм для запроса: пофилдовый, если он есть у `field`,
+    /// иначе общий. Второе значение — поле для ключей пофилдового индекса.
+    fn gram_index<'f>(&self, field: Option<&'f str>) -> (&GramIndex, Option<&'f str>) {
+        match field.filter(|f| self.has_field_grams(f)) {
+            Some(f) => (self.field_grams.as_ref().unwrap(), Some(f)),
+            None => (&self.grams, None),
+        }
+    }
+
+    /// Анализаторы, которыми собран сегмент.
+    pub fn analyzers(&self) -> &Analyzers {
+        &self.analyzers
+    }
+
+    /// Собран ли сегмент с индексом коротких грамм (`short_grams.*`).
+    pub fn has_short_grams(&self) -> bool {
+        self.short_grams.is_some()
+    }
+
+    /// Есть ли у поля собственный индекс грамм в этом сегменте.
+    pub fn has_field_grams(&self, field: &str) -> bool {
+        self.field_grams.as_ref().is_some_and(|fg| {
+            fg.dict
+                .lookup(&field_gram_key(field, ""))
+                .is_ok_and(|hit| hit.is_some())
+        })
+    }
+
+    fn apply_field_mask(&self, acc: &mut Bitmap, field: Option<&str>) -> Result<()> {
         if let Some(fname) = field {
             if let Some((off, len)) = self.field_offsets.get(fname).copied() {
                 let mask = read_field_bitmap(&self.fields_dat, off, len)?;
@@ -207,29 +528,129 @@ impl SegmentReader for BinSegmentReader {
                 acc.clear();
             }
         }
-        Ok(acc)
+        Ok(())
     }
 
-    fn get_doc(&self, doc_id: u32) -> Option<&StoredDoc> {
-        if doc_id >= self.doc_count {
-            return None;
+    /// Удаления, действующие для этого читателя (снимок при открытии).
+    pub fn live_docs(&self) -> &LiveDocs {
+        &self.live
+    }
+
+    /// Поля с колонками doc-values.
+    pub fn doc_value_fields(&self) -> Vec<&DocValueField> {
+        self.columns
+            .as_ref()
+            .map(|(_, idx)| idx.fields().collect())
+            .unwrap_or_default()
+    }
+
+    /// Колонка doc-values поля (`None` — колонки нет).
+    pub fn doc_values(&self, field: &str) -> Result<Option<Column<'_>>> {
+        match &self.columns {
+            Some((m, idx)) => idx.column(&m[..m.len() - 8], field),
+            None => Ok(None),
         }
-        if let Some(doc) = self.docs_cells[doc_id as usize].get() {
-            return Some(doc);
+    }
+
+    /// Оставить в `acc` только документы, проходящие все фильтры по
+    /// диапазону. Поле без колонки в этом сегменте не проходит ни один документ.
+    pub fn apply_range_filters(&self, acc: &mut Bitmap, filters: &[RangeFilter]) -> Result<()> {
+        for f in filters {
+            if acc.is_empty() {
+                break;
+            }
+            match self.doc_values(&f.field)? {
+                Some(col) => acc.and_inplace(&col.filter(f)?),
+                None => acc.clear(),
+            }
         }
-        let (from, to) = self.doc_bounds(doc_id)?;
-        let parsed = match self.parse_doc(doc_id, from, to) {
-            Ok(d) => d,
-            Err(_) => return None,
-        };
-        let _ = self.docs_cells[doc_id as usize].set(parsed);
-        self.docs_cells[doc_id as usize].get()
+        Ok(())
     }
-}
 
-// --- docs.dat helpers ---
+    /// Собран ли сегмент с исходным текстом полей (`--raw-text`).
+    pub fn has_raw_text(&self) -> bool {
+        self.meta_header().is_ok_and(|h| h.has_raw_text())
+    }
+
+    /// Заголовок `meta.bin`.
+    pub fn meta_header(&self) -> Result<MetaHeader> {
+        MetaHeader::parse(&self.meta_mmap[..self.meta_mmap.len() - 8])
+    }
+
+    /// Размеры постингов общего индекса без декодирования:
+    /// (грамма, число DocId, байт в `grams.dat`) по возрастанию ключа.
+    pub fn gram_sizes(&self) -> impl Iterator<Item = Result<(String, u32, u64)>> + '_ {
+        self.grams.dict.iter().map(move |e| {
+            let e = e?;
+            let start = e.off as usize;
+            let Some(hdr) = self.grams.dat.get(start..start + 5) else {
+                bail!("postings OOB");
+            };
+            let docs = u32::from_le_bytes(hdr[1..5].try_into().unwrap());
+            Ok((String::from_utf8_lossy(&e.key).into_owned(), docs, e.len))
+        })
+    }
+
+    /// Маска документов поля из `fields.dat` (`None` — поля нет).
+    pub fn field_docs(&self, field: &str) -> Result<Option<Bitmap>> {
+        match self.field_offsets.get(field) {
+            Some(&(off, len)) => Ok(Some(read_field_bitmap(&self.fields_dat, off, len)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Длина записи документа в `docs.dat` (байт).
+    pub fn doc_len(&self, doc_id: u32) -> Option<u64> {
+        self.doc_bounds(doc_id)
+            .map(|(from, to)| to.saturating_sub(from) as u64)
+    }
+
+    /// Имена полей сегмента (индекс = FieldId).
+    pub fn field_names(&self) -> &[String] {
+        &self.field_names_by_id
+    }
+
+    /// Все постинги общего индекса по возрастанию ключа (для слияния).
+    pub fn gram_postings(&self) -> impl Iterator<Item = Result<(String, Vec<MaskedDoc>)>> + '_ {
+        self.grams.entries()
+    }
+
+    /// Все постинги `field_grams.*` (если есть) по возрастанию ключа.
+    pub fn field_gram_postings(
+        &self,
+    ) -> Option<impl Iterator<Item = Result<(String, Vec<MaskedDoc>)>> + '_> {
+        self.field_grams.as_ref().map(|fg| fg.entries())
+    }
+
+    /// Постинги `short_grams.*`, если индекс есть.
+    pub fn short_gram_postings(
+        &self,
+    ) -> Option<impl Iterator<Item = Result<(String, Vec<MaskedDoc>)>> + '_> {
+        self.short_grams.as_ref().map(|sg| sg.entries())
+    }
+
+    /// Внешний `_id` документа — без разбора полей и без кеширования.
+    pub fn ext_id(&self, doc_id: u32) -> Result<String> {
+        let (from, to) = self
+            .doc_bounds(doc_id)
+            .ok_or_else(|| anyhow!("doc_id {doc_id} out of range"))?;
+        let payload = &self.docs_payload_slice()[from..to];
+        let (ext_len, adv) = get_uvar_u64(payload)?;
+        let ext_end = adv + ext_len as usize;
+        if ext_end > payload.len() {
+            bail!("docs.dat ext_id OOB");
+        }
+        Ok(std::str::from_utf8(&payload[adv..ext_end])?.to_string())
+    }
+
+    /// Разобрать документ без кеширования (потоковые проходы по сегменту).
+    pub fn read_doc(&self, doc_id: u32) -> Result<StoredDoc> {
+        let (from, to) = self
+            .doc_bounds(doc_id)
+            .ok_or_else(|| anyhow!("doc_id {doc_id} out of range"))?;
+        self.parse_doc(doc_id, from, to)
+    }
 
-impl BinSegmentReader {
     /// Синхронный прогрев OnceCell по списку doc_id.
     pub fn prefetch_docs<I: IntoIterator<Item = u32>>(&self, ids: I) {
         for id in ids {
@@ -293,16 +714,47 @@ impl BinSegmentReader {
             }
         }
 
+        // необязательный хвост: исходный текст полей
+        let mut raw: BTreeMap<String, String> = BTreeMap::new();
+        if p < payload.len() {
+            let (rl, adv) = get_uvar_u64(&payload[p..])?;
+            p += adv;
+            for _ in 0..rl {
+                let (fid, a1) = get_uvar_u64(&payload[p..])?;
+                p += a1;
+                let (slen, a2) = get_uvar_u64(&payload[p..])?;
+                p += a2;
+                let s_end = p + (slen as usize);
+                if s_end > payload.len() {
+                    bail!("docs.dat raw string OOB");
+                }
+                let s = std::str::from_utf8(&payload[p..s_end])?.to_string();
+                p = s_end;
+                if let Some(name) = self.field_names_by_id.get(fid as usize) {
+                    raw.insert(name.clone(), s);
+                }
+            }
+        }
+
         Ok(StoredDoc {
             doc_id,
             ext_id,
             fields,
+            raw,
         })
     }
 }
 
 // --- helpers (без изменений снизу, кроме использования выше) ---
 
+/// План AND по `grams` в индексе `index` (`scope` — поле пофилдового индекса).
+fn plan_in(index: &GramIndex, scope: Option<&str>, grams: &[String]) -> Result<GramPlan> {
+    plan_and(grams, |g| match scope {
+        Some(f) => index.doc_freq(&field_gram_key(f, g)),
+        None => index.doc_freq(g),
+    })
+}
+
 fn mmap_with_crc(p: PathBuf) -> Result<Mmap> {
     let f = File::open(&p)?;
     let m = unsafe { Mmap::map(&f)? };
@@ -372,69 +824,14 @@ fn get_uvar(bytes: &[u8]) -> Result<(usize, usize)> {
     bail!("truncated varint")
 }
 
-fn lookup_gram(idx: &Mmap, key: [u8; 3]) -> Result<Option<(u64, u64)>> {
-    // header: magic(4) ver(2) flags(2) count(u32) record_len(u32=24)
-    if idx.len() < 4 + 2 + 2 + 4 + 4 + 8 {
-        bail!("grams.idx too small");
-    }
-    let count = u32::from_le_bytes(idx[8..12].try_into().unwrap()) as usize;
-    let rec_len = u32::from_le_bytes(idx[12..16].try_into().unwrap()) as usize;
-    let base = 16usize;
-    // бинарный поиск
-    let mut lo = 0isize;
-    let mut hi = count as isize - 1;
-    while lo <= hi {
-        let mid = (lo + hi) >> 1;
-        let off = base + (mid as usize) * rec_len;
-        let k = &idx[off..off + 3];
-        match k.cmp(&key) {
-            std::cmp::Ordering::Less => lo = mid + 1,
-            std::cmp::Ordering::Greater => hi = mid - 1,
-            std::cmp::Ordering::Equal => {
-                let off64 = u64::from_le_bytes(idx[off + 3..off + 11].try_into().unwrap());
-                let len64 = u64::from_le_bytes(idx[off + 11..off + 19].try_into().unwrap());
-                return Ok(Some((off64, len64)));
-            }
-        }
-    }
-    Ok(None)
-}
-
-fn read_postings(dat: &Mmap, off: u64, len: u64) -> Result<Bitmap> {
+fn read_postings(dat: &Mmap, off: u64, len: u64, range: RangeInclusive<u32>) -> Result<Bitmap> {
     let start = off as usize;
     let end = (off + len) as usize;
-    let body = &dat[start..end]; // без CRC64 — смещения/длины от meta уже учтены без CRC
-    if body.len() < 1 + 4 {
-        bail!("postings too small");
-    }
-    let kind = body[0];
-    let doc_cnt = u32::from_le_bytes(body[1..5].try_into().unwrap()) as usize;
-    match kind {
-        1 => {
-            // inline varints
-            let mut ids: Vec<u32> = Vec::with_capacity(doc_cnt);
-            let mut p = 5usize;
-            if doc_cnt > 0 {
-                let (first, adv) = get_uvar_u64(&body[p..])?;
-                p += adv;
-                ids.push(first as u32);
-                let mut prev = first as u32;
-                for _ in 1..doc_cnt {
-                    let (d, adv2) = get_uvar_u64(&body[p..])?;
-                    p += adv2;
-                    prev = prev + (d as u32);
-                    ids.push(prev);
-                }
-            }
-            let mut bm = Bitmap::new();
-            for id in ids {
-                bm.add(id);
-            }
-            Ok(bm)
-        }
-        2 => bail!("block codec not implemented yet"),
-        _ => bail!("unknown postings kind {}", kind),
+    if end > dat.len() {
+        bail!("postings OOB");
     }
+    // без CRC64 — смещения/длины от meta уже учтены без CRC
+    decode_postings_in(&dat[start..end], range)
 }
 
 fn get_uvar_u64(bytes: &[u8]) -> Result<(u64, usize)> {
@@ -0,0 +1,307 @@
+// crates/grepzilla_segment/src/v2/spill.rs
+//! Внешняя сортировка постингов грамм для сборки сегмента с ограниченной
+//! памятью.
+//!
+//! Writer копит постинги в памяти; когда оценка занятой памяти превышает
+//! бюджет, накопленное сбрасывается на диск отсортированным «раном».
+//! В конце раны сливаются k-way merge в порядке ключей. Документы приходят
+//! по возрастанию DocId и не разрываются между ранами, поэтому постинги
+//! одного ключа из разных ранов просто склеиваются в порядке ранов.
+//!
+//! Формат рана: записи `[varint key_len][key][varint n]` + `n` ×
+//! `[varint doc_delta][u8 loc][u8 next]`, отсортированные по байтам ключа.
+use anyhow::{Result, bail};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+
+use crate::v2::codec::put_varint_to_writer;
+use crate::v2::postings::MaskedDoc;
+
+/// Грубая оценка накладных расходов на ключ (String + Vec + слот HashMap).
+const KEY_OVERHEAD: usize = 80;
+
+/// Накопитель постингов со сбросом на диск.
+pub struct GramSpill {
+    dir: PathBuf,
+    name: &'static str,
+    mem: HashMap<String, Vec<MaskedDoc>>,
+    mem_bytes: usize,
+    runs: Vec<PathBuf>,
+}
+
+impl GramSpill {
+    /// Раны пишутся в `dir` под именами `<name>.run.<N>`.
+    pub fn new(dir: PathBuf, name: &'static str) -> Self {
+        Self {
+            dir,
+            name,
+            mem: HashMap::new(),
+            mem_bytes: 0,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Оценка памяти под накопленные постинги, байт.
+    pub fn mem_bytes(&self) -> usize {
+        self.mem_bytes
+    }
+
+    /// Сколько ранов уже сброшено на диск.
+    pub fn run_count(&self) -> usize {
+        self.runs.len()
+    }
+
+    fn list(&mut self, key: &str) -> &mut Vec<MaskedDoc> {
+        if !self.mem.contains_key(key) {
+            self.mem_bytes += key.len() + KEY_OVERHEAD;
+            self.mem.insert(key.to_string(), Vec::new());
+        }
+        self.mem.get_mut(key).unwrap()
+    }
+
+    /// Завести ключ без постингов (маркеры `field_grams`).
+    pub fn touch(&mut self, key: &str) {
+        self.list(key);
+    }
+
+    /// Учесть вхождение ключа в документ `doc`: маски одного документа
+    /// объединяются по OR (вхождения в разных полях и позициях).
+    pub fn add(&mut self, key: &str, doc: u32, loc: u8, next: u8) {
+        let list = self.list(key);
+        let cap = list.capacity();
+        match list.last_mut() {
+            Some(last) if last.doc == doc => {
+                last.loc |= loc;
+                last.next |= next;
+            }
+            _ => list.push(MaskedDoc { doc, loc, next }),
+        }
+        let grown = list.capacity() - cap;
+        self.mem_bytes += grown * std::mem::size_of::<MaskedDoc>();
+    }
+
+    /// Сбросить накопленное в новый ран. Вызывать только между документами.
+    pub fn spill(&mut self) -> Result<()> {
+        if self.mem.is_empty() {
+            return Ok(());
+        }
+        let path = self
+            .dir
+            .join(format!("{}.run.{}", self.name, self.runs.len()));
+        let mut w = BufWriter::new(File::create(&path)?);
+        for (key, list) in sorted(std::mem::take(&mut self.mem)) {
+            write_record(&mut w, &key, &list)?;
+        }
+        w.flush()?;
+        self.runs.push(path);
+        self.mem_bytes = 0;
+        Ok(())
+    }
+
+    /// Все ключи по возрастанию с полными постингами.
+    pub fn finish(mut self) -> Result<MergedGrams<'static>> {
+        if self.runs.is_empty() {
+            // всё поместилось в память — без диска
+            let mem = sorted(std::mem::take(&mut self.mem));
+            return MergedGrams::new(vec![Box::new(mem.into_iter().map(Ok))]);
+        }
+        self.spill()?;
+        let mut sources: Vec<GramStream<'static>> = Vec::with_capacity(self.runs.len());
+        for p in &self.runs {
+            sources.push(Box::new(RunReader(BufReader::new(File::open(p)?))));
+        }
+        MergedGrams::new(sources)
+    }
+}
+
+fn sorted(mem: HashMap<String, Vec<MaskedDoc>>) -> Vec<(String, Vec<MaskedDoc>)> {
+    let mut v: Vec<(String, Vec<MaskedDoc>)> = mem.into_iter().collect();
+    v.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    v
+}
+
+/// Поток (ключ, постинги) по возрастанию ключа.
+pub type GramStream<'a> = Box<dyn Iterator<Item = Result<(String, Vec<MaskedDoc>)>> + 'a>;
+
+struct RunReader(BufReader<File>);
+
+impl Iterator for RunReader {
+    type Item = Result<(String, Vec<MaskedDoc>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        read_record(&mut self.0).transpose()
+    }
+}
+
+/// k-way merge отсортированных потоков. Постинги равных ключей склеиваются
+/// в порядке потоков, поэтому DocId в потоках MUST идти по возрастанию
+/// от потока к потоку (раны одной сборки, сегменты при слиянии).
+pub struct MergedGrams<'a> {
+    sources: Vec<GramStream<'a>>,
+    /// текущая запись каждого потока
+    heads: Vec<Option<(String, Vec<MaskedDoc>)>>,
+    /// (ключ, номер потока) — меньший номер раньше при равных ключах
+    heap: BinaryHeap<Reverse<(String, usize)>>,
+}
+
+impl<'a> MergedGrams<'a> {
+    pub fn new(mut sources: Vec<GramStream<'a>>) -> Result<Self> {
+        let mut heads = Vec::with_capacity(sources.len());
+        let mut heap = BinaryHeap::new();
+        for (i, src) in sources.iter_mut().enumerate() {
+            let head = src.next().transpose()?;
+            if let Some((key, _)) = &head {
+                heap.push(Reverse((key.clone(), i)));
+            }
+            heads.push(head);
+        }
+        Ok(Self {
+            sources,
+            heads,
+            heap,
+        })
+    }
+
+    fn advance(&mut self, src: usize) -> Result<Vec<MaskedDoc>> {
+        let next = self.sources[src].next().transpose()?;
+        if let Some((key, _)) = &next {
+            if let Some((prev, _)) = &self.heads[src]
+                && key <= prev
+            {
+                bail!("gram stream is not sorted");
+            }
+            self.heap.push(Reverse((key.clone(), src)));
+        }
+        let (_, list) = std::mem::replace(&mut self.heads[src], next).unwrap();
+        Ok(list)
+    }
+
+    fn next_merged(&mut self) -> Result<Option<(String, Vec<MaskedDoc>)>> {
+        let Some(Reverse((key, src))) = self.heap.pop() else {
+            return Ok(None);
+        };
+        let mut list = self.advance(src)?;
+        while let Some(Reverse((k, s))) = self.heap.peek() {
+            if *k != key {
+                break;
+            }
+            let s = *s;
+            self.heap.pop();
+            let tail = self.advance(s)?;
+            if let (Some(a), Some(b)) = (list.last(), tail.first())
+                && a.doc >= b.doc
+            {
+                bail!("gram streams out of DocId order");
+            }
+            list.extend(tail);
+        }
+        Ok(Some((key, list)))
+    }
+}
+
+impl Iterator for MergedGrams<'_> {
+    type Item = Result<(String, Vec<MaskedDoc>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_merged().transpose()
+    }
+}
+
+fn write_record<W: Write>(w: &mut W, key: &str, list: &[MaskedDoc]) -> Result<()> {
+    put_varint_to_writer(key.len() as u64, w)?;
+    w.write_all(key.as_bytes())?;
+    put_varint_to_writer(list.len() as u64, w)?;
+    let mut prev = 0u32;
+    for m in list {
+        put_varint_to_writer((m.doc - prev) as u64, w)?;
+        w.write_all(&[m.loc, m.next])?;
+        prev = m.doc;
+    }
+    Ok(())
+}
+
+fn read_record<R: Read>(r: &mut R) -> Result<Option<(String, Vec<MaskedDoc>)>> {
+    let Some(klen) = read_varint(r)? else {
+        return Ok(None);
+    };
+    let mut key = vec![0u8; klen as usize];
+    r.read_exact(&mut key)?;
+    let key = String::from_utf8(key)?;
+    let n = read_varint(r)?.ok_or_else(|| anyhow::anyhow!("spill run truncated"))?;
+    let mut list = Vec::with_capacity(n as usize);
+    let mut doc = 0u32;
+    for _ in 0..n {
+        let d = read_varint(r)?.ok_or_else(|| anyhow::anyhow!("spill run truncated"))?;
+        doc += u32::try_from(d)?;
+        let mut m = [0u8; 2];
+        r.read_exact(&mut m)?;
+        list.push(MaskedDoc {
+            doc,
+            loc: m[0],
+            next: m[1],
+        });
+    }
+    Ok(Some((key, list)))
+}
+
+/// varint из потока; `None` — чистый EOF перед первым байтом.
+fn read_varint<R: Read>(r: &mut R) -> Result<Option<u64>> {
+    let mut val = 0u64;
+    let mut shift = 0u32;
+    let mut b = [0u8; 1];
+    loop {
+        match r.read_exact(&mut b) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof && shift == 0 => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        val |= ((b[0] & 0x7F) as u64) << shift;
+        if b[0] & 0x80 == 0 {
+            return Ok(Some(val));
+        }
+        shift += 7;
+        if shift > 63 {
+            bail!("varint too long");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(s: GramSpill) -> Vec<(String, Vec<u32>)> {
+        s.finish()
+            .unwrap()
+            .map(|e| {
+                let (k, l) = e.unwrap();
+                (k, l.iter().map(|m| m.doc).collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spilled_runs_merge_like_memory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut mem = GramSpill::new(tmp.path().to_path_buf(), "a");
+        let mut disk = GramSpill::new(tmp.path().to_path_buf(), "b");
+        for doc in 0..100u32 {
+            for key in ["abc", "xyz", "кот"] {
+                if !(doc as usize + key.len()).is_multiple_of(3) {
+                    mem.add(key, doc, 1, 2);
+                    disk.add(key, doc, 1, 2);
+                }
+            }
+            if doc.is_multiple_of(7) {
+                disk.spill().unwrap();
+            }
+        }
+        mem.touch("empty");
+        disk.touch("empty");
+        assert!(disk.run_count() > 10);
+        assert_eq!(collect(mem), collect(disk));
+    }
+}
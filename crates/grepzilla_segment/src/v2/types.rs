@@ -1,9 +1,20 @@
+use anyhow::{Result, bail};
+use serde::Serialize;
+
 pub const META_MAGIC: u32 = 0x475A5347; // "GZSG"
 pub const META_VERSION: u16 = 2;
 pub const META_HEADER_LEN: u16 = 48;
+/// Фактически занятые заголовком байты: обязательные поля `MetaHeader` без
+/// паддинга (больше `META_HEADER_LEN`, поэтому writer паддинг не добавляет).
+pub const META_FIELDS_LEN: usize = 64;
+/// `flags` (u64) идут за обязательными полями; в сегментах, собранных до
+/// их появления, поля нет — флаги читаются как 0.
+pub const META_FLAGS_LEN: usize = 8;
+/// В `docs.dat` сохранён исходный текст полей (`--raw-text`).
+pub const META_FLAG_RAW_TEXT: u64 = 1;
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
 pub struct MetaHeader {
     pub magic: u32,
     pub version: u16,
@@ -15,6 +26,8 @@ pub struct MetaHeader {
     pub fields_idx_len: u64,
     pub fields_dat_len: u64,
     pub docs_dat_len: u64,
+    /// `META_FLAG_*`
+    pub flags: u64,
 }
 
 impl Default for MetaHeader {
@@ -30,10 +43,43 @@ impl Default for MetaHeader {
             fields_idx_len: 0,
             fields_dat_len: 0,
             docs_dat_len: 0,
+            flags: 0,
         }
     }
 }
 
+impl MetaHeader {
+    /// Разобрать заголовок из начала `meta.bin` (CRC проверяет вызывающий).
+    pub fn parse(b: &[u8]) -> Result<Self> {
+        if b.len() < META_FIELDS_LEN {
+            bail!("meta.bin too small");
+        }
+        let u64_at = |at: usize| u64::from_le_bytes(b[at..at + 8].try_into().unwrap());
+        Ok(Self {
+            magic: u32::from_le_bytes(b[0..4].try_into().unwrap()),
+            version: u16::from_le_bytes(b[4..6].try_into().unwrap()),
+            header_len: u16::from_le_bytes(b[6..8].try_into().unwrap()),
+            doc_count: u64_at(8),
+            gram_count: u64_at(16),
+            grams_idx_len: u64_at(24),
+            grams_dat_len: u64_at(32),
+            fields_idx_len: u64_at(40),
+            fields_dat_len: u64_at(48),
+            docs_dat_len: u64_at(56),
+            flags: if b.len() >= META_FIELDS_LEN + META_FLAGS_LEN {
+                u64_at(META_FIELDS_LEN)
+            } else {
+                0
+            },
+        })
+    }
+
+    /// Сегмент собран с исходным текстом полей.
+    pub fn has_raw_text(&self) -> bool {
+        self.flags & META_FLAG_RAW_TEXT != 0
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StoredDoc {
     pub ext_id: String,
@@ -5,43 +5,147 @@ use serde_json::Value;
 use std::{
     collections::{BTreeMap, HashMap},
     fs::{File, OpenOptions},
-    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
-    path::Path,
+    io::{BufRead, BufReader, BufWriter, Read, Seek, Write},
+    path::{Path, PathBuf},
 };
 
+use crate::analyzer::Analyzers;
+use crate::doc_values::DocValueField;
+use crate::summary::{GramBloom, SegmentSummary, gram_hash};
+use crate::v2::columns::{ColumnsWriter, DOC_VALUES_FILE};
 use crate::v2::crc::crc64_ecma;
-use crate::v2::types::{META_HEADER_LEN, MetaHeader};
-use crate::{normalizer::normalize, v2::codec::put_varint_to_writer};
+use crate::v2::gram_dict::{FIELD_GRAM_SEP, field_gram_key, write_grams_idx};
+use crate::v2::postings::{MaskedDoc, encode_masked_postings, loc_bit, next_char_bit};
+use crate::v2::spill::GramSpill;
+use crate::v2::types::{META_FLAG_RAW_TEXT, META_HEADER_LEN, MetaHeader};
 use croaring::Portable;
 
 const DOCS_MAGIC: &[u8; 8] = b"GZDOCS2\0";
 
-pub struct BinSegmentWriter;
+/// Для каких полей строить отдельный индекс грамм (`field_grams.idx/dat`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FieldGrams {
+    /// Только общий индекс `grams.*` (по умолчанию).
+    #[default]
+    Off,
+    /// Все строковые поля.
+    All,
+    /// Только перечисленные поля (полные пути, например `text.title`).
+    Only(Vec<String>),
+}
+
+impl FieldGrams {
+    pub(crate) fn covers(&self, field: &str) -> bool {
+        // имя поля — часть ключа до разделителя, поэтому он в имени недопустим
+        if field.contains(FIELD_GRAM_SEP) {
+            return false;
+        }
+        match self {
+            FieldGrams::Off => false,
+            FieldGrams::All => true,
+            FieldGrams::Only(list) => list.iter().any(|f| f == field),
+        }
+    }
+}
+
+/// Бюджет памяти сборки по умолчанию: 256 MiB.
+pub const DEFAULT_MEMORY_BUDGET: usize = 256 << 20;
+
+/// Имя индекса 1–2-грамм (`short_grams.idx/dat`).
+pub const SHORT_GRAMS: &str = "short_grams";
+
+/// Каталог временных файлов внутри `out_dir` (удаляется по завершении,
+/// в том числе неудачном — см. [`BuildTmp`]).
+const BUILD_TMP_DIR: &str = ".build.tmp";
+
+/// Writer сегмента V2. Читает JSONL потоком: `docs.dat` пишется по мере
+/// поступления документов, постинги грамм копятся в памяти до
+/// `memory_budget` и затем сбрасываются на диск отсортированными ранами
+/// (см. [`crate::v2::spill`]), которые в конце сливаются в `grams.dat`.
+/// Результат не зависит от бюджета байт-в-байт.
+#[derive(Debug, Clone)]
+pub struct BinSegmentWriter {
+    field_grams: FieldGrams,
+    short_grams: bool,
+    memory_budget: usize,
+    raw_text: bool,
+    doc_values: Vec<DocValueField>,
+    analyzers: Analyzers,
+}
+
 impl Default for BinSegmentWriter {
     fn default() -> Self {
-        Self
+        Self {
+            field_grams: FieldGrams::Off,
+            short_grams: false,
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+            raw_text: false,
+            doc_values: Vec::new(),
+            analyzers: Analyzers::default(),
+        }
     }
 }
 
-#[derive(Default)]
-struct DocTmp {
-    ext_id: String,
-    // на первом проходе храним имя поля, позже сопоставим с field_id
-    fields: Vec<(String, String)>, // (field_name, normalized_value)
+impl BinSegmentWriter {
+    /// Дополнительно строить постинги по ключу (поле, грамма).
+    pub fn with_field_grams(mut self, field_grams: FieldGrams) -> Self {
+        self.field_grams = field_grams;
+        self
+    }
+
+    /// Строить индекс 1–2-грамм (`short_grams.*`) для паттернов без
+    /// литерала из трёх символов (`*id*`, `*42*`).
+    pub fn with_short_grams(mut self, on: bool) -> Self {
+        self.short_grams = on;
+        self
+    }
+
+    /// Ограничить память под постинги (байт, оценка). Остальное — на диск.
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
+
+    /// Сохранять в `docs.dat` исходный (ненормализованный) текст полей.
+    pub fn with_raw_text(mut self, on: bool) -> Self {
+        self.raw_text = on;
+        self
+    }
+
+    /// Строить колонки doc-values (`doc_values.dat`) для этих полей.
+    /// Значения, не подходящие к типу колонки, пропускаются; из массива
+    /// берётся первый подходящий элемент.
+    pub fn with_doc_values(mut self, fields: Vec<DocValueField>) -> Self {
+        self.doc_values = fields;
+        self
+    }
+
+    /// Анализаторы полей (см. [`crate::analyzer`]); нестандартная схема
+    /// записывается в `analyzers.json`.
+    pub fn with_analyzers(mut self, analyzers: Analyzers) -> Self {
+        self.analyzers = analyzers;
+        self
+    }
 }
 
 impl crate::SegmentWriter for BinSegmentWriter {
     fn write_segment(&mut self, input_jsonl: &str, out_dir: &str) -> Result<()> {
-        std::fs::create_dir_all(out_dir)?;
+        let mut sink = SegmentSink::create(Path::new(out_dir))?;
+        sink.set_columns(&self.doc_values)?;
+        sink.set_analyzers(self.analyzers.clone());
+        sink.set_raw_text(self.raw_text);
 
-        // --- 1) Пройдём jsonl: соберём docs, grams, field_masks ---
+        // --- 1) Пройдём jsonl потоком: docs.dat пишем сразу, постинги копим
+        // в пределах бюджета памяти и сбрасываем на диск ---
         let f = File::open(input_jsonl)?;
         let br = BufReader::new(f);
 
-        let mut doc_count: u32 = 0;
-        let mut grams: HashMap<[u8; 3], Vec<u32>> = HashMap::new();
-        let mut field_masks: HashMap<String, Bitmap> = HashMap::new();
-        let mut docs_tmp: Vec<DocTmp> = Vec::new(); // NEW
+        // постинги с позиционными масками; doc_id растут, поэтому листы
+        // получаются отсортированными и без дублей
+        let mut grams = GramSpill::new(sink.tmp_dir.clone(), "grams");
+        // ключ "<field>\0<gram>"; "<field>\0" — маркер проиндексированного поля
+        let mut field_grams = GramSpill::new(sink.tmp_dir.clone(), "field_grams");
+        let mut short_grams = GramSpill::new(sink.tmp_dir.clone(), SHORT_GRAMS);
 
         for line in br.lines() {
             let line = line?;
@@ -54,115 +158,296 @@ impl crate::SegmentWriter for BinSegmentWriter {
             let ext_id = v
                 .get("_id")
                 .and_then(|vv| vv.as_str())
-                .ok_or_else(|| anyhow!("_id missing or not string"))?
-                .to_string();
-
-            docs_tmp.push(DocTmp {
-                ext_id,
-                fields: Vec::new(),
-            });
-            let cur_ix = docs_tmp.len() - 1;
+                .ok_or_else(|| anyhow!("_id missing or not string"))?;
 
             // обойдём все строковые поля (как в V1)
-            collect_strings("", &v, &mut |path, s| {
-                let ns = normalize(s);
-                // grams
-                for g in crate::gram::trigrams(&ns) {
-                    let mut key = [0u8; 3];
-                    for (i, b) in g.as_bytes().iter().take(3).enumerate() {
-                        key[i] = *b;
+            let doc_id = sink.doc_count;
+            if !self.doc_values.is_empty() {
+                let mut err = None;
+                visit_scalars("", &v, &mut |path, val| {
+                    for (col, f) in self.doc_values.iter().enumerate() {
+                        if f.name == path
+                            && let Some(key) = f.kind.key_of(val)
+                            && let Err(e) = sink.put_value(col, key)
+                        {
+                            err.get_or_insert(e);
+                        }
                     }
-                    grams.entry(key).or_default().push(doc_count);
+                });
+                if let Some(e) = err {
+                    return Err(e);
+                }
+            }
+            let mut fields: Vec<(u32, String)> = Vec::new();
+            let mut raw: Vec<(u32, String)> = Vec::new();
+            collect_strings("", &v, &mut |path, s| {
+                let ns = self.analyzers.for_field(path).analyze(s);
+                let per_field = self.field_grams.covers(path);
+                index_text(path, &ns, doc_id, per_field, &mut grams, &mut field_grams);
+                if self.short_grams {
+                    index_short_text(&ns, doc_id, &mut short_grams);
                 }
-                // field mask
-                field_masks
-                    .entry(path.to_string())
-                    .or_default()
-                    .add(doc_count);
-
-                // NEW: для docs.dat
-                docs_tmp[cur_ix].fields.push((path.to_string(), ns));
+                let fid = sink.field_id(path);
+                if self.raw_text && ns != s {
+                    raw.push((fid, s.to_string()));
+                }
+                fields.push((fid, ns));
             });
+            sink.push_doc(ext_id, fields, raw)?;
+
+            if grams.mem_bytes() + field_grams.mem_bytes() + short_grams.mem_bytes()
+                > self.memory_budget
+            {
+                grams.spill()?;
+                field_grams.spill()?;
+                short_grams.spill()?;
+            }
+        }
+
+        let field_grams = match self.field_grams {
+            FieldGrams::Off => None,
+            _ => Some(field_grams.finish()?),
+        };
+        let short_grams = if self.short_grams {
+            Some(short_grams.finish()?)
+        } else {
+            None
+        };
+        sink.finish(grams.finish()?, field_grams, short_grams)
+    }
+}
+
+/// Добавить граммы нормализованного текста поля `path` документа `doc_id`
+/// в постинги (и в пофилдовый индекс, если `per_field`).
+pub(crate) fn index_text(
+    path: &str,
+    ns: &str,
+    doc_id: u32,
+    per_field: bool,
+    grams: &mut GramSpill,
+    field_grams: &mut GramSpill,
+) {
+    // ключ — полная UTF-8 триграмма (grams.idx v2)
+    if per_field {
+        field_grams.touch(&field_gram_key(path, ""));
+    }
+    let chars: Vec<char> = ns.chars().collect();
+    for (pos, w) in chars.windows(3).enumerate() {
+        let g: String = w.iter().collect();
+        let loc = loc_bit(pos);
+        let next = chars.get(pos + 3).map_or(0, |&c| next_char_bit(c));
+        if per_field {
+            field_grams.add(&field_gram_key(path, &g), doc_id, loc, next);
+        }
+        grams.add(&g, doc_id, loc, next);
+    }
+}
 
-            doc_count += 1;
+/// Добавить 1- и 2-граммы нормализованного текста документа `doc_id` в
+/// индекс коротких грамм (общий для всех полей, маски — как у 3-грамм).
+pub(crate) fn index_short_text(ns: &str, doc_id: u32, short_grams: &mut GramSpill) {
+    let chars: Vec<char> = ns.chars().collect();
+    for n in 1..=2 {
+        for (pos, w) in chars.windows(n).enumerate() {
+            let g: String = w.iter().collect();
+            let next = chars.get(pos + n).map_or(0, |&c| next_char_bit(c));
+            short_grams.add(&g, doc_id, loc_bit(pos), next);
         }
+    }
+}
+
+/// Каталог сегмента в процессе записи: документы и маски полей копятся по
+/// мере поступления, постинги грамм передаются готовыми потоками в
+/// [`SegmentSink::finish`]. Общая часть writer’а и слияния сегментов.
+pub(crate) struct SegmentSink {
+    out_dir: PathBuf,
+    pub(crate) tmp_dir: PathBuf,
+    /// удаляет `tmp_dir`, если сборка оборвалась ошибкой
+    tmp_guard: BuildTmp,
+    pub(crate) doc_count: u32,
+    // FieldId = порядок первого появления поля
+    field_ids: HashMap<String, u32>,
+    field_names: Vec<String>,
+    field_masks: Vec<Bitmap>,
+    // payload docs.dat и его offsets (относительно начала payload)
+    docs_payload: BufWriter<File>,
+    docs_offsets: BufWriter<File>,
+    payload_pos: u64,
+    rec: Vec<u8>,
+    // колонки doc-values (если заданы)
+    columns: Option<ColumnsWriter>,
+    analyzers: Analyzers,
+    /// исходный текст полей пишется (флаг `meta.bin`)
+    raw_text: bool,
+}
 
-        // Отсортируем doc_id в листах и удалим дубли (на всякий)
-        for v in grams.values_mut() {
-            v.sort_unstable();
-            v.dedup();
+impl SegmentSink {
+    pub(crate) fn create(out_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(out_dir)?;
+        // временные файлы сборки (раны грамм, куски docs.dat) — рядом с сегментом
+        let tmp_dir = out_dir.join(BUILD_TMP_DIR);
+        if tmp_dir.exists() {
+            std::fs::remove_dir_all(&tmp_dir)?;
         }
+        std::fs::create_dir_all(&tmp_dir)?;
+        // с этого места каталог убирается и при ошибке
+        let tmp_guard = BuildTmp(tmp_dir.clone());
+        Ok(Self {
+            out_dir: out_dir.to_path_buf(),
+            docs_payload: BufWriter::new(File::create(tmp_dir.join("docs.payload"))?),
+            docs_offsets: BufWriter::new(File::create(tmp_dir.join("docs.offsets"))?),
+            tmp_guard,
+            tmp_dir,
+            doc_count: 0,
+            field_ids: HashMap::new(),
+            field_names: Vec::new(),
+            field_masks: Vec::new(),
+            payload_pos: 0,
+            rec: Vec::new(),
+            columns: None,
+            analyzers: Analyzers::default(),
+            raw_text: false,
+        })
+    }
 
-        // --- 2) Подготовим файлы ---
-        let meta_path = Path::new(out_dir).join("meta.bin");
-        let grams_idx_path = Path::new(out_dir).join("grams.idx");
-        let grams_dat_path = Path::new(out_dir).join("grams.dat");
-        let fields_idx_path = Path::new(out_dir).join("fields.idx");
-        let fields_dat_path = Path::new(out_dir).join("fields.dat");
-        let docs_dat_path = Path::new(out_dir).join("docs.dat");
-
-        // grams.dat
-        let mut grams_dat = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&grams_dat_path)?;
-        // Формат: [u8 kind=1][u32 doc_count][varint first][varint delta...], КАЖДАЯ запись подряд,
-        // CRC64 будет в конце файла.
-        let mut grams_index: Vec<([u8; 3], u64, u64)> = Vec::new();
-        // Сортировка ключей по лексикографическому порядку
-        let mut keys: Vec<[u8; 3]> = grams.keys().cloned().collect();
-        keys.sort_unstable();
-
-        for key in keys {
-            let list = grams.get(&key).unwrap();
-            let offset = grams_dat.stream_position()?;
-            // kind=1
-            grams_dat.write_all(&[1u8])?;
-            grams_dat.write_all(&(list.len() as u32).to_le_bytes())?;
-            if list.is_empty() {
-                // пустой список — ок
-            } else {
-                // первый docid
-                put_varint_to_writer(list[0] as u64, &mut grams_dat)?;
-                // дельты
-                let mut prev = list[0];
-                for &d in &list[1..] {
-                    let delta = (d - prev) as u64;
-                    put_varint_to_writer(delta, &mut grams_dat)?;
-                    prev = d;
-                }
-            }
-            let end = grams_dat.stream_position()?;
-            let length = end - offset;
-            grams_index.push((key, offset, length));
+    /// Включить колонки doc-values (пустой список — без `doc_values.dat`).
+    pub(crate) fn set_columns(&mut self, fields: &[DocValueField]) -> Result<()> {
+        self.columns = if fields.is_empty() {
+            None
+        } else {
+            Some(ColumnsWriter::new(&self.tmp_dir, fields)?)
+        };
+        Ok(())
+    }
+
+    /// Схема анализаторов сегмента (`analyzers.json` при `finish`).
+    pub(crate) fn set_analyzers(&mut self, analyzers: Analyzers) {
+        self.analyzers = analyzers;
+    }
+
+    /// Исходный текст полей сохраняется (`raw` в [`Self::push_doc`]) —
+    /// флаг [`META_FLAG_RAW_TEXT`] в `meta.bin`: без него строгий verify не
+    /// отличит «текст не менялся нормализацией» от «исходник не хранится».
+    pub(crate) fn set_raw_text(&mut self, on: bool) {
+        self.raw_text = on;
+    }
+
+    /// Значение колонки `col` для документа, который будет добавлен следующим.
+    pub(crate) fn put_value(&mut self, col: usize, key: i64) -> Result<()> {
+        match self.columns.as_mut() {
+            Some(c) => c.put(col, self.doc_count, key),
+            None => Err(anyhow!("doc-value columns are not configured")),
         }
-        // footer CRC64 для grams.dat
-        let (grams_dat_body_len, _crc) = finalize_with_crc64(&mut grams_dat)?;
+    }
 
-        // grams.idx
-        let mut grams_idx = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&grams_idx_path)?;
-        // header
-        grams_idx.write_all(&0x475A4944u32.to_le_bytes())?; // "GZID"
-        grams_idx.write_all(&1u16.to_le_bytes())?; // version
-        grams_idx.write_all(&0u16.to_le_bytes())?; // flags
-        grams_idx.write_all(&(grams_index.len() as u32).to_le_bytes())?;
-        grams_idx.write_all(&(24u32).to_le_bytes())?; // record_len (выравненный)
-        // records
-        for (key, off, len) in &grams_index {
-            grams_idx.write_all(key)?;
-            grams_idx.write_all(&off.to_le_bytes())?;
-            grams_idx.write_all(&len.to_le_bytes())?;
-            grams_idx.write_all(&[0u8; 5])?; // pad до 24
+    /// FieldId поля (новые поля получают следующий номер).
+    pub(crate) fn field_id(&mut self, name: &str) -> u32 {
+        if let Some(&fid) = self.field_ids.get(name) {
+            return fid;
         }
-        // footer CRC64
-        let (grams_idx_body_len, _crc) = finalize_with_crc64(&mut grams_idx)?;
+        let fid = self.field_names.len() as u32;
+        self.field_ids.insert(name.to_string(), fid);
+        self.field_names.push(name.to_string());
+        self.field_masks.push(Bitmap::new());
+        fid
+    }
+
+    /// Число различных полей на данный момент.
+    pub(crate) fn field_count(&self) -> usize {
+        self.field_names.len()
+    }
+
+    /// Дописать документ в docs.dat; возвращает его DocId. `raw` — исходный
+    /// текст полей (пишется необязательным хвостом записи, если не пуст).
+    pub(crate) fn push_doc(
+        &mut self,
+        ext_id: &str,
+        mut fields: Vec<(u32, String)>,
+        mut raw: Vec<(u32, String)>,
+    ) -> Result<u32> {
+        let doc_id = self.doc_count;
+        // docs.dat: запись документа, поля по возрастанию fid
+        fields.sort_by_key(|(fid, _)| *fid);
+        let rec = &mut self.rec;
+        rec.clear();
+        put_uvar(ext_id.len() as u64, rec);
+        rec.extend_from_slice(ext_id.as_bytes());
+        put_uvar(fields.len() as u64, rec);
+        for (fid, val) in &fields {
+            put_uvar(*fid as u64, rec);
+            put_uvar(val.len() as u64, rec);
+            rec.extend_from_slice(val.as_bytes());
+            // field mask
+            self.field_masks[*fid as usize].add(doc_id);
+        }
+        // хвост: исходный текст полей (старые читатели его не заметят)
+        if !raw.is_empty() {
+            raw.sort_by_key(|(fid, _)| *fid);
+            put_uvar(raw.len() as u64, rec);
+            for (fid, val) in &raw {
+                put_uvar(*fid as u64, rec);
+                put_uvar(val.len() as u64, rec);
+                rec.extend_from_slice(val.as_bytes());
+            }
+        }
+        self.docs_offsets
+            .write_all(&self.payload_pos.to_le_bytes())?;
+        self.docs_payload.write_all(rec)?;
+        self.payload_pos += rec.len() as u64;
+        self.doc_count += 1;
+        Ok(doc_id)
+    }
+
+    /// Записать все файлы сегмента. `field_grams = None` — без пофилдового
+    /// индекса, `short_grams = None` — без индекса 1–2-грамм (устаревшие от
+    /// прошлой сборки в тот же каталог удаляются).
+    pub(crate) fn finish<'a>(
+        mut self,
+        grams: impl Iterator<Item = Result<(String, Vec<MaskedDoc>)>> + 'a,
+        field_grams: Option<impl Iterator<Item = Result<(String, Vec<MaskedDoc>)>> + 'a>,
+        short_grams: Option<impl Iterator<Item = Result<(String, Vec<MaskedDoc>)>> + 'a>,
+    ) -> Result<()> {
+        // guard offset
+        self.docs_offsets
+            .write_all(&self.payload_pos.to_le_bytes())?;
+        self.docs_offsets.flush()?;
+        self.docs_payload.flush()?;
+
+        // --- 2) Подготовим файлы ---
+        let out_dir = self.out_dir.as_path();
+        let meta_path = out_dir.join("meta.bin");
+        let grams_idx_path = out_dir.join("grams.idx");
+        let grams_dat_path = out_dir.join("grams.dat");
+        let fields_idx_path = out_dir.join("fields.idx");
+        let fields_dat_path = out_dir.join("fields.dat");
+        let docs_dat_path = out_dir.join("docs.dat");
+
+        // grams.idx/dat; хеши грамм — для Bloom-фильтра сводки
+        let mut gram_hashes = Vec::new();
+        let grams = grams.inspect(|g| {
+            if let Ok((key, _)) = g {
+                gram_hashes.push(gram_hash(key));
+            }
+        });
+        let (grams_idx_body_len, grams_dat_body_len, gram_count) =
+            write_gram_files(grams, &grams_idx_path, &grams_dat_path)?;
+
+        // field_grams.idx/dat и short_grams.idx/dat — только если включено
+        write_optional_gram_files(field_grams, out_dir, "field_grams")?;
+        write_optional_gram_files(short_grams, out_dir, SHORT_GRAMS)?;
+        self.analyzers.write(out_dir)?;
+
+        // doc_values.dat — только если заданы колонки
+        let dv_path = out_dir.join(DOC_VALUES_FILE);
+        let ranges = match self.columns.take() {
+            Some(cols) => cols.finish(self.doc_count, &dv_path)?,
+            None => {
+                if dv_path.exists() {
+                    std::fs::remove_file(&dv_path)?;
+                }
+                Vec::new()
+            }
+        };
 
         // fields.dat
         let mut fields_dat = OpenOptions::new()
@@ -171,13 +456,9 @@ impl crate::SegmentWriter for BinSegmentWriter {
             .create(true)
             .truncate(true)
             .open(&fields_dat_path)?;
-        // name_dict: соберём список имён (детерминированный порядок)
-        let mut field_names: Vec<String> = field_masks.keys().cloned().collect();
-        field_names.sort(); // FieldId = индекс
         // records для idx: (field_id, off, len)
         let mut field_records: Vec<(u32, u64, u64)> = Vec::new();
-        for (fid, name) in field_names.iter().enumerate() {
-            let bm = field_masks.get(name).unwrap();
+        for (fid, bm) in self.field_masks.iter().enumerate() {
             let off = fields_dat.stream_position()?;
             // kind=1 roaring_stream
             fields_dat.write_all(&[1u8])?;
@@ -201,10 +482,10 @@ impl crate::SegmentWriter for BinSegmentWriter {
         fields_idx.write_all(&0x475A4649u32.to_le_bytes())?; // "GZFI"
         fields_idx.write_all(&1u16.to_le_bytes())?; // version
         fields_idx.write_all(&0u16.to_le_bytes())?; // flags
-        fields_idx.write_all(&(field_names.len() as u32).to_le_bytes())?;
+        fields_idx.write_all(&(self.field_names.len() as u32).to_le_bytes())?;
         // name_dict_len: посчитаем заранее
         let mut name_dict_buf = Vec::new();
-        for name in &field_names {
+        for name in &self.field_names {
             put_uvar(name.len() as u64, &mut name_dict_buf);
             name_dict_buf.extend_from_slice(name.as_bytes());
         }
@@ -221,7 +502,7 @@ impl crate::SegmentWriter for BinSegmentWriter {
         let (fields_idx_body_len, _crc) = finalize_with_crc64(&mut fields_idx)?;
 
         // ----------------------------
-        // docs.dat — ПОЛНОЦЕННАЯ запись
+        // docs.dat — header + offsets + payload из временных файлов
         // ----------------------------
         let mut docs_dat = OpenOptions::new()
             .read(true)
@@ -229,91 +510,140 @@ impl crate::SegmentWriter for BinSegmentWriter {
             .create(true)
             .truncate(true)
             .open(&docs_dat_path)?;
-
-        // словарь name -> field_id (индекс в field_names)
-        let mut name_to_id: HashMap<&str, u32> = HashMap::new();
-        for (fid, name) in field_names.iter().enumerate() {
-            name_to_id.insert(name.as_str(), fid as u32);
+        {
+            let mut w = BufWriter::new(&mut docs_dat);
+            w.write_all(DOCS_MAGIC)?;
+            w.write_all(&(self.doc_count as u64).to_le_bytes())?;
+            let offsets_count = (self.doc_count as u64) + 1;
+            w.write_all(&offsets_count.to_le_bytes())?;
+            std::io::copy(&mut File::open(self.tmp_dir.join("docs.offsets"))?, &mut w)?;
+            std::io::copy(&mut File::open(self.tmp_dir.join("docs.payload"))?, &mut w)?;
+            w.flush()?;
         }
-
-        // header
-        docs_dat.write_all(DOCS_MAGIC)?;
-        docs_dat.write_all(&(doc_count as u64).to_le_bytes())?;
-        let offsets_count = (doc_count as u64) + 1;
-        docs_dat.write_all(&offsets_count.to_le_bytes())?;
-
-        // offsets placeholder
-        let offsets_pos = docs_dat.stream_position()?;
-        for _ in 0..offsets_count {
-            docs_dat.write_all(&0u64.to_le_bytes())?;
-        }
-
-        // payload start
-        let payload_start = docs_dat.stream_position()?;
-        let mut rel_offsets: Vec<u64> = Vec::with_capacity(docs_tmp.len() + 1);
-
-        // helper для varint
-        let mut write_uvar = |x: u64, w: &mut std::fs::File| -> anyhow::Result<()> {
-            put_varint_to_writer(x, w)?;
-            Ok(())
-        };
-
-        for d in &docs_tmp {
-            let abs_start = docs_dat.stream_position()?;
-            rel_offsets.push(abs_start - payload_start);
-
-            // ext_id
-            write_uvar(d.ext_id.as_bytes().len() as u64, &mut docs_dat)?;
-            docs_dat.write_all(d.ext_id.as_bytes())?;
-
-            // сопоставим поля -> (fid, &str), сортируем по fid
-            let mut mapped: Vec<(u32, &str)> = Vec::new();
-            for (name, val) in &d.fields {
-                if let Some(fid) = name_to_id.get(name.as_str()) {
-                    mapped.push((*fid, val.as_str()));
-                }
-            }
-            mapped.sort_by_key(|(fid, _)| *fid);
-
-            // fields_len
-            write_uvar(mapped.len() as u64, &mut docs_dat)?;
-            for (fid, val) in mapped {
-                write_uvar(fid as u64, &mut docs_dat)?;
-                write_uvar(val.as_bytes().len() as u64, &mut docs_dat)?;
-                docs_dat.write_all(val.as_bytes())?;
-            }
-        }
-
-        // guard offset
-        let end = docs_dat.stream_position()?;
-        rel_offsets.push(end - payload_start);
-
-        // вернуться и записать offsets
-        docs_dat.seek(SeekFrom::Start(offsets_pos))?;
-        for o in &rel_offsets {
-            docs_dat.write_all(&o.to_le_bytes())?;
-        }
-
         // footer CRC64
         let (docs_dat_body_len, _crc) = finalize_with_crc64(&mut docs_dat)?;
 
         // meta.bin
         let mut hdr = MetaHeader::default();
-        hdr.doc_count = doc_count as u64;
-        hdr.gram_count = grams_index.len() as u64;
+        hdr.doc_count = self.doc_count as u64;
+        hdr.gram_count = gram_count as u64;
         hdr.grams_idx_len = grams_idx_body_len;
         hdr.grams_dat_len = grams_dat_body_len;
         hdr.fields_idx_len = fields_idx_body_len;
         hdr.fields_dat_len = fields_dat_body_len;
         hdr.docs_dat_len = docs_dat_body_len;
+        if self.raw_text {
+            hdr.flags |= META_FLAG_RAW_TEXT;
+        }
         let mut meta = File::create(&meta_path)?;
         write_meta_with_crc(&mut meta, &hdr)?;
+
+        // summary.bin — сводка для отсечения сегмента планировщиком
+        SegmentSummary::new(
+            self.doc_count,
+            self.field_names.iter().cloned(),
+            ranges,
+            GramBloom::from_hashes(&gram_hashes),
+        )
+        .write(out_dir)?;
+
+        drop((self.docs_offsets, self.docs_payload));
+        self.tmp_guard.remove()
+    }
+}
+
+/// Каталог временных файлов сборки. Успешная сборка удаляет его
+/// [`BuildTmp::remove`] (с ошибкой, если не вышло); оборванная — при drop,
+/// чтобы раны грамм не оставались рядом с сегментом.
+struct BuildTmp(PathBuf);
+
+impl BuildTmp {
+    fn remove(mut self) -> Result<()> {
+        let dir = std::mem::take(&mut self.0);
+        std::fs::remove_dir_all(dir)?;
         Ok(())
     }
 }
 
+impl Drop for BuildTmp {
+    fn drop(&mut self) {
+        if !self.0.as_os_str().is_empty() {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+}
+
 // --- helpers ---
 
+/// Необязательный индекс `<name>.idx/dat`: записать или, если его нет,
+/// не оставлять устаревший от прошлой сборки в тот же каталог.
+fn write_optional_gram_files(
+    grams: Option<impl Iterator<Item = Result<(String, Vec<MaskedDoc>)>>>,
+    out_dir: &Path,
+    name: &str,
+) -> Result<()> {
+    let idx_path = out_dir.join(format!("{name}.idx"));
+    let dat_path = out_dir.join(format!("{name}.dat"));
+    match grams {
+        Some(g) => {
+            write_gram_files(g, &idx_path, &dat_path)?;
+        }
+        None => {
+            for p in [&idx_path, &dat_path] {
+                if p.exists() {
+                    std::fs::remove_file(p)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Записать словарь грамм: постинги в `dat_path`, словарь в `idx_path`.
+/// `grams` идут по возрастанию ключа. Возвращает (длина тела idx, длина тела
+/// dat, число ключей).
+fn write_gram_files(
+    grams: impl Iterator<Item = Result<(String, Vec<MaskedDoc>)>>,
+    idx_path: &Path,
+    dat_path: &Path,
+) -> Result<(u64, u64, usize)> {
+    let mut dat = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(dat_path)?;
+    // Формат записи — см. v2::postings (kind=1 inline / kind=2 блоки),
+    // КАЖДАЯ запись подряд, CRC64 будет в конце файла.
+    let mut index: Vec<(String, u64, u64)> = Vec::new();
+    {
+        let mut w = BufWriter::new(&mut dat);
+        let mut offset = 0u64;
+        let mut rec = Vec::new();
+        for e in grams {
+            let (key, list) = e?;
+            rec.clear();
+            encode_masked_postings(&list, &mut rec);
+            w.write_all(&rec)?;
+            index.push((key, offset, rec.len() as u64));
+            offset += rec.len() as u64;
+        }
+        w.flush()?;
+    }
+    let (dat_body_len, _crc) = finalize_with_crc64(&mut dat)?;
+
+    let mut idx = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(idx_path)?;
+    let refs: Vec<(&str, u64, u64)> = index.iter().map(|(k, o, l)| (k.as_str(), *o, *l)).collect();
+    write_grams_idx(&mut BufWriter::new(&mut idx), &refs)?;
+    let (idx_body_len, _crc) = finalize_with_crc64(&mut idx)?;
+    Ok((idx_body_len, dat_body_len, index.len()))
+}
+
 fn write_meta_with_crc(f: &mut File, hdr: &MetaHeader) -> anyhow::Result<()> {
     let mut buf = Vec::with_capacity(META_HEADER_LEN as usize + 8);
 
@@ -328,6 +658,7 @@ fn write_meta_with_crc(f: &mut File, hdr: &MetaHeader) -> anyhow::Result<()> {
     buf.extend_from_slice(&hdr.fields_idx_len.to_le_bytes());
     buf.extend_from_slice(&hdr.fields_dat_len.to_le_bytes());
     buf.extend_from_slice(&hdr.docs_dat_len.to_le_bytes());
+    buf.extend_from_slice(&hdr.flags.to_le_bytes());
     while buf.len() < META_HEADER_LEN as usize {
         buf.push(0);
     }
@@ -352,6 +683,33 @@ fn put_uvar(x: u64, out: &mut Vec<u8>) {
     crate::v2::codec::put_varint(x, out)
 }
 
+/// Обходит JSON и вызывает `f(path, v)` для всех нестроковых скаляров
+/// (пути — как в `collect_strings`). Элементы массива получают и путь
+/// массива, чтобы `tags` находил `tags[0]`.
+fn visit_scalars(path: &str, v: &Value, f: &mut impl FnMut(&str, &Value)) {
+    match v {
+        Value::Object(map) => {
+            for (k, vv) in map {
+                let np = if path.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{path}.{k}")
+                };
+                visit_scalars(&np, vv, f);
+            }
+        }
+        Value::Array(arr) => {
+            for (i, vv) in arr.iter().enumerate() {
+                if !matches!(vv, Value::Object(_) | Value::Array(_)) {
+                    f(path, vv);
+                }
+                visit_scalars(&format!("{path}[{i}]"), vv, f);
+            }
+        }
+        _ => f(path, v),
+    }
+}
+
 fn collect_strings(path: &str, v: &serde_json::Value, f: &mut impl FnMut(&str, &str)) {
     use serde_json::Value;
     match v {
@@ -393,13 +751,12 @@ fn write_crc64_footer(f: &mut File) -> anyhow::Result<()> {
 }
 
 fn finalize_with_crc64(f: &mut std::fs::File) -> anyhow::Result<(u64 /*body_len*/, u64 /*crc*/)> {
-    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::io::{Seek, SeekFrom, Write};
     // длина тела ДО футера
     let body_len = f.stream_position()?; // где сейчас находится курсор
     f.seek(SeekFrom::Start(0))?;
-    let mut buf = Vec::with_capacity(body_len as usize);
-    f.read_to_end(&mut buf)?;
-    let crc = crate::v2::crc::crc64_ecma(&buf);
+    // потоком: файл может не помещаться в память
+    let crc = crate::v2::crc::crc64_ecma_reader(&mut Read::take(&mut *f, body_len))?;
     f.seek(SeekFrom::End(0))?;
     f.write_all(&crc.to_le_bytes())?;
     Ok((body_len, crc))
@@ -1,26 +1,122 @@
 use anyhow::Result;
 use std::sync::Arc;
 
-use super::{RegexVerify, VerifyEngine};
+use super::wildcard_to_regex_case_sensitive;
+use super::{EngineRegistry, LiteralVerify, RegexVerify, VerifyEngine};
+use crate::analyzer::Analyzer;
+use crate::limits::DEFAULT_MAX_REGEX_SIZE;
+use crate::normalizer::Sensitivity;
+use crate::regex_grams::{RegexQuery, parse_regex_with, verify_pattern_with};
+use crate::verify::registry::DEFAULT_ENGINE;
 
 /// Фабрика движков верификации.
 pub trait VerifyFactory: Send + Sync {
     /// Компилирует движок под нормализованный wildcard-паттерн.
     fn compile(&self, wildcard_normalized: &str) -> Result<Arc<dyn VerifyEngine>>;
+
+    /// Предел программы regex в байтах (`max_regex_size`): его получают
+    /// движки по умолчанию и `RegexSet` мультипоиска. Превышение —
+    /// [`crate::limits::LimitExceeded`].
+    fn regex_size_limit(&self) -> usize {
+        DEFAULT_MAX_REGEX_SIZE
+    }
+
+    /// Компилирует движок под регулярное выражение в синтаксисе `regex`
+    /// (печать HIR из [`crate::regex_grams`]), поэтому по умолчанию — `regex`.
+    fn compile_regex(&self, pattern: &str) -> Result<Arc<dyn VerifyEngine>> {
+        Ok(Arc::new(RegexVerify::compile_regex_limited(
+            pattern,
+            self.regex_size_limit(),
+        )?))
+    }
+
+    /// Пункт `regex` под анализатор `a`: verify-паттерн, формула грамм и
+    /// движок. По умолчанию паттерн — в синтаксисе `regex`
+    /// ([`parse_regex_with`]).
+    fn compile_regex_clause(
+        &self,
+        pattern: &str,
+        a: &dyn Analyzer,
+    ) -> Result<(RegexQuery, Arc<dyn VerifyEngine>)> {
+        let rq = parse_regex_with(pattern, a)?;
+        let engine = self.compile_regex(&rq.verify)?;
+        Ok((rq, engine))
+    }
+
+    /// Движок строгого verify пункта `regex`: литералы приведены
+    /// `normalize_with(_, sens)`.
+    fn compile_regex_sensitive(
+        &self,
+        pattern: &str,
+        sens: Sensitivity,
+    ) -> Result<Arc<dyn VerifyEngine>> {
+        self.compile_regex(&verify_pattern_with(pattern, sens)?)
+    }
+
+    /// Движок строгого verify: wildcard нормализован `normalize_with(_, sens)`,
+    /// при `case_sensitive` регистр различается.
+    fn compile_sensitive(
+        &self,
+        wildcard: &str,
+        sens: Sensitivity,
+    ) -> Result<Arc<dyn VerifyEngine>> {
+        if sens.case_sensitive {
+            if let Some(lit) = LiteralVerify::compile(wildcard, false) {
+                return Ok(Arc::new(lit));
+            }
+            Ok(Arc::new(RegexVerify::compile_regex_limited(
+                &wildcard_to_regex_case_sensitive(wildcard),
+                self.regex_size_limit(),
+            )?))
+        } else {
+            self.compile(wildcard)
+        }
+    }
 }
 
-/// Выбор движка по ENV, с безопасным фолбэком на regex.
-/// Поддерживаем только `regex` (остальное — в будущих эпиках).
+/// Выбор движка по ENV (`GZ_VERIFY`, устаревшее имя — `GZ_VERIFY_ENGINE`;
+/// по умолчанию `regex`) через [`EngineRegistry`]. Неизвестный движок не
+/// подменяется `regex`: [`Self::try_from_env`] и каждая компиляция
+/// возвращают ошибку.
 pub struct EnvVerifyFactory {
     engine: String,
+    factory: std::result::Result<Arc<dyn VerifyFactory>, String>,
 }
 
 impl EnvVerifyFactory {
     pub fn from_env() -> Self {
-        let v = std::env::var("GZ_VERIFY_ENGINE").unwrap_or_else(|_| "regex".to_string());
-        Self {
-            engine: v.to_lowercase(),
+        let engine = std::env::var("GZ_VERIFY")
+            .or_else(|_| std::env::var("GZ_VERIFY_ENGINE"))
+            .unwrap_or_else(|_| DEFAULT_ENGINE.to_string());
+        Self::resolve(engine, &EngineRegistry::builtin())
+    }
+
+    /// Тот же движок с пределом программы regex `bytes` (`max_regex_size`).
+    pub fn with_size_limit(self, bytes: usize) -> Self {
+        Self::resolve(self.engine, &EngineRegistry::with_regex_size_limit(bytes))
+    }
+
+    fn resolve(engine: String, registry: &EngineRegistry) -> Self {
+        let factory = registry.get(&engine).cloned().map_err(|e| e.to_string());
+        Self { engine, factory }
+    }
+
+    /// Как [`Self::from_env`], но неизвестный движок — сразу ошибка.
+    pub fn try_from_env() -> Result<Self> {
+        let f = Self::from_env();
+        if let Err(e) = &f.factory {
+            anyhow::bail!("{e}");
         }
+        Ok(f)
+    }
+
+    /// Имя выбранного движка (как задано в окружении).
+    pub fn engine(&self) -> &str {
+        &self.engine
+    }
+
+    fn factory(&self) -> Result<&Arc<dyn VerifyFactory>> {
+        self.factory.as_ref().map_err(|e| anyhow::anyhow!("{e}"))
     }
 }
 
@@ -32,11 +128,39 @@ impl Default for EnvVerifyFactory {
 
 impl VerifyFactory for EnvVerifyFactory {
     fn compile(&self, wildcard_normalized: &str) -> Result<Arc<dyn VerifyEngine>> {
-        match self.engine.as_str() {
-            "regex" | _ => {
-                let eng = RegexVerify::compile_wildcard(wildcard_normalized)?;
-                Ok(Arc::new(eng))
-            }
-        }
+        self.factory()?.compile(wildcard_normalized)
+    }
+
+    fn regex_size_limit(&self) -> usize {
+        self.factory()
+            .map_or(DEFAULT_MAX_REGEX_SIZE, |f| f.regex_size_limit())
+    }
+
+    fn compile_regex(&self, pattern: &str) -> Result<Arc<dyn VerifyEngine>> {
+        self.factory()?.compile_regex(pattern)
+    }
+
+    fn compile_regex_clause(
+        &self,
+        pattern: &str,
+        a: &dyn Analyzer,
+    ) -> Result<(RegexQuery, Arc<dyn VerifyEngine>)> {
+        self.factory()?.compile_regex_clause(pattern, a)
+    }
+
+    fn compile_regex_sensitive(
+        &self,
+        pattern: &str,
+        sens: Sensitivity,
+    ) -> Result<Arc<dyn VerifyEngine>> {
+        self.factory()?.compile_regex_sensitive(pattern, sens)
+    }
+
+    fn compile_sensitive(
+        &self,
+        wildcard: &str,
+        sens: Sensitivity,
+    ) -> Result<Arc<dyn VerifyEngine>> {
+        self.factory()?.compile_sensitive(wildcard, sens)
     }
 }
@@ -0,0 +1,298 @@
+// crates/grepzilla_segment/src/verify/literal_impl.rs
+use aho_corasick::{AhoCorasick, Input, Span, packed};
+use memchr::memmem;
+
+use super::VerifyEngine;
+use crate::wildcard::{Token, split_anchors};
+
+/// Быстрый verify для wildcard только из литералов и `*` (`*lit*`,
+/// `*lit1*lit2*`, `lit*`): литералы ищутся по порядку `memmem` (без учёта
+/// регистра — Aho-Corasick с ASCII-свёрткой), без regex. Результат и спаны
+/// `find` — те же, что у якорного regex из [`crate::wildcard::to_regex`]:
+/// начало — самое левое вхождение первого литерала, конец — самое правое
+/// вхождение последнего (жадная `.*`).
+pub struct LiteralVerify {
+    lits: Vec<Literal>,
+    open_start: bool,
+    open_end: bool,
+}
+
+impl LiteralVerify {
+    /// `None` — паттерн не для этого пути (`?` или литерал, который без учёта
+    /// регистра нельзя сравнить побайтно, см. [`caseless_bytes`]); тогда
+    /// остаётся regex.
+    pub fn compile(wildcard: &str, caseless: bool) -> Option<Self> {
+        let (open_start, tokens, open_end) = split_anchors(wildcard);
+        let mut lits = Vec::new();
+        for t in tokens {
+            match t {
+                Token::Literal(l) => {
+                    if caseless && !l.chars().all(caseless_bytes) {
+                        return None;
+                    }
+                    lits.push(Literal::new(l, caseless));
+                }
+                Token::AnyString => {}
+                Token::AnyChar => return None,
+            }
+        }
+        Some(Self {
+            lits,
+            open_start,
+            open_end,
+        })
+    }
+
+    /// `tight` — нужен точный конец спана (самое правое вхождение последнего
+    /// литерала); для `is_match` хватает любого.
+    fn span(&self, text: &str, tight: bool) -> Option<(usize, usize)> {
+        let hay = text.as_bytes();
+        let Some((first, rest)) = self.lits.split_first() else {
+            // `*` — любой текст, пустой паттерн — только пустой
+            return (self.open_start || hay.is_empty()).then_some((0, 0));
+        };
+        let Some((last, middle)) = rest.split_last() else {
+            return match (self.open_start, self.open_end) {
+                (true, true) => first.find_from(hay, 0).map(|s| (s, s + first.len())),
+                (false, true) => first.at(hay, 0).then_some((0, first.len())),
+                (true, false) => {
+                    let s = hay.len().checked_sub(first.len())?;
+                    first.at(hay, s).then_some((s, hay.len()))
+                }
+                (false, false) => {
+                    (first.len() == hay.len() && first.at(hay, 0)).then_some((0, hay.len()))
+                }
+            };
+        };
+
+        let start = if self.open_start {
+            first.find_from(hay, 0)?
+        } else {
+            first.at(hay, 0).then_some(0)?
+        };
+        let mut pos = start + first.len();
+        for lit in middle {
+            pos = lit.find_from(hay, pos)? + lit.len();
+        }
+        let last_start = if self.open_end && tight {
+            last.rfind_from(hay, pos)?
+        } else if self.open_end {
+            last.find_from(hay, pos)?
+        } else {
+            let s = hay.len().checked_sub(last.len())?;
+            (s >= pos && last.at(hay, s)).then_some(s)?
+        };
+        Some((start, last_start + last.len()))
+    }
+}
+
+impl VerifyEngine for LiteralVerify {
+    #[inline]
+    fn is_match(&self, text: &str) -> bool {
+        self.span(text, false).is_some()
+    }
+
+    #[inline]
+    fn find(&self, text: &str) -> Option<(usize, usize)> {
+        self.span(text, true)
+    }
+}
+
+/// Символ литерала можно сравнивать побайтно вместо `(?i)`: ASCII — с
+/// ASCII-свёрткой, прочие — только без регистра (цифры, знаки, CJK).
+/// Литерал с кириллицей и т.п. без учёта регистра остаётся за regex.
+/// Расхождение с Unicode-`(?i)` только на `K` (Kelvin) и `ſ` в тексте — после
+/// NFKC-нормализации их там нет.
+fn caseless_bytes(c: char) -> bool {
+    c.is_ascii() || (c.to_lowercase().eq([c]) && c.to_uppercase().eq([c]))
+}
+
+/// Teddy по всем ASCII-регистровым вариантам префикса литерала (префикс —
+/// пока вариантов не больше 16).
+fn case_variants_prefix(lit: &[u8]) -> Option<packed::Searcher> {
+    let mut len = 0;
+    let mut letters = 0;
+    for b in lit {
+        if b.is_ascii_alphabetic() {
+            if letters == 4 {
+                break;
+            }
+            letters += 1;
+        }
+        len += 1;
+    }
+    let prefix = &lit[..len];
+    let variants = (0..1u32 << letters).map(|mask| {
+        let mut bit = 0;
+        prefix
+            .iter()
+            .map(|&b| {
+                if !b.is_ascii_alphabetic() {
+                    return b;
+                }
+                bit += 1;
+                if mask & (1 << (bit - 1)) != 0 {
+                    b.to_ascii_uppercase()
+                } else {
+                    b.to_ascii_lowercase()
+                }
+            })
+            .collect::<Vec<u8>>()
+    });
+    packed::Config::new().builder().extend(variants).build()
+}
+
+/// Литерал и его поисковик.
+struct Literal {
+    bytes: Vec<u8>,
+    finder: Finder,
+}
+
+enum Finder {
+    Exact(Box<(memmem::Finder<'static>, memmem::FinderRev<'static>)>),
+    /// ASCII без учёта регистра: автомат (`Standard` — ради перекрывающихся
+    /// вхождений при поиске самого правого) и, если собрался, Teddy по
+    /// регистровым вариантам префикса — кандидаты для поиска слева
+    Caseless(AhoCorasick, Option<packed::Searcher>),
+}
+
+impl Literal {
+    fn new(lit: String, caseless: bool) -> Self {
+        let bytes = lit.into_bytes();
+        let finder = if caseless && bytes.iter().any(u8::is_ascii_alphabetic) {
+            Finder::Caseless(
+                AhoCorasick::builder()
+                    .ascii_case_insensitive(true)
+                    .build([&bytes])
+                    .expect("single literal automaton"),
+                case_variants_prefix(&bytes),
+            )
+        } else {
+            Finder::Exact(Box::new((
+                memmem::Finder::new(&bytes).into_owned(),
+                memmem::FinderRev::new(&bytes).into_owned(),
+            )))
+        };
+        Self { bytes, finder }
+    }
+
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Литерал стоит в `hay` с позиции `pos`.
+    fn at(&self, hay: &[u8], pos: usize) -> bool {
+        let Some(s) = hay.get(pos..pos + self.len()) else {
+            return false;
+        };
+        match self.finder {
+            Finder::Exact(..) => s == self.bytes,
+            Finder::Caseless(..) => s.eq_ignore_ascii_case(&self.bytes),
+        }
+    }
+
+    /// Самое левое вхождение, начинающееся не раньше `from`.
+    fn find_from(&self, hay: &[u8], from: usize) -> Option<usize> {
+        match &self.finder {
+            Finder::Exact(f) => f.0.find(hay.get(from..)?).map(|i| from + i),
+            Finder::Caseless(_, Some(prefix)) => {
+                let mut from = from;
+                while from < hay.len() {
+                    let s = prefix.find_in(hay, Span::from(from..hay.len()))?.start();
+                    if self.at(hay, s) {
+                        return Some(s);
+                    }
+                    from = s + 1;
+                }
+                None
+            }
+            Finder::Caseless(ac, None) => ac
+                .find(Input::new(hay).span(from.min(hay.len())..hay.len()))
+                .map(|m| m.start()),
+        }
+    }
+
+    /// Самое правое вхождение, начинающееся не раньше `from`.
+    fn rfind_from(&self, hay: &[u8], from: usize) -> Option<usize> {
+        match &self.finder {
+            Finder::Exact(f) => f.1.rfind(hay.get(from..)?).map(|i| from + i),
+            Finder::Caseless(ac, _) => ac
+                .find_overlapping_iter(Input::new(hay).span(from.min(hay.len())..hay.len()))
+                .last()
+                .map(|m| m.start()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify::RegexVerify;
+
+    #[test]
+    fn agrees_with_regex() {
+        let patterns = [
+            "*",
+            "",
+            "foo",
+            "foo*",
+            "*foo",
+            "*foo*",
+            "**foo**",
+            "*aa*",
+            "*a*a*",
+            "a*a",
+            "*ab*ba*",
+            "ab*",
+            "*ab",
+            "ab*cd",
+            "*ab*cd",
+            "ab*cd*",
+            "*Fo*",
+            "*timeout*",
+            "*t-1:x*",
+        ];
+        let texts = [
+            "",
+            "foo",
+            "foofoo",
+            "a",
+            "aa",
+            "aaa",
+            "aba",
+            "abba",
+            "ab ba ab",
+            "xfoo",
+            "foox",
+            "abcd",
+            "ab-cd-ab-cd",
+            "FOO",
+            "a\nb",
+            "ёfooё",
+            "xTIMEOUT timeOut",
+            "t-1:t-1:X",
+        ];
+        for p in patterns {
+            for caseless in [true, false] {
+                let lit = LiteralVerify::compile(p, caseless).unwrap();
+                let rx = if caseless {
+                    RegexVerify::compile_wildcard(p)
+                } else {
+                    RegexVerify::compile_wildcard_case_sensitive(p)
+                }
+                .unwrap();
+                for t in texts {
+                    assert_eq!(lit.find(t), rx.find(t), "{p:?} ~ {t:?} caseless={caseless}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn falls_back_to_regex() {
+        assert!(LiteralVerify::compile("f?o", true).is_none());
+        assert!(LiteralVerify::compile("*ошибка*", true).is_none());
+        assert!(LiteralVerify::compile("*ошибка*", false).is_some());
+        assert!(LiteralVerify::compile("*err 42*", true).is_some());
+    }
+}
@@ -2,13 +2,22 @@
 use anyhow::Result;
 use std::sync::Arc;
 
+mod literal_impl;
+mod multi;
 #[cfg(feature = "engine-pcre2")]
 mod pcre2_impl;
+#[cfg(feature = "engine-pcre2")]
+mod pcre2_syntax;
 mod regex_impl;
 
 pub mod factory;
+pub mod registry;
 pub use factory::{EnvVerifyFactory, VerifyFactory};
+pub use registry::{DEFAULT_ENGINE, EngineRegistry};
 
+pub use literal_impl::LiteralVerify;
+pub use multi::MultiVerifyEngine;
+pub use regex_impl::RegexFactory;
 pub use regex_impl::RegexVerify; // оставляем для совместимости там, где он явно использовался
 
 /// Унифицированный интерфейс верификации совпадений.
@@ -24,28 +33,25 @@ pub trait VerifyEngine: Send + Sync {
 /// Wildcard → regex-строка с семантикой (?si) (dotall + case-insensitive)
 /// Нужен как утилита в некоторых местах.
 pub fn wildcard_to_regex_case_insensitive(pat: &str) -> String {
-    let mut rx = String::from("(?si)");
-    for ch in pat.chars() {
-        match ch {
-            '*' => rx.push_str(".*"),
-            '?' => rx.push('.'),
-            c => {
-                if "\\.^$|()[]{}+*?".contains(c) {
-                    rx.push('\\');
-                }
-                rx.push(c);
-            }
-        }
-    }
-    rx
+    wildcard_to_regex("(?si)", pat)
+}
+
+/// Wildcard → regex-строка с семантикой (?s): регистр различается
+/// (строгий verify, см. [`crate::normalizer::Sensitivity`]).
+pub fn wildcard_to_regex_case_sensitive(pat: &str) -> String {
+    wildcard_to_regex("(?s)", pat)
+}
+
+fn wildcard_to_regex(flags: &str, pat: &str) -> String {
+    format!("{flags}{}", crate::wildcard::to_regex(pat))
 }
 
-/// Компиляция движка из wildcard с учётом переменной окружения GZ_VERIFY.
+/// Компиляция движка из wildcard с учётом переменной окружения `GZ_VERIFY`.
 /// Возвращает `Arc<dyn VerifyEngine>` для прозрачно-заменяемой реализации.
 ///
-/// Примечание: нормализацию wildcard делаем здесь же, чтобы call-site всегда
-/// передавал «сырой» паттерн.
+/// Примечание: нормализацию литералов wildcard делаем здесь же, чтобы
+/// call-site всегда передавал «сырой» паттерн.
 pub fn compile_wildcard_engine(raw_wildcard: &str) -> Result<Arc<dyn VerifyEngine>> {
-    let norm = crate::normalizer::normalize(raw_wildcard);
+    let norm = crate::wildcard::map_literals(raw_wildcard, crate::normalizer::normalize);
     EnvVerifyFactory::from_env().compile(&norm)
 }
@@ -0,0 +1,90 @@
+// crates/grepzilla_segment/src/verify/multi.rs
+use anyhow::Result;
+use regex::{RegexSet, RegexSetBuilder};
+
+use super::{wildcard_to_regex_case_insensitive, wildcard_to_regex_case_sensitive};
+use crate::limits::LimitExceeded;
+
+/// Verify N wildcard-паттернов за один проход по тексту (`RegexSet`):
+/// отвечает, какие из паттернов совпали. Спан для подсветки даёт движок
+/// отдельного паттерна.
+pub struct MultiVerifyEngine {
+    set: RegexSet,
+}
+
+impl MultiVerifyEngine {
+    /// Паттерны `wildcards` нормализованы; `case_sensitive` — регистр
+    /// сверяется как есть (анализатор поля его сохраняет). Программа всего
+    /// набора больше `size_limit` байт — [`LimitExceeded`] (`max_regex_size`).
+    pub fn compile<S: AsRef<str>>(
+        wildcards: &[S],
+        case_sensitive: bool,
+        size_limit: usize,
+    ) -> Result<Self> {
+        let to_regex = if case_sensitive {
+            wildcard_to_regex_case_sensitive
+        } else {
+            wildcard_to_regex_case_insensitive
+        };
+        let set = RegexSetBuilder::new(wildcards.iter().map(|w| to_regex(w.as_ref())))
+            .size_limit(size_limit)
+            .build()
+            .map_err(|e| match e {
+                regex::Error::CompiledTooBig(_) => LimitExceeded::regex_size(size_limit).into(),
+                e => anyhow::Error::from(e),
+            })?;
+        Ok(Self { set })
+    }
+
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// Номера совпавших паттернов по возрастанию (в порядке компиляции).
+    pub fn matches(&self, text: &str) -> impl Iterator<Item = usize> {
+        self.set.matches(text).into_iter()
+    }
+
+    #[inline]
+    pub fn is_match(&self, text: &str) -> bool {
+        self.set.is_match(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::DEFAULT_MAX_REGEX_SIZE;
+
+    #[test]
+    fn reports_every_matching_pattern() {
+        let e = MultiVerifyEngine::compile(
+            &["*timeout*", "conn*", "*refused", "x?z"],
+            false,
+            DEFAULT_MAX_REGEX_SIZE,
+        )
+        .unwrap();
+        let m: Vec<_> = e.matches("Connection timeout").collect();
+        assert_eq!(m, [0, 1]);
+        assert_eq!(e.matches("xyz").collect::<Vec<_>>(), [3]);
+        assert!(!e.is_match("nothing here"));
+
+        let strict =
+            MultiVerifyEngine::compile(&["*Timeout*"], true, DEFAULT_MAX_REGEX_SIZE).unwrap();
+        assert!(!strict.is_match("timeout"));
+    }
+
+    #[test]
+    fn size_limit_covers_the_whole_set() {
+        let one = ["*a?b?c?d*"];
+        assert!(MultiVerifyEngine::compile(&one, false, 20_000).is_ok());
+        let many = vec!["*a?b?c?d*"; 64];
+        let err = MultiVerifyEngine::compile(&many, false, 20_000).err().unwrap();
+        let limit = err.downcast_ref::<LimitExceeded>().expect("limit error");
+        assert_eq!((limit.limit, limit.max), ("max_regex_size", 20_000));
+    }
+}
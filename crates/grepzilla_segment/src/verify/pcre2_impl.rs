@@ -1,5 +1,11 @@
 // crates/grepzilla_segment/src/verify/pcre2_impl.rs
-use super::{VerifyEngine, VerifyFactory};
+use super::pcre2_syntax::rewrite;
+use super::{LiteralVerify, VerifyEngine, VerifyFactory};
+use crate::analyzer::{Analyzer, DEFAULT_ANALYZER};
+use crate::gram::GramQuery;
+use crate::normalizer::{Sensitivity, normalize_with};
+use crate::query::InvalidQuery;
+use crate::regex_grams::{RegexQuery, parse_regex_with};
 use anyhow::Result;
 use pcre2::bytes::{Regex, RegexBuilder};
 use std::sync::Arc;
@@ -26,37 +32,99 @@ impl VerifyEngine for Pcre2Engine {
     }
 }
 
-pub struct Pcre2Factory;
+/// Фабрика движка `pcre2` (JIT, если он доступен в сборке libpcre2); паттерн
+/// из одних литералов и `*` — быстрым путём [`LiteralVerify`].
+/// Пункты `regex` — в синтаксисе PCRE2 (проверки вокруг, обратные ссылки);
+/// формула грамм — по приближению из [`super::pcre2_syntax`].
+///
+/// Предела программы крейт `pcre2` не выставляет: у PCRE2 он свой
+/// (`LINK_SIZE`), превышение — ошибка компиляции. `size_limit` — для
+/// `RegexSet` мультипоиска ([`VerifyFactory::regex_size_limit`]).
+pub struct Pcre2Factory {
+    size_limit: usize,
+}
 
-impl VerifyFactory for Pcre2Factory {
-    fn compile(&self, normalized_wildcard: &str) -> Result<Arc<dyn VerifyEngine>> {
-        let pat = wildcard_to_regex_pattern(normalized_wildcard);
+impl Pcre2Factory {
+    pub fn with_size_limit(bytes: usize) -> Self {
+        Self { size_limit: bytes }
+    }
+
+    fn build(normalized_wildcard: &str, caseless: bool) -> Result<Arc<dyn VerifyEngine>> {
+        if let Some(lit) = LiteralVerify::compile(normalized_wildcard, caseless) {
+            return Ok(Arc::new(lit));
+        }
+        let pat = crate::wildcard::to_regex(normalized_wildcard);
         let rx = RegexBuilder::new()
-            .caseless(true) // (?i)
+            .utf(true)
+            .ucp(true)
+            .caseless(caseless) // (?i)
             .dotall(true) // (?s)
+            .jit_if_available(true)
             .build(&pat)
             .map_err(|e| anyhow::anyhow!("pcre2 compile error: {e}"))?;
         Ok(Arc::new(Pcre2Engine { rx }))
     }
+
+    fn build_regex(pattern: &str, caseless: bool) -> Result<Arc<dyn VerifyEngine>> {
+        let rx = RegexBuilder::new()
+            .utf(true)
+            .ucp(true)
+            .caseless(caseless)
+            .jit_if_available(true)
+            .build(pattern)
+            .map_err(|e| InvalidQuery(format!("bad regex: {e}")))?;
+        Ok(Arc::new(Pcre2Engine { rx }))
+    }
 }
 
-/// Преобразование normalized_wildcard -> regex-паттерн:
-/// '*' => '.*', '?' => '.', остальные символы — экранируем.
-/// (Флаги (?si) задаются через RegexBuilder, поэтому их здесь НЕ добавляем.)
-fn wildcard_to_regex_pattern(norm_wildcard: &str) -> String {
-    let mut out = String::new();
-    for ch in norm_wildcard.chars() {
-        match ch {
-            '*' => out.push_str(".*"),
-            '?' => out.push('.'),
-            _ => {
-                // экранируем спецсимволы PCRE
-                if "\\.^$|()[]{}+*?".contains(ch) {
-                    out.push('\\');
-                }
-                out.push(ch);
-            }
-        }
+impl VerifyFactory for Pcre2Factory {
+    fn compile(&self, normalized_wildcard: &str) -> Result<Arc<dyn VerifyEngine>> {
+        Self::build(normalized_wildcard, true)
+    }
+
+    fn regex_size_limit(&self) -> usize {
+        self.size_limit
+    }
+
+    fn compile_regex(&self, pattern: &str) -> Result<Arc<dyn VerifyEngine>> {
+        Self::build_regex(pattern, false)
+    }
+
+    fn compile_regex_clause(
+        &self,
+        pattern: &str,
+        a: &dyn Analyzer,
+    ) -> Result<(RegexQuery, Arc<dyn VerifyEngine>)> {
+        let rw = rewrite(pattern, &|s: &str| a.analyze(s));
+        // классы идут как есть: регистр снимает caseless
+        let engine = Self::build_regex(&rw.verify, a.id() == DEFAULT_ANALYZER)?;
+        let grams = rw
+            .approx
+            .and_then(|p| parse_regex_with(&p, a).ok())
+            .map_or(GramQuery::All, |q| q.grams);
+        Ok((
+            RegexQuery {
+                verify: rw.verify,
+                grams,
+            },
+            engine,
+        ))
+    }
+
+    fn compile_regex_sensitive(
+        &self,
+        pattern: &str,
+        sens: Sensitivity,
+    ) -> Result<Arc<dyn VerifyEngine>> {
+        let rw = rewrite(pattern, &|s: &str| normalize_with(s, sens));
+        Self::build_regex(&rw.verify, !sens.case_sensitive)
+    }
+
+    fn compile_sensitive(
+        &self,
+        wildcard: &str,
+        sens: Sensitivity,
+    ) -> Result<Arc<dyn VerifyEngine>> {
+        Self::build(wildcard, !sens.case_sensitive)
     }
-    out
 }
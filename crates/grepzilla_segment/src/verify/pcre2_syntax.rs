@@ -0,0 +1,494 @@
+// crates/grepzilla_segment/src/verify/pcre2_syntax.rs
+//! Пункт `regex` в синтаксисе PCRE2. `regex-syntax` его не разбирает
+//! (проверки вокруг, обратные ссылки, атомарные группы), поэтому один
+//! проход по тексту паттерна даёт:
+//!
+//! * verify-паттерн — тот же PCRE2, литералы вне классов приведены к виду
+//!   текста поля;
+//! * приближение в синтаксисе `regex` для формулы грамм: всякое совпадение
+//!   исходного выражения совпадает и с приближением. Проверки вокруг и
+//!   комментарии выброшены (нулевая ширина), обратные ссылки, рекурсия и
+//!   незнакомые `regex` экранирования заменены на `(?s:.*)`, атомарные
+//!   группы и притяжательные кванторы стали обычными. Условные группы,
+//!   `(*ACCEPT)` и режим `x` приближению не поддаются — его нет, и формула
+//!   грамм пуста.
+//!
+//! Классы символов идут в verify как есть: регистр в них снимает
+//! caseless-компиляция.
+
+use regex_syntax::escape;
+
+/// Замена для конструкций, которые `regex` не выражает: любая строка.
+const ANY: &str = "(?s:.*)";
+
+/// Экранирования, которые `regex` понимает так же, как PCRE2.
+const PORTABLE_ESCAPES: &str = "dDwWsSbBAztnrfapPx";
+
+pub(super) struct Rewrite {
+    pub verify: String,
+    /// `None` — приближения нет
+    pub approx: Option<String>,
+}
+
+/// Переписывает PCRE2-паттерн; `fold` приводит литералы.
+pub(super) fn rewrite(pattern: &str, fold: &dyn Fn(&str) -> String) -> Rewrite {
+    let mut w = Rewriter {
+        chars: pattern.chars().collect(),
+        pos: 0,
+        fold,
+        verify: String::new(),
+        approx: String::new(),
+        run: String::new(),
+        groups: Vec::new(),
+        hidden: 0,
+        opaque: false,
+        extended: false,
+    };
+    w.walk();
+    Rewrite {
+        verify: w.verify,
+        approx: (!w.opaque).then_some(w.approx),
+    }
+}
+
+struct Rewriter<'f> {
+    chars: Vec<char>,
+    pos: usize,
+    fold: &'f dyn Fn(&str) -> String,
+    verify: String,
+    approx: String,
+    /// литералы подряд: приводятся и экранируются вместе
+    run: String,
+    /// открытые группы: `true` — скрыта в приближении
+    groups: Vec<bool>,
+    /// сколько открытых групп скрыто
+    hidden: usize,
+    opaque: bool,
+    /// режим `x`: пробелы и `#…` не литералы
+    extended: bool,
+}
+
+impl Rewriter<'_> {
+    fn walk(&mut self) {
+        while let Some(c) = self.next() {
+            match c {
+                '\\' => self.escape(),
+                '[' => {
+                    self.flush(false);
+                    self.class();
+                }
+                '(' => {
+                    self.flush(false);
+                    self.group();
+                }
+                ')' => {
+                    self.flush(false);
+                    let hidden = self.groups.pop().unwrap_or(false);
+                    self.emit(")", ")");
+                    if hidden {
+                        self.hidden -= 1;
+                    }
+                }
+                '*' | '+' | '?' => {
+                    self.flush(true);
+                    let q = c.to_string();
+                    self.quantifier(&q, &q);
+                }
+                '{' => match self.counted() {
+                    Some((v, a)) => {
+                        self.flush(true);
+                        self.quantifier(&v, &a);
+                    }
+                    None => self.run.push(c),
+                },
+                '^' | '$' | '.' | '|' => {
+                    self.flush(false);
+                    let s = c.to_string();
+                    self.emit(&s, &s);
+                }
+                c if self.extended && c.is_whitespace() => {
+                    self.flush(false);
+                    self.verify.push(c);
+                }
+                '#' if self.extended => {
+                    self.flush(false);
+                    let comment = self.take_through('\n');
+                    self.verify.push('#');
+                    self.verify.push_str(&comment);
+                }
+                c => self.run.push(c),
+            }
+        }
+        self.flush(false);
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.pos).copied();
+        self.pos += c.is_some() as usize;
+        c
+    }
+
+    fn peek(&self, k: usize) -> Option<char> {
+        self.chars.get(self.pos + k).copied()
+    }
+
+    /// Символы по `end` включительно (или до конца паттерна).
+    fn take_through(&mut self, end: char) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.next() {
+            s.push(c);
+            if c == end {
+                break;
+            }
+        }
+        s
+    }
+
+    fn emit(&mut self, verify: &str, approx: &str) {
+        self.verify.push_str(verify);
+        if self.hidden == 0 {
+            self.approx.push_str(approx);
+        }
+    }
+
+    /// Сбрасывает накопленные литералы. Перед квантором последний символ
+    /// приводится отдельно: квантор должен относиться ко всему результату.
+    fn flush(&mut self, quantified: bool) {
+        if self.run.is_empty() {
+            return;
+        }
+        let run = std::mem::take(&mut self.run);
+        let mut verify = String::new();
+        let last = if quantified { run.chars().last() } else { None };
+        match last {
+            Some(last) => {
+                let head = &run[..run.len() - last.len_utf8()];
+                verify.push_str(&pcre_escape(&(self.fold)(head)));
+                let f = pcre_escape(&(self.fold)(last.encode_utf8(&mut [0; 4])));
+                if f.chars().count() == 1 || f.starts_with('\\') && f.chars().count() == 2 {
+                    verify.push_str(&f);
+                } else {
+                    verify.push_str(&format!("(?:{f})"));
+                }
+            }
+            None => verify.push_str(&pcre_escape(&(self.fold)(&run))),
+        }
+        self.emit(&verify, &escape(&run));
+    }
+
+    /// Квантор с возможными ленивым `?` и притяжательным `+`.
+    fn quantifier(&mut self, verify: &str, approx: &str) {
+        self.emit(verify, approx);
+        match self.peek(0) {
+            Some('?') => {
+                self.pos += 1;
+                self.emit("?", "?");
+            }
+            Some('+') => {
+                self.pos += 1;
+                self.emit("+", "");
+            }
+            _ => {}
+        }
+    }
+
+    /// `{n}`, `{n,}`, `{n,m}`, `{,m}` после `{`; иначе `{` — литерал.
+    fn counted(&mut self) -> Option<(String, String)> {
+        let rest: String = self.chars[self.pos..]
+            .iter()
+            .take_while(|&&c| c != '}')
+            .collect();
+        let (min, max) = match rest.split_once(',') {
+            Some((min, max)) => (min, Some(max)),
+            None => (rest.as_str(), None),
+        };
+        let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+        let valid = digits(min)
+            && max.is_none_or(digits)
+            && !(min.is_empty() && max.is_none_or(str::is_empty))
+            && self.chars.get(self.pos + rest.chars().count()) == Some(&'}');
+        if !valid {
+            return None;
+        }
+        self.pos += rest.chars().count() + 1;
+        let approx = match max {
+            Some(max) => format!("{{{},{max}}}", if min.is_empty() { "0" } else { min }),
+            None => format!("{{{min}}}"),
+        };
+        Some((format!("{{{rest}}}"), approx))
+    }
+
+    fn escape(&mut self) {
+        let Some(c) = self.next() else {
+            self.verify.push('\\');
+            return;
+        };
+        if c == 'Q' {
+            // `\Q…\E` — литералы
+            while let Some(c) = self.next() {
+                if c == '\\' && self.peek(0) == Some('E') {
+                    self.pos += 1;
+                    break;
+                }
+                self.run.push(c);
+            }
+            return;
+        }
+        if !c.is_ascii_alphanumeric() {
+            self.run.push(c);
+            return;
+        }
+        self.flush(false);
+        let mut esc = format!("\\{c}");
+        match (c, self.peek(0)) {
+            ('p' | 'P' | 'x' | 'o' | 'N' | 'g' | 'k', Some('{')) => esc += &self.take_through('}'),
+            ('g' | 'k', Some('<')) => esc += &self.take_through('>'),
+            ('g' | 'k', Some('\'')) => {
+                self.pos += 1;
+                esc += &format!("'{}", self.take_through('\''));
+            }
+            ('p' | 'P' | 'c', Some(n)) => {
+                self.pos += 1;
+                esc.push(n);
+            }
+            ('x', _) => {
+                while esc.len() < 4 && self.peek(0).is_some_and(|n| n.is_ascii_hexdigit()) {
+                    esc.extend(self.next());
+                }
+            }
+            ('g', Some('+' | '-')) | ('0'..='9' | 'g', _) => {
+                if matches!(self.peek(0), Some('+' | '-')) {
+                    esc.extend(self.next());
+                }
+                while self.peek(0).is_some_and(|n| n.is_ascii_digit()) {
+                    esc.extend(self.next());
+                }
+            }
+            _ => {}
+        }
+        let approx = if PORTABLE_ESCAPES.contains(c) {
+            esc.clone()
+        } else {
+            ANY.to_string()
+        };
+        self.emit(&esc, &approx);
+    }
+
+    /// Класс после `[`: в verify как есть; в приближение — как есть, если
+    /// `regex` прочтёт его так же, иначе любой символ.
+    fn class(&mut self) {
+        let start = self.pos - 1;
+        if self.peek(0) == Some('^') {
+            self.pos += 1;
+        }
+        if self.peek(0) == Some(']') {
+            self.pos += 1;
+        }
+        let mut portable = true;
+        while let Some(c) = self.next() {
+            match c {
+                ']' => break,
+                '\\' => {
+                    let e = self.next();
+                    if e.is_some_and(|e| e.is_ascii_alphanumeric() && !PORTABLE_ESCAPES.contains(e))
+                    {
+                        portable = false;
+                    }
+                    if e.is_some_and(|e| "pPxoN".contains(e)) && self.peek(0) == Some('{') {
+                        self.take_through('}');
+                    }
+                }
+                '[' if self.peek(0) == Some(':') => {
+                    while let Some(c) = self.next() {
+                        if c == ']' {
+                            break;
+                        }
+                    }
+                }
+                // `[` и `&&`, `--`, `~~` — вложенные классы и операции `regex`
+                '[' => portable = false,
+                '&' | '-' | '~' if self.peek(0) == Some(c) => portable = false,
+                _ => {}
+            }
+        }
+        let class: String = self.chars[start..self.pos].iter().collect();
+        self.emit(&class, if portable { &class } else { "(?s:.)" });
+    }
+
+    /// Группа после `(`.
+    fn group(&mut self) {
+        match (self.peek(0), self.peek(1), self.peek(2)) {
+            (Some('?'), Some('#'), _) => {
+                let comment = self.take_through(')');
+                self.emit(&format!("({comment}"), "");
+            }
+            (Some('?'), Some('=' | '!'), _) => self.open(2, true, ""),
+            (Some('?'), Some('<'), Some('=' | '!')) => self.open(3, true, ""),
+            (Some('?'), Some(':' | '>' | '|'), _) => self.open(2, false, "(?:"),
+            (Some('?'), Some('<' | '\''), _) | (Some('?'), Some('P'), Some('<')) => {
+                let close = if self.peek(1) == Some('\'') {
+                    '\''
+                } else {
+                    '>'
+                };
+                let header = self.take_through(close);
+                self.groups.push(false);
+                self.emit(&format!("({header}"), "(");
+            }
+            (Some('?'), Some('P'), Some('=' | '>'))
+            | (Some('?'), Some('+' | '-'), Some('0'..='9'))
+            | (Some('?'), Some('R' | '&' | '0'..='9'), _) => {
+                let call = self.take_through(')');
+                self.emit(&format!("({call}"), ANY);
+            }
+            (Some('?'), Some('C'), _) => {
+                let callout = self.take_through(')');
+                self.emit(&format!("({callout}"), "");
+            }
+            (Some('?'), Some('('), _) => {
+                // условная группа; условие — не литералы
+                self.opaque = true;
+                if self.peek(2) == Some('?') {
+                    self.open(1, false, "");
+                } else {
+                    self.pos += 2;
+                    let cond = self.take_through(')');
+                    self.groups.push(false);
+                    self.emit(&format!("(?({cond}"), "");
+                }
+            }
+            (Some('?'), _, _) => self.flags(),
+            (Some('*'), _, _) => self.verb(),
+            _ => self.open(0, false, "("),
+        }
+    }
+
+    /// Открывает группу: заголовок — `len` символов после `(`.
+    fn open(&mut self, len: usize, hidden: bool, approx: &str) {
+        let header: String = self.chars[self.pos..self.pos + len].iter().collect();
+        self.pos += len;
+        if hidden {
+            self.hidden += 1;
+        }
+        self.groups.push(hidden);
+        self.emit(&format!("({header}"), approx);
+    }
+
+    /// `(?flags)` и `(?flags:…)`.
+    fn flags(&mut self) {
+        let mut header = String::new();
+        while let Some(c) = self.next() {
+            header.push(c);
+            if c == ')' || c == ':' {
+                break;
+            }
+        }
+        let flags = header.trim_start_matches('?').trim_end_matches([')', ':']);
+        if flags.contains('x') && !flags.starts_with('-') {
+            self.extended = true;
+            self.opaque = true;
+        }
+        let portable = flags.chars().all(|c| "imsU-".contains(c));
+        let approx = match (portable, header.ends_with(':')) {
+            (true, _) => format!("({header}"),
+            (false, true) => "(?:".to_string(),
+            (false, false) => String::new(),
+        };
+        if header.ends_with(':') {
+            self.groups.push(false);
+        }
+        self.emit(&format!("({header}"), &approx);
+    }
+
+    /// `(*VERB)` и `(*name:…)`.
+    fn verb(&mut self) {
+        let name: String = self.chars[self.pos + 1..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+            .collect();
+        let group = name.starts_with(|c: char| c.is_ascii_lowercase())
+            && self.chars.get(self.pos + 1 + name.len()) == Some(&':');
+        if group {
+            let lookaround = name.contains("look") || name.ends_with("la") || name.ends_with("lb");
+            // `*name:` — заголовок группы
+            self.open(
+                name.len() + 2,
+                lookaround,
+                if lookaround { "" } else { "(?:" },
+            );
+            return;
+        }
+        if name == "ACCEPT" {
+            self.opaque = true;
+        }
+        let verb = self.take_through(')');
+        self.emit(&format!("({verb}"), "");
+    }
+}
+
+/// Экранирование литералов для PCRE2; пробел и `#` — на случай режима `x`.
+fn pcre_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if r"\^$.|?*+()[]{}# ".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lower(s: &str) -> String {
+        s.to_lowercase()
+    }
+
+    fn rw(p: &str) -> (String, Option<String>) {
+        let r = rewrite(p, &lower);
+        (r.verify, r.approx)
+    }
+
+    #[test]
+    fn literals_are_folded_outside_classes() {
+        let (v, a) = rw(r"Foo[A-Z]\d+Bar");
+        assert_eq!(v, r"foo[A-Z]\d+bar");
+        assert_eq!(a.as_deref(), Some(r"Foo[A-Z]\d+Bar"));
+    }
+
+    #[test]
+    fn lookaround_is_dropped_from_approximation() {
+        let (v, a) = rw("(?<=Connection )Timeout(?!s)");
+        assert_eq!(v, r"(?<=connection\ )timeout(?!s)");
+        assert_eq!(a.as_deref(), Some("Timeout"));
+    }
+
+    #[test]
+    fn backrefs_and_atomic_groups_are_widened() {
+        let (v, a) = rw(r"(?>ab+)\1x*+");
+        assert_eq!(v, r"(?>ab+)\1x*+");
+        assert_eq!(a.as_deref(), Some("(?:ab+)(?s:.*)x*"));
+        let (_, a) = rw(r"(?<w>abc)-\k<w>");
+        assert_eq!(a.as_deref(), Some("(abc)\\-(?s:.*)"));
+    }
+
+    #[test]
+    fn quantifier_binds_to_folded_last_char() {
+        let r = rewrite("straße+", &|s: &str| s.replace('ß', "ss"));
+        assert_eq!(r.verify, "strasse+");
+        let r = rewrite("straß+", &|s: &str| s.replace('ß', "ss"));
+        assert_eq!(r.verify, "stra(?:ss)+");
+        assert_eq!(rw("a{,3}b{2}").1.as_deref(), Some("a{0,3}b{2}"));
+        assert_eq!(rw("a{x}").0, r"a\{x\}");
+    }
+
+    #[test]
+    fn conditionals_and_extended_mode_have_no_approximation() {
+        assert_eq!(rw("(a)?(?(1)b|c)"), ("(a)?(?(1)b|c)".into(), None));
+        assert_eq!(rw("(?(DEFINE)(?<n>X))").0, "(?(DEFINE)(?<n>x))");
+        assert_eq!(rw("(?x) a b # c").1, None);
+        assert_eq!(rw(r"\Qa.b\E").0, r"a\.b");
+    }
+}
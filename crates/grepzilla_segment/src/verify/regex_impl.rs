@@ -1,8 +1,13 @@
 // crates/grepzilla_segment/src/verify/regex_impl.rs
 use anyhow::Result;
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 
-use super::{VerifyEngine, wildcard_to_regex_case_insensitive};
+use super::{
+    LiteralVerify, VerifyEngine, VerifyFactory, wildcard_to_regex_case_insensitive,
+    wildcard_to_regex_case_sensitive,
+};
+use crate::limits::{DEFAULT_MAX_REGEX_SIZE, LimitExceeded};
+use std::sync::Arc;
 
 /// Базовая реализация VerifyEngine на regex
 pub struct RegexVerify {
@@ -11,15 +16,31 @@ pub struct RegexVerify {
 
 impl RegexVerify {
     pub fn compile_regex(pat: &str) -> Result<Self> {
-        Ok(Self {
-            rx: Regex::new(pat)?,
-        })
+        Self::compile_regex_limited(pat, DEFAULT_MAX_REGEX_SIZE)
+    }
+
+    /// Программа больше `size_limit` байт — [`LimitExceeded`]
+    /// (`max_regex_size`).
+    pub fn compile_regex_limited(pat: &str, size_limit: usize) -> Result<Self> {
+        let rx = RegexBuilder::new(pat)
+            .size_limit(size_limit)
+            .build()
+            .map_err(|e| match e {
+                regex::Error::CompiledTooBig(_) => LimitExceeded::regex_size(size_limit).into(),
+                e => anyhow::Error::from(e),
+            })?;
+        Ok(Self { rx })
     }
 
     pub fn compile_wildcard(wildcard: &str) -> Result<Self> {
         let pat = wildcard_to_regex_case_insensitive(wildcard);
         Self::compile_regex(&pat)
     }
+
+    pub fn compile_wildcard_case_sensitive(wildcard: &str) -> Result<Self> {
+        let pat = wildcard_to_regex_case_sensitive(wildcard);
+        Self::compile_regex(&pat)
+    }
 }
 
 impl VerifyEngine for RegexVerify {
@@ -33,3 +54,38 @@ impl VerifyEngine for RegexVerify {
         self.rx.find(text).map(|m| (m.start(), m.end()))
     }
 }
+
+/// Фабрика движка `regex` (по умолчанию). Паттерн из одних литералов и
+/// `*` идёт быстрым путём [`LiteralVerify`].
+pub struct RegexFactory {
+    size_limit: usize,
+}
+
+impl Default for RegexFactory {
+    fn default() -> Self {
+        Self::with_size_limit(DEFAULT_MAX_REGEX_SIZE)
+    }
+}
+
+impl RegexFactory {
+    /// Программа regex не больше `bytes` байт (`max_regex_size`).
+    pub fn with_size_limit(bytes: usize) -> Self {
+        Self { size_limit: bytes }
+    }
+}
+
+impl VerifyFactory for RegexFactory {
+    fn compile(&self, wildcard_normalized: &str) -> Result<Arc<dyn VerifyEngine>> {
+        if let Some(lit) = LiteralVerify::compile(wildcard_normalized, true) {
+            return Ok(Arc::new(lit));
+        }
+        Ok(Arc::new(RegexVerify::compile_regex_limited(
+            &wildcard_to_regex_case_insensitive(wildcard_normalized),
+            self.size_limit,
+        )?))
+    }
+
+    fn regex_size_limit(&self) -> usize {
+        self.size_limit
+    }
+}
@@ -0,0 +1,99 @@
+// crates/grepzilla_segment/src/verify/registry.rs
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::VerifyFactory;
+use super::regex_impl::RegexFactory;
+use crate::limits::DEFAULT_MAX_REGEX_SIZE;
+
+/// Движок verify по умолчанию.
+pub const DEFAULT_ENGINE: &str = "regex";
+
+/// Движки, которые собираются только с фичей крейта: имя → фича.
+const FEATURE_ENGINES: &[(&str, &str)] = &[("pcre2", "engine-pcre2")];
+
+/// Реестр движков verify: имя → фабрика. Встроенные — `regex` всегда и
+/// `pcre2` с фичей `engine-pcre2`; новые движки добавляются [`Self::register`].
+#[derive(Clone)]
+pub struct EngineRegistry {
+    engines: BTreeMap<String, Arc<dyn VerifyFactory>>,
+}
+
+impl Default for EngineRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl EngineRegistry {
+    /// Реестр со встроенными движками этой сборки.
+    pub fn builtin() -> Self {
+        Self::with_regex_size_limit(DEFAULT_MAX_REGEX_SIZE)
+    }
+
+    /// Встроенные движки с пределом программы regex `bytes`
+    /// (`max_regex_size`).
+    pub fn with_regex_size_limit(bytes: usize) -> Self {
+        let mut r = Self {
+            engines: BTreeMap::new(),
+        };
+        r.register(
+            DEFAULT_ENGINE,
+            Arc::new(RegexFactory::with_size_limit(bytes)),
+        );
+        #[cfg(feature = "engine-pcre2")]
+        r.register(
+            "pcre2",
+            Arc::new(super::pcre2_impl::Pcre2Factory::with_size_limit(bytes)),
+        );
+        r
+    }
+
+    /// Добавить (или заменить) движок; имя без учёта регистра.
+    pub fn register(&mut self, name: &str, factory: Arc<dyn VerifyFactory>) {
+        self.engines.insert(name.to_lowercase(), factory);
+    }
+
+    /// Фабрика движка `name`; неизвестное имя — ошибка со списком доступных.
+    pub fn get(&self, name: &str) -> Result<&Arc<dyn VerifyFactory>> {
+        let key = name.trim().to_lowercase();
+        if let Some(f) = self.engines.get(&key) {
+            return Ok(f);
+        }
+        if let Some((_, feature)) = FEATURE_ENGINES.iter().find(|(n, _)| *n == key) {
+            bail!("verify engine `{key}` is not built in; rebuild with feature `{feature}`");
+        }
+        bail!(
+            "unknown verify engine `{name}`; available: {}",
+            self.names().collect::<Vec<_>>().join(", ")
+        )
+    }
+
+    /// Имена зарегистрированных движков по алфавиту.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.engines.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_engine_fails() {
+        let r = EngineRegistry::builtin();
+        assert!(r.get("regex").is_ok());
+        assert!(r.get("Regex").is_ok());
+        let err = r.get("hyperscan").err().unwrap().to_string();
+        assert!(err.contains("unknown verify engine"), "{err}");
+        assert!(err.contains("regex"), "{err}");
+        #[cfg(not(feature = "engine-pcre2"))]
+        {
+            let err = r.get("pcre2").err().unwrap().to_string();
+            assert!(err.contains("engine-pcre2"), "{err}");
+        }
+        #[cfg(feature = "engine-pcre2")]
+        assert!(r.get("pcre2").is_ok());
+    }
+}
@@ -0,0 +1,164 @@
+// crates/grepzilla_segment/src/wildcard.rs
+//! Синтаксис wildcard-паттерна: `*` — любая строка, `?` — любой символ,
+//! `\` экранирует следующий символ (`\*`, `\?`, `\\`); `\` в конце паттерна —
+//! сам обратный слэш. Паттерн сопоставляется со всем значением поля:
+//! `foo*` — значение начинается с `foo`, `*foo` — заканчивается, `*foo*` —
+//! содержит, `foo` — равно.
+//!
+//! Анализатор (см. [`crate::analyzer`]) применяется только к литералам
+//! ([`map_literals`]), поэтому не может превратить символ текста в `*`/`?`.
+
+/// Элемент паттерна.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// литерал без экранирования (соседние символы склеены)
+    Literal(String),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyString,
+}
+
+/// Разобрать паттерн на литералы и метасимволы.
+pub fn parse(pattern: &str) -> Vec<Token> {
+    let mut out = Vec::new();
+    let mut lit = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        let meta = match c {
+            '\\' => {
+                lit.push(chars.next().unwrap_or('\\'));
+                continue;
+            }
+            '*' => Token::AnyString,
+            '?' => Token::AnyChar,
+            c => {
+                lit.push(c);
+                continue;
+            }
+        };
+        if !lit.is_empty() {
+            out.push(Token::Literal(std::mem::take(&mut lit)));
+        }
+        out.push(meta);
+    }
+    if !lit.is_empty() {
+        out.push(Token::Literal(lit));
+    }
+    out
+}
+
+/// Непустые литералы паттерна (без экранирования).
+pub fn literals(pattern: &str) -> Vec<String> {
+    parse(pattern)
+        .into_iter()
+        .filter_map(|t| match t {
+            Token::Literal(l) => Some(l),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Экранировать метасимволы wildcard в литерале.
+pub fn escape(literal: &str) -> String {
+    let mut out = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if matches!(c, '*' | '?' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Привести литералы паттерна функцией `f` (нормализация, анализатор),
+/// сохранив метасимволы; результат — снова паттерн с экранированием.
+pub fn map_literals(pattern: &str, f: impl Fn(&str) -> String) -> String {
+    parse(pattern)
+        .into_iter()
+        .map(|t| match t {
+            Token::Literal(l) => escape(&f(&l)),
+            Token::AnyChar => "?".to_string(),
+            Token::AnyString => "*".to_string(),
+        })
+        .collect()
+}
+
+/// Разобрать паттерн и снять `*` по краям: `(начало открыто, середина,
+/// конец открыт)`. Открытый край — значение поля может продолжаться за ним.
+pub fn split_anchors(pattern: &str) -> (bool, Vec<Token>, bool) {
+    let mut tokens = parse(pattern);
+    let lead = tokens
+        .iter()
+        .take_while(|t| **t == Token::AnyString)
+        .count();
+    let open_start = lead > 0;
+    tokens.drain(..lead);
+    let open_end = tokens.last() == Some(&Token::AnyString) || (open_start && tokens.is_empty());
+    while tokens.last() == Some(&Token::AnyString) {
+        tokens.pop();
+    }
+    (open_start, tokens, open_end)
+}
+
+/// Паттерн → регулярное выражение (без флагов) с якорями по краям, где нет
+/// `*`: ведущая/замыкающая `*` просто снимает якорь, чтобы `find` отдавал
+/// для подсветки сам литерал, а не всё значение поля. Экранирование общее
+/// для `regex` и PCRE2.
+pub fn to_regex(pattern: &str) -> String {
+    let (open_start, tokens, open_end) = split_anchors(pattern);
+
+    let mut rx = String::new();
+    if !open_start {
+        rx.push_str(r"\A");
+    }
+    for t in &tokens {
+        match t {
+            Token::Literal(l) => {
+                for c in l.chars() {
+                    if "\\.^$|()[]{}+*?".contains(c) {
+                        rx.push('\\');
+                    }
+                    rx.push(c);
+                }
+            }
+            Token::AnyChar => rx.push('.'),
+            Token::AnyString => rx.push_str(".*"),
+        }
+    }
+    if !open_end {
+        rx.push_str(r"\z");
+    }
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            parse(r"a\*b?\\*"),
+            [
+                Token::Literal(r"a*b".into()),
+                Token::AnyChar,
+                Token::Literal(r"\".into()),
+                Token::AnyString,
+            ]
+        );
+        assert_eq!(literals(r"*\?x\"), ["?x\\"]);
+        assert_eq!(map_literals(r"*A\*B*", |s| s.to_lowercase()), r"*a\*b*");
+    }
+
+    #[test]
+    fn anchors() {
+        assert_eq!(to_regex("foo"), r"\Afoo\z");
+        assert_eq!(to_regex("foo*"), r"\Afoo");
+        assert_eq!(to_regex("*foo"), r"foo\z");
+        assert_eq!(to_regex("*f?o*"), "f.o");
+        assert_eq!(to_regex("*"), "");
+        assert_eq!(to_regex("**foo**"), "foo");
+        assert_eq!(to_regex(r"*2\*3*"), r"2\*3");
+    }
+}
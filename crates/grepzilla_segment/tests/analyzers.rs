@@ -0,0 +1,205 @@
+// crates/grepzilla_segment/tests/analyzers.rs
+use grepzilla_segment::analyzer::{ANALYZERS_FILE, AnalyzerSchema, Analyzers};
+use grepzilla_segment::integrity::verify_segment;
+use grepzilla_segment::normalizer::Sensitivity;
+use grepzilla_segment::query::{BoolQuery, Clause, PrefilterOpts};
+use grepzilla_segment::segjson::{JsonSegmentReader, JsonSegmentWriter};
+use grepzilla_segment::v2::convert::{ConvertOptions, convert_v1_to_v2};
+use grepzilla_segment::v2::merge::{MergeOptions, merge_segments};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::verify::EnvVerifyFactory;
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::path::Path;
+
+const DOCS: &[&str] = &[
+    r#"{"_id":"a","text":{"title":"Ёлка","body":"привет"}}"#,
+    r#"{"_id":"b","text":{"title":"елка","body":"ghbdtn"}}"#,
+    r#"{"_id":"c","text":{"title":"ЕЛКА","body":"мир"}}"#,
+];
+
+const SCHEMA: &str = r#"{
+    "analyzers": {
+        "ru": ["case_fold", "nfkc", "yo", "strip_marks"],
+        "layout": ["lowercase", "keyboard_layout"]
+    },
+    "fields": {"text.title": "ru", "text.body": "layout"}
+}"#;
+
+fn schema() -> Analyzers {
+    Analyzers::new(serde_json::from_str::<AnalyzerSchema>(SCHEMA).unwrap()).unwrap()
+}
+
+fn input(dir: &Path) -> String {
+    let input = dir.join("docs.jsonl");
+    std::fs::write(&input, DOCS.join("\n")).unwrap();
+    input.to_str().unwrap().to_string()
+}
+
+fn build_v1(dir: &Path, name: &str, analyzers: Analyzers) -> String {
+    let out = dir.join(name).to_str().unwrap().to_string();
+    JsonSegmentWriter::default()
+        .with_analyzers(analyzers)
+        .write_segment(&input(dir), &out)
+        .unwrap();
+    out
+}
+
+fn build_v2(dir: &Path, name: &str, analyzers: Analyzers) -> String {
+    let out = dir.join(name).to_str().unwrap().to_string();
+    BinSegmentWriter::default()
+        .with_analyzers(analyzers)
+        .write_segment(&input(dir), &out)
+        .unwrap();
+    out
+}
+
+fn hits_v1(seg: &str, clause: Clause) -> Vec<String> {
+    let r = JsonSegmentReader::open_segment(seg).unwrap();
+    let q = BoolQuery::single(clause)
+        .compile_with(
+            &EnvVerifyFactory::from_env(),
+            Sensitivity::default(),
+            r.analyzers(),
+        )
+        .unwrap();
+    let bm = q
+        .prefilter(PrefilterOpts::default(), |runs, field| {
+            r.prefilter_runs(runs, field)
+        })
+        .unwrap();
+    bm.iter()
+        .filter_map(|d| r.get_doc(d))
+        .filter(|doc| q.matches(doc).is_some())
+        .map(|doc| doc.ext_id.clone())
+        .collect()
+}
+
+fn hits_v2(seg: &str, clause: Clause) -> Vec<String> {
+    let r = BinSegmentReader::open_segment(seg).unwrap();
+    let q = BoolQuery::single(clause)
+        .compile_with(
+            &EnvVerifyFactory::from_env(),
+            Sensitivity::default(),
+            r.analyzers(),
+        )
+        .unwrap();
+    let bm = q
+        .prefilter(PrefilterOpts::default(), |runs, field| {
+            r.prefilter_adjacent_after(runs, field, None)
+        })
+        .unwrap();
+    bm.iter()
+        .filter_map(|d| r.get_doc(d))
+        .filter(|doc| q.matches(doc).is_some())
+        .map(|doc| doc.ext_id.clone())
+        .collect()
+}
+
+#[test]
+fn field_analyzers_apply_to_index_and_query() {
+    let tmp = tempfile::tempdir().unwrap();
+    let v1 = build_v1(tmp.path(), "v1", schema());
+    let v2 = build_v2(tmp.path(), "v2", schema());
+    for (seg, hits) in [
+        (&v1, hits_v1 as fn(&str, Clause) -> Vec<String>),
+        (&v2, hits_v2),
+    ] {
+        assert!(Path::new(seg).join(ANALYZERS_FILE).exists());
+        assert!(verify_segment(Path::new(seg)).unwrap().is_ok(), "{seg}");
+
+        // ё → е и свёртка регистра в title
+        assert_eq!(
+            hits(seg, Clause::wildcard("*ёлка*", Some("text.title"))),
+            ["a", "b", "c"],
+            "{seg}"
+        );
+        assert_eq!(
+            hits(seg, Clause::regex("^ёлка$", Some("text.title"))),
+            ["a", "b", "c"],
+            "{seg}"
+        );
+        // раскладка в body: «привет» и «ghbdtn» — одно и то же
+        assert_eq!(
+            hits(seg, Clause::wildcard("*привет*", Some("text.body"))),
+            ["a", "b"],
+            "{seg}"
+        );
+        // без поля паттерн приводится каждым анализатором схемы
+        assert_eq!(
+            hits(seg, Clause::wildcard("*ghbdtn*", None)),
+            ["a", "b"],
+            "{seg}"
+        );
+        assert_eq!(
+            hits(seg, Clause::wildcard("*елка*", None)),
+            ["a", "b", "c"],
+            "{seg}"
+        );
+    }
+}
+
+#[test]
+fn standard_schema_is_not_written() {
+    let tmp = tempfile::tempdir().unwrap();
+    let v1 = build_v1(tmp.path(), "v1", Analyzers::default());
+    let v2 = build_v2(tmp.path(), "v2", Analyzers::default());
+    for seg in [&v1, &v2] {
+        assert!(!Path::new(seg).join(ANALYZERS_FILE).exists());
+    }
+    // стандартная нормализация раскладку не сворачивает
+    assert_eq!(hits_v2(&v2, Clause::wildcard("*ghbdtn*", None)), ["b"]);
+    assert_eq!(hits_v1(&v1, Clause::wildcard("*ghbdtn*", None)), ["b"]);
+}
+
+#[test]
+fn merge_and_convert_keep_schema() {
+    let tmp = tempfile::tempdir().unwrap();
+    let v1 = build_v1(tmp.path(), "v1", schema());
+    let a = build_v2(tmp.path(), "a", schema());
+    let plain = build_v2(tmp.path(), "plain", Analyzers::default());
+
+    let conv = tmp.path().join("conv").to_str().unwrap().to_string();
+    convert_v1_to_v2(&v1, &conv, &ConvertOptions::default()).unwrap();
+    assert_eq!(
+        BinSegmentReader::open_segment(&conv).unwrap().analyzers(),
+        &schema()
+    );
+    assert_eq!(
+        hits_v2(&conv, Clause::wildcard("*привет*", Some("text.body"))),
+        ["a", "b"]
+    );
+
+    let merged = tmp.path().join("merged").to_str().unwrap().to_string();
+    merge_segments(&[&a, &conv], &merged, &MergeOptions::default()).unwrap();
+    assert_eq!(
+        BinSegmentReader::open_segment(&merged).unwrap().analyzers(),
+        &schema()
+    );
+
+    let err = merge_segments(
+        &[&a, &plain],
+        tmp.path().join("bad").to_str().unwrap(),
+        &MergeOptions::default(),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("different analyzers"), "{err:#}");
+}
+
+#[test]
+fn broken_schema_is_reported() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build_v2(tmp.path(), "v2", schema());
+    std::fs::write(
+        Path::new(&seg).join(ANALYZERS_FILE),
+        r#"{"fields": {"text.title": "missing"}}"#,
+    )
+    .unwrap();
+    let report = verify_segment(Path::new(&seg)).unwrap();
+    assert!(
+        report.violations.iter().any(|v| v.file == ANALYZERS_FILE),
+        "{:?}",
+        report.violations
+    );
+    assert!(BinSegmentReader::open_segment(&seg).is_err());
+}
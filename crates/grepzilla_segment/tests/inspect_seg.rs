@@ -0,0 +1,92 @@
+// crates/grepzilla_segment/tests/inspect_seg.rs
+use grepzilla_segment::SegmentWriter;
+use grepzilla_segment::deletes::delete_docs;
+use grepzilla_segment::segjson::JsonSegmentWriter;
+use grepzilla_segment::v2::inspect::inspect_segment;
+use grepzilla_segment::v2::writer::{BinSegmentWriter, FieldGrams};
+
+const DOCS: &str = r#"{"_id":"a","text":{"title":"Ошибка","body":"ошибка диска"}}
+{"_id":"b","text":{"body":"ошибка сети"}}
+{"_id":"c","text":{"body":"ошибка памяти и ещё очень длинный текст про всё на свете"}}
+{"_id":"d","text":{"body":"тишина"}}
+"#;
+
+#[test]
+fn inspects_v2_segment() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("docs.jsonl");
+    std::fs::write(&input, DOCS).unwrap();
+    let seg = tmp.path().join("seg");
+    let seg = seg.to_str().unwrap();
+    BinSegmentWriter::default()
+        .with_field_grams(FieldGrams::Only(vec!["text.title".into()]))
+        .write_segment(input.to_str().unwrap(), seg)
+        .unwrap();
+    delete_docs(std::path::Path::new(seg), 4, [3]).unwrap();
+
+    let st = inspect_segment(seg, 3).unwrap();
+    assert_eq!(st.meta.doc_count, 4);
+    assert_eq!(st.meta.version, 2);
+    assert_eq!(st.deleted, 1);
+
+    // файлы: все на месте, сумма сходится, длины тел — как в meta.bin
+    let size = |name: &str| st.files.iter().find(|f| f.name == name).unwrap().bytes;
+    assert_eq!(
+        st.total_bytes,
+        st.files.iter().map(|f| f.bytes).sum::<u64>()
+    );
+    assert_eq!(size("grams.dat"), st.meta.grams_dat_len + 8);
+    assert_eq!(size("docs.dat"), st.meta.docs_dat_len + 8);
+    assert!(st.files.iter().any(|f| f.name == "deletes.roaring"));
+
+    // самые длинные постинги — граммы «ошибка » (3 документа), по ключу
+    assert_eq!(st.postings.grams, st.meta.gram_count);
+    assert_eq!(st.top_grams.len(), 3);
+    let top: Vec<(&str, u32)> = st
+        .top_grams
+        .iter()
+        .map(|g| (g.gram.as_str(), g.docs))
+        .collect();
+    assert_eq!(top, [("бка", 3), ("ибк", 3), ("ка ", 3)]);
+
+    // поля по убыванию мощности
+    let fields: Vec<(&str, u64, bool)> = st
+        .fields
+        .iter()
+        .map(|f| (f.name.as_str(), f.docs, f.field_grams))
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            ("_id", 4, false),
+            ("text.body", 4, false),
+            ("text.title", 1, true)
+        ]
+    );
+
+    assert_eq!(st.docs.count, 4);
+    assert_eq!(
+        st.docs.total_bytes,
+        st.meta.docs_dat_len - 24 - 8 * 5,
+        "payload = тело минус заголовок и offsets"
+    );
+    assert!(st.docs.max > st.docs.min);
+    assert_eq!(st.docs.histogram.iter().map(|b| b.count).sum::<u64>(), 4);
+
+    let json = serde_json::to_value(&st).unwrap();
+    assert_eq!(json["meta"]["doc_count"], 4);
+    assert_eq!(json["top_grams"][0]["docs"], 3);
+}
+
+#[test]
+fn rejects_v1_segment() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("docs.jsonl");
+    std::fs::write(&input, DOCS).unwrap();
+    let seg = tmp.path().join("v1");
+    JsonSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), seg.to_str().unwrap())
+        .unwrap();
+    let err = inspect_segment(seg.to_str().unwrap(), 5).unwrap_err();
+    assert!(err.to_string().contains("not a V2 segment"), "{err}");
+}
@@ -0,0 +1,111 @@
+// crates/grepzilla_segment/tests/live_docs.rs
+use grepzilla_segment::deletes::{delete_docs, read_generation};
+use grepzilla_segment::gram::{BooleanOp, required_gram_runs_from_wildcard};
+use grepzilla_segment::segjson::{JsonSegmentReader, JsonSegmentWriter};
+use grepzilla_segment::v2::merge::{MergeOptions, merge_segments};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::fs;
+use std::path::Path;
+
+const DOCS: &str = r#"{"_id":"a","text":{"body":"ошибка диска"}}
+{"_id":"b","text":{"body":"ошибка сети"}}
+{"_id":"c","text":{"body":"всё хорошо"}}
+{"_id":"d","text":{"body":"ошибка памяти"}}
+"#;
+
+fn ids<R: SegmentReader>(r: &R, bm: &croaring::Bitmap) -> Vec<String> {
+    bm.iter()
+        .map(|d| r.get_doc(d).unwrap().ext_id.clone())
+        .collect()
+}
+
+fn check_reader<R: SegmentReader>(seg: &Path, prefilter: impl Fn(&R) -> croaring::Bitmap) {
+    let seg_s = seg.to_str().unwrap();
+    let before = R::open_segment(seg_s).unwrap();
+    assert_eq!(ids(&before, &prefilter(&before)), ["a", "b", "d"]);
+
+    // удаляем без пересборки: "b" (doc 1) и "c" (doc 2)
+    let live = delete_docs(seg, before.doc_count(), [1, 2]).unwrap();
+    assert_eq!(live.generation(), 1);
+
+    // открытый читатель держит прежний снимок
+    assert_eq!(ids(&before, &prefilter(&before)), ["a", "b", "d"]);
+
+    let after = R::open_segment(seg_s).unwrap();
+    assert_eq!(ids(&after, &prefilter(&after)), ["a", "d"]);
+    // NOT и пустой набор грамм тоже не возвращают удалённые
+    let not = after
+        .prefilter(BooleanOp::Not, &["оши".into()], None)
+        .unwrap();
+    assert!(not.is_empty());
+
+    delete_docs(seg, after.doc_count(), [3]).unwrap();
+    assert_eq!(read_generation(seg).unwrap(), 2);
+    let last = R::open_segment(seg_s).unwrap();
+    assert_eq!(ids(&last, &prefilter(&last)), ["a"]);
+}
+
+fn write_input(dir: &Path) -> String {
+    let p = dir.join("docs.jsonl");
+    fs::write(&p, DOCS).unwrap();
+    p.to_str().unwrap().to_string()
+}
+
+#[test]
+fn v2_prefilter_skips_deleted_docs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = tmp.path().join("seg");
+    BinSegmentWriter::default()
+        .write_segment(&write_input(tmp.path()), seg.to_str().unwrap())
+        .unwrap();
+    let runs = required_gram_runs_from_wildcard("*ошибка*").unwrap();
+    check_reader::<BinSegmentReader>(&seg, |r| {
+        r.prefilter_adjacent_after(&runs, None, None).unwrap()
+    });
+    // без грамм — все живые документы
+    let r = BinSegmentReader::open_segment(seg.to_str().unwrap()).unwrap();
+    let all = r.prefilter(BooleanOp::And, &[], None).unwrap();
+    assert_eq!(ids(&r, &all), ["a"]);
+}
+
+#[test]
+fn v1_prefilter_skips_deleted_docs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = tmp.path().join("seg");
+    JsonSegmentWriter::default()
+        .write_segment(&write_input(tmp.path()), seg.to_str().unwrap())
+        .unwrap();
+    let grams = required_gram_runs_from_wildcard("*ошибка*")
+        .unwrap()
+        .concat();
+    check_reader::<JsonSegmentReader>(&seg, |r| r.prefilter(BooleanOp::And, &grams, None).unwrap());
+}
+
+#[test]
+fn merge_drops_deleted_docs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = tmp.path().join("seg");
+    BinSegmentWriter::default()
+        .write_segment(&write_input(tmp.path()), seg.to_str().unwrap())
+        .unwrap();
+    delete_docs(&seg, 4, [0]).unwrap();
+
+    let out = tmp.path().join("merged");
+    let stats = merge_segments(
+        &[seg.to_str().unwrap()],
+        out.to_str().unwrap(),
+        &MergeOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(stats.dropped_deleted, 1);
+    assert_eq!(stats.output_docs, 3);
+
+    let m = BinSegmentReader::open_segment(out.to_str().unwrap()).unwrap();
+    assert_eq!(m.live_docs().generation(), 0);
+    let bm = m
+        .prefilter(BooleanOp::And, &["оши".into(), "шиб".into()], None)
+        .unwrap();
+    assert_eq!(ids(&m, &bm), ["b", "d"]);
+}
@@ -0,0 +1,129 @@
+// crates/grepzilla_segment/tests/multi_search.rs
+use grepzilla_segment::analyzer::{AnalyzerSchema, Analyzers};
+use grepzilla_segment::multi::{CompiledMulti, Pattern};
+use grepzilla_segment::query::{InvalidQuery, PrefilterOpts};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::verify::RegexFactory;
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::path::Path;
+
+const DOCS: &[&str] = &[
+    r#"{"_id":"a","text":{"title":"Connection timeout","body":"retry later"}}"#,
+    r#"{"_id":"b","text":{"title":"Disk full","body":"connection refused"}}"#,
+    r#"{"_id":"c","text":{"title":"All good","body":"привет"}}"#,
+    r#"{"_id":"d","text":{"title":"Nothing","body":"to see"}}"#,
+];
+
+fn build(dir: &Path, analyzers: Analyzers) -> String {
+    let input = dir.join("docs.jsonl");
+    std::fs::write(&input, DOCS.join("\n")).unwrap();
+    let out = dir.join("seg").to_str().unwrap().to_string();
+    BinSegmentWriter::default()
+        .with_analyzers(analyzers)
+        .write_segment(input.to_str().unwrap(), &out)
+        .unwrap();
+    out
+}
+
+fn pattern(id: &str, wildcard: &str, field: Option<&str>) -> Pattern {
+    Pattern {
+        id: id.into(),
+        wildcard: wildcard.into(),
+        field: field.map(str::to_string),
+    }
+}
+
+/// `ext_id` → id совпавших паттернов, по кандидатам префильтра.
+fn run(seg: &str, patterns: &[Pattern]) -> Vec<(String, Vec<String>)> {
+    let r = BinSegmentReader::open_segment(seg).unwrap();
+    let m = CompiledMulti::compile(patterns, &RegexFactory::default(), r.analyzers()).unwrap();
+    let bm = m
+        .prefilter(PrefilterOpts::default(), |runs, field| {
+            r.prefilter_adjacent_after(runs, field, None)
+        })
+        .unwrap();
+    bm.iter()
+        .filter_map(|d| r.get_doc(d))
+        .filter_map(|doc| {
+            let ids: Vec<String> = m
+                .matches(doc)
+                .into_iter()
+                .map(|(i, _)| m.id(i).to_string())
+                .collect();
+            (!ids.is_empty()).then(|| (doc.ext_id.clone(), ids))
+        })
+        .collect()
+}
+
+#[test]
+fn hits_are_tagged_with_all_matching_patterns() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build(tmp.path(), Analyzers::default());
+    let hits = run(
+        &seg,
+        &[
+            pattern("timeout", "*timeout*", None),
+            pattern("conn", "*connection*", None),
+            pattern("conn-body", "*connection*", Some("text.body")),
+            pattern("never", "*kernel panic*", None),
+        ],
+    );
+    assert_eq!(
+        hits,
+        [
+            (
+                "a".to_string(),
+                vec!["timeout".to_string(), "conn".to_string()]
+            ),
+            (
+                "b".to_string(),
+                vec!["conn".to_string(), "conn-body".to_string()]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn patterns_follow_field_analyzers() {
+    let schema: AnalyzerSchema = serde_json::from_str(
+        r#"{"analyzers": {"layout": ["lowercase", "keyboard_layout"]},
+            "fields": {"text.body": "layout"}}"#,
+    )
+    .unwrap();
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build(tmp.path(), Analyzers::new(schema).unwrap());
+    let hits = run(
+        &seg,
+        &[
+            pattern("layout", "*ghbdtn*", Some("text.body")),
+            pattern("title", "*GOOD*", None),
+        ],
+    );
+    assert_eq!(
+        hits,
+        [(
+            "c".to_string(),
+            vec!["layout".to_string(), "title".to_string()]
+        )]
+    );
+}
+
+#[test]
+fn bad_pattern_sets_are_rejected() {
+    let analyzers = Analyzers::default();
+    let err = CompiledMulti::compile(&[], &RegexFactory::default(), &analyzers)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("at least one pattern"), "{err:#}");
+    assert!(err.downcast_ref::<InvalidQuery>().is_some(), "{err:#}");
+
+    let dup = [pattern("x", "*abc*", None), pattern("x", "*def*", None)];
+    let err = CompiledMulti::compile(&dup, &RegexFactory::default(), &analyzers)
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().contains("duplicate pattern id `x`"),
+        "{err:#}"
+    );
+}
@@ -1,5 +1,6 @@
 // crates/grepzilla_segment/tests/preview_tests.rs
 use grepzilla_segment::StoredDoc;
+use grepzilla_segment::analyzer::Analyzers;
 use grepzilla_segment::common::preview::{
     PreviewOpts, build_preview, truncate_chars_with_ellipsis,
 };
@@ -14,6 +15,7 @@ fn doc_with(fields: &[(&str, &str)]) -> StoredDoc {
         doc_id: 0,
         ext_id: "test".into(),
         fields: m,
+        raw: BTreeMap::new(),
     }
 }
 
@@ -32,6 +34,7 @@ fn preview_picks_preferred_and_highlights() {
             preferred_fields: &["text.title", "text.body"],
             max_len: 22, // узкое окно, важно, что не ломаем UTF-8
             highlight_needle: Some("игра"),
+            analyzers: &Analyzers::default(),
         },
     );
     assert!(
@@ -51,6 +54,7 @@ fn preview_fallbacks_to_first_field() {
             preferred_fields: &["text.title", "text.body"],
             max_len: 10,
             highlight_needle: Some("игра"),
+            analyzers: &Analyzers::default(),
         },
     );
     // иглы нет — должен быть простой truncate
@@ -0,0 +1,113 @@
+// crates/grepzilla_segment/tests/query_limits.rs
+use grepzilla_segment::limits::{LimitExceeded, QueryLimits};
+use grepzilla_segment::multi::Pattern;
+use grepzilla_segment::query::{BoolQuery, Clause};
+use grepzilla_segment::verify::RegexFactory;
+
+fn wildcard(p: &str) -> BoolQuery {
+    BoolQuery::single(Clause::wildcard(p, None))
+}
+
+#[test]
+fn defaults_accept_ordinary_queries() {
+    let limits = QueryLimits::default();
+    assert!(limits.check(&wildcard("*timeout*")).is_ok());
+    assert!(limits.check(&wildcard("*upstream*502*reset*")).is_ok());
+    let rx = BoolQuery::single(Clause::regex(r"err(or)?\s+\d{3}", None));
+    assert!(limits.check(&rx).is_ok());
+}
+
+#[test]
+fn each_limit_is_reported() {
+    let limits = QueryLimits {
+        max_pattern_len: 32,
+        max_wildcard_segments: 3,
+        max_regex_size: 10_000,
+        max_patterns: 2,
+    };
+
+    let err = limits.check(&wildcard(&"a".repeat(40))).unwrap_err();
+    assert_eq!(
+        (err.limit, err.max, err.actual),
+        ("max_pattern_len", 32, Some(40))
+    );
+
+    let err = limits.check(&wildcard("*a*b*c*d*")).unwrap_err();
+    assert_eq!(
+        (err.limit, err.max, err.actual),
+        ("max_wildcard_segments", 3, Some(4))
+    );
+
+    // размер regex ограничивает сборка движка, а не `check`
+    let q = BoolQuery {
+        must: vec![Clause::wildcard("*ok*", None)],
+        must_not: vec![Clause::regex(r"\w{50}", None)],
+        ..Default::default()
+    };
+    assert!(limits.check(&q).is_ok());
+    let err = q
+        .compile(&RegexFactory::with_size_limit(limits.max_regex_size))
+        .err()
+        .unwrap();
+    let err = err.downcast::<LimitExceeded>().expect("limit error");
+    assert_eq!((err.limit, err.actual), ("max_regex_size", None));
+    assert_eq!(err.clause, "must_not[0]");
+
+    let body = err.body();
+    assert_eq!(body["error"], "query_too_complex");
+    assert_eq!(body["limit"], "max_regex_size");
+    assert!(body.get("actual").is_none());
+    assert!(body["message"].as_str().unwrap().contains("must_not[0]"));
+
+    let p = |id: &str, wildcard: &str| Pattern {
+        id: id.into(),
+        wildcard: wildcard.into(),
+        field: None,
+    };
+    assert!(
+        limits
+            .check_multi(&[p("a", "*ok*"), p("b", "*fine*")])
+            .is_ok()
+    );
+    let err = limits
+        .check_multi(&[p("a", "*ok*"), p("b", "*x*"), p("c", "*y*")])
+        .unwrap_err();
+    assert_eq!(
+        (err.limit, err.clause.as_str(), err.actual),
+        ("max_patterns", "patterns", Some(3))
+    );
+    let err = limits
+        .check_multi(&[p("a", "*ok*"), p("b", "*a*b*c*d*")])
+        .unwrap_err();
+    assert_eq!(
+        (err.limit, err.clause.as_str()),
+        ("max_wildcard_segments", "patterns[1]")
+    );
+}
+
+#[test]
+fn wildcard_regex_size_is_limited_too() {
+    let q = BoolQuery {
+        should: vec![
+            Clause::wildcard("*ok*", None),
+            Clause::wildcard(&"?".repeat(200), None),
+        ],
+        ..Default::default()
+    };
+    let err = q
+        .compile(&RegexFactory::with_size_limit(10_000))
+        .err()
+        .unwrap();
+    let err = err.downcast::<LimitExceeded>().expect("limit error");
+    assert_eq!(
+        (err.limit, err.clause.as_str()),
+        ("max_regex_size", "should[1]")
+    );
+    assert!(q.compile(&RegexFactory::default()).is_ok());
+}
+
+#[test]
+fn regex_syntax_errors_are_left_to_compile() {
+    let rx = BoolQuery::single(Clause::regex("(unclosed", None));
+    assert!(QueryLimits::default().check(&rx).is_ok());
+}
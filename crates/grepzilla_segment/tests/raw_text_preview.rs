@@ -0,0 +1,204 @@
+// crates/grepzilla_segment/tests/raw_text_preview.rs
+use grepzilla_segment::analyzer::{AnalyzerSchema, Analyzers};
+use grepzilla_segment::common::preview::{PreviewOpts, build_preview, snippet_for_match};
+use grepzilla_segment::normalizer::Sensitivity;
+use grepzilla_segment::query::{BoolQuery, Clause};
+use grepzilla_segment::segjson::{JsonSegmentReader, JsonSegmentWriter};
+use grepzilla_segment::v2::merge::{MergeOptions, merge_segments};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::verify::EnvVerifyFactory;
+use grepzilla_segment::{SegmentReader, SegmentWriter, StoredDoc, segment_has_raw_text};
+use std::fs;
+
+const DOCS: &str = r#"{"_id":"1","text":{"title":"Ошибка в модуле API","body":"строчные буквы"}}
+{"_id":"2","text":{"title":"Café Crème","body":"Запрос к API вернул 500"}}
+"#;
+
+fn opts(needle: &str) -> PreviewOpts<'_> {
+    PreviewOpts {
+        preferred_fields: &["text.title"],
+        max_len: 180,
+        highlight_needle: Some(needle),
+        analyzers: &STANDARD,
+    }
+}
+
+static STANDARD: std::sync::LazyLock<Analyzers> = std::sync::LazyLock::new(Analyzers::default);
+
+fn check_docs(d0: &StoredDoc, d1: &StoredDoc) {
+    assert_eq!(d0.fields["text.title"], "ошибка в модуле api");
+    assert_eq!(d0.raw["text.title"], "Ошибка в модуле API");
+    // совпадает с нормализованным — не дублируем
+    assert!(!d0.raw.contains_key("text.body"));
+
+    assert_eq!(build_preview(d0, opts("api")), "Ошибка в модуле [API]");
+    assert_eq!(build_preview(d1, opts("creme")), "Café [Crème]");
+
+    // спан от verify-движка — в нормализованном тексте
+    let body = &d1.fields["text.body"];
+    let s = body.find("api").unwrap();
+    assert_eq!(
+        snippet_for_match(
+            d1,
+            "text.body",
+            s,
+            s + 3,
+            180,
+            STANDARD.default_analyzer().as_ref()
+        ),
+        "Запрос к [API] вернул 500"
+    );
+}
+
+#[test]
+fn v2_keeps_raw_text_for_previews() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("docs.jsonl");
+    fs::write(&input, DOCS).unwrap();
+    let seg = tmp.path().join("seg");
+    BinSegmentWriter::default()
+        .with_raw_text(true)
+        .write_segment(input.to_str().unwrap(), seg.to_str().unwrap())
+        .unwrap();
+
+    let r = BinSegmentReader::open_segment(seg.to_str().unwrap()).unwrap();
+    check_docs(r.get_doc(0).unwrap(), r.get_doc(1).unwrap());
+    assert!(r.has_raw_text());
+
+    // слияние переносит исходный текст
+    let merged = tmp.path().join("merged");
+    merge_segments(
+        &[seg.to_str().unwrap()],
+        merged.to_str().unwrap(),
+        &MergeOptions::default(),
+    )
+    .unwrap();
+    let m = BinSegmentReader::open_segment(merged.to_str().unwrap()).unwrap();
+    check_docs(m.get_doc(0).unwrap(), m.get_doc(1).unwrap());
+    assert!(m.has_raw_text());
+}
+
+#[test]
+fn v1_keeps_raw_text_for_previews() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("docs.jsonl");
+    fs::write(&input, DOCS).unwrap();
+    let seg = tmp.path().join("seg");
+    JsonSegmentWriter::default()
+        .with_raw_text(true)
+        .write_segment(input.to_str().unwrap(), seg.to_str().unwrap())
+        .unwrap();
+
+    let r = JsonSegmentReader::open_segment(seg.to_str().unwrap()).unwrap();
+    check_docs(r.get_doc(0).unwrap(), r.get_doc(1).unwrap());
+    assert!(r.has_raw_text());
+}
+
+#[test]
+fn raw_text_preview_follows_field_analyzers() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("docs.jsonl");
+    fs::write(
+        &input,
+        r#"{"_id":"1","text":{"title":"Ёлка в модуле API","body":"Café MODULE Ёлка"}}"#,
+    )
+    .unwrap();
+    let schema: AnalyzerSchema = serde_json::from_str(
+        r#"{"analyzers": {"layout": ["lowercase", "keyboard_layout"], "lower": ["lowercase"]},
+            "fields": {"text.title": "layout", "text.body": "lower"}}"#,
+    )
+    .unwrap();
+    let seg = tmp.path().join("seg");
+    BinSegmentWriter::default()
+        .with_raw_text(true)
+        .with_analyzers(Analyzers::new(schema).unwrap())
+        .write_segment(input.to_str().unwrap(), seg.to_str().unwrap())
+        .unwrap();
+
+    let r = BinSegmentReader::open_segment(seg.to_str().unwrap()).unwrap();
+    let d = r.get_doc(0).unwrap();
+    assert_eq!(d.fields["text.title"], "`krf d vjlekt api");
+    assert_eq!(d.fields["text.body"], "café module ёлка");
+
+    let snippet = |field: &str, needle: &str| {
+        let a = r.analyzers().for_field(field);
+        let s = d.fields[field].find(needle).unwrap();
+        snippet_for_match(d, field, s, s + needle.len(), 180, a.as_ref())
+    };
+    assert_eq!(snippet("text.title", "vjlekt"), "Ёлка в [модуле] API");
+    assert_eq!(snippet("text.body", "module"), "Café [MODULE] Ёлка");
+
+    let opts = PreviewOpts {
+        preferred_fields: &["text.title"],
+        max_len: 180,
+        highlight_needle: Some("api"),
+        analyzers: r.analyzers(),
+    };
+    assert_eq!(build_preview(d, opts), "Ёлка в модуле [API]");
+}
+
+#[test]
+fn raw_text_is_off_by_default() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("docs.jsonl");
+    fs::write(&input, DOCS).unwrap();
+    let seg = tmp.path().join("seg");
+    BinSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), seg.to_str().unwrap())
+        .unwrap();
+
+    let r = BinSegmentReader::open_segment(seg.to_str().unwrap()).unwrap();
+    let d0 = r.get_doc(0).unwrap();
+    assert!(d0.raw.is_empty());
+    assert!(!r.has_raw_text());
+    assert_eq!(build_preview(d0, opts("api")), "ошибка в модуле [api]");
+}
+
+#[test]
+fn strict_query_needs_raw_text() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("docs.jsonl");
+    fs::write(&input, DOCS).unwrap();
+    let input = input.to_str().unwrap();
+    let dir = |name: &str| tmp.path().join(name);
+    BinSegmentWriter::default()
+        .write_segment(input, dir("v2").to_str().unwrap())
+        .unwrap();
+    JsonSegmentWriter::default()
+        .write_segment(input, dir("v1").to_str().unwrap())
+        .unwrap();
+    BinSegmentWriter::default()
+        .with_raw_text(true)
+        .write_segment(input, dir("v2_raw").to_str().unwrap())
+        .unwrap();
+    JsonSegmentWriter::default()
+        .with_raw_text(true)
+        .write_segment(input, dir("v1_raw").to_str().unwrap())
+        .unwrap();
+
+    let query = BoolQuery::single(Clause::wildcard("*API*", None));
+    let compile = |sens| {
+        query
+            .compile_with(&EnvVerifyFactory::from_env(), sens, &Analyzers::default())
+            .unwrap()
+    };
+    let strict = compile(Sensitivity {
+        case_sensitive: true,
+        ..Default::default()
+    });
+    let folded = compile(Sensitivity::default());
+    assert!(strict.needs_raw_text());
+    assert!(!folded.needs_raw_text());
+
+    for name in ["v1", "v2"] {
+        assert!(!segment_has_raw_text(&dir(name)).unwrap(), "{name}");
+        let err = strict.check_raw_text(&dir(name)).unwrap_err().to_string();
+        assert!(err.contains("built without raw text"), "{name}: {err}");
+        assert!(folded.check_raw_text(&dir(name)).is_ok(), "{name}");
+    }
+    for name in ["v1_raw", "v2_raw"] {
+        assert!(segment_has_raw_text(&dir(name)).unwrap(), "{name}");
+        assert!(strict.check_raw_text(&dir(name)).is_ok(), "{name}");
+    }
+}
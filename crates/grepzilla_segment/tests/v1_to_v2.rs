@@ -0,0 +1,128 @@
+// crates/grepzilla_segment/tests/v1_to_v2.rs
+use grepzilla_segment::deletes::{LiveDocs, delete_docs};
+use grepzilla_segment::integrity::verify_segment;
+use grepzilla_segment::segjson::JsonSegmentWriter;
+use grepzilla_segment::v2::convert::{ConvertOptions, compare_hits, convert_v1_to_v2};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::{BinSegmentWriter, FieldGrams};
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::path::Path;
+
+const DOCS: &str = r#"{"_id":"a","text":{"title":"Ошибка диска","body":"диск переполнен, ошибка записи"}}
+{"_id":"b","text":{"body":"ошибка сети: таймаут"}}
+{"_id":"c","text":{"title":"Всё хорошо","body":"ёлка и ЁЖИК"}}
+{"_id":"d","text":{"body":"ошибка памяти","title":"Память"},"tags":["ops","сеть"]}
+{"_id":"e","text":{"title":"сетевой таймаут"}}
+"#;
+
+fn queries() -> Vec<String> {
+    [
+        "*ошибка*",
+        "*ОШИБКА*диск*",
+        "*сет*",
+        "*таймаут*",
+        "*ежик*",
+        "*ёлка*",
+        "*памят?*",
+        "*нет такого*",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn build(dir: &Path) -> (String, String) {
+    let input = dir.join("docs.jsonl");
+    std::fs::write(&input, DOCS).unwrap();
+    let v1 = dir.join("v1").to_str().unwrap().to_string();
+    let v2 = dir.join("v2").to_str().unwrap().to_string();
+    JsonSegmentWriter::default()
+        .with_raw_text(true)
+        .write_segment(input.to_str().unwrap(), &v1)
+        .unwrap();
+    BinSegmentWriter::default()
+        .with_raw_text(true)
+        .write_segment(input.to_str().unwrap(), &v2)
+        .unwrap();
+    (v1, v2)
+}
+
+#[test]
+fn converted_segment_matches_v1_and_native_v2() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2) = build(tmp.path());
+    let conv = tmp.path().join("conv").to_str().unwrap().to_string();
+
+    let opts = ConvertOptions {
+        field_grams: FieldGrams::All,
+        // маленький бюджет — постинги уходят на диск
+        memory_budget: 1,
+        ..Default::default()
+    };
+    let stats = convert_v1_to_v2(&v1, &conv, &opts).unwrap();
+    assert_eq!(stats.docs, 5);
+    assert_eq!(stats.fields, 5);
+    assert_eq!(stats.deleted, 0);
+    assert!(verify_segment(Path::new(&conv)).unwrap().is_ok());
+
+    // документы — те же, что у V2, собранного из исходного JSONL
+    let a = BinSegmentReader::open_segment(&conv).unwrap();
+    let b = BinSegmentReader::open_segment(&v2).unwrap();
+    assert_eq!(a.doc_count(), b.doc_count());
+    for d in 0..a.doc_count() {
+        let (x, y) = (a.read_doc(d).unwrap(), b.read_doc(d).unwrap());
+        assert_eq!((x.ext_id, x.fields, x.raw), (y.ext_id, y.fields, y.raw));
+    }
+    assert!(a.has_field_grams("text.body"));
+
+    for field in [None, Some("text.title"), Some("text.body")] {
+        for target in [&conv, &v2] {
+            let diffs = compare_hits(&v1, target, &queries(), field).unwrap();
+            for d in &diffs {
+                assert!(d.is_equal(), "{field:?} {target}: {d:?}");
+            }
+        }
+    }
+    let diffs = compare_hits(&v1, &conv, &queries(), None).unwrap();
+    assert_eq!(diffs[0].v1_hits, 3);
+    assert_eq!(diffs[4].v2_hits, 1);
+    // слишком короткий паттерн отвергается, как и при поиске
+    assert!(compare_hits(&v1, &conv, &["*ab*".into()], None).is_err());
+}
+
+#[test]
+fn deletes_survive_conversion() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, _) = build(tmp.path());
+    delete_docs(Path::new(&v1), 5, [1]).unwrap();
+    delete_docs(Path::new(&v1), 5, [3]).unwrap();
+
+    let conv = tmp.path().join("conv").to_str().unwrap().to_string();
+    let stats = convert_v1_to_v2(&v1, &conv, &ConvertOptions::default()).unwrap();
+    assert_eq!(stats.deleted, 2);
+
+    let live = LiveDocs::load(Path::new(&conv)).unwrap();
+    assert_eq!(live.generation(), 2);
+    assert_eq!(live.deleted().iter().collect::<Vec<_>>(), vec![1, 3]);
+
+    let diffs = compare_hits(&v1, &conv, &queries(), None).unwrap();
+    assert!(diffs.iter().all(|d| d.is_equal()), "{diffs:?}");
+    assert_eq!(diffs[0].v2_hits, 1);
+
+    assert!(convert_v1_to_v2(&v1, &v1, &ConvertOptions::default()).is_err());
+}
+
+#[test]
+fn compare_reports_differences() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2) = build(tmp.path());
+    delete_docs(Path::new(&v2), 5, [0]).unwrap();
+
+    let diffs = compare_hits(&v1, &v2, &queries(), None).unwrap();
+    let d = &diffs[1];
+    assert!(!d.is_equal());
+    assert_eq!(d.only_v1, ["a"]);
+    assert!(d.only_v2.is_empty());
+    assert_eq!((d.v1_hits, d.v2_hits), (1, 0));
+    // запросы, не задевающие `a`, совпадают
+    assert!(diffs[4].is_equal());
+}
@@ -0,0 +1,63 @@
+// crates/grepzilla_segment/tests/v2_block_postings.rs
+use grepzilla_segment::gram::{BooleanOp, required_grams_from_wildcard};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::fs::File;
+use std::io::Write;
+
+#[test]
+fn v2_block_postings_prefilter_after_cursor() {
+    let tmp = tempfile::tempdir().unwrap();
+    let segdir = tmp.path().join("seg");
+    let jsonl = tmp.path().join("in.jsonl");
+
+    // 50 документов с "ошибка" (больше inline-порога) + каждый третий с "таймаут"
+    let mut f = File::create(&jsonl).unwrap();
+    for i in 0..50 {
+        let extra = if i % 3 == 0 { " таймаут" } else { "" };
+        writeln!(
+            f,
+            r#"{{"_id":"d{i}","text":{{"body":"ошибка номер {i}{extra}"}}}}"#
+        )
+        .unwrap();
+    }
+    drop(f);
+
+    let mut w = BinSegmentWriter::default();
+    w.write_segment(jsonl.to_str().unwrap(), segdir.to_str().unwrap())
+        .unwrap();
+    let r = BinSegmentReader::open_segment(segdir.to_str().unwrap()).unwrap();
+
+    let grams = required_grams_from_wildcard("*ошибка*").unwrap();
+    let all: Vec<u32> = r
+        .prefilter(BooleanOp::And, &grams, None)
+        .unwrap()
+        .iter()
+        .collect();
+    assert_eq!(all, (0..50).collect::<Vec<u32>>());
+
+    // после курсора 40 — только 41..49
+    let tail: Vec<u32> = r
+        .prefilter_after(BooleanOp::And, &grams, None, Some(40))
+        .unwrap()
+        .iter()
+        .collect();
+    assert_eq!(tail, (41..50).collect::<Vec<u32>>());
+
+    // пересечение двух частых грамм с курсором
+    let grams = required_grams_from_wildcard("*ошибка*таймаут*").unwrap();
+    let ids: Vec<u32> = r
+        .prefilter_after(BooleanOp::And, &grams, Some("text.body"), Some(20))
+        .unwrap()
+        .iter()
+        .collect();
+    assert_eq!(ids, vec![21, 24, 27, 30, 33, 36, 39, 42, 45, 48]);
+
+    // курсор за концом сегмента — пусто
+    assert!(
+        r.prefilter_after(BooleanOp::And, &grams, None, Some(1000))
+            .unwrap()
+            .is_empty()
+    );
+}
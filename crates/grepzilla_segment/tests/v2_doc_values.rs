@@ -0,0 +1,152 @@
+// crates/grepzilla_segment/tests/v2_doc_values.rs
+use grepzilla_segment::SegmentReader;
+use grepzilla_segment::SegmentWriter;
+use grepzilla_segment::doc_values::{
+    DocValueField, DocValueKind, RangeFilter, RangeValue, f64_key,
+};
+use grepzilla_segment::gram::BooleanOp;
+use grepzilla_segment::v2::merge::{MergeOptions, merge_segments};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use std::fs;
+use std::path::Path;
+
+const T0: i64 = 1_755_691_200_000; // 2025-08-20T12:00:00Z
+
+fn iso(ms: i64) -> String {
+    let s = ms / 1000 - T0 / 1000;
+    format!(
+        "2025-08-20T{:02}:{:02}:{:02}Z",
+        12 + s / 3600,
+        s / 60 % 60,
+        s % 60
+    )
+}
+
+fn build(dir: &Path, name: &str, n: i64, budget: usize) -> String {
+    let mut lines = Vec::new();
+    for i in 0..n {
+        // каждый 7-й без latency; каждый 11-й — latency строкой (не число)
+        let latency = match i {
+            _ if i % 7 == 0 => String::new(),
+            _ if i % 11 == 0 => r#","latency":"slow""#.to_string(),
+            _ => format!(r#","latency":{}"#, (i * 37) % 1000),
+        };
+        lines.push(format!(
+            r#"{{"_id":"{name}-{i}","ts":"{}","score":{}.5{latency},"msg":"ошибка номер {i}"}}"#,
+            iso(T0 + i * 1000),
+            i % 10 - 5,
+        ));
+    }
+    let input = dir.join(format!("{name}.jsonl"));
+    fs::write(&input, lines.join("\n")).unwrap();
+    let out = dir.join(name);
+    BinSegmentWriter::default()
+        .with_memory_budget(budget)
+        .with_doc_values(vec![
+            "ts:timestamp".parse().unwrap(),
+            "latency:i64".parse().unwrap(),
+            DocValueField::new("score", DocValueKind::F64),
+        ])
+        .write_segment(input.to_str().unwrap(), out.to_str().unwrap())
+        .unwrap();
+    out.to_str().unwrap().to_string()
+}
+
+fn filtered(r: &BinSegmentReader, filters: &[RangeFilter]) -> Vec<u32> {
+    let mut bm = r.prefilter(BooleanOp::And, &[], None).unwrap();
+    r.apply_range_filters(&mut bm, filters).unwrap();
+    bm.iter().collect()
+}
+
+#[test]
+fn range_filters_match_brute_force() {
+    let tmp = tempfile::tempdir().unwrap();
+    let n = 3000;
+    let seg = build(tmp.path(), "s", n, usize::MAX);
+    let r = BinSegmentReader::open_segment(&seg).unwrap();
+
+    let ts = r.doc_values("ts").unwrap().unwrap();
+    assert_eq!(ts.kind(), DocValueKind::Timestamp);
+    assert_eq!(ts.min_max(), Some((T0, T0 + (n - 1) * 1000)));
+    assert_eq!(ts.get(5), Some(T0 + 5000));
+
+    // «последние 10 минут» — блоки до них пропускаются целиком
+    let last = RangeFilter {
+        field: "ts".into(),
+        gte: Some(RangeValue::Text(iso(T0 + (n - 600) * 1000))),
+        ..Default::default()
+    };
+    let expect: Vec<u32> = ((n - 600) as u32..n as u32).collect();
+    assert_eq!(filtered(&r, std::slice::from_ref(&last)), expect);
+
+    // latency ∈ (100, 500] AND ts < T0+2000s; без значения — не проходят
+    let lat = RangeFilter {
+        field: "latency".into(),
+        gt: Some(RangeValue::Int(100)),
+        lte: Some(RangeValue::Int(500)),
+        ..Default::default()
+    };
+    let early = RangeFilter {
+        field: "ts".into(),
+        lt: Some(RangeValue::Int(T0 + 2_000_000)),
+        ..Default::default()
+    };
+    let expect: Vec<u32> = (0..n)
+        .filter(|i| i % 7 != 0 && i % 11 != 0)
+        .filter(|i| (101..=500).contains(&((i * 37) % 1000)) && *i < 2000)
+        .map(|i| i as u32)
+        .collect();
+    assert_eq!(filtered(&r, &[lat.clone(), early]), expect);
+
+    // f64: score >= 2.5 ⇔ i % 10 >= 7
+    let score = RangeFilter {
+        field: "score".into(),
+        gte: Some(RangeValue::Float(2.5)),
+        ..Default::default()
+    };
+    let expect: Vec<u32> = (0..n as u32).filter(|i| i % 10 >= 7).collect();
+    assert_eq!(filtered(&r, std::slice::from_ref(&score)), expect);
+    let col = r.doc_values("score").unwrap().unwrap();
+    assert_eq!(col.get(9), Some(f64_key(4.5)));
+
+    // нет колонки — нет документов; граница не того типа — ошибка
+    let missing = RangeFilter {
+        field: "nope".into(),
+        gte: Some(RangeValue::Int(0)),
+        ..Default::default()
+    };
+    assert!(filtered(&r, &[missing]).is_empty());
+    let bad = RangeFilter {
+        field: "latency".into(),
+        gte: Some(RangeValue::Text("soon".into())),
+        ..Default::default()
+    };
+    let mut bm = r.prefilter(BooleanOp::And, &[], None).unwrap();
+    assert!(r.apply_range_filters(&mut bm, &[bad]).is_err());
+
+    // колонки не зависят от бюджета памяти
+    let spilled = build(tmp.path(), "spilled", n, 1);
+    assert_eq!(
+        fs::read(Path::new(&seg).join("doc_values.dat")).unwrap(),
+        fs::read(Path::new(&spilled).join("doc_values.dat")).unwrap()
+    );
+}
+
+#[test]
+fn merge_carries_doc_values() {
+    let tmp = tempfile::tempdir().unwrap();
+    let a = build(tmp.path(), "a", 50, usize::MAX);
+    let b = build(tmp.path(), "b", 30, usize::MAX);
+    let out = tmp.path().join("m");
+    merge_segments(&[&a, &b], out.to_str().unwrap(), &MergeOptions::default()).unwrap();
+
+    let m = BinSegmentReader::open_segment(out.to_str().unwrap()).unwrap();
+    assert_eq!(m.doc_value_fields().len(), 3);
+    let ts = m.doc_values("ts").unwrap().unwrap();
+    assert_eq!(ts.get(49), Some(T0 + 49_000));
+    assert_eq!(ts.get(50), Some(T0));
+    let lat = m.doc_values("latency").unwrap().unwrap();
+    assert_eq!(lat.get(50 + 7), None);
+    assert_eq!(lat.get(50 + 8), Some(8 * 37));
+}
@@ -0,0 +1,86 @@
+// crates/grepzilla_segment/tests/v2_field_grams.rs
+use grepzilla_segment::gram::{BooleanOp, required_grams_from_wildcard};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::{BinSegmentWriter, FieldGrams};
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::fs::File;
+use std::io::Write;
+
+fn build(field_grams: FieldGrams) -> (tempfile::TempDir, BinSegmentReader) {
+    let tmp = tempfile::tempdir().unwrap();
+    let segdir = tmp.path().join("seg");
+    let jsonl = tmp.path().join("in.jsonl");
+
+    // "ошибка" есть в body у всех, но в title — только у doc 1
+    let mut f = File::create(&jsonl).unwrap();
+    writeln!(
+        f,
+        r#"{{"_id":"a","text":{{"title":"отчёт","body":"ошибка сети"}}}}"#
+    )
+    .unwrap();
+    writeln!(
+        f,
+        r#"{{"_id":"b","text":{{"title":"ошибка диска","body":"ошибка диска"}}}}"#
+    )
+    .unwrap();
+    writeln!(
+        f,
+        r#"{{"_id":"c","text":{{"title":"итоги","body":"ошибка сети"}}}}"#
+    )
+    .unwrap();
+    drop(f);
+
+    let mut w = BinSegmentWriter::default().with_field_grams(field_grams);
+    w.write_segment(jsonl.to_str().unwrap(), segdir.to_str().unwrap())
+        .unwrap();
+    let r = BinSegmentReader::open_segment(segdir.to_str().unwrap()).unwrap();
+    (tmp, r)
+}
+
+fn ids(r: &BinSegmentReader, op: BooleanOp, q: &str, field: Option<&str>) -> Vec<u32> {
+    let grams = required_grams_from_wildcard(q).unwrap();
+    r.prefilter(op, &grams, field).unwrap().iter().collect()
+}
+
+#[test]
+fn field_grams_scope_candidates_to_target_field() {
+    let (_tmp, r) = build(FieldGrams::Only(vec!["text.title".into()]));
+    assert!(r.has_field_grams("text.title"));
+    assert!(!r.has_field_grams("text.body"));
+
+    // только документ, где грамма есть именно в title
+    assert_eq!(
+        ids(&r, BooleanOp::And, "*ошибка*", Some("text.title")),
+        vec![1]
+    );
+    // body не проиндексирован отдельно — общий индекс + маска поля
+    assert_eq!(
+        ids(&r, BooleanOp::And, "*ошибка*", Some("text.body")),
+        vec![0, 1, 2]
+    );
+    // без поля — общий индекс
+    assert_eq!(ids(&r, BooleanOp::And, "*ошибка*", None), vec![0, 1, 2]);
+    // NOT в пределах поля
+    assert_eq!(
+        ids(&r, BooleanOp::Not, "*ошибка*", Some("text.title")),
+        vec![0, 2]
+    );
+    assert!(ids(&r, BooleanOp::And, "*сети*", Some("text.title")).is_empty());
+}
+
+#[test]
+fn without_field_grams_falls_back_to_field_mask() {
+    let (_tmp, r) = build(FieldGrams::Off);
+    assert!(!r.has_field_grams("text.title"));
+    assert_eq!(
+        ids(&r, BooleanOp::And, "*ошибка*", Some("text.title")),
+        vec![0, 1, 2]
+    );
+
+    let (_tmp, r) = build(FieldGrams::All);
+    assert!(r.has_field_grams("text.body"));
+    assert_eq!(
+        ids(&r, BooleanOp::And, "*сети*", Some("text.body")),
+        vec![0, 2]
+    );
+}
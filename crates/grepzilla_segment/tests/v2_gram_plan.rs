@@ -0,0 +1,97 @@
+// crates/grepzilla_segment/tests/v2_gram_plan.rs
+use grepzilla_segment::gram::{
+    BooleanOp, required_gram_runs_from_wildcard, required_grams_from_wildcard,
+};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::fmt::Write as _;
+
+fn build(dir: &std::path::Path, bodies: &[String]) -> BinSegmentReader {
+    let mut jsonl = String::new();
+    for (i, b) in bodies.iter().enumerate() {
+        writeln!(jsonl, r#"{{"_id":"d{i}","text":{{"body":"{b}"}}}}"#).unwrap();
+    }
+    let input = dir.join("in.jsonl");
+    std::fs::write(&input, jsonl).unwrap();
+    let seg = dir.join("seg");
+    BinSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), seg.to_str().unwrap())
+        .unwrap();
+    BinSegmentReader::open_segment(seg.to_str().unwrap()).unwrap()
+}
+
+#[test]
+fn long_literal_uses_few_rare_grams() {
+    let tmp = tempfile::tempdir().unwrap();
+    let bodies: Vec<String> = (0..400)
+        .map(|i| match i % 100 {
+            7 => "internationalization of software".to_string(),
+            // граммы вразброс, но не подряд
+            13 => "international realization zation".to_string(),
+            _ if i % 2 == 0 => format!("international station {i}"),
+            _ => format!("nation ratio {i}"),
+        })
+        .collect();
+    let r = build(tmp.path(), &bodies);
+    let q = "*internationalization*";
+    let grams = required_grams_from_wildcard(q).unwrap();
+    assert_eq!(grams.len(), 18);
+
+    let plan = r.plan_prefilter(&grams, None).unwrap();
+    assert!(plan.missing.is_none());
+    assert!(plan.steps.len() <= 6, "{plan:?}");
+    assert_eq!(plan.steps.len() + plan.skipped.len(), 15);
+    // от редких к частым
+    assert!(plan.steps.windows(2).all(|w| w[0].docs <= w[1].docs));
+    assert_eq!(plan.steps[0].docs, 8);
+
+    // отбор шире точного, но без потерь; смежность по маскам его сужает
+    let exact: Vec<u32> = (0..400).filter(|i| i % 100 == 7).collect();
+    let cand: Vec<u32> = r
+        .prefilter(BooleanOp::And, &grams, None)
+        .unwrap()
+        .iter()
+        .collect();
+    assert!(exact.iter().all(|d| cand.contains(d)), "{cand:?}");
+    let runs = required_gram_runs_from_wildcard(q).unwrap();
+    let adjacent: Vec<u32> = r
+        .prefilter_adjacent_after(&runs, None, None)
+        .unwrap()
+        .iter()
+        .collect();
+    assert_eq!(adjacent, exact);
+}
+
+#[test]
+fn stops_once_candidates_are_few() {
+    let tmp = tempfile::tempdir().unwrap();
+    let mut bodies: Vec<String> = (0..1000).map(|i| format!("ошибка диска {i}")).collect();
+    bodies[10] = "ошибка зонтика".into();
+    bodies[20] = "ошибка: зонт сломан".into();
+    // есть «зонт», но нет «ошибка» — в отбор попадает из-за ранней остановки
+    bodies[30] = "зонт".into();
+    let r = build(tmp.path(), &bodies);
+
+    let grams = required_grams_from_wildcard("*ошибка*зонт*").unwrap();
+    let plan = r.plan_prefilter(&grams, None).unwrap();
+    assert_eq!(plan.steps[0].gram, "зон");
+    assert_eq!(plan.steps[0].docs, 3);
+
+    let cand: Vec<u32> = r
+        .prefilter(BooleanOp::And, &grams, None)
+        .unwrap()
+        .iter()
+        .collect();
+    assert_eq!(cand, [10, 20, 30]);
+
+    // отсутствующая грамма — пусто без декодирования остальных
+    let grams = required_grams_from_wildcard("*ошибка*щщщ*").unwrap();
+    let plan = r.plan_prefilter(&grams, None).unwrap();
+    assert_eq!(plan.missing.as_deref(), Some("щщщ"));
+    assert!(
+        r.prefilter(BooleanOp::And, &grams, None)
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -0,0 +1,82 @@
+// crates/grepzilla_segment/tests/v2_merge.rs
+use grepzilla_segment::v2::merge::{MergeOptions, merge_segments};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::{BinSegmentWriter, FieldGrams};
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::fs;
+use std::path::Path;
+
+fn doc(id: &str, title: &str, body: &str) -> String {
+    format!(r#"{{"_id":"{id}","text":{{"title":"{title}","body":"{body}"}}}}"#)
+}
+
+fn build(dir: &Path, name: &str, lines: &[String]) -> String {
+    let jsonl = dir.join(format!("{name}.jsonl"));
+    fs::write(&jsonl, lines.join("\n")).unwrap();
+    let out = dir.join(name);
+    let mut w = BinSegmentWriter::default().with_field_grams(FieldGrams::All);
+    w.write_segment(jsonl.to_str().unwrap(), out.to_str().unwrap())
+        .unwrap();
+    out.to_str().unwrap().to_string()
+}
+
+#[test]
+fn merge_matches_fresh_build_of_live_docs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let a = vec![
+        doc("1", "ошибка диска", "диск переполнен"),
+        doc("2", "отчёт", "всё хорошо"),
+        doc("3", "таймаут", "сеть недоступна"),
+    ];
+    let b = vec![
+        doc("2", "отчёт v2", "ошибка в отчёте"),
+        doc("4", "итоги", "таймаут сети"),
+    ];
+    let c = vec![doc("5", "ошибка сети", "повтор через минуту")];
+    let segs = [
+        build(tmp.path(), "a", &a),
+        build(tmp.path(), "b", &b),
+        build(tmp.path(), "c", &c),
+    ];
+    let inputs: Vec<&str> = segs.iter().map(|s| s.as_str()).collect();
+
+    let merged = tmp.path().join("merged");
+    let opts = MergeOptions {
+        tombstones: ["3".to_string()].into(),
+    };
+    let stats = merge_segments(&inputs, merged.to_str().unwrap(), &opts).unwrap();
+    assert_eq!(stats.input_docs, 6);
+    assert_eq!(stats.output_docs, 4);
+    assert_eq!(stats.dropped_superseded, 1);
+    assert_eq!(stats.dropped_tombstoned, 1);
+
+    // эталон: те же живые документы в том же порядке, собранные с нуля
+    let expect = vec![a[0].clone(), b[0].clone(), b[1].clone(), c[0].clone()];
+    let fresh = build(tmp.path(), "fresh", &expect);
+
+    let m = BinSegmentReader::open_segment(merged.to_str().unwrap()).unwrap();
+    let f = BinSegmentReader::open_segment(&fresh).unwrap();
+    assert_eq!(m.doc_count(), f.doc_count());
+    for d in 0..m.doc_count() {
+        let (x, y) = (m.get_doc(d).unwrap(), f.get_doc(d).unwrap());
+        assert_eq!((&x.ext_id, &x.fields), (&y.ext_id, &y.fields));
+    }
+    let postings =
+        |r: &BinSegmentReader| -> Vec<_> { r.gram_postings().map(|e| e.unwrap()).collect() };
+    assert_eq!(postings(&m), postings(&f));
+    let field_postings = |r: &BinSegmentReader| -> Vec<_> {
+        r.field_gram_postings()
+            .unwrap()
+            .map(|e| e.unwrap())
+            .collect()
+    };
+    assert_eq!(field_postings(&m), field_postings(&f));
+    assert!(m.has_field_grams("text.title"));
+}
+
+#[test]
+fn merge_refuses_to_overwrite_input() {
+    let tmp = tempfile::tempdir().unwrap();
+    let a = build(tmp.path(), "a", &[doc("1", "x", "y")]);
+    assert!(merge_segments(&[&a], &a, &MergeOptions::default()).is_err());
+}
@@ -0,0 +1,63 @@
+// crates/grepzilla_segment/tests/v2_positional_masks.rs
+use grepzilla_segment::gram::{
+    BooleanOp, required_gram_runs_from_wildcard, required_grams_from_wildcard,
+};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::fs::File;
+use std::io::Write;
+
+#[test]
+fn adjacency_masks_drop_scattered_grams() {
+    let tmp = tempfile::tempdir().unwrap();
+    let segdir = tmp.path().join("seg");
+    let jsonl = tmp.path().join("in.jsonl");
+
+    let bodies = [
+        "таймаут соединения", // 0: литерал целиком
+        "тайма и маут",       // 1: все граммы есть, но не подряд
+        "маут тайма",         // 2: тоже разбросаны
+        "ошибка: таймаут",    // 3: литерал целиком
+        "атайма смаут",       // 4: разбросаны
+    ];
+    let mut f = File::create(&jsonl).unwrap();
+    for (i, b) in bodies.iter().enumerate() {
+        writeln!(f, r#"{{"_id":"d{i}","text":{{"body":"{b}"}}}}"#).unwrap();
+    }
+    drop(f);
+
+    let mut w = BinSegmentWriter::default();
+    w.write_segment(jsonl.to_str().unwrap(), segdir.to_str().unwrap())
+        .unwrap();
+    let r = BinSegmentReader::open_segment(segdir.to_str().unwrap()).unwrap();
+
+    let q = "*таймаут*";
+    let plain: Vec<u32> = r
+        .prefilter(
+            BooleanOp::And,
+            &required_grams_from_wildcard(q).unwrap(),
+            None,
+        )
+        .unwrap()
+        .iter()
+        .collect();
+    let runs = required_gram_runs_from_wildcard(q).unwrap();
+    let adjacent: Vec<u32> = r
+        .prefilter_adjacent_after(&runs, None, None)
+        .unwrap()
+        .iter()
+        .collect();
+
+    // обычный AND пропускает разбросанные граммы, маски — нет
+    assert_eq!(plain, vec![0, 1, 2, 3, 4]);
+    assert_eq!(adjacent, vec![0, 3]);
+
+    // курсор и поле учитываются как в prefilter_after
+    let tail: Vec<u32> = r
+        .prefilter_adjacent_after(&runs, Some("text.body"), Some(0))
+        .unwrap()
+        .iter()
+        .collect();
+    assert_eq!(tail, vec![3]);
+}
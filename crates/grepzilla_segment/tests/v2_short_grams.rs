@@ -0,0 +1,154 @@
+// crates/grepzilla_segment/tests/v2_short_grams.rs
+use grepzilla_segment::integrity::verify_segment;
+use grepzilla_segment::query::{BoolQuery, Clause, CompiledQuery, PrefilterOpts};
+use grepzilla_segment::segjson::{JsonSegmentReader, JsonSegmentWriter};
+use grepzilla_segment::v2::merge::{MergeOptions, merge_segments};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::verify::EnvVerifyFactory;
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::path::Path;
+
+const DOCS: &[&str] = &[
+    r#"{"_id":"a","text":{"title":"ID 42","body":"ok"}}"#,
+    r#"{"_id":"b","text":{"title":"order 4","body":"id missing"}}"#,
+    r#"{"_id":"c","text":{"title":"Ёж","body":"x"}}"#,
+    r#"{"_id":"d","text":{"title":"no match","body":"at all"}}"#,
+];
+
+fn build(dir: &Path, name: &str, docs: &[&str], short: bool) -> String {
+    let input = dir.join(format!("{name}.jsonl"));
+    std::fs::write(&input, docs.join("\n")).unwrap();
+    let out = dir.join(name).to_str().unwrap().to_string();
+    BinSegmentWriter::default()
+        .with_short_grams(short)
+        .write_segment(input.to_str().unwrap(), &out)
+        .unwrap();
+    out
+}
+
+fn compile(wildcard: &str, field: Option<&str>) -> CompiledQuery {
+    BoolQuery::single(Clause::wildcard(wildcard, field))
+        .compile(&EnvVerifyFactory::from_env())
+        .unwrap()
+}
+
+fn candidates(
+    r: &BinSegmentReader,
+    q: &CompiledQuery,
+    full_scan: bool,
+) -> anyhow::Result<Vec<String>> {
+    let opts = PrefilterOpts {
+        short_grams: r.has_short_grams(),
+        full_scan,
+    };
+    let bm = q.prefilter(opts, |runs, field| {
+        r.prefilter_adjacent_after(runs, field, None)
+    })?;
+    Ok(bm
+        .iter()
+        .map(|d| r.get_doc(d).unwrap().ext_id.clone())
+        .collect())
+}
+
+#[test]
+fn short_literals_use_short_index() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build(tmp.path(), "seg", DOCS, true);
+    assert!(verify_segment(Path::new(&seg)).unwrap().is_ok());
+    let r = BinSegmentReader::open_segment(&seg).unwrap();
+    assert!(r.has_short_grams());
+
+    // регистр нормализуется, как у обычных грамм
+    assert_eq!(
+        candidates(&r, &compile("*ID*", None), false).unwrap(),
+        ["a", "b"]
+    );
+    // индекс общий для полей: поле сужает только по маске
+    assert_eq!(
+        candidates(&r, &compile("*ID*", Some("text.title")), false).unwrap(),
+        ["a", "b"]
+    );
+    assert!(
+        candidates(&r, &compile("*ID*", Some("text.none")), false)
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(
+        candidates(&r, &compile("*4?2*", None), false).unwrap(),
+        ["a"]
+    );
+    assert_eq!(candidates(&r, &compile("*е*", None), false).unwrap(), ["c"]);
+    // нет такой биграммы — пусто
+    assert!(
+        candidates(&r, &compile("*zq*", None), false)
+            .unwrap()
+            .is_empty()
+    );
+    // без литералов сузить нечем
+    assert!(candidates(&r, &compile("*", None), false).is_err());
+    assert_eq!(
+        candidates(&r, &compile("*", None), true).unwrap(),
+        ["a", "b", "c", "d"]
+    );
+}
+
+#[test]
+fn without_short_index_only_full_scan() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build(tmp.path(), "seg", DOCS, false);
+    let r = BinSegmentReader::open_segment(&seg).unwrap();
+    assert!(!r.has_short_grams());
+    let q = compile("*id*", Some("text.body"));
+    assert!(candidates(&r, &q, false).is_err());
+    assert_eq!(candidates(&r, &q, true).unwrap(), ["a", "b", "c", "d"]);
+
+    // V1: то же, короткие литералы не сужают
+    let input = tmp.path().join("seg.jsonl");
+    let v1 = tmp.path().join("v1").to_str().unwrap().to_string();
+    JsonSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), &v1)
+        .unwrap();
+    let r = JsonSegmentReader::open_segment(&v1).unwrap();
+    let scan = PrefilterOpts {
+        short_grams: false,
+        full_scan: true,
+    };
+    let bm = q
+        .prefilter(scan, |runs, field| r.prefilter_runs(runs, field))
+        .unwrap();
+    assert_eq!(bm.cardinality(), 4);
+    let scan = PrefilterOpts {
+        full_scan: false,
+        ..scan
+    };
+    assert!(
+        q.prefilter(scan, |runs, field| r.prefilter_runs(runs, field))
+            .is_err()
+    );
+}
+
+#[test]
+fn merge_keeps_short_index_only_if_all_inputs_have_it() {
+    let tmp = tempfile::tempdir().unwrap();
+    let a = build(tmp.path(), "a", &DOCS[..2], true);
+    let b = build(tmp.path(), "b", &DOCS[2..], true);
+    let c = build(tmp.path(), "c", &DOCS[2..], false);
+
+    let ab = tmp.path().join("ab").to_str().unwrap().to_string();
+    merge_segments(&[&a, &b], &ab, &MergeOptions::default()).unwrap();
+    assert!(verify_segment(Path::new(&ab)).unwrap().is_ok());
+    let r = BinSegmentReader::open_segment(&ab).unwrap();
+    assert!(r.has_short_grams());
+    assert_eq!(candidates(&r, &compile("*ё*", None), false).unwrap(), ["c"]);
+    assert_eq!(
+        candidates(&r, &compile("*42*", None), false).unwrap(),
+        ["a"]
+    );
+
+    let ac = tmp.path().join("ac").to_str().unwrap().to_string();
+    merge_segments(&[&a, &c], &ac, &MergeOptions::default()).unwrap();
+    let r = BinSegmentReader::open_segment(&ac).unwrap();
+    assert!(!r.has_short_grams());
+    assert!(!Path::new(&ac).join("short_grams.idx").exists());
+}
@@ -0,0 +1,94 @@
+// crates/grepzilla_segment/tests/v2_streaming_build.rs
+use grepzilla_segment::gram::{BooleanOp, required_grams_from_wildcard};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::{BinSegmentWriter, FieldGrams};
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+const FILES: &[&str] = &[
+    "meta.bin",
+    "grams.idx",
+    "grams.dat",
+    "fields.idx",
+    "fields.dat",
+    "docs.dat",
+    "field_grams.idx",
+    "field_grams.dat",
+];
+
+fn build(jsonl: &Path, out: &Path, budget: usize) {
+    let mut w = BinSegmentWriter::default()
+        .with_field_grams(FieldGrams::All)
+        .with_memory_budget(budget);
+    w.write_segment(jsonl.to_str().unwrap(), out.to_str().unwrap())
+        .unwrap();
+}
+
+#[test]
+fn spilled_build_is_byte_identical() {
+    let tmp = tempfile::tempdir().unwrap();
+    let jsonl = tmp.path().join("in.jsonl");
+    let words = ["ошибка", "таймаут", "диск", "сеть", "error", "timeout"];
+    let mut f = File::create(&jsonl).unwrap();
+    for i in 0..300 {
+        let a = words[i % words.len()];
+        let b = words[(i * 7 + 3) % words.len()];
+        writeln!(
+            f,
+            r#"{{"_id":"d{i}","text":{{"title":"{a} {i}","body":"{b} и {a} номер {i}"}},"tags":["{b}"]}}"#
+        )
+        .unwrap();
+    }
+    drop(f);
+
+    let mem = tmp.path().join("mem");
+    let spilled = tmp.path().join("spilled");
+    build(&jsonl, &mem, usize::MAX);
+    // крошечный бюджет — сброс ранов почти после каждого документа
+    build(&jsonl, &spilled, 1);
+
+    for name in FILES {
+        let a = fs::read(mem.join(name)).unwrap();
+        let b = fs::read(spilled.join(name)).unwrap();
+        assert!(a == b, "{name} differs");
+    }
+    // временные файлы убраны
+    assert!(!spilled.join(".build.tmp").exists());
+
+    let r = BinSegmentReader::open_segment(spilled.to_str().unwrap()).unwrap();
+    assert_eq!(r.doc_count(), 300);
+    let grams = required_grams_from_wildcard("*таймаут*").unwrap();
+    let bm = r
+        .prefilter(BooleanOp::And, &grams, Some("text.title"))
+        .unwrap();
+    assert_eq!(bm.cardinality(), 50);
+    let doc = r.get_doc(299).unwrap();
+    assert_eq!(doc.ext_id, "d299");
+    assert!(doc.fields["text.body"].contains("номер 299"));
+}
+
+#[test]
+fn failed_build_removes_temp_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    let jsonl = tmp.path().join("in.jsonl");
+    let mut f = File::create(&jsonl).unwrap();
+    for i in 0..50 {
+        writeln!(
+            f,
+            r#"{{"_id":"d{i}","text":{{"title":"ошибка номер {i}"}}}}"#
+        )
+        .unwrap();
+    }
+    // битая строка после того, как раны грамм уже сброшены на диск
+    writeln!(f, r#"{{"_id":"broken","#).unwrap();
+    drop(f);
+
+    let out = tmp.path().join("seg");
+    let err = BinSegmentWriter::default()
+        .with_memory_budget(1)
+        .write_segment(jsonl.to_str().unwrap(), out.to_str().unwrap());
+    assert!(err.is_err());
+    assert!(!out.join(".build.tmp").exists());
+}
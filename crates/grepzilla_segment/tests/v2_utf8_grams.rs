@@ -0,0 +1,36 @@
+// crates/grepzilla_segment/tests/v2_utf8_grams.rs
+use grepzilla_segment::gram::{BooleanOp, required_grams_from_wildcard};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::fs::File;
+use std::io::Write;
+
+#[test]
+fn v2_cyrillic_trigrams_are_distinct_keys() {
+    let tmp = tempfile::tempdir().unwrap();
+    let segdir = tmp.path().join("seg");
+    let jsonl = tmp.path().join("in.jsonl");
+
+    // "кот", "кош", "коф" совпадают по первым трём байтам UTF-8
+    let mut f = File::create(&jsonl).unwrap();
+    writeln!(f, r#"{{"_id":"1","text":{{"body":"кот"}}}}"#).unwrap();
+    writeln!(f, r#"{{"_id":"2","text":{{"body":"кошка"}}}}"#).unwrap();
+    writeln!(f, r#"{{"_id":"3","text":{{"body":"кофе"}}}}"#).unwrap();
+    drop(f);
+
+    let mut w = BinSegmentWriter::default();
+    w.write_segment(jsonl.to_str().unwrap(), segdir.to_str().unwrap())
+        .unwrap();
+    let r = BinSegmentReader::open_segment(segdir.to_str().unwrap()).unwrap();
+
+    for (q, want) in [("*кот*", 0u32), ("*кош*", 1), ("*коф*", 2)] {
+        let grams = required_grams_from_wildcard(q).unwrap();
+        let ids: Vec<u32> = r
+            .prefilter(BooleanOp::And, &grams, None)
+            .unwrap()
+            .iter()
+            .collect();
+        assert_eq!(ids, vec![want], "query {q}");
+    }
+}
@@ -0,0 +1,84 @@
+// crates/grepzilla_segment/tests/verify_engines.rs
+use grepzilla_segment::normalizer::Sensitivity;
+use grepzilla_segment::verify::{DEFAULT_ENGINE, EngineRegistry, VerifyFactory};
+
+/// (паттерн, текст, совпадение без учёта регистра)
+const CASES: &[(&str, &str, bool)] = &[
+    ("*timeout*", "connection timeout here", true),
+    ("timeout*", "connection timeout", false),
+    ("*timeout", "connection timeout", true),
+    ("t?meout", "timeout", true),
+    (r"*2\*3*", "2*3=6", true),
+    (r"*2\*3*", "2x3=6", false),
+    ("*(a+b)*", "x (a+b) y", true),
+    ("*ошибка*", "ОШИБКА сети", true),
+    ("*line*", "first\nline two", true),
+];
+
+fn check(factory: &dyn VerifyFactory, name: &str) {
+    for &(pattern, text, expected) in CASES {
+        let e = factory.compile(pattern).unwrap();
+        assert_eq!(e.is_match(text), expected, "{name}: {pattern} ~ {text:?}");
+    }
+    // подсветка — сам литерал, а не всё значение
+    let e = factory.compile("*timeout*").unwrap();
+    assert_eq!(e.find("a timeout b"), Some((2, 9)), "{name}");
+    // упорядоченные литералы: от первого вхождения первого до последнего
+    // вхождения последнего
+    let e = factory.compile("*err*42*").unwrap();
+    assert_eq!(e.find("x err 42 err 42 y"), Some((2, 15)), "{name}");
+    assert!(!e.is_match("42 err"), "{name}");
+
+    let strict = Sensitivity {
+        case_sensitive: true,
+        ..Default::default()
+    };
+    let e = factory.compile_sensitive("*Timeout*", strict).unwrap();
+    assert!(e.is_match("Timeout"), "{name}");
+    assert!(!e.is_match("timeout"), "{name}");
+}
+
+#[test]
+fn builtin_engines_agree() {
+    let registry = EngineRegistry::builtin();
+    for name in registry.names() {
+        check(registry.get(name).unwrap().as_ref(), name);
+    }
+    assert!(registry.names().any(|n| n == DEFAULT_ENGINE));
+    #[cfg(feature = "engine-pcre2")]
+    assert!(registry.names().any(|n| n == "pcre2"));
+}
+
+#[test]
+fn unknown_engine_is_an_error() {
+    let err = EngineRegistry::builtin()
+        .get("hyperscan")
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(err.contains("unknown verify engine `hyperscan`"), "{err}");
+}
+
+#[cfg(feature = "engine-pcre2")]
+#[test]
+fn pcre2_compiles_regex_clauses_in_its_own_syntax() {
+    use grepzilla_segment::analyzer::Pipeline;
+
+    let pcre2 = EngineRegistry::builtin().get("pcre2").unwrap().clone();
+    let (rq, e) = pcre2
+        .compile_regex_clause(r"(?<=Connection )Time(out)\1?", &Pipeline::standard())
+        .unwrap();
+    assert!(e.is_match("connection timeout"));
+    assert!(!e.is_match("timeout"));
+    assert!(rq.gram_query().is_ok(), "{rq:?}");
+
+    let strict = Sensitivity {
+        case_sensitive: true,
+        ..Default::default()
+    };
+    let e = pcre2
+        .compile_regex_sensitive("(?<=Connection )timeout", strict)
+        .unwrap();
+    assert!(e.is_match("Connection timeout"));
+    assert!(!e.is_match("connection timeout"));
+}
@@ -0,0 +1,192 @@
+// crates/grepzilla_segment/tests/verify_seg.rs
+use grepzilla_segment::SegmentWriter;
+use grepzilla_segment::deletes::delete_docs;
+use grepzilla_segment::integrity::{IntegrityReport, verify_segment};
+use grepzilla_segment::segjson::JsonSegmentWriter;
+use grepzilla_segment::v2::crc::crc64_ecma;
+use grepzilla_segment::v2::gram_dict::GramDict;
+use grepzilla_segment::v2::writer::{BinSegmentWriter, FieldGrams};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DOCS: &str = r#"{"_id":"a","ts":"2025-08-20T12:00:00Z","text":{"title":"Ошибка диска","body":"диск переполнен"}}
+{"_id":"b","ts":"2025-08-20T12:01:00Z","text":{"body":"ошибка сети"}}
+{"_id":"c","ts":"2025-08-20T12:02:00Z","text":{"title":"всё хорошо"}}
+{"_id":"d","text":{"body":"ошибка памяти","title":"память"}}
+"#;
+
+fn build_v2(dir: &Path) -> PathBuf {
+    let input = dir.join("docs.jsonl");
+    fs::write(&input, DOCS).unwrap();
+    let seg = dir.join("v2");
+    BinSegmentWriter::default()
+        .with_field_grams(FieldGrams::All)
+        .with_raw_text(true)
+        .with_doc_values(vec!["ts:timestamp".parse().unwrap()])
+        .write_segment(input.to_str().unwrap(), seg.to_str().unwrap())
+        .unwrap();
+    seg
+}
+
+fn build_v1(dir: &Path) -> PathBuf {
+    let input = dir.join("docs.jsonl");
+    fs::write(&input, DOCS).unwrap();
+    let seg = dir.join("v1");
+    JsonSegmentWriter::default()
+        .with_raw_text(true)
+        .write_segment(input.to_str().unwrap(), seg.to_str().unwrap())
+        .unwrap();
+    seg
+}
+
+/// Поправить тело файла и пересчитать CRC64 — нарушение только структурное.
+fn patch(path: &Path, f: impl FnOnce(&mut Vec<u8>)) {
+    let mut bytes = fs::read(path).unwrap();
+    bytes.truncate(bytes.len() - 8);
+    f(&mut bytes);
+    let crc = crc64_ecma(&bytes);
+    bytes.extend_from_slice(&crc.to_le_bytes());
+    fs::write(path, bytes).unwrap();
+}
+
+fn messages(r: &IntegrityReport) -> Vec<String> {
+    r.violations
+        .iter()
+        .map(|v| format!("{}: {}", v.file, v.message))
+        .collect()
+}
+
+fn assert_violation(r: &IntegrityReport, needle: &str) {
+    let all = messages(r);
+    assert!(
+        all.iter().any(|m| m.contains(needle)),
+        "no `{needle}` in {all:#?}"
+    );
+}
+
+#[test]
+fn clean_segments_pass() {
+    let tmp = tempfile::tempdir().unwrap();
+    let v2 = build_v2(tmp.path());
+    let v1 = build_v1(tmp.path());
+    delete_docs(&v2, 4, [1]).unwrap();
+    for seg in [v2, v1] {
+        let r = verify_segment(&seg).unwrap();
+        assert!(r.is_ok(), "{:#?}", messages(&r));
+        assert_eq!(r.doc_count, 4);
+    }
+    assert!(verify_segment(&tmp.path().join("nope")).is_err());
+}
+
+#[test]
+fn v2_crc_mismatch_is_reported() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build_v2(tmp.path());
+    let p = seg.join("grams.dat");
+    let mut bytes = fs::read(&p).unwrap();
+    bytes[3] ^= 0xFF;
+    fs::write(&p, bytes).unwrap();
+    let r = verify_segment(&seg).unwrap();
+    assert_violation(&r, "grams.dat: CRC64 mismatch");
+}
+
+#[test]
+fn v2_docs_offsets_must_be_monotonic() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build_v2(tmp.path());
+    // offsets начинаются с байта 24; меняем местами offsets[1] и offsets[2]
+    patch(&seg.join("docs.dat"), |b| {
+        let (x, y) = (24 + 8, 24 + 16);
+        let tmp: Vec<u8> = b[x..x + 8].to_vec();
+        b.copy_within(y..y + 8, x);
+        b[y..y + 8].copy_from_slice(&tmp);
+    });
+    let r = verify_segment(&seg).unwrap();
+    assert_violation(&r, "docs.dat: doc 1: offsets not monotonic");
+}
+
+#[test]
+fn v2_field_masks_must_match_docs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build_v2(tmp.path());
+    // меняем местами (off,len) масок двух первых полей в fields.idx
+    patch(&seg.join("fields.idx"), |b| {
+        let dict_len = u32::from_le_bytes(b[12..16].try_into().unwrap()) as usize;
+        let rec = 16 + dict_len;
+        let (x, y) = (rec + 4, rec + 20 + 4);
+        let tmp: Vec<u8> = b[x..x + 16].to_vec();
+        b.copy_within(y..y + 16, x);
+        b[y..y + 16].copy_from_slice(&tmp);
+    });
+    let r = verify_segment(&seg).unwrap();
+    assert_violation(&r, "fields.dat: field `");
+    assert_violation(&r, "are not in the mask");
+}
+
+#[test]
+fn v2_postings_must_be_sorted_and_in_range() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build_v2(tmp.path());
+    let m = unsafe { memmap2::Mmap::map(&fs::File::open(seg.join("grams.idx")).unwrap()) };
+    let dict = GramDict::open(m.unwrap()).unwrap();
+    let dat = fs::read(seg.join("grams.dat")).unwrap();
+    // inline-записи (kind=1|маски): [kind][u32 n][varint first][varint дельты]...
+    let inline: Vec<(String, usize, u32)> = dict
+        .iter()
+        .map(|e| e.unwrap())
+        .filter(|e| dat[e.off as usize] & 0x7F == 1)
+        .map(|e| {
+            let n = u32::from_le_bytes(
+                dat[e.off as usize + 1..e.off as usize + 5]
+                    .try_into()
+                    .unwrap(),
+            );
+            (e.key_str().to_string(), e.off as usize, n)
+        })
+        .collect();
+    let (two_key, two_off, _) = inline.iter().find(|(_, _, n)| *n >= 2).unwrap().clone();
+    let (one_key, one_off, _) = inline
+        .iter()
+        .find(|(k, _, _)| *k != two_key)
+        .unwrap()
+        .clone();
+    patch(&seg.join("grams.dat"), |b| {
+        // дельта 0 — повтор DocId
+        b[two_off + 6] = 0;
+        // первый DocId за пределами doc_count
+        b[one_off + 5] = 100;
+    });
+    let r = verify_segment(&seg).unwrap();
+    assert_violation(
+        &r,
+        &format!("key {two_key:?}: postings not strictly ascending"),
+    );
+    assert_violation(&r, &format!("key {one_key:?}: doc_id 100 >= doc_count 4"));
+}
+
+#[test]
+fn v1_structure_is_checked() {
+    let tmp = tempfile::tempdir().unwrap();
+    let seg = build_v1(tmp.path());
+
+    let p = seg.join("grams.json");
+    let mut grams: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(&fs::read_to_string(&p).unwrap()).unwrap();
+    grams.insert("оши".into(), serde_json::json!([3, 1, 9]));
+    fs::write(&p, serde_json::to_string(&grams).unwrap()).unwrap();
+
+    let p = seg.join("docs.jsonl");
+    let docs = fs::read_to_string(&p)
+        .unwrap()
+        .replace(r#""doc_id":2"#, r#""doc_id":7"#);
+    fs::write(&p, docs).unwrap();
+
+    let r = verify_segment(&seg).unwrap();
+    assert_eq!(r.format, "v1");
+    assert_violation(
+        &r,
+        r#"grams.json: key "оши": doc ids not strictly ascending (3 then 1)"#,
+    );
+    assert_violation(&r, r#"grams.json: key "оши": doc_id 9 >= doc_count 4"#);
+    assert_violation(&r, "docs.jsonl: line 3: doc_id 7 != 2");
+}
@@ -0,0 +1,100 @@
+// crates/grepzilla_segment/tests/wildcard_anchors.rs
+use grepzilla_segment::gram::required_grams_from_wildcard;
+use grepzilla_segment::query::{BoolQuery, Clause, PrefilterOpts};
+use grepzilla_segment::segjson::{JsonSegmentReader, JsonSegmentWriter};
+use grepzilla_segment::v2::reader::BinSegmentReader;
+use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::verify::EnvVerifyFactory;
+use grepzilla_segment::{SegmentReader, SegmentWriter};
+use std::path::Path;
+
+const DOCS: &[&str] = &[
+    r#"{"_id":"a","text":{"title":"Foo bar"}}"#,
+    r#"{"_id":"b","text":{"title":"bar foo"}}"#,
+    r#"{"_id":"c","text":{"title":"foo"}}"#,
+    r#"{"_id":"d","text":{"title":"2*3=6 and 2x3"}}"#,
+    r#"{"_id":"e","text":{"title":"what?"}}"#,
+    r#"{"_id":"f","text":{"title":"whatever"}}"#,
+];
+
+fn build(dir: &Path) -> (String, String) {
+    let input = dir.join("docs.jsonl");
+    std::fs::write(&input, DOCS.join("\n")).unwrap();
+    let v1 = dir.join("v1").to_str().unwrap().to_string();
+    let v2 = dir.join("v2").to_str().unwrap().to_string();
+    JsonSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), &v1)
+        .unwrap();
+    BinSegmentWriter::default()
+        .write_segment(input.to_str().unwrap(), &v2)
+        .unwrap();
+    (v1, v2)
+}
+
+/// (кандидаты префильтра, совпавшие документы)
+fn search(v1: &str, v2: &str, wildcard: &str) -> (usize, Vec<String>) {
+    let q = BoolQuery::single(Clause::wildcard(wildcard, Some("text.title")))
+        .compile(&EnvVerifyFactory::from_env())
+        .unwrap();
+    let opts = PrefilterOpts {
+        full_scan: true,
+        ..Default::default()
+    };
+
+    let r2 = BinSegmentReader::open_segment(v2).unwrap();
+    let bm = q
+        .prefilter(opts, |runs, field| {
+            r2.prefilter_adjacent_after(runs, field, None)
+        })
+        .unwrap();
+    let hits: Vec<String> = bm
+        .iter()
+        .filter_map(|d| r2.get_doc(d))
+        .filter(|doc| q.matches(doc).is_some())
+        .map(|doc| doc.ext_id.clone())
+        .collect();
+
+    let r1 = JsonSegmentReader::open_segment(v1).unwrap();
+    let bm1 = q
+        .prefilter(opts, |runs, field| r1.prefilter_runs(runs, field))
+        .unwrap();
+    let hits1: Vec<String> = bm1
+        .iter()
+        .filter_map(|d| r1.get_doc(d))
+        .filter(|doc| q.matches(doc).is_some())
+        .map(|doc| doc.ext_id.clone())
+        .collect();
+    assert_eq!(hits, hits1, "{wildcard}: V1 и V2 расходятся");
+    (bm.cardinality() as usize, hits)
+}
+
+#[test]
+fn wildcard_is_anchored_to_field_value() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2) = build(tmp.path());
+    assert_eq!(search(&v1, &v2, "foo*").1, ["a", "c"]);
+    assert_eq!(search(&v1, &v2, "*foo").1, ["b", "c"]);
+    assert_eq!(search(&v1, &v2, "*foo*").1, ["a", "b", "c"]);
+    assert_eq!(search(&v1, &v2, "foo").1, ["c"]);
+    assert_eq!(search(&v1, &v2, "f?o").1, ["c"]);
+    assert_eq!(search(&v1, &v2, "bar*foo").1, ["b"]);
+}
+
+#[test]
+fn escaped_metachars_are_literals() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (v1, v2) = build(tmp.path());
+
+    // граммы берутся и из экранированного литерала
+    assert_eq!(required_grams_from_wildcard(r"*2\*3*").unwrap(), ["2*3"]);
+    let (candidates, hits) = search(&v1, &v2, r"*2\*3*");
+    assert_eq!(hits, ["d"]);
+    assert_eq!(candidates, 1);
+    // без экранирования `*` — любая строка
+    assert_eq!(search(&v1, &v2, "*2*3*").1, ["d"]);
+    assert!(search(&v1, &v2, r"*2\*x3*").1.is_empty());
+
+    assert_eq!(search(&v1, &v2, r"what\?").1, ["e"]);
+    assert_eq!(search(&v1, &v2, "what?").1, ["e"]);
+    assert!(search(&v1, &v2, r"what\??").1.is_empty());
+}
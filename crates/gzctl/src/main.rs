@@ -2,19 +2,30 @@
 use anyhow::Result;
 use clap::ValueEnum;
 use clap::{Parser, Subcommand};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::time::Instant;
 
+use grepzilla_segment::analyzer::Analyzers;
 use grepzilla_segment::common::preview::{PreviewOpts, build_preview};
-use grepzilla_segment::gram::{BooleanOp, required_grams_from_wildcard};
+use grepzilla_segment::deletes::{LiveDocs, delete_docs};
+use grepzilla_segment::doc_values::{DocValueField, RangeFilter, RangeValue};
+use grepzilla_segment::integrity::verify_segment;
+use grepzilla_segment::limits::{
+    DEFAULT_MAX_PATTERN_LEN, DEFAULT_MAX_REGEX_SIZE, DEFAULT_MAX_WILDCARD_SEGMENTS, LimitExceeded,
+    QueryLimits,
+};
+use grepzilla_segment::query::{BoolQuery, Clause, PrefilterOpts};
 use grepzilla_segment::segjson::{JsonSegmentReader, JsonSegmentWriter};
+use grepzilla_segment::v2::convert::{ConvertOptions, compare_hits, convert_v1_to_v2};
+use grepzilla_segment::v2::inspect::{SegmentInspection, inspect_segment};
+use grepzilla_segment::v2::merge::{MergeOptions, merge_segments};
 use grepzilla_segment::v2::reader::BinSegmentReader;
-use grepzilla_segment::v2::writer::BinSegmentWriter;
+use grepzilla_segment::v2::writer::{BinSegmentWriter, FieldGrams};
 use grepzilla_segment::{SegmentReader, SegmentWriter};
 
-use grepzilla_segment::normalizer::normalize;
-use grepzilla_segment::verify::{EnvVerifyFactory, VerifyEngine, VerifyFactory};
+use grepzilla_segment::normalizer::Sensitivity;
+use grepzilla_segment::verify::{EnvVerifyFactory, VerifyEngine};
 
 #[derive(Parser)]
 #[command(
@@ -33,6 +44,7 @@ enum SegFormat {
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::enum_variant_names)] // имена подкоманд: build-seg, merge-seg, ...
 enum Cmd {
     /// Построить сегмент из JSONL
     BuildSeg {
@@ -42,19 +54,136 @@ enum Cmd {
         out: String,
         #[arg(long, value_enum, default_value_t=SegFormat::V1)]
         format: SegFormat,
+        /// V2: поля с собственным индексом грамм (через запятую; `*` — все)
+        #[arg(long, value_delimiter = ',')]
+        field_grams: Vec<String>,
+        /// V2: бюджет памяти под постинги (MiB); сверх него — сброс на диск
+        #[arg(long, default_value_t = 256)]
+        mem_budget_mb: usize,
+        /// Сохранять исходный текст полей (превью без нормализации)
+        #[arg(long, default_value_t = false)]
+        raw_text: bool,
+        /// V2: колонки doc-values `name:kind` через запятую (kind: i64|f64|timestamp)
+        #[arg(long, value_delimiter = ',')]
+        doc_values: Vec<DocValueField>,
+        /// V2: индекс 1–2-грамм для коротких паттернов (`*id*`, `*42*`)
+        #[arg(long, default_value_t = false)]
+        short_grams: bool,
+        /// Схема анализаторов полей (JSON: analyzers, default, fields)
+        #[arg(long)]
+        analyzers: Option<String>,
     },
-    /// Поиск в одном сегменте (wildcard-паттерн)
-    SearchSeg {
+    /// Слить несколько V2-сегментов (от старых к новым) в один
+    MergeSeg {
+        /// Каталоги сегментов через запятую
+        #[arg(long, value_delimiter = ',', required = true)]
+        inputs: Vec<String>,
+        #[arg(long)]
+        out: String,
+        /// Файл с удалёнными `_id` (по одному на строку)
+        #[arg(long)]
+        tombstones: Option<String>,
+    },
+    /// Перевести сегмент V1 (JSON) в V2 без исходного JSONL
+    ConvertSeg {
+        /// Каталог сегмента V1
+        #[arg(long)]
+        input: String,
+        #[arg(long)]
+        out: String,
+        /// V2: поля с собственным индексом грамм (через запятую; `*` — все)
+        #[arg(long, value_delimiter = ',')]
+        field_grams: Vec<String>,
+        /// V2: бюджет памяти под постинги (MiB); сверх него — сброс на диск
+        #[arg(long, default_value_t = 256)]
+        mem_budget_mb: usize,
+        /// V2: индекс 1–2-грамм для коротких паттернов
+        #[arg(long, default_value_t = false)]
+        short_grams: bool,
+    },
+    /// Сверить хиты сегментов V1 и V2 на наборе запросов; код выхода ≠ 0 при расхождении
+    CompareSeg {
+        #[arg(long)]
+        v1: String,
+        #[arg(long)]
+        v2: String,
+        /// Wildcard-запросы (можно несколько)
+        #[arg(long = "q")]
+        queries: Vec<String>,
+        /// Файл с запросами (по одному на строку)
+        #[arg(long)]
+        queries_file: Option<String>,
+        #[arg(long)]
+        field: Option<String>,
+    },
+    /// Пометить документы сегмента удалёнными (`deletes.roaring`, без пересборки)
+    DeleteDocs {
         #[arg(long)]
         seg: String,
+        /// Внешние `_id` через запятую
+        #[arg(long, value_delimiter = ',', required = true)]
+        ids: Vec<String>,
+    },
+    /// Глубокая проверка сегмента V1/V2; код выхода ≠ 0, если найдены нарушения
+    VerifySeg {
         #[arg(long)]
-        q: String,
+        seg: String,
+    },
+    /// Статистика V2-сегмента: meta.bin, самые длинные постинги, поля, размеры
+    InspectSeg {
+        #[arg(long)]
+        seg: String,
+        /// Сколько самых длинных постингов показать
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+        /// Печатать JSON (для отслеживания во времени)
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Поиск в одном сегменте (wildcard-паттерн)
+    SearchSeg {
         #[arg(long)]
+        seg: String,
+        /// Wildcard по всему значению поля: `*` — любая строка, `?` — символ,
+        /// `\` экранирует (`*foo*` — вхождение, `foo*` — префикс)
+        #[arg(long, required_unless_present_any = ["regex", "query_file"])]
+        q: Option<String>,
+        /// Регулярное выражение вместо wildcard (граммы — из разбора выражения)
+        #[arg(long, conflicts_with = "q")]
+        regex: Option<String>,
+        #[arg(long, conflicts_with = "query_file")]
         field: Option<String>,
+        /// Булев запрос JSON-ом (`must`/`should`/`must_not`), как `query` в `/search`
+        #[arg(long, conflicts_with_all = ["q", "regex"])]
+        query_file: Option<String>,
         #[arg(long, default_value_t = 10)]
         limit: usize,
         #[arg(long, default_value_t = 0)]
         offset: usize,
+        /// Фильтр по doc-values: `ts>=2025-08-20T12:00:00Z`, `latency<500` (можно несколько)
+        #[arg(long = "filter", value_parser = parse_range_filter)]
+        filters: Vec<RangeFilter>,
+        /// Пункт без грамм — просмотр всего сегмента (вместо ошибки)
+        #[arg(long, default_value_t = false)]
+        full_scan: bool,
+        /// Различать регистр (verify по исходному тексту, нужен `--raw-text`)
+        #[arg(long, default_value_t = false)]
+        case_sensitive: bool,
+        /// Различать диакритику (`é` ≠ `e`)
+        #[arg(long, default_value_t = false)]
+        accent_sensitive: bool,
+        /// Не сворачивать NFKC (полноширинные формы, лигатуры)
+        #[arg(long, default_value_t = false)]
+        width_sensitive: bool,
+        /// Лимит длины паттерна пункта (символов)
+        #[arg(long, default_value_t = DEFAULT_MAX_PATTERN_LEN)]
+        max_pattern_len: usize,
+        /// Лимит кусков wildcard между `*`
+        #[arg(long, default_value_t = DEFAULT_MAX_WILDCARD_SEGMENTS)]
+        max_wildcard_segments: usize,
+        /// Лимит размера скомпилированного regex (байт)
+        #[arg(long, default_value_t = DEFAULT_MAX_REGEX_SIZE)]
+        max_regex_size: usize,
         /// Включить расширенные метрики (печатаются в stderr JSON-ом)
         #[arg(long, default_value_t = false)]
         debug_metrics: bool,
@@ -64,48 +193,347 @@ enum Cmd {
 fn main() -> Result<()> {
     let cli = Cli::parse();
     match cli.cmd {
-        Cmd::BuildSeg { input, out, format } => match format {
-            SegFormat::V1 => {
-                let mut w = JsonSegmentWriter::default();
-                w.write_segment(&input, &out)?;
+        Cmd::BuildSeg {
+            input,
+            out,
+            format,
+            field_grams,
+            mem_budget_mb,
+            raw_text,
+            doc_values,
+            short_grams,
+            analyzers,
+        } => {
+            let analyzers = match analyzers {
+                Some(p) => Analyzers::from_file(Path::new(&p))?,
+                None => Analyzers::default(),
+            };
+            match format {
+                SegFormat::V1 => {
+                    let mut w = JsonSegmentWriter::default()
+                        .with_raw_text(raw_text)
+                        .with_analyzers(analyzers);
+                    w.write_segment(&input, &out)?;
+                }
+                SegFormat::V2 => {
+                    let mut w = BinSegmentWriter::default()
+                        .with_analyzers(analyzers)
+                        .with_field_grams(parse_field_grams(field_grams))
+                        .with_memory_budget(mem_budget_mb << 20)
+                        .with_raw_text(raw_text)
+                        .with_doc_values(doc_values)
+                        .with_short_grams(short_grams);
+                    w.write_segment(&input, &out)?;
+                }
+            }
+        }
+        Cmd::MergeSeg {
+            inputs,
+            out,
+            tombstones,
+        } => {
+            let mut opts = MergeOptions::default();
+            if let Some(p) = tombstones {
+                opts.tombstones = std::fs::read_to_string(p)?
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(String::from)
+                    .collect();
+            }
+            let inputs: Vec<&str> = inputs.iter().map(String::as_str).collect();
+            let stats = merge_segments(&inputs, &out, &opts)?;
+            println!("{}", serde_json::to_string(&stats)?);
+        }
+        Cmd::ConvertSeg {
+            input,
+            out,
+            field_grams,
+            mem_budget_mb,
+            short_grams,
+        } => {
+            let opts = ConvertOptions {
+                field_grams: parse_field_grams(field_grams),
+                short_grams,
+                memory_budget: mem_budget_mb << 20,
+            };
+            let stats = convert_v1_to_v2(&input, &out, &opts)?;
+            println!("{}", serde_json::to_string(&stats)?);
+        }
+        Cmd::CompareSeg {
+            v1,
+            v2,
+            mut queries,
+            queries_file,
+            field,
+        } => {
+            if let Some(p) = queries_file {
+                queries.extend(
+                    std::fs::read_to_string(p)?
+                        .lines()
+                        .map(str::trim)
+                        .filter(|l| !l.is_empty())
+                        .map(String::from),
+                );
+            }
+            if queries.is_empty() {
+                anyhow::bail!("no queries: pass --q or --queries-file");
             }
-            SegFormat::V2 => {
-                let mut w = BinSegmentWriter::default();
-                w.write_segment(&input, &out)?;
+            let diffs = compare_hits(&v1, &v2, &queries, field.as_deref())?;
+            for d in &diffs {
+                println!("{}", serde_json::to_string(d)?);
             }
-        },
+            let differ = diffs.iter().filter(|d| !d.is_equal()).count();
+            if differ > 0 {
+                anyhow::bail!(
+                    "{differ} of {} queries differ between V1 and V2",
+                    diffs.len()
+                );
+            }
+        }
+        Cmd::DeleteDocs { seg, ids } => {
+            let live = if Path::new(&seg).join("meta.bin").exists() {
+                delete_by_ext_id(&BinSegmentReader::open_segment(&seg)?, &seg, &ids)?
+            } else {
+                delete_by_ext_id(&JsonSegmentReader::open_segment(&seg)?, &seg, &ids)?
+            };
+            println!(
+                "{}",
+                serde_json::json!({
+                    "generation": live.generation(),
+                    "deleted": live.deleted().cardinality(),
+                })
+            );
+        }
+        Cmd::VerifySeg { seg } => {
+            let report = verify_segment(Path::new(&seg))?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !report.is_ok() {
+                anyhow::bail!(
+                    "segment {seg} is corrupt: {} violation(s)",
+                    report.violations.len() as u64 + report.suppressed
+                );
+            }
+        }
+        Cmd::InspectSeg { seg, top, json } => {
+            let st = inspect_segment(&seg, top)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&st)?);
+            } else {
+                print_inspection(&st);
+            }
+        }
         Cmd::SearchSeg {
             seg,
             q,
+            regex,
             field,
+            query_file,
             limit,
             offset,
+            filters,
+            full_scan,
+            case_sensitive,
+            accent_sensitive,
+            width_sensitive,
+            max_pattern_len,
+            max_wildcard_segments,
+            max_regex_size,
             debug_metrics,
         } => {
-            search_one_segment_cli(&seg, &q, field.as_deref(), limit, offset, debug_metrics)?;
+            let sens = Sensitivity {
+                case_sensitive,
+                accent_sensitive,
+                width_sensitive,
+            };
+            let (query, label) = match (q, regex, query_file) {
+                (Some(q), _, _) => (
+                    BoolQuery::single(Clause::wildcard(&q, field.as_deref())),
+                    serde_json::json!(q),
+                ),
+                (None, Some(rx), _) => (
+                    BoolQuery::single(Clause::regex(&rx, field.as_deref())),
+                    serde_json::json!({ "regex": rx }),
+                ),
+                (None, None, Some(path)) => {
+                    let text = std::fs::read_to_string(&path)?;
+                    let query: BoolQuery = serde_json::from_str(&text)
+                        .map_err(|e| anyhow::anyhow!("bad query file {path}: {e}"))?;
+                    let label = serde_json::to_value(&query)?;
+                    (query, label)
+                }
+                (None, None, None) => anyhow::bail!("--q, --regex or --query-file is required"),
+            };
+            // длина и куски — до открытия сегмента, размер regex — при сборке
+            // движков; описание нарушения — как тело 400 у `/search`
+            let limits = QueryLimits {
+                max_pattern_len,
+                max_wildcard_segments,
+                max_regex_size,
+                ..Default::default()
+            };
+            let res = limits.check(&query).map_err(Into::into).and_then(|()| {
+                search_one_segment_cli(
+                    &seg,
+                    &query,
+                    label,
+                    field.as_deref(),
+                    &filters,
+                    limit,
+                    offset,
+                    full_scan,
+                    sens,
+                    max_regex_size,
+                    debug_metrics,
+                )
+            });
+            if let Some(e) = res
+                .as_ref()
+                .err()
+                .and_then(|e| e.downcast_ref::<LimitExceeded>())
+            {
+                eprintln!("{}", e.body());
+            }
+            res?;
         }
     }
     Ok(())
 }
 
+fn print_inspection(st: &SegmentInspection) {
+    let m = &st.meta;
+    println!("segment {}", st.segment);
+    println!(
+        "meta: magic={:#010x} version={} header_len={}",
+        m.magic, m.version, m.header_len
+    );
+    println!(
+        "      doc_count={} gram_count={} deleted={}",
+        m.doc_count, m.gram_count, st.deleted
+    );
+    println!(
+        "      grams.idx={} grams.dat={} fields.idx={} fields.dat={} docs.dat={}",
+        m.grams_idx_len, m.grams_dat_len, m.fields_idx_len, m.fields_dat_len, m.docs_dat_len
+    );
+
+    println!("\nfiles ({} bytes):", st.total_bytes);
+    for f in &st.files {
+        let share = 100.0 * f.bytes as f64 / st.total_bytes.max(1) as f64;
+        println!("  {:<20} {:>12} {:>6.1}%", f.name, f.bytes, share);
+    }
+
+    let p = &st.postings;
+    println!(
+        "\npostings: grams={} docs={} bytes={} in_all_docs={}",
+        p.grams, p.total_docs, p.total_bytes, p.in_all_docs
+    );
+    println!("top {} grams by docs:", st.top_grams.len());
+    for g in &st.top_grams {
+        let share = 100.0 * g.docs as f64 / m.doc_count.max(1) as f64;
+        println!(
+            "  {:<8} {:>10} docs {:>6.1}% {:>10} bytes",
+            format!("{:?}", g.gram),
+            g.docs,
+            share,
+            g.bytes
+        );
+    }
+
+    println!("\nfields ({}):", st.fields.len());
+    for f in &st.fields {
+        let share = 100.0 * f.docs as f64 / m.doc_count.max(1) as f64;
+        let fg = if f.field_grams { "  [field_grams]" } else { "" };
+        println!("  {:<24} {:>10} docs {:>6.1}%{fg}", f.name, f.docs, share);
+    }
+
+    let d = &st.docs;
+    println!(
+        "\ndocs.dat records: count={} bytes={} min={} p50={} p90={} p99={} max={} mean={:.1}",
+        d.count, d.total_bytes, d.min, d.p50, d.p90, d.p99, d.max, d.mean
+    );
+    for b in &d.histogram {
+        let le = b.le.map_or_else(|| "inf".to_string(), |le| le.to_string());
+        println!("  <= {le:>6}: {}", b.count);
+    }
+}
+
+/// `--field-grams`: пусто — выкл., `*` — все поля, иначе список полей.
+fn parse_field_grams(fields: Vec<String>) -> FieldGrams {
+    match fields.as_slice() {
+        [] => FieldGrams::Off,
+        [all] if all == "*" => FieldGrams::All,
+        _ => FieldGrams::Only(fields),
+    }
+}
+
+/// Найти DocId по внешним `_id` и дописать их в удаления сегмента.
+fn delete_by_ext_id<R: SegmentReader>(reader: &R, seg: &str, ids: &[String]) -> Result<LiveDocs> {
+    let wanted: HashSet<&str> = ids.iter().map(String::as_str).collect();
+    let doc_ids: Vec<u32> = (0..reader.doc_count())
+        .filter(|&d| {
+            reader
+                .get_doc(d)
+                .is_some_and(|doc| wanted.contains(doc.ext_id.as_str()))
+        })
+        .collect();
+    delete_docs(Path::new(seg), reader.doc_count(), doc_ids)
+}
+
+/// `field>=value` (также `>`, `<=`, `<`); значение — число или время RFC 3339.
+fn parse_range_filter(s: &str) -> Result<RangeFilter> {
+    let Some(at) = s.find(['<', '>']) else {
+        anyhow::bail!("filter must look like `field>=value`: {s}");
+    };
+    let (field, rest) = s.split_at(at);
+    let (op, value) = match rest.get(..2) {
+        Some(op @ (">=" | "<=")) => (op, &rest[2..]),
+        _ => (&rest[..1], &rest[1..]),
+    };
+    let value = if let Ok(i) = value.parse::<i64>() {
+        RangeValue::Int(i)
+    } else if let Ok(f) = value.parse::<f64>() {
+        RangeValue::Float(f)
+    } else {
+        RangeValue::Text(value.to_string())
+    };
+    let mut f = RangeFilter {
+        field: field.trim().to_string(),
+        ..Default::default()
+    };
+    match op {
+        ">=" => f.gte = Some(value),
+        ">" => f.gt = Some(value),
+        "<=" => f.lte = Some(value),
+        _ => f.lt = Some(value),
+    }
+    Ok(f)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn search_one_segment_cli(
     seg: &str,
-    wildcard: &str,
+    query: &BoolQuery,
+    label: serde_json::Value,
     field: Option<&str>,
+    filters: &[RangeFilter],
     limit: usize,
     offset: usize,
+    full_scan: bool,
+    sens: Sensitivity,
+    max_regex_size: usize,
     debug_metrics: bool,
 ) -> Result<()> {
     let start = Instant::now();
 
-    // 1) нормализуем wildcard и извлекаем обязательные триграммы
-    let norm_wc = normalize(wildcard);
-    let grams = required_grams_from_wildcard(&norm_wc)?;
+    // 1) приводим пункты анализаторами полей сегмента, извлекаем
+    //    обязательные триграммы и компилируем VerifyEngine каждого один раз
+    let analyzers = Analyzers::load(Path::new(seg))?;
+    let factory = EnvVerifyFactory::try_from_env()?.with_size_limit(max_regex_size);
+    let query = query.compile_with(&factory, sens, &analyzers)?;
+    // строгое сравнение — только по сегменту с исходным текстом
+    query.check_raw_text(Path::new(seg))?;
 
-    // 2) компилируем VerifyEngine один раз
-    let eng = EnvVerifyFactory::from_env().compile(&norm_wc)?;
-
-    // 3) autodetect V2/V1
+    // 2) autodetect V2/V1
     let is_v2 = Path::new(seg).join("meta.bin").exists();
 
     let mut shown = 0usize;
@@ -114,135 +542,98 @@ fn search_one_segment_cli(
     let mut verified = 0usize;
     let mut by_field: HashMap<String, usize> = HashMap::new();
     let mut scanned_docs = 0usize;
+    let mut plan = serde_json::Value::Null;
 
-    if is_v2 {
+    // 3) префильтр: must — AND, should — OR; must_not — только на verify
+    let v2;
+    let v1;
+    let (reader, bm): (&dyn SegmentReader, _) = if is_v2 {
         // -------- V2 ----------
-        let reader = BinSegmentReader::open_segment(seg)?;
-        let bm = reader.prefilter(BooleanOp::And, &grams, field)?;
+        v2 = BinSegmentReader::open_segment(seg)?;
+        if debug_metrics {
+            // один пункт — план объектом, несколько — массивом по порядку must, should
+            let mut plans = Vec::new();
+            for c in query.positive() {
+                plans.push(serde_json::to_value(
+                    v2.plan_prefilter(&c.grams(), c.field.as_deref())?,
+                )?);
+            }
+            plan = match plans.len() {
+                1 => plans.pop().unwrap_or_default(),
+                _ => serde_json::Value::Array(plans),
+            };
+        }
+        let opts = PrefilterOpts {
+            short_grams: v2.has_short_grams(),
+            full_scan,
+        };
+        let mut bm = query.prefilter(opts, |runs, field| {
+            v2.prefilter_adjacent_after(runs, field, None)
+        })?;
+        v2.apply_range_filters(&mut bm, filters)?;
 
         // прогрев документов для сниппетов
         let prefetch_cap = (limit.saturating_mul(4)).min(5_000);
         let warm: Vec<u32> = bm.iter().take(prefetch_cap).collect();
-        reader.prefetch_docs(warm.into_iter());
-
-        for doc_id in bm.iter() {
-            candidates += 1;
-            scanned_docs += 1;
-
-            if skipped < offset {
-                skipped += 1;
-                continue;
-            }
-            if shown >= limit {
-                break;
-            }
-
-            if let Some(doc) = reader.get_doc(doc_id) {
-                // verify: либо конкретное поле, либо первое совпавшее
-                let (matched, matched_field) = match field {
-                    Some(f) => {
-                        let ok = doc.fields.get(f).map(|t| eng.is_match(t)).unwrap_or(false);
-                        (ok, ok.then(|| f.to_string()))
-                    }
-                    None => {
-                        if let Some((k, _)) = doc.fields.iter().find(|(_, t)| eng.is_match(t)) {
-                            (true, Some(k.clone()))
-                        } else {
-                            (false, None)
-                        }
-                    }
-                };
-                if !matched {
-                    continue;
-                }
-                verified += 1;
-
-                // превью — общий helper
-                let preview = build_preview(
-                    doc,
-                    PreviewOpts {
-                        preferred_fields: &["text.title", "text.body", "title", "body"],
-                        max_len: 180,
-                        highlight_needle: longest_literal_needle(&norm_wc).as_deref(),
-                    },
-                );
-
-                let stat_field = matched_field
-                    .as_deref()
-                    .unwrap_or_else(|| pick_preview_field_name(doc, field).unwrap_or("-"));
-                *by_field.entry(stat_field.to_string()).or_insert(0) += 1;
-
-                println!(
-                    "{}\t{}\t{}: {}",
-                    doc.ext_id,
-                    doc_id,
-                    pick_preview_field_name(doc, field).unwrap_or("-"),
-                    preview
-                );
-                shown += 1;
-            }
-        }
+        v2.prefetch_docs(warm.into_iter());
+        (&v2, bm)
     } else {
         // -------- V1 ----------
-        let reader = JsonSegmentReader::open_segment(seg)?;
-        let bm = reader.prefilter(BooleanOp::And, &grams, field)?;
+        v1 = JsonSegmentReader::open_segment(seg)?;
+        let opts = PrefilterOpts {
+            short_grams: false,
+            full_scan,
+        };
+        let mut bm = query.prefilter(opts, |runs, field| v1.prefilter_runs(runs, field))?;
+        // в V1 нет doc-values
+        if !filters.is_empty() {
+            bm.clear();
+        }
+        (&v1, bm)
+    };
 
-        for doc_id in bm.iter() {
-            candidates += 1;
-            scanned_docs += 1;
+    for doc_id in bm.iter() {
+        candidates += 1;
+        scanned_docs += 1;
 
-            if skipped < offset {
-                skipped += 1;
-                continue;
-            }
-            if shown >= limit {
-                break;
-            }
+        if skipped < offset {
+            skipped += 1;
+            continue;
+        }
+        if shown >= limit {
+            break;
+        }
 
-            if let Some(doc) = reader.get_doc(doc_id) {
-                // verify
-                let (matched, matched_field) = match field {
-                    Some(f) => {
-                        let ok = doc.fields.get(f).map(|t| eng.is_match(t)).unwrap_or(false);
-                        (ok, ok.then(|| f.to_string()))
-                    }
-                    None => {
-                        if let Some((k, _)) = doc.fields.iter().find(|(_, t)| eng.is_match(t)) {
-                            (true, Some(k.clone()))
-                        } else {
-                            (false, None)
-                        }
-                    }
-                };
-                if !matched {
-                    continue;
-                }
-                verified += 1;
+        let Some(doc) = reader.get_doc(doc_id) else {
+            continue;
+        };
+        // verify: все пункты; поле — первого совпавшего must/should
+        let Some((matched_field, clause)) = query.matches(doc) else {
+            continue;
+        };
+        verified += 1;
 
-                let preview = build_preview(
-                    doc,
-                    PreviewOpts {
-                        preferred_fields: &["text.title", "text.body", "title", "body"],
-                        max_len: 180,
-                        highlight_needle: longest_literal_needle(&norm_wc).as_deref(),
-                    },
-                );
+        // превью — общий helper
+        let preview = build_preview(
+            doc,
+            PreviewOpts {
+                preferred_fields: &["text.title", "text.body", "title", "body"],
+                max_len: 180,
+                highlight_needle: clause.needle().as_deref(),
+                analyzers: &analyzers,
+            },
+        );
 
-                let stat_field = matched_field
-                    .as_deref()
-                    .unwrap_or_else(|| pick_preview_field_name(doc, field).unwrap_or("-"));
-                *by_field.entry(stat_field.to_string()).or_insert(0) += 1;
+        *by_field.entry(matched_field).or_insert(0) += 1;
 
-                println!(
-                    "{}\t{}\t{}: {}",
-                    doc.ext_id,
-                    doc_id,
-                    pick_preview_field_name(doc, field).unwrap_or("-"),
-                    preview
-                );
-                shown += 1;
-            }
-        }
+        println!(
+            "{}\t{}\t{}: {}",
+            doc.ext_id,
+            doc_id,
+            pick_preview_field_name(doc, clause.field.as_deref()).unwrap_or("-"),
+            preview
+        );
+        shown += 1;
     }
 
     if debug_metrics {
@@ -260,7 +651,7 @@ fn search_one_segment_cli(
 
         let metrics = serde_json::json!({
             "segment_path": seg,
-            "query": wildcard,
+            "query": label,
             "field_filter": field,
             "elapsed_ms": elapsed_ms,
             "doc_count": if is_v2 {
@@ -278,6 +669,7 @@ fn search_one_segment_cli(
                 "verify_to_hits": ratio_hits,
             },
             "by_field": by_field,
+            "plan": plan,
         });
         eprintln!("{}", serde_json::to_string_pretty(&metrics)?);
     }
@@ -307,25 +699,3 @@ fn pick_preview_field_name<'a>(
 fn null<T>() -> Option<T> {
     None
 }
-
-#[inline]
-fn longest_literal_needle(wc: &str) -> Option<String> {
-    let mut best = String::new();
-    let mut cur = String::new();
-    for ch in wc.chars() {
-        match ch {
-            '*' | '?' => {
-                if cur.len() > best.len() {
-                    best = std::mem::take(&mut cur);
-                } else {
-                    cur.clear();
-                }
-            }
-            _ => cur.push(ch),
-        }
-    }
-    if cur.len() > best.len() {
-        best = cur;
-    }
-    if best.is_empty() { None } else { Some(best) }
-}